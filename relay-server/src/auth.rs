@@ -0,0 +1,55 @@
+use axum::http::{header, HeaderMap};
+use std::collections::HashMap;
+
+/// Environment variable holding the shared relay token (used when `--token` is not given)
+pub const TOKEN_ENV: &str = "SPARKY_RELAY_TOKEN";
+
+/// Extract the client token from an `Authorization: Bearer <token>` header
+pub fn bearer_token(headers: &HeaderMap) -> Option<String> {
+    headers
+        .get(header::AUTHORIZATION)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.strip_prefix("Bearer "))
+        .map(|v| v.trim().to_string())
+}
+
+/// Token for a WebSocket upgrade: the bearer header, or the `token` query param as a fallback
+/// because browser WebSocket clients cannot set request headers
+pub fn ws_request_token(headers: &HeaderMap, query: &HashMap<String, String>) -> Option<String> {
+    bearer_token(headers).or_else(|| query.get("token").cloned())
+}
+
+/// Compare two secrets without short-circuiting on the first differing byte
+pub fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    if a.len() != b.len() {
+        return false;
+    }
+    a.iter().zip(b).fold(0u8, |acc, (x, y)| acc | (x ^ y)) == 0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_constant_time_eq() {
+        assert!(constant_time_eq(b"secret", b"secret"));
+        assert!(!constant_time_eq(b"secret", b"secreT"));
+        assert!(!constant_time_eq(b"secret", b"secret2"));
+    }
+
+    #[test]
+    fn test_request_token_prefers_bearer_header() {
+        let mut headers = HeaderMap::new();
+        let mut query = HashMap::new();
+        assert_eq!(ws_request_token(&headers, &query), None);
+
+        query.insert("token".to_string(), "from-query".to_string());
+        assert_eq!(ws_request_token(&headers, &query).as_deref(), Some("from-query"));
+        assert_eq!(bearer_token(&headers), None);
+
+        headers.insert(header::AUTHORIZATION, "Bearer from-header".parse().unwrap());
+        assert_eq!(ws_request_token(&headers, &query).as_deref(), Some("from-header"));
+        assert_eq!(bearer_token(&headers).as_deref(), Some("from-header"));
+    }
+}
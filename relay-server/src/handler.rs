@@ -1,21 +1,95 @@
-use axum::extract::ws::{Message, WebSocket};
+use axum::extract::ws::rejection::WebSocketUpgradeRejection;
+use axum::extract::ws::{Message, WebSocket, WebSocketUpgrade};
+use axum::extract::{Path, Query, State};
+use axum::http::{HeaderMap, StatusCode};
+use axum::response::{IntoResponse, Response};
+use axum::Json;
 use futures_util::{sink::SinkExt, stream::StreamExt};
+use std::collections::HashMap;
 use std::sync::Arc;
 
-use crate::state::AppState;
+use crate::auth::{bearer_token, ws_request_token};
+use crate::message::{validate_message, DEFAULT_MAX_MESSAGE_SIZE};
+use crate::state::{AppState, Metrics};
+
+/// Whether a plain HTTP request carries the configured relay token in its Authorization header
+fn is_authorized(state: &AppState, headers: &HeaderMap) -> bool {
+    state.is_authorized(bearer_token(headers).as_deref())
+}
+
+fn unauthorized() -> Response {
+    (StatusCode::UNAUTHORIZED, "missing or invalid token").into_response()
+}
+
+pub async fn ws_handler(
+    ws: Result<WebSocketUpgrade, WebSocketUpgradeRejection>,
+    Path(task_id): Path<String>,
+    Query(query): Query<HashMap<String, String>>,
+    headers: HeaderMap,
+    State(state): State<Arc<AppState>>,
+) -> Response {
+    if !state.is_authorized(ws_request_token(&headers, &query).as_deref()) {
+        tracing::warn!("Rejected unauthenticated WebSocket connection for task_id: {}", task_id);
+        return unauthorized();
+    }
+    // Authenticate before inspecting the upgrade so unauthorized clients always get 401
+    let ws = match ws {
+        Ok(ws) => ws,
+        Err(rejection) => return rejection.into_response(),
+    };
+    tracing::info!("New WebSocket connection for task_id: {}", task_id);
+
+    ws.on_upgrade(move |socket| handle_socket(socket, task_id, state))
+}
+
+/// GET /metrics — active rooms, per-room receiver counts and total relayed messages
+pub async fn metrics(headers: HeaderMap, State(state): State<Arc<AppState>>) -> Response {
+    if !is_authorized(&state, &headers) {
+        return unauthorized();
+    }
+    Json::<Metrics>(state.metrics()).into_response()
+}
+
+/// POST /rooms/:task_id/messages — push a message into an existing room without a WebSocket
+pub async fn post_message(
+    Path(task_id): Path<String>,
+    headers: HeaderMap,
+    State(state): State<Arc<AppState>>,
+    body: String,
+) -> Response {
+    if !is_authorized(&state, &headers) {
+        return unauthorized();
+    }
+    if !state.rooms.read().contains_key(&task_id) {
+        return (StatusCode::NOT_FOUND, format!("room not found: {}", task_id)).into_response();
+    }
+
+    let max_size = state.strict_max_size.unwrap_or(DEFAULT_MAX_MESSAGE_SIZE);
+    if let Err(e) = validate_message(&body, &task_id, max_size) {
+        tracing::warn!("Rejecting REST message for task_id {}: {}", task_id, e);
+        return (StatusCode::BAD_REQUEST, e.to_string()).into_response();
+    }
+
+    // send fails only when the room has no subscribers left
+    if state.get_or_create_channel(&task_id).send(body).is_err() {
+        return (StatusCode::NOT_FOUND, format!("room has no subscribers: {}", task_id)).into_response();
+    }
+    state.record_relayed();
+    tracing::debug!("Accepted REST message for task_id: {}", task_id);
+    StatusCode::ACCEPTED.into_response()
+}
 
 pub async fn handle_socket(socket: WebSocket, task_id: String, state: Arc<AppState>) {
     let (mut sender, mut receiver) = socket.split();
     
-    // Get or create broadcast channel for this task
-    let tx = state.get_or_create_channel(&task_id);
-    let mut rx = tx.subscribe();
+    // Join the room; subscribing under the rooms lock keeps a concurrent leave from removing it
+    let (tx, mut rx) = state.subscribe_room(&task_id);
 
     // Clone sender for broadcasting tasks
     let tx_clone = tx.clone();
 
     // Spawn task to forward messages from broadcast channel to client
-    let forward_task = tokio::spawn(async move {
+    let mut forward_task = tokio::spawn(async move {
         while let Ok(msg) = rx.recv().await {
             if sender.send(Message::Text(msg)).await.is_err() {
                 break;
@@ -25,13 +99,23 @@ pub async fn handle_socket(socket: WebSocket, task_id: String, state: Arc<AppSta
 
     // Handle incoming messages from client
     let task_id_for_recv = task_id.clone();
-    let recv_task = tokio::spawn(async move {
+    let strict_max_size = state.strict_max_size;
+    let state_for_recv = state.clone();
+    let mut recv_task = tokio::spawn(async move {
         while let Some(msg) = receiver.next().await {
             match msg {
                 Ok(Message::Text(text)) => {
                     tracing::debug!("Received from {}: {}", task_id_for_recv, text);
+                    if let Some(max_size) = strict_max_size {
+                        if let Err(e) = validate_message(&text, &task_id_for_recv, max_size) {
+                            tracing::warn!("Dropping invalid message for task_id {}: {}", task_id_for_recv, e);
+                            continue;
+                        }
+                    }
                     // Broadcast to all subscribers in the same room
-                    let _ = tx_clone.send(text);
+                    if tx_clone.send(text).is_ok() {
+                        state_for_recv.record_relayed();
+                    }
                 }
                 Ok(Message::Close(_)) => {
                     tracing::info!("Client closed connection for task_id: {}", task_id_for_recv);
@@ -46,13 +130,21 @@ pub async fn handle_socket(socket: WebSocket, task_id: String, state: Arc<AppSta
         }
     });
 
-    // Wait for either task to complete
+    // Wait for either task to complete, then stop the other so this
+    // connection's broadcast receiver is dropped before checking the room
     tokio::select! {
-        _ = forward_task => {}
-        _ = recv_task => {}
+        _ = &mut forward_task => {
+            recv_task.abort();
+            let _ = recv_task.await;
+        }
+        _ = &mut recv_task => {
+            forward_task.abort();
+            let _ = forward_task.await;
+        }
     }
+    drop(tx);
 
-    // Check if room is empty and remove if so
-    state.remove_room(&task_id);
+    // Only remove the room once no other client is subscribed to it
+    state.remove_room_if_unused(&task_id);
     tracing::info!("Connection closed for task_id: {}", task_id);
 }
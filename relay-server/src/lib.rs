@@ -1,4 +1,20 @@
 // lib.rs
+pub mod auth;
 pub mod handler;
+pub mod message;
 pub mod state;
+pub use message::MessagePayload;
 pub use state::AppState;
+
+use axum::routing::{get, post};
+use axum::Router;
+use std::sync::Arc;
+
+/// Build the relay router (WebSocket rooms, REST message endpoint and metrics)
+pub fn router(state: Arc<AppState>) -> Router {
+    Router::new()
+        .route("/ws/:task_id", get(handler::ws_handler))
+        .route("/rooms/:task_id/messages", post(handler::post_message))
+        .route("/metrics", get(handler::metrics))
+        .with_state(state)
+}
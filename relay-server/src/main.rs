@@ -1,24 +1,7 @@
-use axum::{
-    extract::ws::WebSocketUpgrade,
-    response::Response,
-    routing::get,
-    Router,
-};
+use relay_server::{auth, message, state};
 use std::sync::Arc;
 use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt};
 
-mod handler;
-mod state;
-
-#[derive(Clone, serde::Serialize, serde::Deserialize, Debug)]
-pub struct MessagePayload {
-    pub sender: String,
-    pub task_id: String,
-    pub msg_type: String,
-    pub action: Option<String>,
-    pub data: serde_json::Value,
-}
-
 #[tokio::main]
 async fn main() {
     // Parse command line arguments
@@ -29,16 +12,40 @@ async fn main() {
         .and_then(|i| args.get(i + 1))
         .map(|p| p.parse::<u16>().unwrap_or(8005))
         .unwrap_or(8005);
+    // --strict: validate messages against MessagePayload before broadcasting
+    let strict = args.iter().any(|arg| arg == "--strict");
+    let max_message_size = args
+        .iter()
+        .position(|arg| arg == "--max-message-size")
+        .and_then(|i| args.get(i + 1))
+        .and_then(|s| s.parse::<usize>().ok())
+        .unwrap_or(message::DEFAULT_MAX_MESSAGE_SIZE);
+    // --token <secret> (or SPARKY_RELAY_TOKEN): require clients to present this shared secret
+    let token = args
+        .iter()
+        .position(|arg| arg == "--token")
+        .and_then(|i| args.get(i + 1))
+        .cloned()
+        .or_else(|| std::env::var(auth::TOKEN_ENV).ok());
 
     tracing_subscriber::registry()
         .with(tracing_subscriber::fmt::layer())
         .init();
 
-    let state = Arc::new(state::AppState::new());
-
-    let app = Router::new()
-        .route("/ws/:task_id", get(ws_handler))
-        .with_state(state);
+    let state = if strict {
+        tracing::info!("Strict mode enabled (max message size: {} bytes)", max_message_size);
+        state::AppState::strict(max_message_size)
+    } else {
+        state::AppState::new()
+    };
+    let state = Arc::new(state.with_auth_token(token));
+    if state.auth_token.is_some() {
+        tracing::info!("Token authentication enabled");
+    } else {
+        tracing::warn!("No relay token configured; any client can join any room");
+    }
+
+    let app = relay_server::router(state);
 
     let addr = format!("0.0.0.0:{}", port);
     tracing::info!("Relay server starting on {}", addr);
@@ -46,9 +53,3 @@ async fn main() {
     let listener = tokio::net::TcpListener::bind(&addr).await.unwrap();
     axum::serve(listener, app).await.unwrap();
 }
-
-async fn ws_handler(ws: WebSocketUpgrade, axum::extract::Path(task_id): axum::extract::Path<String>, state: axum::extract::State<Arc<state::AppState>>) -> Response {
-    tracing::info!("New WebSocket connection for task_id: {}", task_id);
-    
-    ws.on_upgrade(move |socket| handler::handle_socket(socket, task_id, state.0.clone()))
-}
@@ -0,0 +1,113 @@
+use std::fmt;
+
+/// Default upper bound for a single relay message in strict mode (1 MiB)
+pub const DEFAULT_MAX_MESSAGE_SIZE: usize = 1024 * 1024;
+
+#[derive(Clone, serde::Serialize, serde::Deserialize, Debug)]
+pub struct MessagePayload {
+    pub sender: String,
+    pub task_id: String,
+    #[serde(rename = "type")]
+    pub msg_type: String,
+    pub action: Option<String>,
+    pub data: serde_json::Value,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum ValidationError {
+    TooLarge { size: usize, max: usize },
+    Malformed(String),
+    TaskIdMismatch { expected: String, actual: String },
+}
+
+impl fmt::Display for ValidationError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ValidationError::TooLarge { size, max } => {
+                write!(f, "message too large ({} > {} bytes)", size, max)
+            }
+            ValidationError::Malformed(e) => write!(f, "malformed message: {}", e),
+            ValidationError::TaskIdMismatch { expected, actual } => {
+                write!(f, "task_id mismatch (room={}, message={})", expected, actual)
+            }
+        }
+    }
+}
+
+/// Validate a message before broadcasting it to a room (strict mode only)
+pub fn validate_message(
+    text: &str,
+    room_task_id: &str,
+    max_size: usize,
+) -> Result<MessagePayload, ValidationError> {
+    if text.len() > max_size {
+        return Err(ValidationError::TooLarge { size: text.len(), max: max_size });
+    }
+
+    let payload: MessagePayload =
+        serde_json::from_str(text).map_err(|e| ValidationError::Malformed(e.to_string()))?;
+
+    if payload.task_id != room_task_id {
+        return Err(ValidationError::TaskIdMismatch {
+            expected: room_task_id.to_string(),
+            actual: payload.task_id,
+        });
+    }
+
+    Ok(payload)
+}
+
+// The payload type workers and the UI actually send, so the tests exercise the real wire format
+#[cfg(test)]
+#[allow(dead_code)]
+#[path = "../../src-tauri/src/relay_message.rs"]
+mod worker_message;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn message(task_id: &str) -> String {
+        serde_json::to_string(&worker_message::MessagePayload {
+            sender: "local_worker".to_string(),
+            task_id: task_id.to_string(),
+            msg_type: "status".to_string(),
+            action: None,
+            data: worker_message::MessageData { status: Some("running".to_string()), ..Default::default() },
+        })
+        .unwrap()
+    }
+
+    #[test]
+    fn test_valid_message() {
+        let text = message("task-1");
+        assert!(text.contains(r#""type":"status""#));
+        let payload = validate_message(&text, "task-1", DEFAULT_MAX_MESSAGE_SIZE).unwrap();
+        assert_eq!(payload.msg_type, "status");
+    }
+
+    #[test]
+    fn test_rejects_oversized_message() {
+        let text = message("task-1");
+        let err = validate_message(&text, "task-1", text.len() - 1).unwrap_err();
+        assert!(matches!(err, ValidationError::TooLarge { .. }));
+    }
+
+    #[test]
+    fn test_rejects_wrong_task_id() {
+        let err = validate_message(&message("task-2"), "task-1", DEFAULT_MAX_MESSAGE_SIZE).unwrap_err();
+        assert_eq!(
+            err,
+            ValidationError::TaskIdMismatch {
+                expected: "task-1".to_string(),
+                actual: "task-2".to_string(),
+            }
+        );
+    }
+
+    #[test]
+    fn test_rejects_malformed_message() {
+        let err = validate_message("not json", "task-1", DEFAULT_MAX_MESSAGE_SIZE).unwrap_err();
+        assert!(matches!(err, ValidationError::Malformed(_)));
+    }
+}
@@ -1,15 +1,62 @@
 use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
 use tokio::sync::broadcast;
 use parking_lot::RwLock;
 
 pub struct AppState {
     pub rooms: RwLock<HashMap<String, broadcast::Sender<String>>>,
+    /// Max message size to accept; `Some` enables strict validation, `None` is raw passthrough
+    pub strict_max_size: Option<usize>,
+    /// Total messages broadcast to rooms since startup
+    pub messages_relayed: AtomicU64,
+    /// Shared secret clients must present; `None` disables authentication
+    pub auth_token: Option<String>,
+}
+
+#[derive(Debug, serde::Serialize)]
+pub struct RoomMetrics {
+    pub task_id: String,
+    pub receiver_count: usize,
+}
+
+#[derive(Debug, serde::Serialize)]
+pub struct Metrics {
+    pub active_rooms: usize,
+    pub rooms: Vec<RoomMetrics>,
+    pub messages_relayed: u64,
 }
 
 impl AppState {
     pub fn new() -> Self {
         Self {
             rooms: RwLock::new(HashMap::new()),
+            strict_max_size: None,
+            messages_relayed: AtomicU64::new(0),
+            auth_token: None,
+        }
+    }
+
+    pub fn strict(max_message_size: usize) -> Self {
+        Self {
+            rooms: RwLock::new(HashMap::new()),
+            strict_max_size: Some(max_message_size),
+            messages_relayed: AtomicU64::new(0),
+            auth_token: None,
+        }
+    }
+
+    /// Require clients to present `token` (empty tokens leave authentication disabled)
+    pub fn with_auth_token(mut self, token: Option<String>) -> Self {
+        self.auth_token = token.filter(|t| !t.is_empty());
+        self
+    }
+
+    pub fn is_authorized(&self, presented: Option<&str>) -> bool {
+        match &self.auth_token {
+            None => true,
+            Some(expected) => presented
+                .map(|token| crate::auth::constant_time_eq(token.as_bytes(), expected.as_bytes()))
+                .unwrap_or(false),
         }
     }
 
@@ -25,10 +72,108 @@ impl AppState {
         sender
     }
 
+    /// Join a room: the receiver is created while the rooms write lock is held, so a
+    /// concurrent `remove_room_if_unused` cannot drop the room between lookup and subscribe
+    pub fn subscribe_room(&self, task_id: &str) -> (broadcast::Sender<String>, broadcast::Receiver<String>) {
+        let mut rooms = self.rooms.write();
+        let sender = rooms
+            .entry(task_id.to_string())
+            .or_insert_with(|| {
+                tracing::info!("Created new room for task_id: {}", task_id);
+                broadcast::channel(1000).0
+            })
+            .clone();
+        let receiver = sender.subscribe();
+        (sender, receiver)
+    }
+
     pub fn remove_room(&self, task_id: &str) {
         let mut rooms = self.rooms.write();
         if rooms.remove(task_id).is_some() {
             tracing::info!("Removed room for task_id: {}", task_id);
         }
     }
+
+    /// Remove the room only if it has no remaining subscribers. The check runs
+    /// under the write lock so a client joining concurrently keeps the room alive.
+    pub fn remove_room_if_unused(&self, task_id: &str) -> bool {
+        let mut rooms = self.rooms.write();
+        let unused = rooms
+            .get(task_id)
+            .map(|sender| sender.receiver_count() == 0)
+            .unwrap_or(false);
+        if unused {
+            rooms.remove(task_id);
+            tracing::info!("Removed room for task_id: {}", task_id);
+        }
+        unused
+    }
+
+    pub fn record_relayed(&self) {
+        self.messages_relayed.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Snapshot of room occupancy; rooms with zero receivers indicate leaked rooms
+    pub fn metrics(&self) -> Metrics {
+        let rooms = self.rooms.read();
+        let mut room_metrics: Vec<RoomMetrics> = rooms
+            .iter()
+            .map(|(task_id, sender)| RoomMetrics {
+                task_id: task_id.clone(),
+                receiver_count: sender.receiver_count(),
+            })
+            .collect();
+        room_metrics.sort_by(|a, b| a.task_id.cmp(&b.task_id));
+        Metrics {
+            active_rooms: rooms.len(),
+            rooms: room_metrics,
+            messages_relayed: self.messages_relayed.load(Ordering::Relaxed),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_room_kept_while_other_subscriber_remains() {
+        let state = AppState::new();
+        let first = state.get_or_create_channel("task-1").subscribe();
+        let mut second = state.get_or_create_channel("task-1").subscribe();
+
+        // first client leaves; the room must survive for the second one
+        drop(first);
+        assert!(!state.remove_room_if_unused("task-1"));
+        assert!(state.rooms.read().contains_key("task-1"));
+
+        state.get_or_create_channel("task-1").send("hello".to_string()).unwrap();
+        assert_eq!(second.try_recv().unwrap(), "hello");
+
+        // last client leaves; the room is removed
+        drop(second);
+        assert!(state.remove_room_if_unused("task-1"));
+        assert!(!state.rooms.read().contains_key("task-1"));
+    }
+
+    #[test]
+    fn test_join_racing_with_last_leave_keeps_room() {
+        let state = std::sync::Arc::new(AppState::new());
+        for _ in 0..200 {
+            let leaving = state.subscribe_room("task-1").1;
+            let joiner_state = state.clone();
+            let joiner = std::thread::spawn(move || joiner_state.subscribe_room("task-1"));
+            // the last client leaves while the new one is joining
+            drop(leaving);
+            state.remove_room_if_unused("task-1");
+            let (_tx, mut rx) = joiner.join().unwrap();
+
+            // a sender arriving later must reach the joined client
+            state.subscribe_room("task-1").0.send("hello".to_string()).unwrap();
+            assert_eq!(rx.try_recv().unwrap(), "hello");
+
+            drop(rx);
+            assert!(state.remove_room_if_unused("task-1"));
+        }
+    }
 }
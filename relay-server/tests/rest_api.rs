@@ -0,0 +1,136 @@
+use axum::body::Body;
+use axum::http::{Request, StatusCode};
+use relay_server::{router, AppState};
+use std::sync::Arc;
+use tower::ServiceExt;
+
+// The payload type workers and the UI actually send and parse
+#[allow(dead_code)]
+#[path = "../../src-tauri/src/relay_message.rs"]
+mod worker_message;
+
+use worker_message::{MessageData, MessagePayload};
+
+fn message(task_id: &str) -> String {
+    serde_json::to_string(&MessagePayload {
+        sender: "ci".to_string(),
+        task_id: task_id.to_string(),
+        msg_type: "command".to_string(),
+        action: Some("start_task".to_string()),
+        data: MessageData { prompt: Some("run tests".to_string()), ..Default::default() },
+    })
+    .unwrap()
+}
+
+fn post(task_id: &str, body: String) -> Request<Body> {
+    Request::builder()
+        .method("POST")
+        .uri(format!("/rooms/{}/messages", task_id))
+        .header("content-type", "application/json")
+        .body(Body::from(body))
+        .unwrap()
+}
+
+#[tokio::test]
+async fn test_post_message_to_missing_room_returns_404() {
+    let state = Arc::new(AppState::new());
+    let response = router(state).oneshot(post("task-1", message("task-1"))).await.unwrap();
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+async fn test_post_message_broadcasts_to_room() {
+    let state = Arc::new(AppState::new());
+    let mut rx = state.get_or_create_channel("task-1").subscribe();
+
+    let body = message("task-1");
+    let response = router(state.clone()).oneshot(post("task-1", body.clone())).await.unwrap();
+    assert_eq!(response.status(), StatusCode::ACCEPTED);
+    let broadcast = rx.recv().await.unwrap();
+    assert_eq!(broadcast, body);
+
+    // Workers must be able to parse what the relay broadcasts
+    let payload: MessagePayload = serde_json::from_str(&broadcast).unwrap();
+    assert_eq!(payload.msg_type, "command");
+    assert_eq!(payload.action.as_deref(), Some("start_task"));
+    assert_eq!(payload.data.prompt.as_deref(), Some("run tests"));
+}
+
+#[tokio::test]
+async fn test_post_message_with_msg_type_field_returns_400() {
+    let state = Arc::new(AppState::new());
+    let _rx = state.get_or_create_channel("task-1").subscribe();
+
+    let body = serde_json::json!({
+        "sender": "ci",
+        "task_id": "task-1",
+        "msg_type": "command",
+        "action": "start_task",
+        "data": {"prompt": "run tests"}
+    })
+    .to_string();
+    let response = router(state).oneshot(post("task-1", body)).await.unwrap();
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+}
+
+#[tokio::test]
+async fn test_post_invalid_message_returns_400() {
+    let state = Arc::new(AppState::new());
+    let _rx = state.get_or_create_channel("task-1").subscribe();
+
+    let response = router(state.clone()).oneshot(post("task-1", "not json".to_string())).await.unwrap();
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+    let response = router(state).oneshot(post("task-1", message("task-2"))).await.unwrap();
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+}
+
+#[tokio::test]
+async fn test_metrics_reports_rooms_and_relayed_count() {
+    let state = Arc::new(AppState::new());
+    let _rx = state.get_or_create_channel("task-1").subscribe();
+    state.get_or_create_channel("task-leaked");
+
+    let response = router(state.clone()).oneshot(post("task-1", message("task-1"))).await.unwrap();
+    assert_eq!(response.status(), StatusCode::ACCEPTED);
+
+    let request = Request::builder().uri("/metrics").body(Body::empty()).unwrap();
+    let response = router(state).oneshot(request).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+    let metrics: serde_json::Value = serde_json::from_slice(&body).unwrap();
+    assert_eq!(metrics["active_rooms"], 2);
+    assert_eq!(metrics["messages_relayed"], 1);
+    assert_eq!(metrics["rooms"][0]["task_id"], "task-1");
+    assert_eq!(metrics["rooms"][0]["receiver_count"], 1);
+    assert_eq!(metrics["rooms"][1]["receiver_count"], 0);
+}
+
+#[tokio::test]
+async fn test_token_required_when_configured() {
+    let state = Arc::new(AppState::new().with_auth_token(Some("s3cret".to_string())));
+    let _rx = state.get_or_create_channel("task-1").subscribe();
+
+    let response = router(state.clone()).oneshot(post("task-1", message("task-1"))).await.unwrap();
+    assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+
+    let mut request = post("task-1", message("task-1"));
+    request.headers_mut().insert("authorization", "Bearer wrong".parse().unwrap());
+    let response = router(state.clone()).oneshot(request).await.unwrap();
+    assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+
+    let mut request = post("task-1", message("task-1"));
+    request.headers_mut().insert("authorization", "Bearer s3cret".parse().unwrap());
+    let response = router(state.clone()).oneshot(request).await.unwrap();
+    assert_eq!(response.status(), StatusCode::ACCEPTED);
+
+    // REST routes only accept the Authorization header; ?token= is reserved for browser WebSockets
+    let mut request = post("task-1", message("task-1"));
+    *request.uri_mut() = "/rooms/task-1/messages?token=s3cret".parse().unwrap();
+    let response = router(state.clone()).oneshot(request).await.unwrap();
+    assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+
+    let request = Request::builder().uri("/ws/task-1").body(Body::empty()).unwrap();
+    let response = router(state).oneshot(request).await.unwrap();
+    assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+}
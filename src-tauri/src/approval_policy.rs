@@ -0,0 +1,113 @@
+// Auto-approval policies: regex rules evaluated by the CLI hook on PermissionRequest
+
+use rusqlite::params;
+use serde::{Deserialize, Serialize};
+
+/// 正则长度上限，与 CLI 侧保持一致
+const MAX_PATTERN_LEN: usize = 512;
+const REGEX_SIZE_LIMIT: usize = 1 << 20;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ApprovalPolicy {
+    pub id: i64,
+    pub tool: String,
+    pub regex_pattern: String,
+    pub action: String,
+    pub created_at: i64,
+}
+
+fn validate_policy(regex_pattern: &str, action: &str) -> Result<String, String> {
+    let action = action.trim().to_lowercase();
+    if !matches!(action.as_str(), "allow" | "deny" | "ask") {
+        return Err(format!("Invalid action '{}', expected allow/deny/ask", action));
+    }
+    if regex_pattern.trim().is_empty() {
+        return Err("Pattern must not be empty".to_string());
+    }
+    if regex_pattern.len() > MAX_PATTERN_LEN {
+        return Err(format!("Pattern too long ({} > {} chars)", regex_pattern.len(), MAX_PATTERN_LEN));
+    }
+    regex::RegexBuilder::new(regex_pattern)
+        .size_limit(REGEX_SIZE_LIMIT)
+        .build()
+        .map_err(|e| format!("Invalid regex '{}': {}", regex_pattern, e))?;
+    Ok(action)
+}
+
+#[tauri::command]
+pub fn list_approval_policies() -> Result<Vec<ApprovalPolicy>, String> {
+    let conn = crate::open_db()?;
+    let mut stmt = conn
+        .prepare(
+            "SELECT id, tool, regex_pattern, action, created_at FROM auto_approval_policies
+             ORDER BY id ASC",
+        )
+        .map_err(|e| e.to_string())?;
+    let rows = stmt
+        .query_map([], |row| {
+            Ok(ApprovalPolicy {
+                id: row.get(0)?,
+                tool: row.get(1)?,
+                regex_pattern: row.get(2)?,
+                action: row.get(3)?,
+                created_at: row.get(4)?,
+            })
+        })
+        .map_err(|e| e.to_string())?;
+
+    let mut policies = Vec::new();
+    for policy in rows {
+        policies.push(policy.map_err(|e| e.to_string())?);
+    }
+    Ok(policies)
+}
+
+/// 新增策略；tool 为空或 "*" 表示匹配所有工具
+#[tauri::command]
+pub fn add_approval_policy(tool: String, regex_pattern: String, action: String) -> Result<ApprovalPolicy, String> {
+    let action = validate_policy(&regex_pattern, &action)?;
+    let conn = crate::open_db()?;
+    let now = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map_err(|e| e.to_string())?
+        .as_secs() as i64;
+    let tool = tool.trim().to_string();
+
+    conn.execute(
+        "INSERT INTO auto_approval_policies (tool, regex_pattern, action, created_at) VALUES (?1, ?2, ?3, ?4)",
+        params![tool, regex_pattern, action, now],
+    )
+    .map_err(|e| e.to_string())?;
+
+    Ok(ApprovalPolicy {
+        id: conn.last_insert_rowid(),
+        tool,
+        regex_pattern,
+        action,
+        created_at: now,
+    })
+}
+
+#[tauri::command]
+pub fn update_approval_policy(id: i64, tool: String, regex_pattern: String, action: String) -> Result<(), String> {
+    let action = validate_policy(&regex_pattern, &action)?;
+    let conn = crate::open_db()?;
+    let updated = conn
+        .execute(
+            "UPDATE auto_approval_policies SET tool = ?1, regex_pattern = ?2, action = ?3 WHERE id = ?4",
+            params![tool.trim(), regex_pattern, action, id],
+        )
+        .map_err(|e| e.to_string())?;
+    if updated == 0 {
+        return Err(format!("Approval policy not found: {}", id));
+    }
+    Ok(())
+}
+
+#[tauri::command]
+pub fn delete_approval_policy(id: i64) -> Result<(), String> {
+    let conn = crate::open_db()?;
+    conn.execute("DELETE FROM auto_approval_policies WHERE id = ?1", params![id])
+        .map_err(|e| e.to_string())?;
+    Ok(())
+}
@@ -0,0 +1,411 @@
+// Editing sparky's entries in .claude/settings.local.json without touching other hooks
+
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+
+/// hook 安装位置：project 为 `<项目>/.claude/settings.local.json`，user 为 `~/.claude/settings.json`（对所有项目生效）
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum HookScope {
+    #[default]
+    Project,
+    User,
+}
+
+/// sparky 负责安装的 Claude Code hook 事件
+pub const SPARKY_EVENTS: [&str; 4] = ["Notification", "PermissionRequest", "Stop", "UserPromptSubmit"];
+
+/// 判断 hook 命令是否由 sparky 安装：与当前命令完全一致，或形如 `<...>/sparky hook`（兼容旧安装路径）
+pub fn is_sparky_command(command: &str, hook_command: &str) -> bool {
+    let command = command.trim();
+    if command == hook_command.trim() {
+        return true;
+    }
+    let Some(program) = command.strip_suffix(" hook") else {
+        return false;
+    };
+    let program = program.trim().trim_matches(|c| c == '"' || c == '\'');
+    // 设置文件可能来自其他平台，同时按 / 和 \ 切分路径
+    let file_name = program.rsplit(['/', '\\']).next().unwrap_or(program);
+    file_name == "sparky" || file_name.eq_ignore_ascii_case("sparky.exe")
+}
+
+fn hook_command_of(hook: &Value) -> Option<&str> {
+    hook.get("command").and_then(|v| v.as_str())
+}
+
+/// 从事件的 matcher 数组中删除命令满足 remove 的 hook，返回删除后是否为空
+fn strip_event(entries: &mut Value, remove: &dyn Fn(&str) -> bool) -> bool {
+    let Some(items) = entries.as_array_mut() else {
+        return false;
+    };
+    for entry in items.iter_mut() {
+        if let Some(hooks) = entry.get_mut("hooks").and_then(|v| v.as_array_mut()) {
+            hooks.retain(|hook| !hook_command_of(hook).map(remove).unwrap_or(false));
+        }
+    }
+    // 只删除因此变空的 matcher 项，原本就没有 hooks 数组的项保持原样
+    items.retain(|entry| {
+        entry
+            .get("hooks")
+            .and_then(|v| v.as_array())
+            .map(|hooks| !hooks.is_empty())
+            .unwrap_or(true)
+    });
+    items.is_empty()
+}
+
+/// 删除 settings 中 sparky 安装的 hook（包括旧版写在顶层的事件 key），保留其他工具和用户手写的 hook；
+/// 事件清空后删除该事件，hooks 对象清空后删除 hooks key
+pub fn remove_sparky_hooks(settings: &mut Value, hook_command: &str) {
+    let Some(obj) = settings.as_object_mut() else {
+        return;
+    };
+    let is_sparky = |command: &str| is_sparky_command(command, hook_command);
+
+    strip_legacy_events(obj, &is_sparky);
+
+    let hooks_empty = match obj.get_mut("hooks").and_then(|v| v.as_object_mut()) {
+        Some(hooks) => {
+            let events: Vec<String> = hooks.keys().cloned().collect();
+            for event in events {
+                if hooks.get_mut(&event).map(|v| strip_event(v, &is_sparky)).unwrap_or(false) {
+                    hooks.remove(&event);
+                }
+            }
+            hooks.is_empty()
+        }
+        None => false,
+    };
+    if hooks_empty {
+        obj.remove("hooks");
+    }
+}
+
+/// 旧版 sparky 把事件写在顶层（Claude Code 不读取），清理其中 sparky 的 hook
+fn strip_legacy_events(obj: &mut serde_json::Map<String, Value>, remove: &dyn Fn(&str) -> bool) {
+    for event in SPARKY_EVENTS {
+        if obj.get_mut(event).map(|v| strip_event(v, remove)).unwrap_or(false) {
+            obj.remove(event);
+        }
+    }
+}
+
+/// 把 sparky 的 hook 合并进 settings：只处理 sparky 的四个事件，已存在相同命令时不重复追加，
+/// 旧安装路径留下的 sparky 命令会被替换；其他事件、其他 hook 和无关 key 保持不变
+pub fn merge_sparky_hooks(settings: &mut Value, hook_command: &str) {
+    if !settings.is_object() {
+        *settings = json!({});
+    }
+    let obj = settings.as_object_mut().expect("settings is an object");
+    let is_stale = |command: &str| {
+        command.trim() != hook_command.trim() && is_sparky_command(command, hook_command)
+    };
+
+    strip_legacy_events(obj, &|command| is_sparky_command(command, hook_command));
+
+    let hooks = obj.entry("hooks").or_insert_with(|| json!({}));
+    if !hooks.is_object() {
+        *hooks = json!({});
+    }
+    let hooks = hooks.as_object_mut().expect("hooks is an object");
+
+    for event in SPARKY_EVENTS {
+        let entries = hooks.entry(event).or_insert_with(|| json!([]));
+        if !entries.is_array() {
+            *entries = json!([]);
+        }
+        strip_event(entries, &is_stale);
+        let items = entries.as_array_mut().expect("event entries is an array");
+        let installed = items.iter().any(|entry| {
+            entry
+                .get("hooks")
+                .and_then(|v| v.as_array())
+                .map(|hooks| {
+                    hooks
+                        .iter()
+                        .any(|hook| hook_command_of(hook).map(|c| c.trim() == hook_command.trim()).unwrap_or(false))
+                })
+                .unwrap_or(false)
+        });
+        if !installed {
+            items.push(json!({ "hooks": [{ "type": "command", "command": hook_command }] }));
+        }
+    }
+}
+
+/// sparky 的四个事件是否都已安装 sparky 的命令 hook；其他工具的 hook 不算在内
+pub fn has_sparky_hooks(settings: &Value, hook_command: &str) -> bool {
+    let Some(hooks) = settings.get("hooks").and_then(|v| v.as_object()) else {
+        return false;
+    };
+    SPARKY_EVENTS.iter().all(|event| {
+        hooks
+            .get(*event)
+            .and_then(|v| v.as_array())
+            .map(|entries| {
+                entries.iter().any(|entry| {
+                    entry
+                        .get("hooks")
+                        .and_then(|v| v.as_array())
+                        .map(|hooks| {
+                            hooks.iter().any(|hook| {
+                                hook.get("type").and_then(|v| v.as_str()) == Some("command")
+                                    && hook_command_of(hook).map(|c| is_sparky_command(c, hook_command)).unwrap_or(false)
+                            })
+                        })
+                        .unwrap_or(false)
+                })
+            })
+            .unwrap_or(false)
+    })
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ChangeKind {
+    Added,
+    Changed,
+    Removed,
+}
+
+/// 设置文件中一个 key 的变化；hooks 下按事件细分，key 形如 "hooks.Stop"
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct SettingsChange {
+    pub key: String,
+    pub kind: ChangeKind,
+}
+
+/// 比较修改前后的 settings，列出新增、修改、删除的顶层 key 与 hooks 下的事件
+pub fn diff_settings(before: &Value, after: &Value) -> Vec<SettingsChange> {
+    let empty = serde_json::Map::new();
+    let before_obj = before.as_object().unwrap_or(&empty);
+    let after_obj = after.as_object().unwrap_or(&empty);
+
+    let mut changes = Vec::new();
+    diff_objects(before_obj, after_obj, "", &mut changes);
+    changes
+}
+
+fn diff_objects(
+    before: &serde_json::Map<String, Value>,
+    after: &serde_json::Map<String, Value>,
+    prefix: &str,
+    changes: &mut Vec<SettingsChange>,
+) {
+    let mut push = |key: &str, kind| changes.push(SettingsChange { key: format!("{}{}", prefix, key), kind });
+    for (key, old) in before {
+        match after.get(key) {
+            None => push(key, ChangeKind::Removed),
+            Some(new) if new != old => {
+                if prefix.is_empty() && key == "hooks" && old.is_object() && new.is_object() {
+                    continue;
+                }
+                push(key, ChangeKind::Changed)
+            }
+            Some(_) => {}
+        }
+    }
+    for key in after.keys().filter(|key| !before.contains_key(*key)) {
+        if !(prefix.is_empty() && key == "hooks" && after[key].is_object()) {
+            push(key, ChangeKind::Added);
+        }
+    }
+
+    // hooks 按事件展开，方便界面展示具体改动了哪些事件
+    if prefix.is_empty() {
+        let empty = serde_json::Map::new();
+        let old_hooks = before.get("hooks").and_then(|v| v.as_object());
+        let new_hooks = after.get("hooks").and_then(|v| v.as_object());
+        if old_hooks.is_some() || new_hooks.is_some() {
+            diff_objects(
+                old_hooks.unwrap_or(&empty),
+                new_hooks.unwrap_or(&empty),
+                "hooks.",
+                changes,
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    const CMD: &str = "/Applications/Sparky.app/Contents/MacOS/sparky hook";
+
+    fn mixed_settings() -> Value {
+        json!({
+            "permissions": { "allow": ["Bash(ls:*)"] },
+            "Stop": [{ "hooks": [{ "type": "command", "command": "/old/target/debug/sparky hook" }] }],
+            "hooks": {
+                "Notification": [{ "hooks": [{ "type": "command", "command": CMD }] }],
+                "Stop": [{
+                    "hooks": [
+                        { "type": "command", "command": CMD },
+                        { "type": "command", "command": "say done" }
+                    ]
+                }],
+                "PreToolUse": [{
+                    "matcher": "Bash",
+                    "hooks": [{ "type": "command", "command": "./scripts/guard.sh" }]
+                }],
+                "UserPromptSubmit": [
+                    { "hooks": [{ "type": "command", "command": "sparky hook" }] },
+                    { "matcher": "", "hooks": [{ "type": "command", "command": "notify-send prompt" }] }
+                ]
+            }
+        })
+    }
+
+    #[test]
+    fn test_is_sparky_command() {
+        assert!(is_sparky_command(CMD, CMD));
+        assert!(is_sparky_command("sparky hook", CMD));
+        assert!(is_sparky_command("\"C:\\Program Files\\Sparky\\sparky.exe\" hook", CMD));
+        assert!(is_sparky_command("custom wrapper", "custom wrapper"));
+        assert!(!is_sparky_command("say done", CMD));
+        assert!(!is_sparky_command("sparkyish hook", CMD));
+        assert!(!is_sparky_command("/usr/bin/sparky notify", CMD));
+    }
+
+    #[test]
+    fn test_has_sparky_hooks() {
+        // 只有其他工具的 hook 时不算已安装
+        let foreign = json!({
+            "hooks": {
+                "Notification": [{ "hooks": [{ "type": "command", "command": "notify-send claude" }] }],
+                "PermissionRequest": [{ "hooks": [{ "type": "command", "command": "./guard.sh" }] }],
+                "Stop": [{ "hooks": [{ "type": "command", "command": "say done" }] }],
+                "UserPromptSubmit": [{ "hooks": [{ "type": "command", "command": "notify-send prompt" }] }]
+            }
+        });
+        assert!(!has_sparky_hooks(&foreign, CMD));
+
+        let mut settings = foreign.clone();
+        merge_sparky_hooks(&mut settings, CMD);
+        assert!(has_sparky_hooks(&settings, CMD));
+
+        // 卸载后其他工具的 hook 仍在，但不再视为已安装
+        remove_sparky_hooks(&mut settings, CMD);
+        assert_eq!(settings, foreign);
+        assert!(!has_sparky_hooks(&settings, CMD));
+        assert!(!has_sparky_hooks(&json!({}), CMD));
+    }
+
+    #[test]
+    fn test_remove_keeps_foreign_hooks() {
+        let mut settings = mixed_settings();
+        remove_sparky_hooks(&mut settings, CMD);
+        assert_eq!(
+            settings,
+            json!({
+                "permissions": { "allow": ["Bash(ls:*)"] },
+                "hooks": {
+                    "Stop": [{ "hooks": [{ "type": "command", "command": "say done" }] }],
+                    "PreToolUse": [{
+                        "matcher": "Bash",
+                        "hooks": [{ "type": "command", "command": "./scripts/guard.sh" }]
+                    }],
+                    "UserPromptSubmit": [
+                        { "matcher": "", "hooks": [{ "type": "command", "command": "notify-send prompt" }] }
+                    ]
+                }
+            })
+        );
+    }
+
+    #[test]
+    fn test_merge_keeps_existing_hooks() {
+        let mut settings = json!({
+            "permissions": { "allow": ["Bash(ls:*)"] },
+            "hooks": {
+                "PreToolUse": [{
+                    "matcher": "Bash",
+                    "hooks": [{ "type": "command", "command": "./scripts/guard.sh" }]
+                }],
+                "Notification": [{ "hooks": [{ "type": "command", "command": "notify-send claude" }] }],
+                "Stop": [{ "hooks": [{ "type": "command", "command": "/old/target/debug/sparky hook" }] }]
+            }
+        });
+        merge_sparky_hooks(&mut settings, CMD);
+
+        assert_eq!(settings["permissions"], json!({ "allow": ["Bash(ls:*)"] }));
+        assert_eq!(
+            settings["hooks"]["PreToolUse"],
+            json!([{ "matcher": "Bash", "hooks": [{ "type": "command", "command": "./scripts/guard.sh" }] }])
+        );
+        assert_eq!(
+            settings["hooks"]["Notification"],
+            json!([
+                { "hooks": [{ "type": "command", "command": "notify-send claude" }] },
+                { "hooks": [{ "type": "command", "command": CMD }] }
+            ])
+        );
+        // 旧路径的 sparky 命令被替换而不是重复
+        assert_eq!(settings["hooks"]["Stop"], json!([{ "hooks": [{ "type": "command", "command": CMD }] }]));
+        for event in SPARKY_EVENTS {
+            assert!(settings["hooks"][event].is_array(), "{} missing", event);
+        }
+
+        // 再次安装不会重复追加
+        let before = settings.clone();
+        merge_sparky_hooks(&mut settings, CMD);
+        assert_eq!(settings, before);
+
+        // 卸载后恢复到安装前的其他 hook
+        remove_sparky_hooks(&mut settings, CMD);
+        assert_eq!(
+            settings["hooks"]["Notification"],
+            json!([{ "hooks": [{ "type": "command", "command": "notify-send claude" }] }])
+        );
+        assert!(settings["hooks"].get("Stop").is_none());
+    }
+
+    #[test]
+    fn test_diff_settings() {
+        let before = json!({
+            "model": "opus",
+            "Stop": [{ "hooks": [{ "type": "command", "command": "sparky hook" }] }],
+            "hooks": {
+                "PreToolUse": [{ "hooks": [{ "type": "command", "command": "./guard.sh" }] }],
+                "Stop": [{ "hooks": [{ "type": "command", "command": "say done" }] }]
+            }
+        });
+        let mut after = before.clone();
+        merge_sparky_hooks(&mut after, CMD);
+
+        let mut changes: Vec<(String, ChangeKind)> =
+            diff_settings(&before, &after).into_iter().map(|c| (c.key, c.kind)).collect();
+        changes.sort_by(|a, b| a.0.cmp(&b.0));
+        assert_eq!(
+            changes,
+            vec![
+                ("Stop".to_string(), ChangeKind::Removed),
+                ("hooks.Notification".to_string(), ChangeKind::Added),
+                ("hooks.PermissionRequest".to_string(), ChangeKind::Added),
+                ("hooks.Stop".to_string(), ChangeKind::Changed),
+                ("hooks.UserPromptSubmit".to_string(), ChangeKind::Added),
+            ]
+        );
+        assert!(diff_settings(&after, &after).is_empty());
+        assert_eq!(
+            diff_settings(&json!({}), &json!({ "hooks": { "Stop": [] } })),
+            vec![SettingsChange { key: "hooks.Stop".to_string(), kind: ChangeKind::Added }]
+        );
+    }
+
+    #[test]
+    fn test_remove_drops_empty_hooks_key() {
+        let mut settings = json!({
+            "model": "opus",
+            "hooks": {
+                "Notification": [{ "hooks": [{ "type": "command", "command": CMD }] }],
+                "Stop": [{ "hooks": [{ "type": "command", "command": CMD }] }]
+            }
+        });
+        remove_sparky_hooks(&mut settings, CMD);
+        assert_eq!(settings, json!({ "model": "opus" }));
+    }
+}
@@ -0,0 +1,65 @@
+// Shared SQLite connection pool for the desktop app (~/sparky/hooks.db)
+
+use rusqlite::Connection;
+use std::path::PathBuf;
+use std::sync::OnceLock;
+use std::time::Duration;
+
+/// 连接池最大连接数（命令可能嵌套获取连接，留出余量）
+const POOL_MAX_SIZE: u32 = 8;
+/// 获取连接的最长等待时间
+const POOL_CONNECTION_TIMEOUT: Duration = Duration::from_secs(5);
+
+pub type DbPool = r2d2::Pool<SqliteConnectionManager>;
+pub type DbConn = r2d2::PooledConnection<SqliteConnectionManager>;
+
+/// r2d2 的 rusqlite 连接管理器
+pub struct SqliteConnectionManager {
+    path: PathBuf,
+}
+
+impl r2d2::ManageConnection for SqliteConnectionManager {
+    type Connection = Connection;
+    type Error = rusqlite::Error;
+
+    /// 每个连接都设置 WAL 与 busy_timeout，避免与 CLI hook 进程并发写入时报 SQLITE_BUSY
+    fn connect(&self) -> Result<Connection, rusqlite::Error> {
+        crate::migrations::open_connection(&self.path)
+    }
+
+    fn is_valid(&self, conn: &mut Connection) -> Result<(), rusqlite::Error> {
+        conn.execute_batch("SELECT 1")
+    }
+
+    fn has_broken(&self, _conn: &mut Connection) -> bool {
+        false
+    }
+}
+
+/// 进程内共享的连接池；首次使用时创建，并通过 init 完成一次性的 schema 迁移与 WAL 设置
+pub fn pool(
+    path: impl FnOnce() -> Result<PathBuf, String>,
+    init: impl FnOnce(&Connection) -> Result<(), String>,
+) -> Result<&'static DbPool, String> {
+    static POOL: OnceLock<DbPool> = OnceLock::new();
+    static INIT: std::sync::Mutex<()> = std::sync::Mutex::new(());
+
+    if let Some(pool) = POOL.get() {
+        return Ok(pool);
+    }
+    // 初始化失败时不缓存，下次调用重试
+    let _guard = INIT.lock().map_err(|e| e.to_string())?;
+    if let Some(pool) = POOL.get() {
+        return Ok(pool);
+    }
+    let pool = r2d2::Pool::builder()
+        .max_size(POOL_MAX_SIZE)
+        .connection_timeout(POOL_CONNECTION_TIMEOUT)
+        .build(SqliteConnectionManager { path: path()? })
+        .map_err(|e| e.to_string())?;
+    {
+        let conn = pool.get().map_err(|e| e.to_string())?;
+        init(&conn)?;
+    }
+    Ok(POOL.get_or_init(|| pool))
+}
@@ -0,0 +1,154 @@
+// End-to-end self check of the notification pipeline: DB -> config -> Feishu token -> receiver -> hooks
+
+use rusqlite::{params, OptionalExtension};
+use serde::Serialize;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DiagnosticStatus {
+    Pass,
+    Fail,
+    /// 依赖的前置步骤失败，未执行
+    Skip,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct DiagnosticStep {
+    pub name: String,
+    pub status: DiagnosticStatus,
+    pub message: String,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct DiagnosticsReport {
+    /// 所有步骤均通过
+    pub ok: bool,
+    pub steps: Vec<DiagnosticStep>,
+}
+
+struct Steps(Vec<DiagnosticStep>);
+
+impl Steps {
+    fn push(&mut self, name: &str, result: Result<String, String>) -> bool {
+        let (status, message) = match result {
+            Ok(message) => (DiagnosticStatus::Pass, message),
+            Err(message) => (DiagnosticStatus::Fail, message),
+        };
+        self.0.push(DiagnosticStep { name: name.to_string(), status, message });
+        status == DiagnosticStatus::Pass
+    }
+
+    fn skip(&mut self, name: &str, reason: &str) {
+        self.0.push(DiagnosticStep {
+            name: name.to_string(),
+            status: DiagnosticStatus::Skip,
+            message: reason.to_string(),
+        });
+    }
+}
+
+fn check_database() -> Result<String, String> {
+    let conn = crate::open_db()?;
+    let now = chrono::Utc::now().timestamp_millis();
+    conn.execute(
+        "INSERT INTO db_meta (key, value) VALUES ('diagnostics_last_run', ?1)
+         ON CONFLICT(key) DO UPDATE SET value = excluded.value",
+        params![now.to_string()],
+    )
+    .map_err(|e| format!("Database is not writable: {}", e))?;
+    Ok(format!("Database is writable ({})", crate::get_db_path()?.display()))
+}
+
+/// 项目级通知目标优先，其次是全局配置
+fn check_receiver(config: &crate::AppConfig, project_path: Option<&str>) -> Result<String, String> {
+    if let Some(path) = project_path {
+        let conn = crate::open_db()?;
+        let target: Option<(Option<String>, Option<String>)> = conn
+            .query_row(
+                "SELECT chat_id, open_id FROM projects WHERE path = ?1",
+                params![path],
+                |row| Ok((row.get(0)?, row.get(1)?)),
+            )
+            .optional()
+            .map_err(|e| e.to_string())?;
+        if let Some((chat_id, open_id)) = target {
+            if let Some(id) = chat_id.or(open_id).filter(|s| !s.trim().is_empty()) {
+                return Ok(format!("Project receiver configured: {}", id));
+            }
+        }
+    }
+    let global = [&config.chat_id, &config.open_id]
+        .into_iter()
+        .flatten()
+        .find(|s| !s.trim().is_empty());
+    match global {
+        Some(id) => Ok(format!("Receiver configured: {}", id)),
+        None => Err("No chat_id or open_id configured; notifications are recorded but never sent".to_string()),
+    }
+}
+
+fn check_hooks(project_path: Option<&str>) -> Result<String, String> {
+    if crate::check_user_hooks_installed()? {
+        return Ok("Hooks installed in ~/.claude/settings.json".to_string());
+    }
+    let path = project_path.ok_or("No active project and no user-level hooks installed")?;
+    if crate::check_hooks_installed_for_path(path)? {
+        Ok(format!("Hooks installed for {}", path))
+    } else {
+        Err(format!("Hooks are not installed for {}", path))
+    }
+}
+
+fn check_hook_binary() -> Result<String, String> {
+    let info = crate::get_hook_command()?;
+    match info.resolved_path {
+        Some(path) => Ok(format!("{} ({})", info.command, path)),
+        None => Err(format!(
+            "Hook binary for '{}' not found; build the sparky CLI or set CLAUDE_MONITOR_HOOK_COMMAND",
+            info.command
+        )),
+    }
+}
+
+/// 依次检查通知链路的每一环，返回每一步的结果；project_path 为空时使用配置中的 project_path
+#[tauri::command]
+pub async fn run_diagnostics(project_path: Option<String>) -> Result<DiagnosticsReport, String> {
+    let mut steps = Steps(Vec::new());
+
+    let db_ok = steps.push("database", check_database());
+    let config = if db_ok {
+        crate::get_config().unwrap_or_default()
+    } else {
+        crate::AppConfig::default()
+    };
+    let project_path = project_path
+        .or_else(|| config.project_path.clone())
+        .filter(|p| !p.trim().is_empty());
+
+    let credentials = if config.app_id.trim().is_empty() || config.app_secret.trim().is_empty() {
+        Err("app_id / app_secret is not configured".to_string())
+    } else {
+        Ok(format!("app_id {}", config.app_id))
+    };
+    if steps.push("credentials", credentials) {
+        let token = match crate::build_http_client(config.proxy_url.as_deref()) {
+            Ok(client) => crate::fetch_tenant_access_token(&client, &config.app_id, &config.app_secret)
+                .await
+                .map(|_| "Obtained tenant_access_token".to_string()),
+            Err(e) => Err(e),
+        };
+        steps.push("feishu_token", token);
+    } else {
+        steps.skip("feishu_token", "Skipped: credentials are missing");
+    }
+
+    steps.push("receiver", check_receiver(&config, project_path.as_deref()));
+    steps.push("hooks", check_hooks(project_path.as_deref()));
+    steps.push("hook_binary", check_hook_binary());
+
+    let steps = steps.0;
+    Ok(DiagnosticsReport {
+        ok: steps.iter().all(|s| s.status == DiagnosticStatus::Pass),
+        steps,
+    })
+}
@@ -0,0 +1,164 @@
+// Embedded relay: runs relay-server as a managed child process of the desktop app
+
+use std::collections::VecDeque;
+use std::io::{BufRead, BufReader};
+use std::process::{Child, Command, Stdio};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use tauri::Manager;
+
+/// 日志环形缓冲区最多保留的行数
+const MAX_LOG_LINES: usize = 2000;
+
+pub struct EmbeddedRelayState {
+    child: Mutex<Option<Child>>,
+    port: Mutex<Option<u16>>,
+    logs: Arc<Mutex<VecDeque<String>>>,
+}
+
+impl EmbeddedRelayState {
+    pub fn new() -> Self {
+        EmbeddedRelayState {
+            child: Mutex::new(None),
+            port: Mutex::new(None),
+            logs: Arc::new(Mutex::new(VecDeque::new())),
+        }
+    }
+
+    fn is_running(&self) -> bool {
+        let mut child = self.child.lock().unwrap();
+        match child.as_mut().map(|c| c.try_wait()) {
+            Some(Ok(None)) => true,
+            Some(_) => {
+                *child = None;
+                false
+            }
+            None => false,
+        }
+    }
+
+    /// 停止子进程（App 退出时也会调用）
+    pub fn shutdown(&self) {
+        if let Some(mut child) = self.child.lock().unwrap().take() {
+            let _ = child.kill();
+            let _ = child.wait();
+            log::info!("[relay] embedded relay stopped (pid={})", child.id());
+        }
+        *self.port.lock().unwrap() = None;
+    }
+}
+
+fn push_log(logs: &Arc<Mutex<VecDeque<String>>>, line: String) {
+    let mut logs = logs.lock().unwrap();
+    if logs.len() >= MAX_LOG_LINES {
+        logs.pop_front();
+    }
+    logs.push_back(line);
+}
+
+/// 查找 relay-server 可执行文件：环境变量 > 与 App 同目录 > 仓库 target 目录 > PATH
+fn resolve_relay_binary() -> String {
+    if let Ok(bin) = std::env::var("SPARKY_RELAY_BIN") {
+        if !bin.trim().is_empty() {
+            return bin;
+        }
+    }
+
+    let bin_name = if cfg!(windows) { "relay-server.exe" } else { "relay-server" };
+    if let Ok(exe_path) = std::env::current_exe() {
+        if let Some(dir) = exe_path.parent() {
+            let sibling = dir.join(bin_name);
+            if sibling.exists() {
+                return sibling.to_string_lossy().to_string();
+            }
+        }
+
+        let mut current = exe_path.parent();
+        while let Some(dir) = current {
+            if dir.file_name().map(|name| name == "src-tauri").unwrap_or(false) {
+                if let Some(root) = dir.parent() {
+                    for profile in ["debug", "release"] {
+                        let path = root.join("relay-server").join("target").join(profile).join(bin_name);
+                        if path.exists() {
+                            return path.to_string_lossy().to_string();
+                        }
+                    }
+                }
+                break;
+            }
+            current = dir.parent();
+        }
+    }
+
+    bin_name.to_string()
+}
+
+fn spawn_log_reader<R: std::io::Read + Send + 'static>(
+    reader: R,
+    stream: &'static str,
+    logs: Arc<Mutex<VecDeque<String>>>,
+) {
+    thread::spawn(move || {
+        for line in BufReader::new(reader).lines() {
+            match line {
+                Ok(line) => push_log(&logs, format!("[{}] {}", stream, line)),
+                Err(_) => break,
+            }
+        }
+    });
+}
+
+#[tauri::command]
+pub fn start_embedded_relay(app: tauri::AppHandle, port: u16, strict: Option<bool>) -> Result<String, String> {
+    let state = app.state::<EmbeddedRelayState>();
+    if state.is_running() {
+        let current = state.port.lock().unwrap().unwrap_or(port);
+        return Err(format!("Embedded relay already running on port {}", current));
+    }
+
+    let binary = resolve_relay_binary();
+    log::info!("[relay] starting embedded relay: {} --port {}", binary, port);
+
+    let mut command = Command::new(&binary);
+    command.arg("--port").arg(port.to_string());
+    // strict 模式下 relay 会校验消息结构并丢弃非法消息
+    if strict.unwrap_or(false) {
+        command.arg("--strict");
+    }
+    let mut child = command
+        .env("NO_COLOR", "1")
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|e| format!("Failed to start relay-server ({}): {}", binary, e))?;
+
+    state.logs.lock().unwrap().clear();
+    if let Some(stdout) = child.stdout.take() {
+        spawn_log_reader(stdout, "stdout", state.logs.clone());
+    }
+    if let Some(stderr) = child.stderr.take() {
+        spawn_log_reader(stderr, "stderr", state.logs.clone());
+    }
+
+    *state.child.lock().unwrap() = Some(child);
+    *state.port.lock().unwrap() = Some(port);
+
+    Ok(format!("ws://127.0.0.1:{}", port))
+}
+
+#[tauri::command]
+pub fn stop_embedded_relay(app: tauri::AppHandle) -> Result<(), String> {
+    let state = app.state::<EmbeddedRelayState>();
+    state.shutdown();
+    Ok(())
+}
+
+/// 返回最近的 relay 日志（limit 为空时返回全部缓冲）
+#[tauri::command]
+pub fn get_relay_logs(app: tauri::AppHandle, limit: Option<usize>) -> Vec<String> {
+    let state = app.state::<EmbeddedRelayState>();
+    let logs = state.logs.lock().unwrap();
+    let skip = limit.map(|n| logs.len().saturating_sub(n)).unwrap_or(0);
+    logs.iter().skip(skip).cloned().collect()
+}
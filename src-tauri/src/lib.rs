@@ -4,24 +4,87 @@ use std::path::PathBuf;
 use std::sync::Arc;
 use std::sync::atomic::AtomicBool;
 use tokio::sync::{mpsc, Mutex};
-use rusqlite::{params, Connection};
+use tauri::{Emitter, Manager};
+use rusqlite::{params, Connection, OptionalExtension};
 use tracing::{info, warn, error, debug};
 
 mod websocket;
 use websocket::FeishuWsClient;
 
 mod pty;
-use pty::{PtyManager, pty_spawn, pty_write, pty_kill, pty_resize, pty_exists};
+use pty::{PtyManager, pty_spawn, pty_write, pty_kill, pty_resize, pty_exists, pty_get_scrollback, pty_list};
+
+mod relay_message;
 
 mod relay_client;
-pub use relay_client::{start_local_worker, stop_local_worker};
+pub use relay_client::{start_local_worker, stop_local_worker, get_worker_status};
 
 mod remote_worker;
-pub use remote_worker::{start_remote_worker, stop_remote_worker, configure_sandbox, VfsMapping, SandboxConfig};
+pub use remote_worker::{start_remote_worker, stop_remote_worker, configure_sandbox, get_sandbox_config, VfsMapping, SandboxConfig};
 
 mod config;
 pub use config::{Config, load_config};
 
+mod task_log;
+use task_log::{get_task_log, export_task_log};
+
+mod reconnect;
+use reconnect::force_reconnect_now;
+
+mod ws_uptime;
+use ws_uptime::get_ws_uptime_stats;
+
+mod approval_policy;
+use approval_policy::{list_approval_policies, add_approval_policy, update_approval_policy, delete_approval_policy};
+
+mod embedded_relay;
+use embedded_relay::{EmbeddedRelayState, start_embedded_relay, stop_embedded_relay, get_relay_logs};
+
+// 与 CLI 共用同一份密钥位置与密文格式，两端可以解密对方写入的密钥
+#[path = "../../src/secret.rs"]
+mod secret;
+
+mod db_pool;
+
+mod claude_hooks;
+
+mod diagnostics;
+use diagnostics::run_diagnostics;
+
+// 与 CLI 共用同一份迁移定义，避免两端 schema 不一致
+#[path = "../../src/migrations.rs"]
+mod migrations;
+
+#[path = "../../src/project_tables.rs"]
+mod project_tables;
+
+#[path = "../../src/dnd.rs"]
+mod dnd;
+
+#[path = "../../src/redact.rs"]
+mod redact;
+
+#[path = "../../src/wss_status.rs"]
+mod wss_status;
+
+#[path = "../../src/card_choice.rs"]
+mod card_choice;
+
+#[path = "../../src/ws_fragments.rs"]
+mod ws_fragments;
+
+#[path = "../../src/feishu_apps.rs"]
+mod feishu_apps;
+
+#[path = "../../src/ws_connection_log.rs"]
+mod ws_connection_log;
+
+#[path = "../../src/ws_proxy.rs"]
+mod ws_proxy;
+
+#[path = "../../src/text.rs"]
+mod text;
+
 pub struct WsConnectionState(pub Arc<AtomicBool>);
 
 #[tauri::command]
@@ -40,6 +103,15 @@ pub struct AppConfig {
     pub project_path: Option<String>,
     pub open_id: Option<String>,
     pub hook_events_filter: Option<String>,
+    pub proxy_url: Option<String>,
+    pub urgent_events: Option<String>,
+    pub message_style: Option<String>,
+    pub max_notifications_per_session_per_minute: Option<u32>,
+    pub backend: Option<String>,
+    pub max_message_len: Option<u32>,
+    pub max_db_content_len: Option<u32>,
+    pub max_response_chars: Option<u32>,
+    pub max_transcript_chars: Option<u32>,
 }
 
 impl Default for AppConfig {
@@ -54,6 +126,15 @@ impl Default for AppConfig {
             project_path: None,
             open_id: None,
             hook_events_filter: None,
+            proxy_url: None,
+            urgent_events: None,
+            message_style: None,
+            max_notifications_per_session_per_minute: None,
+            backend: None,
+            max_message_len: None,
+            max_db_content_len: None,
+            max_response_chars: None,
+            max_transcript_chars: None,
         }
     }
 }
@@ -128,6 +209,10 @@ pub struct MentionId {
 pub struct AppState {
     pub config: Arc<Mutex<Option<AppConfig>>>,
     pub event_tx: mpsc::Sender<String>,
+    /// 当前运行中的飞书 WSS 客户端，reconnect_websocket 通过它断开旧连接
+    pub ws_client: std::sync::Mutex<Option<Arc<FeishuWsClient>>>,
+    /// 未配置凭证时 WSS 任务在此等待，配置完成后由 reconnect_websocket 唤醒
+    pub ws_restart: tokio::sync::Notify,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -139,13 +224,18 @@ pub struct HookRecord {
     pub transcript_path: String,
     pub content: String,
     pub result: String,
+    /// Unix 时间戳（毫秒），与 CLI 写入端一致
     pub created_at: i64,
+    /// 工具类事件（PreToolUse / PermissionRequest 等）的工具名
+    pub tool_name: Option<String>,
+    pub permission_mode: Option<String>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct HookStatus {
     pub last_event_name: Option<String>,
     pub last_result: Option<String>,
+    /// Unix 时间戳（毫秒）
     pub last_event_at: Option<i64>,
 }
 
@@ -154,223 +244,63 @@ pub struct Project {
     pub id: i64,
     pub name: String,
     pub path: String,
+    /// 项目级或用户级任一处安装了 hook
     pub hooks_installed: bool,
+    /// hook 安装在 ~/.claude/settings.json（对所有项目生效）
+    pub user_hooks_installed: bool,
     pub created_at: i64,
     pub updated_at: i64,
+    /// 项目级通知目标，为空时使用全局配置的 chat_id / open_id
+    pub chat_id: Option<String>,
+    pub open_id: Option<String>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct WssStatus {
+    /// 最近一次收到 WSS 帧的时间（毫秒）
     pub last_receive_time: Option<i64>,
+    /// 最近一次给机器人发消息的用户 open_id
     pub last_open_id: Option<String>,
 }
 
 fn get_db_path() -> Result<PathBuf, String> {
-    let base_dir = dirs::home_dir()
-        .ok_or_else(|| "Failed to get home directory".to_string())?
-        .join("sparky");
-    fs::create_dir_all(&base_dir)
-        .map_err(|e| format!("Failed to create base directory: {}", e))?;
-    Ok(base_dir.join("hooks.db"))
+    // 测试进程使用临时数据库，不写入用户的 ~/sparky/hooks.db（如 worker 消息会经 task_log 落库）
+    if cfg!(test) {
+        return Ok(std::env::temp_dir().join(format!("sparky-test-{}.db", std::process::id())));
+    }
+    migrations::db_path().map_err(|e| format!("Failed to create base directory: {}", e))
 }
 
+/// schema 由与 CLI 共用的 migrations 模块维护（按 PRAGMA user_version 增量迁移）
 fn init_db(conn: &Connection) -> rusqlite::Result<()> {
-    // 创建项目表
-    conn.execute(
-        "CREATE TABLE IF NOT EXISTS projects (
-            id INTEGER PRIMARY KEY AUTOINCREMENT,
-            name TEXT NOT NULL,
-            path TEXT NOT NULL,
-            hooks_installed INTEGER DEFAULT 0,
-            created_at INTEGER NOT NULL,
-            updated_at INTEGER NOT NULL
-        )",
-        [],
-    )?;
-
-    conn.execute(
-        "CREATE TABLE IF NOT EXISTS pty_commands (
-            id INTEGER PRIMARY KEY,
-            project_path TEXT NOT NULL,
-            command TEXT NOT NULL,
-            processed INTEGER DEFAULT 0,
-            created_at INTEGER
-        )",
-        [],
-    )?;
-
-    conn.execute(
-        "CREATE TABLE IF NOT EXISTS permission_requests (
-            id INTEGER PRIMARY KEY,
-            project_path TEXT NOT NULL,
-            status TEXT NOT NULL,
-            choice TEXT,
-            created_at INTEGER
-        )",
-        [],
-    )?;
-
-    conn.execute(
-        "CREATE TABLE IF NOT EXISTS terminal_input_history (
-            id INTEGER PRIMARY KEY AUTOINCREMENT,
-            project_path TEXT NOT NULL,
-            input TEXT NOT NULL,
-            created_at INTEGER NOT NULL
-        )",
-        [],
-    )?;
-
-    conn.execute(
-        "CREATE TABLE IF NOT EXISTS terminal_history (
-            id INTEGER PRIMARY KEY AUTOINCREMENT,
-            project_path TEXT NOT NULL,
-            kind TEXT NOT NULL,
-            content TEXT NOT NULL,
-            created_at INTEGER NOT NULL
-        )",
-        [],
-    )?;
-
-    conn.execute(
-        "CREATE TABLE IF NOT EXISTS app_config_feishu (
-            id INTEGER PRIMARY KEY CHECK (id = 1),
-            app_id TEXT NOT NULL,
-            app_secret TEXT NOT NULL,
-            encrypt_key TEXT,
-            verification_token TEXT,
-            chat_id TEXT,
-            project_path TEXT,
-            open_id TEXT,
-            hook_events_filter TEXT,
-            updated_at INTEGER NOT NULL
-        )",
-        [],
-    )?;
-
-    // 迁移：给已存在的表添加 open_id 列
-    let _ = conn.execute("ALTER TABLE app_config_feishu ADD COLUMN open_id TEXT", []);
-    let _ = conn.execute("ALTER TABLE app_config_feishu ADD COLUMN hook_events_filter TEXT", []);
-    let _ = conn.execute("ALTER TABLE app_config_feishu ADD COLUMN app_name TEXT", []);
-
-    conn.execute(
-        "CREATE TABLE IF NOT EXISTS app_config_dingtalk (
-            id INTEGER PRIMARY KEY CHECK (id = 1),
-            app_id TEXT NOT NULL,
-            app_secret TEXT NOT NULL,
-            encrypt_key TEXT,
-            verification_token TEXT,
-            chat_id TEXT,
-            project_path TEXT,
-            updated_at INTEGER NOT NULL
-        )",
-        [],
-    )?;
-
-    conn.execute(
-        "CREATE TABLE IF NOT EXISTS app_config_wework (
-            id INTEGER PRIMARY KEY CHECK (id = 1),
-            app_id TEXT NOT NULL,
-            app_secret TEXT NOT NULL,
-            encrypt_key TEXT,
-            verification_token TEXT,
-            chat_id TEXT,
-            project_path TEXT,
-            updated_at INTEGER NOT NULL
-        )",
-        [],
-    )?;
-
-    conn.execute(
-        "CREATE TABLE IF NOT EXISTS db_meta (
-            key TEXT PRIMARY KEY,
-            value TEXT NOT NULL
-        )",
-        [],
-    )?;
-
-    Ok(())
-}
-
-pub(crate) fn open_db() -> Result<Connection, String> {
-    let conn = Connection::open(get_db_path()?).map_err(|e| e.to_string())?;
-    init_db(&conn).map_err(|e| e.to_string())?;
-    cleanup_legacy_data(&conn)?;
-    migrate_app_config_table(&conn)?;
-    Ok(conn)
-}
-
-fn project_hooks_table_name(project_path: &str) -> String {
-    let mut hash: u64 = 14695981039346656037;
-    for byte in project_path.as_bytes() {
-        hash ^= *byte as u64;
-        hash = hash.wrapping_mul(1099511628211);
-    }
-    format!("hook_records_{:x}", hash)
+    migrations::run(conn)
 }
 
-fn ensure_project_hooks_table(conn: &Connection, table_name: &str) -> Result<(), String> {
-    let sql = format!(
-        "CREATE TABLE IF NOT EXISTS {} (
-            id INTEGER PRIMARY KEY AUTOINCREMENT,
-            event_name TEXT NOT NULL,
-            session_id TEXT NOT NULL,
-            notification_text TEXT NOT NULL,
-            transcript_path TEXT NOT NULL,
-            content TEXT NOT NULL,
-            result TEXT NOT NULL,
-            created_at INTEGER NOT NULL
-        )",
-        table_name
-    );
-    conn.execute(&sql, []).map_err(|e| e.to_string())?;
-    ensure_session_id_column(conn, table_name)?;
-    Ok(())
+/// 从共享连接池借出一个连接；schema 迁移只在连接池创建时执行一次
+pub(crate) fn open_db() -> Result<db_pool::DbConn, String> {
+    let pool = db_pool::pool(get_db_path, |conn| {
+        init_db(conn).map_err(|e| e.to_string())?;
+        migrate_app_config_table(conn)?;
+        secret::migrate_plaintext_secrets(conn)
+    })?;
+    pool.get().map_err(|e| e.to_string())
 }
 
-fn ensure_session_id_column(conn: &Connection, table_name: &str) -> Result<(), String> {
-    let pragma_sql = format!("PRAGMA table_info({})", table_name);
-    let mut stmt = conn.prepare(&pragma_sql).map_err(|e| e.to_string())?;
-    let rows = stmt
-        .query_map([], |row| row.get::<_, String>(1))
-        .map_err(|e| e.to_string())?;
-    let mut has_session = false;
-    for row in rows {
-        if row.map_err(|e| e.to_string())? == "session_id" {
-            has_session = true;
-            break;
+/// 表名映射与表结构与 CLI 共用，见 project_tables 模块；记录表只由 CLI 创建和登记，
+/// 桌面端只查询已存在的表，项目还没有记录时返回 None
+fn existing_project_hooks_table(conn: &Connection, project_path: &str) -> Result<Option<String>, String> {
+    let table_name = project_tables::lookup_project_hooks_table(conn, project_path).map_err(|e| e.to_string())?;
+    match table_name {
+        Some(table_name) if table_exists(conn, &table_name)? => {
+            ensure_project_hooks_table(conn, &table_name)?;
+            Ok(Some(table_name))
         }
+        _ => Ok(None),
     }
-    if !has_session {
-        let alter_sql = format!(
-            "ALTER TABLE {} ADD COLUMN session_id TEXT NOT NULL DEFAULT ''",
-            table_name
-        );
-        conn.execute(&alter_sql, []).map_err(|e| e.to_string())?;
-    }
-    Ok(())
 }
 
-fn cleanup_legacy_data(conn: &Connection) -> Result<(), String> {
-    let cleaned: Result<String, _> = conn.query_row(
-        "SELECT value FROM db_meta WHERE key = 'cleanup_legacy_v1'",
-        [],
-        |row| row.get(0),
-    );
-    if cleaned.is_ok() {
-        return Ok(());
-    }
-    conn.execute("DROP TABLE IF EXISTS hook_records", [])
-        .map_err(|e| e.to_string())?;
-    conn.execute("DELETE FROM terminal_history", [])
-        .map_err(|e| e.to_string())?;
-    conn.execute("DELETE FROM terminal_input_history", [])
-        .map_err(|e| e.to_string())?;
-    conn.execute(
-        "INSERT INTO db_meta (key, value) VALUES ('cleanup_legacy_v1', '1')",
-        [],
-    )
-    .map_err(|e| e.to_string())?;
-    Ok(())
+fn ensure_project_hooks_table(conn: &Connection, table_name: &str) -> Result<(), String> {
+    project_tables::ensure_project_hooks_table(conn, table_name).map_err(|e| e.to_string())
 }
 
 fn table_exists(conn: &Connection, table_name: &str) -> Result<bool, String> {
@@ -405,6 +335,15 @@ fn load_config_from_table(conn: &Connection, table_name: &str) -> Result<Option<
             open_id: None,
             hook_events_filter: None,
             app_name: None,
+            proxy_url: None,
+            urgent_events: None,
+            message_style: None,
+            max_notifications_per_session_per_minute: None,
+            backend: None,
+            max_message_len: None,
+            max_db_content_len: None,
+            max_response_chars: None,
+            max_transcript_chars: None,
         }))
     } else {
         Ok(None)
@@ -428,7 +367,8 @@ fn migrate_app_config_table(conn: &Connection) -> Result<(), String> {
 fn load_config_from_db(conn: &Connection) -> Result<Option<AppConfig>, String> {
     let mut stmt = conn
         .prepare(
-            "SELECT app_id, app_secret, encrypt_key, verification_token, chat_id, project_path, open_id, hook_events_filter, app_name
+            "SELECT app_id, app_secret, encrypt_key, verification_token, chat_id, project_path, open_id, hook_events_filter, app_name, proxy_url, urgent_events, message_style, max_notifications_per_session_per_minute, backend,
+                    max_message_len, max_db_content_len, max_response_chars, max_transcript_chars
              FROM app_config_feishu WHERE id = 1",
         )
         .map_err(|e| e.to_string())?;
@@ -436,14 +376,23 @@ fn load_config_from_db(conn: &Connection) -> Result<Option<AppConfig>, String> {
     if let Some(row) = rows.next().map_err(|e| e.to_string())? {
         Ok(Some(AppConfig {
             app_id: row.get(0).map_err(|e| e.to_string())?,
-            app_secret: row.get(1).map_err(|e| e.to_string())?,
-            encrypt_key: row.get(2).map_err(|e| e.to_string())?,
-            verification_token: row.get(3).map_err(|e| e.to_string())?,
+            app_secret: secret::decrypt_secret(&row.get::<_, String>(1).map_err(|e| e.to_string())?)?,
+            encrypt_key: secret::decrypt_optional(row.get(2).map_err(|e| e.to_string())?)?,
+            verification_token: secret::decrypt_optional(row.get(3).map_err(|e| e.to_string())?)?,
             chat_id: row.get(4).map_err(|e| e.to_string())?,
             project_path: row.get(5).map_err(|e| e.to_string())?,
             open_id: row.get(6).map_err(|e| e.to_string())?,
             hook_events_filter: row.get(7).map_err(|e| e.to_string())?,
             app_name: row.get(8).map_err(|e| e.to_string())?,
+            proxy_url: row.get(9).map_err(|e| e.to_string())?,
+            urgent_events: row.get(10).map_err(|e| e.to_string())?,
+            message_style: row.get(11).map_err(|e| e.to_string())?,
+            max_notifications_per_session_per_minute: row.get(12).map_err(|e| e.to_string())?,
+            backend: row.get(13).map_err(|e| e.to_string())?,
+            max_message_len: row.get(14).map_err(|e| e.to_string())?,
+            max_db_content_len: row.get(15).map_err(|e| e.to_string())?,
+            max_response_chars: row.get(16).map_err(|e| e.to_string())?,
+            max_transcript_chars: row.get(17).map_err(|e| e.to_string())?,
         }))
     } else {
         Ok(None)
@@ -456,8 +405,9 @@ fn upsert_config(conn: &Connection, config: &AppConfig) -> Result<(), String> {
         .map_err(|e| e.to_string())?
         .as_secs() as i64;
     conn.execute(
-        "INSERT INTO app_config_feishu (id, app_id, app_secret, encrypt_key, verification_token, chat_id, project_path, open_id, hook_events_filter, app_name, updated_at)
-         VALUES (1, ?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10)
+        "INSERT INTO app_config_feishu (id, app_id, app_secret, encrypt_key, verification_token, chat_id, project_path, open_id, hook_events_filter, app_name, proxy_url, urgent_events, message_style, max_notifications_per_session_per_minute, backend,
+                                         max_message_len, max_db_content_len, max_response_chars, max_transcript_chars, updated_at)
+         VALUES (1, ?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15, ?16, ?17, ?18, ?19)
          ON CONFLICT(id) DO UPDATE SET
            app_id = excluded.app_id,
            app_secret = excluded.app_secret,
@@ -468,17 +418,35 @@ fn upsert_config(conn: &Connection, config: &AppConfig) -> Result<(), String> {
            project_path = excluded.project_path,
            open_id = COALESCE(excluded.open_id, app_config_feishu.open_id),
            hook_events_filter = excluded.hook_events_filter,
+           proxy_url = excluded.proxy_url,
+           urgent_events = COALESCE(excluded.urgent_events, app_config_feishu.urgent_events),
+           message_style = COALESCE(excluded.message_style, app_config_feishu.message_style),
+           max_notifications_per_session_per_minute = COALESCE(excluded.max_notifications_per_session_per_minute, app_config_feishu.max_notifications_per_session_per_minute),
+           backend = COALESCE(excluded.backend, app_config_feishu.backend),
+           max_message_len = COALESCE(excluded.max_message_len, app_config_feishu.max_message_len),
+           max_db_content_len = COALESCE(excluded.max_db_content_len, app_config_feishu.max_db_content_len),
+           max_response_chars = COALESCE(excluded.max_response_chars, app_config_feishu.max_response_chars),
+           max_transcript_chars = COALESCE(excluded.max_transcript_chars, app_config_feishu.max_transcript_chars),
            updated_at = excluded.updated_at",
         params![
             config.app_id,
-            config.app_secret,
-            config.encrypt_key,
-            config.verification_token,
+            secret::encrypt_secret(&config.app_secret)?,
+            secret::encrypt_optional(config.encrypt_key.as_deref())?,
+            secret::encrypt_optional(config.verification_token.as_deref())?,
             config.chat_id,
             config.project_path,
             config.open_id,
             config.hook_events_filter,
             config.app_name,
+            config.proxy_url,
+            config.urgent_events,
+            config.message_style,
+            config.max_notifications_per_session_per_minute,
+            config.backend,
+            config.max_message_len,
+            config.max_db_content_len,
+            config.max_response_chars,
+            config.max_transcript_chars,
             now
         ],
     )
@@ -486,6 +454,84 @@ fn upsert_config(conn: &Connection, config: &AppConfig) -> Result<(), String> {
     Ok(())
 }
 
+/// 权限请求处理后的状态：1 → allowed，2 → always（始终允许），3 → rejected（与 CLI 保持一致）
+pub(crate) fn permission_status_for_choice(choice: &str) -> &'static str {
+    match choice {
+        "1" => "allowed",
+        "2" => "always",
+        "3" => "rejected",
+        _ => "completed",
+    }
+}
+
+/// 发给 relay worker 的权限决定，保留 "始终允许" 语义
+pub(crate) fn permission_decision_for_choice(choice: &str) -> &'static str {
+    match choice {
+        "1" => "approve",
+        "2" => "always",
+        _ => "reject",
+    }
+}
+
+/// 配对码对应的 pending 权限请求所属的项目路径
+pub(crate) fn pending_permission_project(code: &str) -> Result<String, String> {
+    let conn = open_db()?;
+    conn.query_row(
+        "SELECT project_path FROM permission_requests
+         WHERE code = ?1 AND status = 'pending'
+         ORDER BY created_at DESC LIMIT 1",
+        params![code],
+        |row| row.get(0),
+    )
+    .optional()
+    .map_err(|e| e.to_string())?
+    .ok_or_else(|| format!("No pending permission request for code {}", code))
+}
+
+/// 按配对码处理一条 pending 权限请求并记录选择，返回项目路径；
+/// queue_pty 为 true 时同时写入该项目的 PTY 命令队列（决定改由 relay worker 接收时为 false）
+pub(crate) fn resolve_permission_by_code(code: &str, choice: &str, queue_pty: bool) -> Result<String, String> {
+    let mut conn = open_db()?;
+    let pending: Option<(i64, String)> = conn
+        .query_row(
+            "SELECT id, project_path FROM permission_requests
+             WHERE code = ?1 AND status = 'pending'
+             ORDER BY created_at DESC LIMIT 1",
+            params![code],
+            |row| Ok((row.get(0)?, row.get(1)?)),
+        )
+        .optional()
+        .map_err(|e| e.to_string())?;
+    let (id, project_path) = pending.ok_or_else(|| format!("No pending permission request for code {}", code))?;
+
+    let now = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map_err(|e| e.to_string())?
+        .as_secs() as i64;
+    let tx = conn.transaction().map_err(|e| e.to_string())?;
+    let updated = tx
+        .execute(
+            "UPDATE permission_requests SET status = ?1, choice = ?2
+             WHERE id = ?3 AND status = 'pending'",
+            params![permission_status_for_choice(choice), choice, id],
+        )
+        .map_err(|e| e.to_string())?;
+    if updated == 0 {
+        return Err(format!("Permission request for code {} is no longer pending", code));
+    }
+    if queue_pty {
+        tx.execute(
+            "INSERT INTO pty_commands (project_path, command, created_at) VALUES (?1, ?2, ?3)",
+            params![project_path, choice, now],
+        )
+        .map_err(|e| e.to_string())?;
+    }
+    tx.commit().map_err(|e| e.to_string())?;
+
+    log::info!("[permission] code={} resolved with choice={} for project={}", code, choice, project_path);
+    Ok(project_path)
+}
+
 /// 单独更新 open_id 到 SQLite（供 WebSocket 回调使用）
 fn save_open_id_to_db(open_id: &str) -> Result<(), String> {
     let conn = open_db()?;
@@ -531,14 +577,47 @@ fn record_terminal_input(project_path: String, input: String) -> Result<(), Stri
     Ok(())
 }
 
+/// 各项目尚未入库的最后一段未完成行：PTY 可能把一个 token 切在两块输出之间，
+/// 等到换行后整行脱敏，避免两半各自都不匹配规则
+fn terminal_output_carry() -> &'static std::sync::Mutex<std::collections::HashMap<String, String>> {
+    static CARRY: std::sync::OnceLock<std::sync::Mutex<std::collections::HashMap<String, String>>> =
+        std::sync::OnceLock::new();
+    CARRY.get_or_init(|| std::sync::Mutex::new(std::collections::HashMap::new()))
+}
+
+/// 缓存编译好的脱敏器，终端输出每块都要脱敏，不必每次读库、编译白名单；set_redact_config 时刷新
+fn redact_cache() -> &'static std::sync::RwLock<Option<Arc<redact::Redactor>>> {
+    static CACHE: std::sync::OnceLock<std::sync::RwLock<Option<Arc<redact::Redactor>>>> = std::sync::OnceLock::new();
+    CACHE.get_or_init(|| std::sync::RwLock::new(None))
+}
+
+fn cached_redactor(conn: &Connection) -> Result<Arc<redact::Redactor>, String> {
+    if let Some(redactor) = redact_cache().read().map_err(|e| e.to_string())?.as_ref() {
+        return Ok(redactor.clone());
+    }
+    let redactor = Arc::new(redact::load(conn).map_err(|e| e.to_string())?.compile());
+    *redact_cache().write().map_err(|e| e.to_string())? = Some(redactor.clone());
+    Ok(redactor)
+}
+
 #[tauri::command]
 fn record_terminal_output(project_path: String, output: String) -> Result<(), String> {
+    let output = {
+        let mut carry = terminal_output_carry().lock().map_err(|e| e.to_string())?;
+        redact::take_complete_lines(carry.entry(project_path.clone()).or_default(), &output)
+    };
+    if output.is_empty() {
+        return Ok(());
+    }
+
     let conn = open_db()?;
     let now = std::time::SystemTime::now()
         .duration_since(std::time::UNIX_EPOCH)
         .map_err(|e| e.to_string())?
         .as_secs() as i64;
 
+    // PTY 输出常带有命令回显的 token、密码等，入库前脱敏
+    let output = cached_redactor(&conn)?.redact(&output);
     conn.execute(
         "INSERT INTO terminal_history (project_path, kind, content, created_at) VALUES (?1, 'output', ?2, ?3)",
         params![project_path, output, now],
@@ -577,26 +656,120 @@ fn get_terminal_history(project_path: String) -> Result<Vec<String>, String> {
         items.push(row.get::<_, String>(0).map_err(|e| e.to_string())?);
     }
     items.reverse();
+
+    // 尚未换行、还没入库的最后一段输出
+    let pending = terminal_output_carry()
+        .lock()
+        .map_err(|e| e.to_string())?
+        .get(&project_path)
+        .filter(|pending| !pending.is_empty())
+        .cloned();
+    if let Some(pending) = pending {
+        items.push(cached_redactor(&conn)?.redact(&pending));
+    }
     Ok(items)
 }
 
+/// 删除项目的终端历史，返回删除的行数；remove_log 为 true 时同时清空 pty_logs 下的日志文件
 #[tauri::command]
-fn get_wss_status() -> Result<WssStatus, String> {
-    let config_dir = dirs::config_dir()
-        .ok_or("Failed to get config directory")?
-        .join("com.claude.monitor");
+fn clear_terminal_history(project_path: String, remove_log: Option<bool>) -> Result<usize, String> {
+    let conn = open_db()?;
+    let deleted = conn
+        .execute("DELETE FROM terminal_history WHERE project_path = ?1", params![project_path])
+        .map_err(|e| e.to_string())?;
+    terminal_output_carry().lock().map_err(|e| e.to_string())?.remove(&project_path);
+    if remove_log.unwrap_or(false) {
+        truncate_file_if_exists(&pty::get_pty_log_path(&project_path))?;
+    }
+    log::info!("[terminal] cleared {} history rows for {}", deleted, project_path);
+    Ok(deleted)
+}
 
-    let last_receive_time = std::fs::read_to_string(config_dir.join("last_receive_time.txt"))
-        .ok()
-        .and_then(|s| s.trim().parse().ok());
+/// 删除所有项目的终端历史，返回删除的行数；remove_logs 为 true 时同时清空 pty_logs 目录中的日志
+#[tauri::command]
+fn clear_all_terminal_history(remove_logs: Option<bool>) -> Result<usize, String> {
+    let conn = open_db()?;
+    let deleted = conn
+        .execute("DELETE FROM terminal_history", [])
+        .map_err(|e| e.to_string())?;
+    terminal_output_carry().lock().map_err(|e| e.to_string())?.clear();
+    if remove_logs.unwrap_or(false) {
+        let log_dir = dirs::home_dir().ok_or("Failed to get home directory")?.join("sparky/pty_logs");
+        if let Ok(entries) = fs::read_dir(&log_dir) {
+            for entry in entries.flatten() {
+                let path = entry.path();
+                if path.extension().is_some_and(|ext| ext == "log") {
+                    truncate_file_if_exists(&path)?;
+                }
+            }
+        }
+    }
+    log::info!("[terminal] cleared {} history rows for all projects", deleted);
+    Ok(deleted)
+}
 
-    let last_open_id = std::fs::read_to_string(config_dir.join("last_open_id.txt"))
-        .ok()
-        .map(|s| s.trim().to_string());
+/// 清空而不是删除日志：运行中的终端仍以追加模式持有该文件，删除后写入会进入已解除链接的旧文件
+fn truncate_file_if_exists(path: &std::path::Path) -> Result<(), String> {
+    match fs::OpenOptions::new().write(true).open(path) {
+        Ok(file) => file
+            .set_len(0)
+            .map_err(|e| format!("Failed to truncate {}: {}", path.display(), e)),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(()),
+        Err(e) => Err(format!("Failed to open {}: {}", path.display(), e)),
+    }
+}
+
+/// 最近的终端输入（去重，最新的在前），供前端实现上下方向键翻阅历史；
+/// 输入与输出都记录在 terminal_history 中，按 kind 区分
+#[tauri::command]
+fn get_terminal_input_history(project_path: String, limit: Option<u32>) -> Result<Vec<String>, String> {
+    let limit = limit.unwrap_or(50).clamp(1, 500);
+    let conn = open_db()?;
+    let mut stmt = conn
+        .prepare(
+            "SELECT content FROM terminal_history
+             WHERE project_path = ?1 AND kind = 'input'
+             GROUP BY content
+             ORDER BY MAX(id) DESC
+             LIMIT ?2",
+        )
+        .map_err(|e| e.to_string())?;
+    let rows = stmt
+        .query_map(params![project_path, limit], |row| row.get::<_, String>(0))
+        .map_err(|e| e.to_string())?;
+    let mut items = Vec::new();
+    for row in rows {
+        items.push(row.map_err(|e| e.to_string())?);
+    }
+    Ok(items)
+}
+
+/// FeishuWsClient 每收到一帧调用一次，供连接状态指示使用；节流期间不访问数据库，
+/// 需要写入时放到阻塞线程池执行，不占用 WSS 读循环所在的 async 工作线程
+pub(crate) fn record_wss_receive() {
+    let now = project_tables::now_millis();
+    if !wss_status::receive_due(now) {
+        return;
+    }
+    tokio::task::spawn_blocking(move || {
+        let result = open_db().and_then(|conn| wss_status::set_last_receive_time(&conn, now).map_err(|e| e.to_string()));
+        if let Err(e) = result {
+            log::error!("Failed to record WSS receive time: {}", e);
+        }
+    });
+}
+
+pub(crate) fn record_wss_open_id(open_id: &str) -> Result<(), String> {
+    let conn = open_db()?;
+    wss_status::record_open_id(&conn, open_id).map_err(|e| e.to_string())
+}
 
+#[tauri::command]
+fn get_wss_status() -> Result<WssStatus, String> {
+    let conn = open_db()?;
     Ok(WssStatus {
-        last_receive_time,
-        last_open_id,
+        last_receive_time: wss_status::last_receive_time(&conn).map_err(|e| e.to_string())?,
+        last_open_id: wss_status::last_open_id(&conn).map_err(|e| e.to_string())?,
     })
 }
 
@@ -610,99 +783,519 @@ fn get_config() -> Result<AppConfig, String> {
     }
 }
 
-#[tauri::command]
-fn save_config(config: AppConfig) -> Result<(), String> {
-    let conn = open_db()?;
-    upsert_config(&conn, &config)?;
-    Ok(())
+/// Claude Code 支持的 hook 事件名（hook_events_filter 按名称精确匹配）
+const SUPPORTED_HOOK_EVENTS: &[&str] = &[
+    "Notification",
+    "PermissionRequest",
+    "Stop",
+    "SubagentStop",
+    "UserPromptSubmit",
+    "PreToolUse",
+    "PostToolUse",
+    "PreCompact",
+    "SessionStart",
+    "SessionEnd",
+];
+
+/// 返回过滤字符串中不在 SUPPORTED_HOOK_EVENTS 里的事件名
+fn unrecognized_hook_events(filter: &str) -> Vec<String> {
+    filter
+        .split(',')
+        .map(|s| s.trim())
+        .filter(|s| !s.is_empty() && !SUPPORTED_HOOK_EVENTS.contains(s))
+        .map(|s| s.to_string())
+        .collect()
 }
 
 #[tauri::command]
-fn open_folder(path: String) -> Result<(), String> {
-    #[cfg(target_os = "macos")]
-    {
-        std::process::Command::new("open")
-            .arg(&path)
-            .spawn()
-            .map_err(|e| e.to_string())?;
-    }
-    #[cfg(target_os = "windows")]
-    {
-        std::process::Command::new("explorer")
-            .arg(&path)
-            .spawn()
-            .map_err(|e| e.to_string())?;
-    }
-    #[cfg(target_os = "linux")]
-    {
-        std::process::Command::new("xdg-open")
-            .arg(&path)
-            .spawn()
-            .map_err(|e| e.to_string())?;
-    }
-    Ok(())
+fn get_supported_hook_events() -> Vec<String> {
+    SUPPORTED_HOOK_EVENTS.iter().map(|s| s.to_string()).collect()
 }
 
+/// 校验 hook_events_filter，返回无法识别的事件名（为空表示全部有效），供前端高亮
+#[tauri::command]
+fn validate_hook_events_filter(filter: String) -> Vec<String> {
+    unrecognized_hook_events(&filter)
+}
 
-fn build_hook_command() -> Result<String, String> {
-    if let Ok(cmd) = std::env::var("CLAUDE_MONITOR_HOOK_COMMAND") {
-        if !cmd.trim().is_empty() {
-            return Ok(cmd);
+/// 保存配置；凭证或代理有变化时先用新凭证获取 token 验证，通过后才写入数据库并让 WSS 任务用新配置重连，
+/// 验证失败则返回错误，旧配置和现有连接都保持不变。返回是否已触发重连
+#[tauri::command]
+async fn save_config(state: tauri::State<'_, Arc<AppState>>, config: AppConfig) -> Result<bool, String> {
+    if let Some(filter) = config.hook_events_filter.as_deref() {
+        let unrecognized = unrecognized_hook_events(filter);
+        if !unrecognized.is_empty() {
+            return Err(format!(
+                "未知的 Hook 事件: {}（支持: {}）",
+                unrecognized.join(", "),
+                SUPPORTED_HOOK_EVENTS.join(", ")
+            ));
         }
     }
+    let previous = get_config().ok();
+    let connection_changed = previous.map_or(true, |old| {
+        old.app_id != config.app_id || old.app_secret != config.app_secret || old.proxy_url != config.proxy_url
+    });
+    let reconnect = connection_changed && !config.app_id.is_empty() && !config.app_secret.is_empty();
 
-    let exe_path = std::env::current_exe()
-        .map_err(|e| format!("Failed to get executable path: {}", e))?;
-
-    // CLI 二进制名固定为 "sparky"（与根目录 Cargo.toml 的 package name 一致）
-    let cli_bin_name = "sparky";
-
-    let mut current = exe_path.parent();
-    let mut repo_root: Option<std::path::PathBuf> = None;
-    while let Some(dir) = current {
-        if dir.file_name().map(|name| name == "src-tauri").unwrap_or(false) {
-            repo_root = dir.parent().map(|p| p.to_path_buf());
-            break;
+    if reconnect {
+        let client = build_http_client(config.proxy_url.as_deref())?;
+        if let Err(e) = fetch_tenant_access_token(&client, &config.app_id, &config.app_secret).await {
+            log::warn!("New Feishu credentials failed validation, keeping the current config: {}", e);
+            return Err(format!("新凭证验证失败，配置未保存: {}", e));
         }
-        current = dir.parent();
     }
-
-    if let Some(root) = repo_root {
-        let debug_path = root.join("target").join("debug").join(cli_bin_name);
-        if debug_path.exists() {
-            return Ok(format!("{} hook", debug_path.to_string_lossy()));
-        }
-        let release_path = root.join("target").join("release").join(cli_bin_name);
-        if release_path.exists() {
-            return Ok(format!("{} hook", release_path.to_string_lossy()));
-        }
+    {
+        let conn = open_db()?;
+        upsert_config(&conn, &config)?;
+    }
+    if reconnect {
+        restart_feishu_ws(&state)?;
     }
+    Ok(reconnect)
+}
 
-    // fallback: 尝试全局 PATH 中查找
-    Ok(format!("{} hook", cli_bin_name))
+/// 只使用钉钉或企业微信时还没有飞书配置行，先插入空的占位行（与 `sparky config set` 相同），
+/// 通用的通知设置与后端选择都保存在这一行
+fn ensure_config_row(conn: &Connection) -> Result<(), String> {
+    let now = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map_err(|e| e.to_string())?
+        .as_secs() as i64;
+    conn.execute(
+        "INSERT INTO app_config_feishu (id, app_id, app_secret, updated_at) VALUES (1, '', '', ?1)
+         ON CONFLICT(id) DO NOTHING",
+        params![now],
+    )
+    .map_err(|e| e.to_string())?;
+    Ok(())
 }
 
+/// 设置需要加急提醒的事件（如 PermissionRequest、error），空列表表示关闭加急
 #[tauri::command]
-fn check_hooks_installed(project_path: String) -> Result<bool, String> {
-    check_hooks_installed_for_path(&project_path)
+fn set_urgent_events(events: Vec<String>) -> Result<(), String> {
+    let events: Vec<String> = events
+        .iter()
+        .map(|e| e.trim().to_string())
+        .filter(|e| !e.is_empty())
+        .collect();
+    let value = if events.is_empty() { None } else { Some(events.join(",")) };
+    let conn = open_db()?;
+    ensure_config_row(&conn)?;
+    conn.execute(
+        "UPDATE app_config_feishu SET urgent_events = ?1 WHERE id = 1",
+        params![value],
+    )
+    .map_err(|e| e.to_string())?;
+    Ok(())
 }
 
-fn check_hooks_installed_for_path(project_path: &str) -> Result<bool, String> {
-    let settings_path = std::path::Path::new(&project_path)
-        .join(".claude")
-        .join("settings.local.json");
-
-    if !settings_path.exists() {
-        return Ok(false);
+/// 设置飞书消息样式：compact（仅标题与摘要）或 detailed（包含 Event/Session/CWD 等元数据）
+#[tauri::command]
+fn set_message_style(style: String) -> Result<(), String> {
+    let style = style.trim().to_lowercase();
+    if style != "compact" && style != "detailed" {
+        return Err(format!("Invalid message style '{}', expected compact/detailed", style));
     }
+    let conn = open_db()?;
+    ensure_config_row(&conn)?;
+    conn.execute(
+        "UPDATE app_config_feishu SET message_style = ?1 WHERE id = 1",
+        params![style],
+    )
+    .map_err(|e| e.to_string())?;
+    Ok(())
+}
 
-    let content = fs::read_to_string(&settings_path)
+/// 设置每个会话每分钟最多发送的通知数，None 或 0 表示不限制（PermissionRequest 不受限制）
+#[tauri::command]
+fn set_max_notifications_per_session(limit: Option<u32>) -> Result<(), String> {
+    let limit = limit.filter(|l| *l > 0);
+    let conn = open_db()?;
+    ensure_config_row(&conn)?;
+    conn.execute(
+        "UPDATE app_config_feishu SET max_notifications_per_session_per_minute = ?1 WHERE id = 1",
+        params![limit],
+    )
+    .map_err(|e| e.to_string())?;
+    Ok(())
+}
+
+/// 设置 hook 内容截断上限（按字符数），None 或 0 表示使用默认值
+#[tauri::command]
+fn set_truncation_limits(
+    max_message_len: Option<u32>,
+    max_db_content_len: Option<u32>,
+    max_response_chars: Option<u32>,
+    max_transcript_chars: Option<u32>,
+) -> Result<(), String> {
+    // 飞书单条消息上限约 20000 字符，超过会被拒绝
+    if max_message_len.is_some_and(|len| len > 20000) {
+        return Err("max_message_len must not exceed 20000".to_string());
+    }
+    let conn = open_db()?;
+    ensure_config_row(&conn)?;
+    conn.execute(
+        "UPDATE app_config_feishu SET max_message_len = ?1, max_db_content_len = ?2, max_response_chars = ?3, max_transcript_chars = ?4
+         WHERE id = 1",
+        params![
+            max_message_len.filter(|v| *v > 0),
+            max_db_content_len.filter(|v| *v > 0),
+            max_response_chars.filter(|v| *v > 0),
+            max_transcript_chars.filter(|v| *v > 0)
+        ],
+    )
+    .map_err(|e| e.to_string())?;
+    Ok(())
+}
+
+/// 设置通知后端：feishu（默认）、dingtalk 或 wework（使用对应 app_config_* 表中的配置）
+#[tauri::command]
+fn set_notification_backend(backend: String) -> Result<(), String> {
+    let backend = backend.trim().to_lowercase();
+    if !matches!(backend.as_str(), "feishu" | "dingtalk" | "wework") {
+        return Err(format!("Invalid backend '{}', expected feishu/dingtalk/wework", backend));
+    }
+    let conn = open_db()?;
+    ensure_config_row(&conn)?;
+    conn.execute(
+        "UPDATE app_config_feishu SET backend = ?1 WHERE id = 1",
+        params![backend],
+    )
+    .map_err(|e| e.to_string())?;
+    Ok(())
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DingTalkConfig {
+    pub app_key: String,
+    pub app_secret: String,
+    /// 群会话 openConversationId
+    pub chat_id: Option<String>,
+}
+
+#[tauri::command]
+fn get_dingtalk_config() -> Result<Option<DingTalkConfig>, String> {
+    let conn = open_db()?;
+    conn.query_row(
+        "SELECT app_id, app_secret, chat_id FROM app_config_dingtalk WHERE id = 1",
+        [],
+        |row| {
+            Ok(DingTalkConfig {
+                app_key: row.get(0)?,
+                app_secret: row.get(1)?,
+                chat_id: row.get(2)?,
+            })
+        },
+    )
+    .optional()
+    .map_err(|e| e.to_string())?
+    .map(|d| Ok(DingTalkConfig { app_secret: secret::decrypt_secret(&d.app_secret)?, ..d }))
+    .transpose()
+}
+
+/// 保存钉钉企业内部应用配置（AppKey / AppSecret / 群会话 ID）
+#[tauri::command]
+fn save_dingtalk_config(config: DingTalkConfig) -> Result<(), String> {
+    if config.app_key.trim().is_empty() || config.app_secret.trim().is_empty() {
+        return Err("AppKey 和 AppSecret 不能为空".to_string());
+    }
+    let conn = open_db()?;
+    let now = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map_err(|e| e.to_string())?
+        .as_secs() as i64;
+    conn.execute(
+        "INSERT INTO app_config_dingtalk (id, app_id, app_secret, chat_id, updated_at)
+         VALUES (1, ?1, ?2, ?3, ?4)
+         ON CONFLICT(id) DO UPDATE SET
+           app_id = excluded.app_id,
+           app_secret = excluded.app_secret,
+           chat_id = excluded.chat_id,
+           updated_at = excluded.updated_at",
+        params![config.app_key.trim(), secret::encrypt_secret(config.app_secret.trim())?, config.chat_id, now],
+    )
+    .map_err(|e| e.to_string())?;
+    Ok(())
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WeWorkConfig {
+    pub corp_id: String,
+    pub corp_secret: String,
+    /// 自建应用 AgentId（向成员发送权限确认提醒时需要）
+    pub agent_id: Option<String>,
+    /// 群聊 chatid
+    pub chat_id: Option<String>,
+    /// 接收权限确认提醒的成员 userid（多个用 | 分隔）
+    pub to_user: Option<String>,
+}
+
+#[tauri::command]
+fn get_wework_config() -> Result<Option<WeWorkConfig>, String> {
+    let conn = open_db()?;
+    conn.query_row(
+        "SELECT app_id, app_secret, agent_id, chat_id, to_user FROM app_config_wework WHERE id = 1",
+        [],
+        |row| {
+            Ok(WeWorkConfig {
+                corp_id: row.get(0)?,
+                corp_secret: row.get(1)?,
+                agent_id: row.get(2)?,
+                chat_id: row.get(3)?,
+                to_user: row.get(4)?,
+            })
+        },
+    )
+    .optional()
+    .map_err(|e| e.to_string())?
+    .map(|w| Ok(WeWorkConfig { corp_secret: secret::decrypt_secret(&w.corp_secret)?, ..w }))
+    .transpose()
+}
+
+/// 保存企业微信自建应用配置（CorpID / Secret / AgentId / 群聊 chatid / 提醒接收成员）
+#[tauri::command]
+fn save_wework_config(config: WeWorkConfig) -> Result<(), String> {
+    if config.corp_id.trim().is_empty() || config.corp_secret.trim().is_empty() {
+        return Err("CorpID 和 Secret 不能为空".to_string());
+    }
+    if let Some(agent_id) = config.agent_id.as_deref().filter(|a| !a.trim().is_empty()) {
+        agent_id
+            .trim()
+            .parse::<i64>()
+            .map_err(|_| format!("Invalid AgentId '{}'", agent_id))?;
+    }
+    let conn = open_db()?;
+    let now = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map_err(|e| e.to_string())?
+        .as_secs() as i64;
+    conn.execute(
+        "INSERT INTO app_config_wework (id, app_id, app_secret, agent_id, chat_id, to_user, updated_at)
+         VALUES (1, ?1, ?2, ?3, ?4, ?5, ?6)
+         ON CONFLICT(id) DO UPDATE SET
+           app_id = excluded.app_id,
+           app_secret = excluded.app_secret,
+           agent_id = excluded.agent_id,
+           chat_id = excluded.chat_id,
+           to_user = excluded.to_user,
+           updated_at = excluded.updated_at",
+        params![
+            config.corp_id.trim(),
+            secret::encrypt_secret(config.corp_secret.trim())?,
+            config.agent_id,
+            config.chat_id,
+            config.to_user,
+            now
+        ],
+    )
+    .map_err(|e| e.to_string())?;
+    Ok(())
+}
+
+#[tauri::command]
+fn open_folder(path: String) -> Result<(), String> {
+    #[cfg(target_os = "macos")]
+    {
+        std::process::Command::new("open")
+            .arg(&path)
+            .spawn()
+            .map_err(|e| e.to_string())?;
+    }
+    #[cfg(target_os = "windows")]
+    {
+        std::process::Command::new("explorer")
+            .arg(&path)
+            .spawn()
+            .map_err(|e| e.to_string())?;
+    }
+    #[cfg(target_os = "linux")]
+    {
+        std::process::Command::new("xdg-open")
+            .arg(&path)
+            .spawn()
+            .map_err(|e| e.to_string())?;
+    }
+    Ok(())
+}
+
+/// hook 命令的来源
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum HookCommandSource {
+    /// CLAUDE_MONITOR_HOOK_COMMAND 环境变量
+    Env,
+    /// 仓库 target/debug 下的 CLI
+    Debug,
+    /// 仓库 target/release 下的 CLI
+    Release,
+    /// 未找到构建产物，依赖 PATH 中的 sparky
+    Path,
+}
+
+/// get_hook_command 的诊断信息；exists 表示命令的可执行文件能否找到
+#[derive(Debug, Clone, Serialize)]
+pub struct HookCommandInfo {
+    pub command: String,
+    pub source: HookCommandSource,
+    pub exists: bool,
+    /// 解析到的可执行文件绝对路径（PATH 查找时为实际命中的路径）
+    pub resolved_path: Option<String>,
+}
+
+/// 返回 install_hooks 将写入的 hook 命令及其来源，用于排查“已安装但收不到通知”
+#[tauri::command]
+fn get_hook_command() -> Result<HookCommandInfo, String> {
+    let (command, source) = resolve_hook_command()?;
+    let resolved_path = resolve_hook_program(&command);
+    Ok(HookCommandInfo {
+        command,
+        source,
+        exists: resolved_path.is_some(),
+        resolved_path: resolved_path.map(|p| p.to_string_lossy().to_string()),
+    })
+}
+
+fn build_hook_command() -> Result<String, String> {
+    resolve_hook_command().map(|(command, _)| command)
+}
+
+fn resolve_hook_command() -> Result<(String, HookCommandSource), String> {
+    if let Ok(cmd) = std::env::var("CLAUDE_MONITOR_HOOK_COMMAND") {
+        if !cmd.trim().is_empty() {
+            return Ok((cmd, HookCommandSource::Env));
+        }
+    }
+
+    let exe_path = std::env::current_exe()
+        .map_err(|e| format!("Failed to get executable path: {}", e))?;
+
+    // CLI 二进制名固定为 "sparky"（与根目录 Cargo.toml 的 package name 一致）
+    let cli_bin_name = "sparky";
+
+    let mut current = exe_path.parent();
+    let mut repo_root: Option<std::path::PathBuf> = None;
+    while let Some(dir) = current {
+        if dir.file_name().map(|name| name == "src-tauri").unwrap_or(false) {
+            repo_root = dir.parent().map(|p| p.to_path_buf());
+            break;
+        }
+        current = dir.parent();
+    }
+
+    if let Some(root) = repo_root {
+        let debug_path = root.join("target").join("debug").join(cli_bin_name);
+        if debug_path.exists() {
+            return Ok((format!("{} hook", debug_path.to_string_lossy()), HookCommandSource::Debug));
+        }
+        let release_path = root.join("target").join("release").join(cli_bin_name);
+        if release_path.exists() {
+            return Ok((format!("{} hook", release_path.to_string_lossy()), HookCommandSource::Release));
+        }
+    }
+
+    // fallback: 尝试全局 PATH 中查找
+    Ok((format!("{} hook", cli_bin_name), HookCommandSource::Path))
+}
+
+/// 取出 hook 命令中的可执行文件部分（支持用引号包裹含空格的路径）
+fn hook_command_program(command: &str) -> &str {
+    let command = command.trim();
+    if let Some(rest) = command.strip_prefix('"') {
+        return rest.split('"').next().unwrap_or(rest);
+    }
+    command.split_whitespace().next().unwrap_or(command)
+}
+
+fn is_executable_file(path: &std::path::Path) -> bool {
+    let Ok(metadata) = fs::metadata(path) else {
+        return false;
+    };
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        metadata.is_file() && metadata.permissions().mode() & 0o111 != 0
+    }
+    #[cfg(not(unix))]
+    {
+        metadata.is_file()
+    }
+}
+
+/// 解析 hook 命令的可执行文件：带路径时直接检查，否则在 PATH 中查找
+fn resolve_hook_program(command: &str) -> Option<PathBuf> {
+    resolve_program(hook_command_program(command))
+}
+
+/// 解析可执行文件：带路径时直接检查，否则在 PATH 中查找（Windows 下补全 .exe）
+pub(crate) fn resolve_program(program: &str) -> Option<PathBuf> {
+    if program.is_empty() {
+        return None;
+    }
+    let path = std::path::Path::new(program);
+    if path.components().count() > 1 {
+        return is_executable_file(path).then(|| path.to_path_buf());
+    }
+    let candidates: Vec<String> = if cfg!(windows) && path.extension().is_none() {
+        vec![program.to_string(), format!("{}.exe", program)]
+    } else {
+        vec![program.to_string()]
+    };
+    std::env::split_paths(&std::env::var_os("PATH")?)
+        .flat_map(|dir| candidates.iter().map(move |name| dir.join(name)))
+        .find(|candidate| is_executable_file(candidate))
+}
+
+/// 安装前校验命令可以执行，否则 hook 安装成功却永远不会触发
+fn validate_hook_command(command: &str) -> Result<(), String> {
+    if resolve_hook_program(command).is_some() {
+        return Ok(());
+    }
+    Err(format!(
+        "Hook command '{}' is not executable: '{}' was not found or is not an executable file. \
+         Build the sparky CLI (cargo build --release) or put it on PATH, \
+         or set CLAUDE_MONITOR_HOOK_COMMAND to the full command, e.g. \"/path/to/sparky hook\"",
+        command,
+        hook_command_program(command)
+    ))
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct HooksInstallStatus {
+    pub project: bool,
+    pub user: bool,
+}
+
+/// 分别返回项目级与用户级的安装状态
+#[tauri::command]
+fn check_hooks_installed(project_path: String) -> Result<HooksInstallStatus, String> {
+    Ok(HooksInstallStatus {
+        project: check_hooks_installed_for_path(&project_path)?,
+        user: check_user_hooks_installed()?,
+    })
+}
+
+fn check_hooks_installed_for_path(project_path: &str) -> Result<bool, String> {
+    check_hooks_installed_at(&claude_settings_path(project_path))
+}
+
+fn check_user_hooks_installed() -> Result<bool, String> {
+    check_hooks_installed_at(&hook_settings_path(claude_hooks::HookScope::User, "")?)
+}
+
+fn check_hooks_installed_at(settings_path: &std::path::Path) -> Result<bool, String> {
+    if !settings_path.exists() {
+        return Ok(false);
+    }
+
+    let content = fs::read_to_string(settings_path)
         .map_err(|e| format!("Failed to read settings: {}", e))?;
 
     let settings: serde_json::Value = serde_json::from_str(&content)
         .map_err(|e| format!("Failed to parse settings: {}", e))?;
 
-    Ok(is_hooks_config_complete(&settings))
+    // 其他工具的 hook 不代表 sparky 已安装，按 sparky 命令判断
+    Ok(claude_hooks::has_sparky_hooks(&settings, &build_hook_command()?))
 }
 
 fn is_hooks_config_complete(settings: &serde_json::Value) -> bool {
@@ -747,11 +1340,61 @@ fn is_hooks_event_complete(value: &serde_json::Value) -> bool {
     true
 }
 
+/// install_hooks 将要写入的内容，before 为当前文件（不存在时为空对象）
+#[derive(Debug, Clone, Serialize)]
+pub struct HooksInstallPreview {
+    pub path: String,
+    pub before: serde_json::Value,
+    pub after: serde_json::Value,
+    pub changes: Vec<claude_hooks::SettingsChange>,
+}
+
+/// 读取当前设置并计算安装后的内容；install_hooks 与 preview_install_hooks 共用，保证预览与实际写入一致
+fn plan_install_hooks(
+    scope: claude_hooks::HookScope,
+    project_path: &str,
+    hook_command: &str,
+) -> Result<(PathBuf, serde_json::Value, serde_json::Value), String> {
+    let settings_path = hook_settings_path(scope, project_path)?;
+
+    let before = if settings_path.exists() {
+        let content = fs::read_to_string(&settings_path)
+            .map_err(|e| format!("Failed to read settings: {}", e))?;
+        serde_json::from_str(&content)
+            .map_err(|e| format!("Failed to parse settings: {}", e))?
+    } else {
+        serde_json::json!({})
+    };
+
+    // Claude Code 要求 hooks 放在 "hooks" key 下；合并进已有事件，不覆盖其他工具的 hook
+    let mut after = before.clone();
+    claude_hooks::merge_sparky_hooks(&mut after, hook_command);
+    Ok((settings_path, before, after))
+}
+
+/// 预览 install_hooks 的改动，不写入文件
 #[tauri::command]
-fn install_hooks(project_path: String) -> Result<(), String> {
-    let settings_path = std::path::Path::new(&project_path)
-        .join(".claude")
-        .join("settings.local.json");
+fn preview_install_hooks(
+    project_path: String,
+    scope: Option<claude_hooks::HookScope>,
+) -> Result<HooksInstallPreview, String> {
+    let hook_command = build_hook_command()?;
+    let (settings_path, before, after) = plan_install_hooks(scope.unwrap_or_default(), &project_path, &hook_command)?;
+    Ok(HooksInstallPreview {
+        path: settings_path.to_string_lossy().to_string(),
+        changes: claude_hooks::diff_settings(&before, &after),
+        before,
+        after,
+    })
+}
+
+/// scope 默认为 project；user 时写入 ~/.claude/settings.json，project_path 被忽略。
+/// 返回写入的 hook 命令，命令无法执行时报错且不修改设置
+#[tauri::command]
+fn install_hooks(project_path: String, scope: Option<claude_hooks::HookScope>) -> Result<String, String> {
+    let hook_command = build_hook_command()?;
+    validate_hook_command(&hook_command)?;
+    let (settings_path, _, settings) = plan_install_hooks(scope.unwrap_or_default(), &project_path, &hook_command)?;
 
     // Ensure .claude directory exists
     if let Some(parent) = settings_path.parent() {
@@ -759,127 +1402,172 @@ fn install_hooks(project_path: String) -> Result<(), String> {
             .map_err(|e| format!("Failed to create .claude directory: {}", e))?;
     }
 
-    let hook_command = build_hook_command()?;
-    let hooks_events = serde_json::json!({
-        "Notification": [
-            {
-                "hooks": [
-                    {
-                        "type": "command",
-                        "command": hook_command.clone()
-                    }
-                ]
-            }
-        ],
-        "PermissionRequest": [
-            {
-                "hooks": [
-                    {
-                        "type": "command",
-                        "command": hook_command.clone()
-                    }
-                ]
-            }
-        ],
-        "Stop": [
-            {
-                "hooks": [
-                    {
-                        "type": "command",
-                        "command": hook_command.clone()
-                    }
-                ]
-            }
-        ],
-        "UserPromptSubmit": [
-            {
-                "hooks": [
-                    {
-                        "type": "command",
-                        "command": hook_command
-                    }
-                ]
-            }
-        ]
-    });
+    let new_content = serde_json::to_string_pretty(&settings)
+        .map_err(|e| format!("Failed to serialize settings: {}", e))?;
 
-    // Claude Code 要求 hooks 放在 "hooks" key 下
-    let hooks_config = serde_json::json!({
-        "hooks": hooks_events
-    });
+    fs::write(&settings_path, new_content)
+        .map_err(|e| format!("Failed to write settings: {}", e))?;
 
-    if settings_path.exists() {
-        // Read existing settings and merge
-        let content = fs::read_to_string(&settings_path)
-            .map_err(|e| format!("Failed to read settings: {}", e))?;
+    log::info!("Hooks installed successfully to {:?}: {}", settings_path, hook_command);
+    Ok(hook_command)
+}
 
-        let mut settings: serde_json::Value = serde_json::from_str(&content)
-            .map_err(|e| format!("Failed to parse settings: {}", e))?;
+#[tauri::command]
+fn uninstall_hooks(project_path: String, scope: Option<claude_hooks::HookScope>) -> Result<(), String> {
+    let settings_path = hook_settings_path(scope.unwrap_or_default(), &project_path)?;
 
-        if let Some(obj) = settings.as_object_mut() {
-            // 移除旧的顶层 hook 事件 key（兼容旧格式）
-            for key in ["Notification", "PermissionRequest", "Stop", "UserPromptSubmit"] {
-                obj.remove(key);
-            }
-            // 设置/覆盖 "hooks" key
-            obj.insert("hooks".to_string(), hooks_events);
-        }
+    if !settings_path.exists() {
+        return Ok(());
+    }
 
-        let new_content = serde_json::to_string_pretty(&settings)
-            .map_err(|e| format!("Failed to serialize settings: {}", e))?;
+    let content = fs::read_to_string(&settings_path)
+        .map_err(|e| format!("Failed to read settings: {}", e))?;
 
-        fs::write(&settings_path, new_content)
-            .map_err(|e| format!("Failed to write settings: {}", e))?;
-    } else {
-        // Create new settings file
-        let content = serde_json::to_string_pretty(&hooks_config)
-            .map_err(|e| format!("Failed to serialize: {}", e))?;
+    let mut settings: serde_json::Value = serde_json::from_str(&content)
+        .map_err(|e| format!("Failed to parse settings: {}", e))?;
 
-        fs::write(&settings_path, content)
-            .map_err(|e| format!("Failed to write settings: {}", e))?;
-    }
+    // 只删除 sparky 自己的 hook，保留用户手写或其他工具安装的 hook
+    claude_hooks::remove_sparky_hooks(&mut settings, &build_hook_command()?);
+
+    let new_content = serde_json::to_string_pretty(&settings)
+        .map_err(|e| format!("Failed to serialize settings: {}", e))?;
+
+    fs::write(&settings_path, new_content)
+        .map_err(|e| format!("Failed to write settings: {}", e))?;
 
-    log::info!("Hooks installed successfully to {:?}", settings_path);
+    log::info!("Hooks uninstalled successfully from {:?}", settings_path);
     Ok(())
 }
 
+fn claude_settings_path(project_path: &str) -> PathBuf {
+    std::path::Path::new(project_path)
+        .join(".claude")
+        .join("settings.local.json")
+}
+
+/// Claude Code 会合并用户级与项目级 settings 中的 hooks，相同命令只执行一次
+fn hook_settings_path(scope: claude_hooks::HookScope, project_path: &str) -> Result<PathBuf, String> {
+    match scope {
+        claude_hooks::HookScope::Project => Ok(claude_settings_path(project_path)),
+        claude_hooks::HookScope::User => Ok(dirs::home_dir()
+            .ok_or("Failed to get home directory")?
+            .join(".claude")
+            .join("settings.json")),
+    }
+}
+
+/// 读取并解析 .claude/settings.local.json，文件不存在时返回空对象
+#[tauri::command]
+fn read_claude_settings(project_path: String) -> Result<serde_json::Value, String> {
+    let settings_path = claude_settings_path(&project_path);
+    if !settings_path.exists() {
+        return Ok(serde_json::json!({}));
+    }
+
+    let content = fs::read_to_string(&settings_path)
+        .map_err(|e| format!("Failed to read settings: {}", e))?;
+
+    serde_json::from_str(&content).map_err(|e| format!("Failed to parse settings: {}", e))
+}
+
+/// 校验后写入 .claude/settings.local.json：必须是合法的 JSON 对象，
+/// 包含 hooks 或原文件已安装 hooks 时，hooks 结构必须完整；覆盖前生成带时间戳的备份
 #[tauri::command]
-fn uninstall_hooks(project_path: String) -> Result<(), String> {
-    let settings_path = std::path::Path::new(&project_path)
-        .join(".claude")
-        .join("settings.local.json");
-
-    if !settings_path.exists() {
-        return Ok(());
+fn write_claude_settings(project_path: String, json: String) -> Result<(), String> {
+    let settings: serde_json::Value = serde_json::from_str(&json)
+        .map_err(|e| format!("Invalid JSON: {}", e))?;
+    if !settings.is_object() {
+        return Err("Settings must be a JSON object".to_string());
     }
 
-    let content = fs::read_to_string(&settings_path)
-        .map_err(|e| format!("Failed to read settings: {}", e))?;
+    let hooks_were_installed = check_hooks_installed_for_path(&project_path).unwrap_or(false);
+    if (hooks_were_installed || settings.get("hooks").is_some()) && !is_hooks_config_complete(&settings) {
+        return Err(
+            "Hooks configuration is incomplete: Notification, PermissionRequest, Stop and UserPromptSubmit must each have a command hook"
+                .to_string(),
+        );
+    }
 
-    let mut settings: serde_json::Value = serde_json::from_str(&content)
-        .map_err(|e| format!("Failed to parse settings: {}", e))?;
+    let settings_path = claude_settings_path(&project_path);
+    if let Some(parent) = settings_path.parent() {
+        fs::create_dir_all(parent)
+            .map_err(|e| format!("Failed to create .claude directory: {}", e))?;
+    }
 
-    if let Some(obj) = settings.as_object_mut() {
-        obj.remove("Notification");
-        obj.remove("PermissionRequest");
-        obj.remove("Stop");
-        obj.remove("UserPromptSubmit");
-        obj.remove("hooks");
+    if settings_path.exists() {
+        let backup_path = settings_path.with_file_name(format!(
+            "settings.local.json.{}.bak",
+            chrono::Local::now().format("%Y%m%d%H%M%S")
+        ));
+        fs::copy(&settings_path, &backup_path)
+            .map_err(|e| format!("Failed to back up settings: {}", e))?;
+        log::info!("Backed up settings to {:?}", backup_path);
     }
 
     let new_content = serde_json::to_string_pretty(&settings)
         .map_err(|e| format!("Failed to serialize settings: {}", e))?;
-
     fs::write(&settings_path, new_content)
         .map_err(|e| format!("Failed to write settings: {}", e))?;
 
-    log::info!("Hooks uninstalled successfully");
+    log::info!("Settings written to {:?}", settings_path);
     Ok(())
 }
 
+/// 解析出站代理：优先使用配置中的 proxy_url，其次回退到 HTTPS_PROXY 环境变量
+fn resolve_proxy_url(configured: Option<&str>) -> Option<String> {
+    configured
+        .map(|s| s.trim().to_string())
+        .filter(|s| !s.is_empty())
+        .or_else(|| std::env::var("HTTPS_PROXY").ok())
+        .or_else(|| std::env::var("https_proxy").ok())
+        .filter(|s| !s.trim().is_empty())
+}
+
+fn configured_proxy_url() -> Option<String> {
+    let configured = open_db()
+        .ok()
+        .and_then(|conn| load_config_from_db(&conn).ok().flatten())
+        .and_then(|config| config.proxy_url);
+    resolve_proxy_url(configured.as_deref())
+}
+
+/// 构建出站 HTTP 客户端，设置了代理时所有请求都走代理（支持 http/https/socks5）
+pub(crate) fn build_http_client(proxy_url: Option<&str>) -> Result<reqwest::Client, String> {
+    let mut builder = reqwest::Client::builder();
+    if let Some(url) = resolve_proxy_url(proxy_url) {
+        let proxy = reqwest::Proxy::all(&url)
+            .map_err(|e| format!("Invalid proxy url '{}': {}", ws_proxy::mask_proxy_url(&url), e))?;
+        builder = builder.proxy(proxy);
+    }
+    builder.build().map_err(|e| format!("Failed to build HTTP client: {}", e))
+}
+
+#[tauri::command]
+async fn test_proxy(proxy_url: Option<String>) -> Result<String, String> {
+    let url = match proxy_url.filter(|s| !s.trim().is_empty()) {
+        Some(url) => url,
+        None => configured_proxy_url().ok_or("未配置代理（proxy_url 或 HTTPS_PROXY）")?,
+    };
+    let client = build_http_client(Some(&url))?;
+
+    let started = std::time::Instant::now();
+    let response = client
+        .get("https://open.feishu.cn")
+        .timeout(std::time::Duration::from_secs(10))
+        .send()
+        .await
+        .map_err(|e| format!("通过代理 {} 无法访问 open.feishu.cn: {}", ws_proxy::mask_proxy_url(&url), e))?;
+
+    Ok(format!(
+        "代理可用: open.feishu.cn 返回 HTTP {} ({} ms)",
+        response.status().as_u16(),
+        started.elapsed().as_millis()
+    ))
+}
+
 #[tauri::command]
 async fn test_feishu_connection(app_id: String, app_secret: String) -> Result<String, String> {
-    let client = reqwest::Client::new();
+    let client = build_http_client(configured_proxy_url().as_deref())?;
     
     // 获取 tenant_access_token
     let token_url = "https://open.feishu.cn/open-apis/auth/v3/tenant_access_token/internal";
@@ -914,7 +1602,7 @@ async fn send_feishu_message(
     receive_id: String,
     message: String,
 ) -> Result<String, String> {
-    let client = reqwest::Client::new();
+    let client = build_http_client(configured_proxy_url().as_deref())?;
     
     // 获取 tenant_access_token
     let token_url = "https://open.feishu.cn/open-apis/auth/v3/tenant_access_token/internal";
@@ -968,6 +1656,218 @@ async fn send_feishu_message(
     Ok("消息发送成功".to_string())
 }
 
+async fn fetch_tenant_access_token(
+    client: &reqwest::Client,
+    app_id: &str,
+    app_secret: &str,
+) -> Result<String, String> {
+    let token_url = "https://open.feishu.cn/open-apis/auth/v3/tenant_access_token/internal";
+    let token_result: serde_json::Value = client
+        .post(token_url)
+        .json(&serde_json::json!({
+            "app_id": app_id,
+            "app_secret": app_secret
+        }))
+        .send()
+        .await
+        .map_err(|e| format!("Failed to request token: {}", e))?
+        .json()
+        .await
+        .map_err(|e| format!("Failed to parse token response: {}", e))?;
+
+    token_result["tenant_access_token"]
+        .as_str()
+        .map(|s| s.to_string())
+        .ok_or_else(|| "Failed to get tenant_access_token".to_string())
+}
+
+/// 机器人所在的飞书群聊，供配置界面下拉选择 chat_id
+#[derive(Debug, Clone, Serialize)]
+struct ChatInfo {
+    chat_id: String,
+    name: String,
+    /// 群类型：飞书返回 chat_mode 时使用该值，否则按 external 区分 external / group
+    chat_type: String,
+}
+
+/// 群列表最多翻页次数，防止接口异常时无限循环
+const MAX_CHAT_PAGES: usize = 50;
+
+/// 分页调用 im/v1/chats 获取机器人所在的全部群聊
+#[tauri::command]
+async fn list_feishu_chats(app_id: String, app_secret: String) -> Result<Vec<ChatInfo>, String> {
+    let client = build_http_client(configured_proxy_url().as_deref())?;
+    let token = fetch_tenant_access_token(&client, &app_id, &app_secret).await?;
+
+    let mut chats = Vec::new();
+    let mut page_token: Option<String> = None;
+    for _ in 0..MAX_CHAT_PAGES {
+        let mut query = vec![("page_size", "100".to_string())];
+        if let Some(token) = &page_token {
+            query.push(("page_token", token.clone()));
+        }
+        let result: serde_json::Value = client
+            .get("https://open.feishu.cn/open-apis/im/v1/chats")
+            .header("Authorization", format!("Bearer {}", token))
+            .query(&query)
+            .send()
+            .await
+            .map_err(|e| format!("Failed to list chats: {}", e))?
+            .json()
+            .await
+            .map_err(|e| format!("Failed to parse chat list response: {}", e))?;
+
+        let code = result["code"].as_i64().unwrap_or(-1);
+        if code != 0 {
+            return Err(format!(
+                "获取群列表失败（code={}）：{}。请确认应用已开通 im:chat:readonly（获取群组信息）权限并发布版本",
+                code,
+                result["msg"].as_str().unwrap_or("Unknown error")
+            ));
+        }
+
+        let data = &result["data"];
+        for item in data["items"].as_array().into_iter().flatten() {
+            let Some(chat_id) = item["chat_id"].as_str() else { continue };
+            let chat_type = item["chat_mode"]
+                .as_str()
+                .map(|s| s.to_string())
+                .unwrap_or_else(|| {
+                    if item["external"].as_bool().unwrap_or(false) { "external" } else { "group" }.to_string()
+                });
+            chats.push(ChatInfo {
+                chat_id: chat_id.to_string(),
+                name: item["name"].as_str().unwrap_or_default().to_string(),
+                chat_type,
+            });
+        }
+
+        page_token = data["page_token"]
+            .as_str()
+            .filter(|s| !s.is_empty())
+            .map(|s| s.to_string());
+        if !data["has_more"].as_bool().unwrap_or(false) || page_token.is_none() {
+            break;
+        }
+    }
+
+    if chats.is_empty() {
+        return Err("机器人尚未加入任何群聊，请先在飞书中将机器人添加到群聊".to_string());
+    }
+    Ok(chats)
+}
+
+/// 将已保存的 hook 通知转发到另一个接收者（如团队群），权限请求会带上相同的确认按钮
+#[tauri::command]
+async fn forward_notification(
+    record_id: i64,
+    project_path: String,
+    target_receive_id: String,
+    receive_id_type: String,
+) -> Result<String, String> {
+    if target_receive_id.trim().is_empty() {
+        return Err("target_receive_id must not be empty".to_string());
+    }
+    if !["chat_id", "open_id", "user_id", "union_id", "email"].contains(&receive_id_type.as_str()) {
+        return Err(format!("Invalid receive_id_type: {}", receive_id_type));
+    }
+
+    let (config, table_name, record) = {
+        let conn = open_db()?;
+        let config = load_config_from_db(&conn)?.ok_or("请先保存飞书配置")?;
+        let table_name = existing_project_hooks_table(&conn, &project_path)?
+            .ok_or_else(|| format!("Hook record not found: {}", record_id))?;
+        let query_sql = format!(
+            "SELECT event_name, session_id, notification_text, transcript_path, content FROM {} WHERE id = ?1",
+            table_name
+        );
+        let record: (String, String, String, String, String) = conn
+            .query_row(&query_sql, params![record_id], |row| {
+                Ok((row.get(0)?, row.get(1)?, row.get(2)?, row.get(3)?, row.get(4)?))
+            })
+            .map_err(|e| format!("Hook record not found: {} ({})", record_id, e))?;
+        (config, table_name, record)
+    };
+    let (event_name, session_id, notification_text, transcript_path, content) = record;
+
+    let mut elements = vec![serde_json::json!({
+        "tag": "div",
+        "text": {
+            "tag": "lark_md",
+            "content": format!("↪️ **转发的通知**\n\n{}", content)
+        }
+    })];
+    if event_name == "PermissionRequest" {
+        elements.push(serde_json::json!({
+            "tag": "action",
+            "actions": [
+                {
+                    "tag": "button",
+                    "text": { "tag": "plain_text", "content": "✅ Yes (1)" },
+                    "type": "primary",
+                    "value": { "choice": "1" }
+                },
+                {
+                    "tag": "button",
+                    "text": { "tag": "plain_text", "content": "❌ No (2)" },
+                    "type": "danger",
+                    "value": { "choice": "2" }
+                }
+            ]
+        }));
+    }
+    let card = serde_json::json!({
+        "config": { "wide_screen_mode": true },
+        "elements": elements
+    });
+
+    let client = build_http_client(config.proxy_url.as_deref())?;
+    let token = fetch_tenant_access_token(&client, &config.app_id, &config.app_secret).await?;
+    let result: serde_json::Value = client
+        .post("https://open.feishu.cn/open-apis/im/v1/messages")
+        .header("Authorization", format!("Bearer {}", token))
+        .query(&[("receive_id_type", receive_id_type.as_str())])
+        .json(&serde_json::json!({
+            "receive_id": target_receive_id,
+            "msg_type": "interactive",
+            "content": card.to_string()
+        }))
+        .send()
+        .await
+        .map_err(|e| format!("Failed to send message: {}", e))?
+        .json()
+        .await
+        .map_err(|e| format!("Failed to parse message response: {}", e))?;
+
+    let send_result = if result["code"].as_i64().unwrap_or(-1) == 0 {
+        Ok(())
+    } else {
+        Err(format!("Failed to send message: {}", result["msg"].as_str().unwrap_or("Unknown error")))
+    };
+
+    // 记录转发（成功或失败），作为同一项目下的一条新记录
+    let forward_result = match &send_result {
+        Ok(_) => format!("forwarded #{} to {}:{}", record_id, receive_id_type, target_receive_id),
+        Err(e) => format!("forward #{} failed: {}", record_id, e),
+    };
+    let now_ms = project_tables::now_millis();
+    let conn = open_db()?;
+    let insert_sql = format!(
+        "INSERT INTO {} (event_name, session_id, notification_text, transcript_path, content, result, created_at)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+        table_name
+    );
+    conn.execute(
+        &insert_sql,
+        params![event_name, session_id, notification_text, transcript_path, content, forward_result, now_ms],
+    )
+    .map_err(|e| e.to_string())?;
+
+    send_result?;
+    log::info!("[forward] record #{} forwarded to {}:{}", record_id, receive_id_type, target_receive_id);
+    Ok("转发成功".to_string())
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct HookRecordsResponse {
     pub records: Vec<HookRecord>,
@@ -976,41 +1876,428 @@ pub struct HookRecordsResponse {
     pub page_size: u32,
 }
 
+/// 构建 hook 记录的过滤条件：按事件名精确匹配、按 result 前缀匹配（如 "failed:"）
+fn hook_records_filter(event_name: Option<&str>, result_prefix: Option<&str>) -> (String, Vec<String>) {
+    let mut clauses = Vec::new();
+    let mut values = Vec::new();
+    if let Some(event_name) = event_name.map(str::trim).filter(|e| !e.is_empty()) {
+        values.push(event_name.to_string());
+        clauses.push(format!("event_name = ?{}", values.len()));
+    }
+    if let Some(prefix) = result_prefix.filter(|p| !p.is_empty()) {
+        values.push(prefix.to_string());
+        // substr 比较避免 LIKE 通配符转义问题
+        clauses.push(format!("substr(result, 1, length(?{n})) = ?{n}", n = values.len()));
+    }
+    let where_sql = if clauses.is_empty() {
+        String::new()
+    } else {
+        format!("WHERE {}", clauses.join(" AND "))
+    };
+    (where_sql, values)
+}
+
+/// 列表查询不返回 content（可达数千字符），详情通过 get_hook_record 按需加载
+const HOOK_RECORD_LIST_COLUMNS: &str =
+    "id, event_name, session_id, notification_text, transcript_path, '' AS content, result, created_at, tool_name, permission_mode";
+
+fn hook_record_from_row(row: &rusqlite::Row) -> rusqlite::Result<HookRecord> {
+    Ok(HookRecord {
+        id: row.get(0)?,
+        event_name: row.get(1)?,
+        session_id: row.get(2)?,
+        notification_text: row.get(3)?,
+        transcript_path: row.get(4)?,
+        content: row.get(5)?,
+        result: row.get(6)?,
+        created_at: row.get(7)?,
+        tool_name: row.get(8)?,
+        permission_mode: row.get(9)?,
+    })
+}
+
+/// 获取单条记录的完整内容
 #[tauri::command]
-fn get_hook_records(project_path: String, page: Option<u32>, page_size: Option<u32>) -> Result<HookRecordsResponse, String> {
+fn get_hook_record(project_path: String, id: i64) -> Result<HookRecord, String> {
     let conn = open_db()?;
-    let table_name = project_hooks_table_name(&project_path);
-    ensure_project_hooks_table(&conn, &table_name)?;
-
-    let total_sql = format!("SELECT COUNT(*) FROM {}", table_name);
-    let total: i64 = conn.query_row(&total_sql, [], |row| row.get(0)).unwrap_or(0);
+    let table_name = existing_project_hooks_table(&conn, &project_path)?
+        .ok_or_else(|| format!("Hook record not found: {}", id))?;
+    let query_sql = format!(
+        "SELECT id, event_name, session_id, notification_text, transcript_path, content, result, created_at, tool_name, permission_mode
+         FROM {} WHERE id = ?1",
+        table_name
+    );
+    conn.query_row(&query_sql, params![id], hook_record_from_row)
+        .optional()
+        .map_err(|e| e.to_string())?
+        .ok_or_else(|| format!("Hook record not found: {}", id))
+}
 
+#[tauri::command]
+fn get_hook_records(
+    project_path: String,
+    page: Option<u32>,
+    page_size: Option<u32>,
+    event_name: Option<String>,
+    result_prefix: Option<String>,
+) -> Result<HookRecordsResponse, String> {
     let page = page.unwrap_or(1).max(1);
     let page_size = page_size.unwrap_or(20).min(100);
     let offset = (page - 1) * page_size;
 
+    let conn = open_db()?;
+    let Some(table_name) = existing_project_hooks_table(&conn, &project_path)? else {
+        return Ok(HookRecordsResponse { records: Vec::new(), total: 0, page, page_size });
+    };
+
+    let (where_sql, filter_values) = hook_records_filter(event_name.as_deref(), result_prefix.as_deref());
+
+    let total_sql = format!("SELECT COUNT(*) FROM {} {}", table_name, where_sql);
+    let total: i64 = conn
+        .query_row(&total_sql, rusqlite::params_from_iter(filter_values.iter()), |row| row.get(0))
+        .unwrap_or(0);
+
     let query_sql = format!(
-        "SELECT id, event_name, session_id, notification_text, transcript_path, content, result, created_at
+        "SELECT {}
          FROM {}
+         {}
          ORDER BY created_at DESC
-         LIMIT ?1 OFFSET ?2",
-        table_name
+         LIMIT {} OFFSET {}",
+        HOOK_RECORD_LIST_COLUMNS, table_name, where_sql, page_size, offset
     );
     let mut stmt = conn.prepare(&query_sql).map_err(|e| e.to_string())?;
 
     let rows = stmt
-        .query_map(params![page_size as i64, offset as i64], |row| {
-            Ok(HookRecord {
-                id: row.get(0)?,
-                event_name: row.get(1)?,
-                session_id: row.get(2)?,
-                notification_text: row.get(3)?,
-                transcript_path: row.get(4)?,
-                content: row.get(5)?,
-                result: row.get(6)?,
-                created_at: row.get(7)?,
-            })
-        })
+        .query_map(rusqlite::params_from_iter(filter_values.iter()), hook_record_from_row)
+        .map_err(|e| e.to_string())?;
+
+    let mut records = Vec::new();
+    for record in rows {
+        records.push(record.map_err(|e| e.to_string())?);
+    }
+    Ok(HookRecordsResponse {
+        records,
+        total,
+        page,
+        page_size,
+    })
+}
+
+/// CSV 字段转义：换行写成字面量 \\n 使每条记录占一行，含逗号或引号时加引号
+fn csv_field(value: &str) -> String {
+    let value = value.replace("\r\n", "\\n").replace(['\n', '\r'], "\\n");
+    if value.contains(',') || value.contains('"') {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value
+    }
+}
+
+fn write_hook_record(
+    out: &mut dyn std::io::Write,
+    record: &HookRecord,
+    format: &str,
+    first: bool,
+) -> std::io::Result<()> {
+    if format == "csv" {
+        let fields = [
+            record.id.to_string(),
+            csv_field(&record.event_name),
+            csv_field(&record.session_id),
+            csv_field(&record.notification_text),
+            csv_field(&record.transcript_path),
+            csv_field(&record.content),
+            csv_field(&record.result),
+            record.created_at.to_string(),
+            csv_field(record.tool_name.as_deref().unwrap_or("")),
+            csv_field(record.permission_mode.as_deref().unwrap_or("")),
+        ];
+        writeln!(out, "{}", fields.join(","))
+    } else {
+        let json = serde_json::to_string_pretty(record).map_err(std::io::Error::other)?;
+        if !first {
+            writeln!(out, ",")?;
+        }
+        let indented: Vec<String> = json.lines().map(|line| format!("  {}", line)).collect();
+        write!(out, "{}", indented.join("\n"))
+    }
+}
+
+/// 导出项目全部 hook 记录为 JSON（格式化）或 CSV，逐行写出，不一次性加载整表
+/// 指定 path（由前端通过 dialog 插件选择）时写入文件并返回路径，否则返回导出文本
+#[tauri::command]
+fn export_hook_records(project_path: String, format: String, path: Option<String>) -> Result<String, String> {
+    let format = format.trim().to_lowercase();
+    if format != "json" && format != "csv" {
+        return Err(format!("Unsupported export format '{}', expected json/csv", format));
+    }
+
+    let conn = open_db()?;
+    let table_name = existing_project_hooks_table(&conn, &project_path)?;
+
+    let path = path.filter(|p| !p.trim().is_empty());
+    let mut file_out;
+    let mut buffer: Vec<u8> = Vec::new();
+    let target: &mut dyn std::io::Write = match &path {
+        Some(p) => {
+            file_out = std::io::BufWriter::new(std::fs::File::create(p).map_err(|e| e.to_string())?);
+            &mut file_out
+        }
+        None => &mut buffer,
+    };
+
+    if format == "csv" {
+        writeln!(
+            target,
+            "id,event_name,session_id,notification_text,transcript_path,content,result,created_at,tool_name,permission_mode"
+        )
+            .map_err(|e| e.to_string())?;
+    } else {
+        writeln!(target, "[").map_err(|e| e.to_string())?;
+    }
+
+    let mut count = 0usize;
+    if let Some(table_name) = table_name {
+        let query_sql = format!(
+            "SELECT id, event_name, session_id, notification_text, transcript_path, content, result, created_at, tool_name, permission_mode
+             FROM {}
+             ORDER BY created_at ASC",
+            table_name
+        );
+        let mut stmt = conn.prepare(&query_sql).map_err(|e| e.to_string())?;
+        let mut rows = stmt.query([]).map_err(|e| e.to_string())?;
+        while let Some(row) = rows.next().map_err(|e| e.to_string())? {
+            let record = hook_record_from_row(row).map_err(|e| e.to_string())?;
+            write_hook_record(target, &record, &format, count == 0).map_err(|e| e.to_string())?;
+            count += 1;
+        }
+    }
+
+    if format == "json" {
+        if count > 0 {
+            writeln!(target).map_err(|e| e.to_string())?;
+        }
+        writeln!(target, "]").map_err(|e| e.to_string())?;
+    }
+    target.flush().map_err(|e| e.to_string())?;
+    log::info!("[export] exported {} hook records as {} for {}", count, format, project_path);
+
+    match path {
+        Some(p) => Ok(p),
+        None => String::from_utf8(buffer).map_err(|e| e.to_string()),
+    }
+}
+
+/// 列出所有项目的 hook 记录表（hook_records_<hash>）
+fn list_project_hook_tables(conn: &Connection) -> Result<Vec<String>, String> {
+    let mut stmt = conn
+        .prepare("SELECT name FROM sqlite_master WHERE type = 'table' AND name LIKE 'hook\\_records\\_%' ESCAPE '\\'")
+        .map_err(|e| e.to_string())?;
+    let rows = stmt
+        .query_map([], |row| row.get::<_, String>(0))
+        .map_err(|e| e.to_string())?;
+    let mut names = Vec::new();
+    for name in rows {
+        names.push(name.map_err(|e| e.to_string())?);
+    }
+    Ok(names)
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct PruneResult {
+    pub rows_deleted: usize,
+    pub tables_dropped: usize,
+}
+
+/// 删除早于 cutoff_ms 的记录（hook 记录的 created_at 为毫秒）
+fn prune_table_older_than(conn: &Connection, table_name: &str, cutoff_ms: i64) -> Result<usize, String> {
+    let sql = format!("DELETE FROM {} WHERE created_at < ?1", table_name);
+    conn.execute(&sql, params![cutoff_ms]).map_err(|e| e.to_string())
+}
+
+/// 删除项目中早于 older_than_secs 秒的 hook 记录
+#[tauri::command]
+fn prune_hook_records(project_path: String, older_than_secs: u64) -> Result<PruneResult, String> {
+    let conn = open_db()?;
+    let Some(table_name) = existing_project_hooks_table(&conn, &project_path)? else {
+        return Ok(PruneResult::default());
+    };
+    let cutoff_ms = project_tables::now_millis() - (older_than_secs as i64).saturating_mul(1000);
+    let rows_deleted = prune_table_older_than(&conn, &table_name, cutoff_ms)?;
+    log::info!("[prune] deleted {} records older than {}s from {}", rows_deleted, older_than_secs, table_name);
+    Ok(PruneResult { rows_deleted, tables_dropped: 0 })
+}
+
+/// 清理所有项目：删除早于 older_than_days 天的记录，并删除 projects 表中已不存在的项目的记录表
+#[tauri::command]
+fn prune_all_projects(older_than_days: u64) -> Result<PruneResult, String> {
+    let conn = open_db()?;
+    let known_tables: std::collections::HashSet<String> = {
+        let mut stmt = conn.prepare("SELECT path FROM projects").map_err(|e| e.to_string())?;
+        let rows = stmt
+            .query_map([], |row| row.get::<_, String>(0))
+            .map_err(|e| e.to_string())?;
+        let mut tables = std::collections::HashSet::new();
+        for path in rows {
+            let path = path.map_err(|e| e.to_string())?;
+            if let Some(table_name) = project_tables::lookup_project_hooks_table(&conn, &path).map_err(|e| e.to_string())? {
+                tables.insert(table_name);
+            }
+        }
+        tables
+    };
+
+    let cutoff_ms = project_tables::now_millis() - (older_than_days as i64).saturating_mul(86_400_000);
+    let mut result = PruneResult::default();
+    for table_name in list_project_hook_tables(&conn)? {
+        if known_tables.contains(&table_name) {
+            result.rows_deleted += prune_table_older_than(&conn, &table_name, cutoff_ms)?;
+        } else {
+            let rows: i64 = conn
+                .query_row(&format!("SELECT COUNT(*) FROM {}", table_name), [], |row| row.get(0))
+                .unwrap_or(0);
+            // 搜索索引的触发器随原表删除，索引表需要单独删除
+            conn.execute_batch(&format!(
+                "DROP TABLE IF EXISTS {table}; DROP TABLE IF EXISTS fts_{table};",
+                table = table_name
+            ))
+            .map_err(|e| e.to_string())?;
+            result.rows_deleted += rows as usize;
+            result.tables_dropped += 1;
+            log::info!("[prune] dropped orphaned table {} ({} records)", table_name, rows);
+        }
+    }
+    log::info!(
+        "[prune] older_than_days={}: {} records deleted, {} tables dropped",
+        older_than_days, result.rows_deleted, result.tables_dropped
+    );
+    Ok(result)
+}
+
+/// 为项目 hook 记录表建立 FTS5（trigram，支持中文子串匹配）索引，由触发器与原表保持同步，返回索引表名
+/// 索引表以 fts_ 为前缀，避免被按 hook_records_ 前缀枚举项目表的逻辑误认
+fn ensure_hook_records_fts(conn: &Connection, table_name: &str) -> Result<String, String> {
+    let fts_name = format!("fts_{}", table_name);
+    let has_trigger: bool = conn
+        .query_row(
+            "SELECT 1 FROM sqlite_master WHERE type = 'trigger' AND name = ?1",
+            params![format!("{}_ai", fts_name)],
+            |_| Ok(()),
+        )
+        .optional()
+        .map_err(|e| e.to_string())?
+        .is_some();
+    if has_trigger {
+        return Ok(fts_name);
+    }
+
+    let sql = format!(
+        "CREATE VIRTUAL TABLE IF NOT EXISTS {fts} USING fts5(
+            event_name, notification_text, content,
+            content='{table}', content_rowid='id', tokenize='trigram'
+        );
+        CREATE TRIGGER IF NOT EXISTS {fts}_ai AFTER INSERT ON {table} BEGIN
+            INSERT INTO {fts}(rowid, event_name, notification_text, content)
+            VALUES (new.id, new.event_name, new.notification_text, new.content);
+        END;
+        CREATE TRIGGER IF NOT EXISTS {fts}_ad AFTER DELETE ON {table} BEGIN
+            INSERT INTO {fts}({fts}, rowid, event_name, notification_text, content)
+            VALUES ('delete', old.id, old.event_name, old.notification_text, old.content);
+        END;
+        CREATE TRIGGER IF NOT EXISTS {fts}_au AFTER UPDATE ON {table} BEGIN
+            INSERT INTO {fts}({fts}, rowid, event_name, notification_text, content)
+            VALUES ('delete', old.id, old.event_name, old.notification_text, old.content);
+            INSERT INTO {fts}(rowid, event_name, notification_text, content)
+            VALUES (new.id, new.event_name, new.notification_text, new.content);
+        END;
+        INSERT INTO {fts}({fts}) VALUES ('rebuild');",
+        fts = fts_name,
+        table = table_name
+    );
+    conn.execute_batch(&format!("BEGIN; {} COMMIT;", sql))
+        .map_err(|e| e.to_string())?;
+    log::info!("[search] built full-text index {}", fts_name);
+    Ok(fts_name)
+}
+
+/// 将用户输入转换为 FTS5 查询：每个词作为短语匹配（AND），转义双引号
+/// trigram 分词要求每个词至少 3 个字符，否则返回 None 以回退到 LIKE
+fn fts_match_query(query: &str) -> Option<String> {
+    let terms: Vec<&str> = query.split_whitespace().collect();
+    if terms.is_empty() || terms.iter().any(|t| t.chars().count() < 3) {
+        return None;
+    }
+    Some(
+        terms
+            .iter()
+            .map(|t| format!("\"{}\"", t.replace('"', "\"\"")))
+            .collect::<Vec<_>>()
+            .join(" AND "),
+    )
+}
+
+/// 转义 LIKE 中的 % _ \ 并在两侧加通配符
+fn like_pattern(query: &str) -> String {
+    let escaped = query
+        .replace('\\', "\\\\")
+        .replace('%', "\\%")
+        .replace('_', "\\_");
+    format!("%{}%", escaped)
+}
+
+/// 在项目 hook 记录的 content / notification_text / event_name 中搜索，按时间倒序分页
+#[tauri::command]
+fn search_hook_records(
+    project_path: String,
+    query: String,
+    page: Option<u32>,
+    page_size: Option<u32>,
+) -> Result<HookRecordsResponse, String> {
+    let query = query.trim().to_string();
+    if query.is_empty() {
+        return get_hook_records(project_path, page, page_size, None, None);
+    }
+
+    let page = page.unwrap_or(1).max(1);
+    let page_size = page_size.unwrap_or(20).min(100);
+    let offset = (page - 1) * page_size;
+
+    let conn = open_db()?;
+    let Some(table_name) = existing_project_hooks_table(&conn, &project_path)? else {
+        return Ok(HookRecordsResponse { records: Vec::new(), total: 0, page, page_size });
+    };
+
+    let (filter, pattern) = match fts_match_query(&query) {
+        Some(match_query) => {
+            let fts_name = ensure_hook_records_fts(&conn, &table_name)?;
+            (
+                format!("id IN (SELECT rowid FROM {} WHERE {} MATCH ?1)", fts_name, fts_name),
+                match_query,
+            )
+        }
+        None => (
+            "(content LIKE ?1 ESCAPE '\\' OR notification_text LIKE ?1 ESCAPE '\\' OR event_name LIKE ?1 ESCAPE '\\')"
+                .to_string(),
+            like_pattern(&query),
+        ),
+    };
+
+    let total_sql = format!("SELECT COUNT(*) FROM {} WHERE {}", table_name, filter);
+    let total: i64 = conn
+        .query_row(&total_sql, params![pattern], |row| row.get(0))
+        .map_err(|e| e.to_string())?;
+
+    let query_sql = format!(
+        "SELECT {}
+         FROM {}
+         WHERE {}
+         ORDER BY created_at DESC
+         LIMIT ?2 OFFSET ?3",
+        HOOK_RECORD_LIST_COLUMNS, table_name, filter
+    );
+    let mut stmt = conn.prepare(&query_sql).map_err(|e| e.to_string())?;
+    let rows = stmt
+        .query_map(params![pattern, page_size as i64, offset as i64], hook_record_from_row)
         .map_err(|e| e.to_string())?;
 
     let mut records = Vec::new();
@@ -1028,8 +2315,9 @@ fn get_hook_records(project_path: String, page: Option<u32>, page_size: Option<u
 #[tauri::command]
 fn delete_hook_record(project_path: String, id: i64) -> Result<(), String> {
     let conn = open_db()?;
-    let table_name = project_hooks_table_name(&project_path);
-    ensure_project_hooks_table(&conn, &table_name)?;
+    let Some(table_name) = existing_project_hooks_table(&conn, &project_path)? else {
+        return Ok(());
+    };
     let delete_sql = format!("DELETE FROM {} WHERE id = ?1", table_name);
     conn.execute(&delete_sql, params![id]).map_err(|e| e.to_string())?;
     Ok(())
@@ -1038,8 +2326,9 @@ fn delete_hook_record(project_path: String, id: i64) -> Result<(), String> {
 #[tauri::command]
 fn delete_hook_records(project_path: String, ids: Vec<i64>) -> Result<(), String> {
     let conn = open_db()?;
-    let table_name = project_hooks_table_name(&project_path);
-    ensure_project_hooks_table(&conn, &table_name)?;
+    let Some(table_name) = existing_project_hooks_table(&conn, &project_path)? else {
+        return Ok(());
+    };
     let delete_sql = format!("DELETE FROM {} WHERE id = ?1", table_name);
     for id in ids {
         conn.execute(&delete_sql, params![id]).map_err(|e| e.to_string())?;
@@ -1050,8 +2339,9 @@ fn delete_hook_records(project_path: String, ids: Vec<i64>) -> Result<(), String
 #[tauri::command]
 fn get_hook_status(project_path: String) -> Result<HookStatus, String> {
     let conn = open_db()?;
-    let table_name = project_hooks_table_name(&project_path);
-    ensure_project_hooks_table(&conn, &table_name)?;
+    let Some(table_name) = existing_project_hooks_table(&conn, &project_path)? else {
+        return Ok(HookStatus { last_event_name: None, last_result: None, last_event_at: None });
+    };
     let query_sql = format!(
         "SELECT event_name, result, created_at
          FROM {}
@@ -1077,12 +2367,140 @@ fn get_hook_status(project_path: String) -> Result<HookStatus, String> {
     }
 }
 
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EventStats {
+    pub event_name: String,
+    pub total: i64,
+    pub sent: i64,
+    pub failed: i64,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct HookStats {
+    pub by_event: Vec<EventStats>,
+    pub total: i64,
+    pub sent: i64,
+    pub failed: i64,
+    /// 统计范围内最早 / 最晚一条记录的时间（毫秒），无记录时为 None
+    pub first_at: Option<i64>,
+    pub last_at: Option<i64>,
+}
+
+/// 按事件统计通知发送结果；since 为毫秒时间戳，只统计此后的记录
+#[tauri::command]
+fn get_hook_statistics(project_path: String, since: Option<i64>) -> Result<HookStats, String> {
+    let conn = open_db()?;
+    let Some(table_name) = existing_project_hooks_table(&conn, &project_path)? else {
+        return Ok(HookStats::default());
+    };
+    let since = since.unwrap_or(0);
+
+    // result 为 "sent" / "sent (urgent)" / "failed: ..." 等，见 CLI 的 run_hook
+    let query_sql = format!(
+        "SELECT event_name,
+                COUNT(*),
+                SUM(CASE WHEN result LIKE 'sent%' THEN 1 ELSE 0 END),
+                SUM(CASE WHEN result LIKE 'failed:%' THEN 1 ELSE 0 END),
+                MIN(created_at),
+                MAX(created_at)
+         FROM {}
+         WHERE created_at >= ?1
+         GROUP BY event_name
+         ORDER BY COUNT(*) DESC",
+        table_name
+    );
+    let mut stmt = conn.prepare(&query_sql).map_err(|e| e.to_string())?;
+    let rows = stmt
+        .query_map(params![since], |row| {
+            Ok((
+                EventStats {
+                    event_name: row.get(0)?,
+                    total: row.get(1)?,
+                    sent: row.get(2)?,
+                    failed: row.get(3)?,
+                },
+                row.get::<_, i64>(4)?,
+                row.get::<_, i64>(5)?,
+            ))
+        })
+        .map_err(|e| e.to_string())?;
+
+    let mut stats = HookStats::default();
+    for row in rows {
+        let (event, first_at, last_at) = row.map_err(|e| e.to_string())?;
+        stats.total += event.total;
+        stats.sent += event.sent;
+        stats.failed += event.failed;
+        stats.first_at = Some(stats.first_at.map_or(first_at, |t| t.min(first_at)));
+        stats.last_at = Some(stats.last_at.map_or(last_at, |t| t.max(last_at)));
+        stats.by_event.push(event);
+    }
+    Ok(stats)
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ActiveSession {
+    pub session_id: String,
+    pub project_path: String,
+    pub last_event: String,
+    pub last_event_at: i64,
+}
+
+/// 跨所有项目的 hook 记录表，找出最近事件在 idle_threshold_secs 内的会话（按最近活跃排序）
+#[tauri::command]
+fn get_active_sessions(idle_threshold_secs: u64) -> Result<Vec<ActiveSession>, String> {
+    let conn = open_db()?;
+    let now_ms = project_tables::now_millis();
+    // hook 记录的 created_at 为毫秒
+    let since_ms = now_ms - (idle_threshold_secs as i64).saturating_mul(1000);
+
+    // 通过 project_tables 映射反查表所属的项目路径
+    let project_paths = project_tables::project_paths_by_table(&conn).map_err(|e| e.to_string())?;
+
+    let table_names = list_project_hook_tables(&conn)?;
+
+    let mut sessions = Vec::new();
+    for table_name in table_names {
+        ensure_project_hooks_table(&conn, &table_name)?;
+        // SQLite 中与 MAX() 同时选择的列取自取得最大值的那一行
+        let query_sql = format!(
+            "SELECT session_id, event_name, MAX(created_at)
+             FROM {}
+             WHERE session_id != ''
+             GROUP BY session_id
+             HAVING MAX(created_at) >= ?1",
+            table_name
+        );
+        let mut stmt = conn.prepare(&query_sql).map_err(|e| e.to_string())?;
+        let project_path = project_paths.get(&table_name).cloned().unwrap_or_default();
+        let rows = stmt
+            .query_map(params![since_ms], |row| {
+                Ok(ActiveSession {
+                    session_id: row.get(0)?,
+                    project_path: project_path.clone(),
+                    last_event: row.get(1)?,
+                    last_event_at: row.get(2)?,
+                })
+            })
+            .map_err(|e| e.to_string())?;
+        for session in rows {
+            sessions.push(session.map_err(|e| e.to_string())?);
+        }
+    }
+
+    sessions.sort_by_key(|s| std::cmp::Reverse(s.last_event_at));
+    Ok(sessions)
+}
+
 #[tauri::command]
 fn get_projects() -> Result<Vec<Project>, String> {
     let conn = open_db()?;
 
     let mut stmt = conn
-        .prepare("SELECT id, name, path, hooks_installed, created_at, updated_at FROM projects ORDER BY created_at DESC")
+        .prepare(
+            "SELECT id, name, path, hooks_installed, created_at, updated_at, chat_id, open_id
+             FROM projects ORDER BY created_at DESC",
+        )
         .map_err(|e| e.to_string())?;
 
     let rows = stmt
@@ -1092,16 +2510,23 @@ fn get_projects() -> Result<Vec<Project>, String> {
                 name: row.get(1)?,
                 path: row.get(2)?,
                 hooks_installed: row.get::<_, i64>(3)? != 0,
+                user_hooks_installed: false,
                 created_at: row.get(4)?,
                 updated_at: row.get(5)?,
+                chat_id: row.get(6)?,
+                open_id: row.get(7)?,
             })
         })
         .map_err(|e| e.to_string())?;
 
+    // 用户级 hook 对所有项目生效，同步时视为已安装
+    let user_installed = check_user_hooks_installed().unwrap_or(false);
     let mut projects = Vec::new();
     for project in rows {
         let mut item = project.map_err(|e| e.to_string())?;
-        if let Ok(actual) = check_hooks_installed_for_path(&item.path) {
+        item.user_hooks_installed = user_installed;
+        if let Ok(project_installed) = check_hooks_installed_for_path(&item.path) {
+            let actual = project_installed || user_installed;
             if actual != item.hooks_installed {
                 let now = std::time::SystemTime::now()
                     .duration_since(std::time::UNIX_EPOCH)
@@ -1130,7 +2555,8 @@ fn add_project(name: String, path: String) -> Result<Project, String> {
         .map_err(|e| e.to_string())?
         .as_secs() as i64;
 
-    let hooks_installed = check_hooks_installed_for_path(&path).unwrap_or(false);
+    let user_hooks_installed = check_user_hooks_installed().unwrap_or(false);
+    let hooks_installed = check_hooks_installed_for_path(&path).unwrap_or(false) || user_hooks_installed;
     conn.execute(
         "INSERT INTO projects (name, path, hooks_installed, created_at, updated_at) VALUES (?1, ?2, ?3, ?4, ?5)",
         params![name, path, hooks_installed as i64, now, now],
@@ -1144,8 +2570,11 @@ fn add_project(name: String, path: String) -> Result<Project, String> {
         name,
         path,
         hooks_installed,
+        user_hooks_installed,
         created_at: now,
         updated_at: now,
+        chat_id: None,
+        open_id: None,
     })
 }
 
@@ -1191,23 +2620,556 @@ fn set_project_hooks_status(id: i64, hooks_installed: bool) -> Result<(), String
     Ok(())
 }
 
+/// 项目的 relay 配置：relay_url 为基础地址（worker 连接时统一追加 /ws/{task_id}），
+/// worker_mode 为 "local"（本机执行）或 "remote"（LiteBox 沙箱执行）
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RelayConfig {
+    pub relay_url: String,
+    pub worker_mode: String,
+}
+
+const WORKER_MODES: [&str; 2] = ["local", "remote"];
+
+fn load_relay_config(conn: &Connection, project_id: i64) -> Result<RelayConfig, String> {
+    let (relay_url, worker_mode): (Option<String>, Option<String>) = conn
+        .query_row(
+            "SELECT relay_url, worker_mode FROM projects WHERE id = ?1",
+            params![project_id],
+            |row| Ok((row.get(0)?, row.get(1)?)),
+        )
+        .optional()
+        .map_err(|e| e.to_string())?
+        .ok_or_else(|| format!("project {} not found", project_id))?;
+    Ok(RelayConfig {
+        relay_url: relay_url
+            .filter(|u| !u.trim().is_empty())
+            .unwrap_or_else(|| relay_client::DEFAULT_RELAY_URL.to_string()),
+        worker_mode: worker_mode
+            .filter(|m| WORKER_MODES.contains(&m.as_str()))
+            .unwrap_or_else(|| "local".to_string()),
+    })
+}
+
+#[tauri::command]
+fn get_relay_config(project_id: i64) -> Result<RelayConfig, String> {
+    let conn = open_db()?;
+    load_relay_config(&conn, project_id)
+}
+
+#[tauri::command]
+fn set_relay_config(project_id: i64, relay_url: String, worker_mode: String) -> Result<(), String> {
+    let relay_url = relay_url.trim().trim_end_matches('/').to_string();
+    if !relay_url.is_empty() && !relay_url.starts_with("ws://") && !relay_url.starts_with("wss://") {
+        return Err("relay_url must start with ws:// or wss://".to_string());
+    }
+    if !WORKER_MODES.contains(&worker_mode.as_str()) {
+        return Err(format!("unsupported worker_mode: {}", worker_mode));
+    }
+
+    let conn = open_db()?;
+    let now = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map_err(|e| e.to_string())?
+        .as_secs() as i64;
+    let updated = conn
+        .execute(
+            "UPDATE projects SET relay_url = ?1, worker_mode = ?2, updated_at = ?3 WHERE id = ?4",
+            params![relay_url, worker_mode, now, project_id],
+        )
+        .map_err(|e| e.to_string())?;
+    if updated == 0 {
+        return Err(format!("project {} not found", project_id));
+    }
+    Ok(())
+}
+
+pub(crate) fn is_registered_project(path: &str) -> Result<bool, String> {
+    let conn = open_db()?;
+    conn.query_row("SELECT 1 FROM projects WHERE path = ?1", params![path], |_| Ok(()))
+        .optional()
+        .map(|row| row.is_some())
+        .map_err(|e| e.to_string())
+}
+
+/// 项目的 claude 启动配置；未设置工作目录时使用项目路径，claude_args 以 JSON 数组保存
+fn load_claude_launch_config(conn: &Connection, project_id: i64) -> Result<relay_client::ClaudeLaunchConfig, String> {
+    let (path, binary, cwd, model, args): (String, Option<String>, Option<String>, Option<String>, Option<String>) = conn
+        .query_row(
+            "SELECT path, claude_binary, claude_cwd, claude_model, claude_args FROM projects WHERE id = ?1",
+            params![project_id],
+            |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?, row.get(3)?, row.get(4)?)),
+        )
+        .optional()
+        .map_err(|e| e.to_string())?
+        .ok_or_else(|| format!("project {} not found", project_id))?;
+    let extra_args = match args.filter(|a| !a.trim().is_empty()) {
+        Some(json) => serde_json::from_str(&json).map_err(|e| format!("Invalid claude_args: {}", e))?,
+        None => Vec::new(),
+    };
+    Ok(relay_client::ClaudeLaunchConfig {
+        binary,
+        cwd: cwd.filter(|c| !c.trim().is_empty()).or(Some(path)),
+        model,
+        extra_args,
+    })
+}
+
+#[tauri::command]
+fn get_claude_launch_config(project_id: i64) -> Result<relay_client::ClaudeLaunchConfig, String> {
+    let conn = open_db()?;
+    load_claude_launch_config(&conn, project_id)
+}
+
+/// 保存项目的 claude 启动配置；空字段表示使用默认值（claude / 项目路径 / 不指定模型）
+#[tauri::command]
+fn set_claude_launch_config(project_id: i64, config: relay_client::ClaudeLaunchConfig) -> Result<(), String> {
+    let trimmed = |value: Option<String>| value.map(|s| s.trim().to_string()).filter(|s| !s.is_empty());
+    let binary = trimmed(config.binary);
+    let cwd = trimmed(config.cwd);
+    let model = trimmed(config.model);
+    if let Some(dir) = cwd.as_deref() {
+        if !std::path::Path::new(dir).is_dir() {
+            return Err(format!("Working directory '{}' does not exist", dir));
+        }
+    }
+    let args: Vec<String> = config.extra_args.into_iter().filter(|a| !a.trim().is_empty()).collect();
+    let args = if args.is_empty() {
+        None
+    } else {
+        Some(serde_json::to_string(&args).map_err(|e| e.to_string())?)
+    };
+
+    let conn = open_db()?;
+    let now = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map_err(|e| e.to_string())?
+        .as_secs() as i64;
+    let updated = conn
+        .execute(
+            "UPDATE projects SET claude_binary = ?1, claude_cwd = ?2, claude_model = ?3, claude_args = ?4, updated_at = ?5
+             WHERE id = ?6",
+            params![binary, cwd, model, args, now, project_id],
+        )
+        .map_err(|e| e.to_string())?;
+    if updated == 0 {
+        return Err(format!("project {} not found", project_id));
+    }
+    Ok(())
+}
+
+#[tauri::command]
+fn get_dnd_config() -> Result<dnd::DndConfig, String> {
+    let conn = open_db()?;
+    dnd::load(&conn).map_err(|e| e.to_string())
+}
+
+/// 保存免打扰配置（CLI 在 run_hook 中读取）
+#[tauri::command]
+fn set_dnd_config(config: dnd::DndConfig) -> Result<(), String> {
+    let conn = open_db()?;
+    dnd::save(&conn, &config)
+}
+
+#[tauri::command]
+fn get_redact_config() -> Result<redact::RedactConfig, String> {
+    let conn = open_db()?;
+    redact::load(&conn).map_err(|e| e.to_string())
+}
+
+/// 保存终端输出脱敏配置（同时作用于 CLI 发送到飞书的终端提示）
+#[tauri::command]
+fn set_redact_config(config: redact::RedactConfig) -> Result<(), String> {
+    let conn = open_db()?;
+    redact::save(&conn, &config)?;
+    *redact_cache().write().map_err(|e| e.to_string())? = Some(Arc::new(config.compile()));
+    Ok(())
+}
+
+/// 列出 `sparky connect` 额外连接的飞书应用（不返回密钥）
+#[tauri::command]
+fn list_feishu_apps() -> Result<Vec<feishu_apps::FeishuApp>, String> {
+    let conn = open_db()?;
+    feishu_apps::list(&conn)
+}
+
+/// 添加额外飞书应用；app_id 已存在时更新密钥并重新启用
+#[tauri::command]
+fn save_feishu_app(
+    app_id: String,
+    app_secret: String,
+    encrypt_key: Option<String>,
+    verification_token: Option<String>,
+) -> Result<(), String> {
+    let conn = open_db()?;
+    feishu_apps::upsert(&conn, &app_id, &app_secret, encrypt_key.as_deref(), verification_token.as_deref())
+}
+
+#[tauri::command]
+fn set_feishu_app_enabled(app_id: String, enabled: bool) -> Result<(), String> {
+    let conn = open_db()?;
+    if !feishu_apps::set_enabled(&conn, &app_id, enabled)? {
+        return Err(format!("飞书应用不存在: {}", app_id));
+    }
+    Ok(())
+}
+
+#[tauri::command]
+fn delete_feishu_app(app_id: String) -> Result<(), String> {
+    let conn = open_db()?;
+    if !feishu_apps::remove(&conn, &app_id)? {
+        return Err(format!("飞书应用不存在: {}", app_id));
+    }
+    Ok(())
+}
+
+/// 设置项目的通知目标；chat_id 优先于 open_id，两者都为空时回退到全局配置
+#[tauri::command]
+fn set_project_notification_target(
+    project_id: i64,
+    chat_id: Option<String>,
+    open_id: Option<String>,
+) -> Result<(), String> {
+    let chat_id = chat_id.map(|s| s.trim().to_string()).filter(|s| !s.is_empty());
+    let open_id = open_id.map(|s| s.trim().to_string()).filter(|s| !s.is_empty());
+
+    let conn = open_db()?;
+    let now = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map_err(|e| e.to_string())?
+        .as_secs() as i64;
+    let updated = conn
+        .execute(
+            "UPDATE projects SET chat_id = ?1, open_id = ?2, updated_at = ?3 WHERE id = ?4",
+            params![chat_id, open_id, now, project_id],
+        )
+        .map_err(|e| e.to_string())?;
+    if updated == 0 {
+        return Err(format!("project {} not found", project_id));
+    }
+    Ok(())
+}
+
+/// 按项目的 relay 配置启动 worker（local / remote）
+#[tauri::command]
+async fn start_project_worker(project_id: i64, task_id: String) -> Result<String, String> {
+    let (config, launch) = {
+        let conn = open_db()?;
+        (load_relay_config(&conn, project_id)?, load_claude_launch_config(&conn, project_id)?)
+    };
+    if config.worker_mode == "remote" {
+        start_remote_worker(task_id, config.relay_url, None).await
+    } else {
+        start_local_worker(task_id, config.relay_url, Some(launch)).await
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PendingResolution {
+    pub id: i64,
+    pub code: Option<String>,
+    pub project_path: String,
+    pub success: bool,
+    pub error: Option<String>,
+}
+
+/// 对所有 pending 的权限请求应用同一个选择，并写入对应项目的 PTY 命令队列。
+/// "全部批准" 风险较高，必须显式传入 confirm = true
+#[tauri::command]
+fn resolve_all_pending(choice: String, confirm: bool) -> Result<Vec<PendingResolution>, String> {
+    if !confirm {
+        return Err("Refusing to resolve all pending requests without confirm = true".to_string());
+    }
+    let choice = choice.trim().to_string();
+    if choice.is_empty() {
+        return Err("Choice must not be empty".to_string());
+    }
+
+    let mut conn = open_db()?;
+    let pending: Vec<(i64, Option<String>, String)> = {
+        let mut stmt = conn
+            .prepare(
+                "SELECT id, code, project_path FROM permission_requests
+                 WHERE status = 'pending'
+                 ORDER BY created_at ASC, id ASC",
+            )
+            .map_err(|e| e.to_string())?;
+        let rows = stmt
+            .query_map([], |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)))
+            .map_err(|e| e.to_string())?;
+        let mut pending = Vec::new();
+        for row in rows {
+            pending.push(row.map_err(|e| e.to_string())?);
+        }
+        pending
+    };
+
+    let now = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map_err(|e| e.to_string())?
+        .as_secs() as i64;
+
+    let mut results = Vec::with_capacity(pending.len());
+    for (id, code, project_path) in pending {
+        let outcome = (|| -> Result<(), String> {
+            let tx = conn.transaction().map_err(|e| e.to_string())?;
+            // status 条件防止与飞书回调同时处理同一请求
+            let updated = tx
+                .execute(
+                    "UPDATE permission_requests SET status = ?1, choice = ?2
+                     WHERE id = ?3 AND status = 'pending'",
+                    params![permission_status_for_choice(&choice), choice, id],
+                )
+                .map_err(|e| e.to_string())?;
+            if updated == 0 {
+                return Err("Request is no longer pending".to_string());
+            }
+            tx.execute(
+                "INSERT INTO pty_commands (project_path, command, created_at) VALUES (?1, ?2, ?3)",
+                params![project_path, choice, now],
+            )
+            .map_err(|e| e.to_string())?;
+            tx.commit().map_err(|e| e.to_string())
+        })();
+
+        if let Err(e) = &outcome {
+            log::warn!("[resolve_all_pending] request id={} failed: {}", id, e);
+        }
+        results.push(PendingResolution {
+            id,
+            code,
+            project_path,
+            success: outcome.is_ok(),
+            error: outcome.err(),
+        });
+    }
+
+    log::info!(
+        "[resolve_all_pending] applied choice='{}' to {} pending requests",
+        choice,
+        results.iter().filter(|r| r.success).count()
+    );
+    Ok(results)
+}
+
+/// 取出发给该项目、尚未使用的附件路径，并标记为已被 task_id 使用，供 relay worker 附加到任务的 prompt
+pub(crate) fn take_pending_attachments(task_id: &str, project_path: &str) -> Result<Vec<String>, String> {
+    let project_path = project_path.trim_end_matches('/');
+    let mut conn = open_db()?;
+    let tx = conn.transaction().map_err(|e| e.to_string())?;
+    let paths = {
+        let mut stmt = tx
+            .prepare(
+                "SELECT file_path FROM attachments WHERE consumed = 0 AND project_path = ?1
+                 ORDER BY created_at ASC, id ASC",
+            )
+            .map_err(|e| e.to_string())?;
+        let rows = stmt
+            .query_map(params![project_path], |row| row.get::<_, String>(0))
+            .map_err(|e| e.to_string())?;
+        let mut paths = Vec::new();
+        for row in rows {
+            paths.push(row.map_err(|e| e.to_string())?);
+        }
+        paths
+    };
+    tx.execute(
+        "UPDATE attachments SET consumed = 1, task_id = ?1 WHERE consumed = 0 AND project_path = ?2",
+        params![task_id, project_path],
+    )
+    .map_err(|e| e.to_string())?;
+    tx.commit().map_err(|e| e.to_string())?;
+    Ok(paths)
+}
+
+/// 权限请求默认超时时间（秒）
+const DEFAULT_PERMISSION_TIMEOUT_SECS: u64 = 300;
+/// 后台扫描过期权限请求的间隔
+const PERMISSION_EXPIRY_SCAN_INTERVAL_SECS: u64 = 30;
+
+fn load_permission_timeout_secs(conn: &Connection) -> u64 {
+    conn.query_row(
+        "SELECT value FROM db_meta WHERE key = 'permission_timeout_secs'",
+        [],
+        |row| row.get::<_, String>(0),
+    )
+    .ok()
+    .and_then(|v| v.trim().parse::<u64>().ok())
+    .filter(|v| *v > 0)
+    .unwrap_or(DEFAULT_PERMISSION_TIMEOUT_SECS)
+}
+
+/// 获取 pending 权限请求的超时时间（秒），未配置时为 300
+#[tauri::command]
+fn get_permission_timeout_secs() -> Result<u64, String> {
+    let conn = open_db()?;
+    Ok(load_permission_timeout_secs(&conn))
+}
+
+/// 设置 pending 权限请求的超时时间（秒），保存在 db_meta 中
+#[tauri::command]
+fn set_permission_timeout_secs(secs: u64) -> Result<(), String> {
+    if secs == 0 {
+        return Err("Timeout must be greater than 0".to_string());
+    }
+    let conn = open_db()?;
+    conn.execute(
+        "INSERT INTO db_meta (key, value) VALUES ('permission_timeout_secs', ?1)
+         ON CONFLICT(key) DO UPDATE SET value = excluded.value",
+        params![secs.to_string()],
+    )
+    .map_err(|e| e.to_string())?;
+    Ok(())
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct ExpiredPermission {
+    id: i64,
+    code: Option<String>,
+    project_path: String,
+}
+
+/// 将超过超时时间仍为 pending 的权限请求标记为 expired，返回被标记的请求
+fn expire_stale_permission_requests() -> Result<Vec<ExpiredPermission>, String> {
+    let mut conn = open_db()?;
+    let timeout = load_permission_timeout_secs(&conn) as i64;
+    let now = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map_err(|e| e.to_string())?
+        .as_secs() as i64;
+    let cutoff = now - timeout;
+
+    let tx = conn.transaction().map_err(|e| e.to_string())?;
+    let expired = {
+        let mut stmt = tx
+            .prepare(
+                "SELECT id, code, project_path FROM permission_requests
+                 WHERE status = 'pending' AND created_at < ?1",
+            )
+            .map_err(|e| e.to_string())?;
+        let rows = stmt
+            .query_map(params![cutoff], |row| {
+                Ok(ExpiredPermission {
+                    id: row.get(0)?,
+                    code: row.get(1)?,
+                    project_path: row.get(2)?,
+                })
+            })
+            .map_err(|e| e.to_string())?;
+        let mut expired = Vec::new();
+        for row in rows {
+            expired.push(row.map_err(|e| e.to_string())?);
+        }
+        expired
+    };
+    for item in &expired {
+        tx.execute(
+            "UPDATE permission_requests SET status = 'expired' WHERE id = ?1 AND status = 'pending'",
+            params![item.id],
+        )
+        .map_err(|e| e.to_string())?;
+    }
+    tx.commit().map_err(|e| e.to_string())?;
+    Ok(expired)
+}
+
+/// 飞书 WSS 连接任务：每轮从数据库重新读取配置并建立客户端，
+/// 客户端被 reconnect_websocket 关闭后进入下一轮，从而使用最新的凭证
+async fn run_feishu_ws(state: Arc<AppState>, connected: Arc<AtomicBool>, event_tx: mpsc::Sender<String>) {
+    loop {
+        let config = get_config().ok().filter(|c| !c.app_id.is_empty() && !c.app_secret.is_empty());
+        let Some(config) = config else {
+            log::warn!("Feishu app_id or app_secret not configured, waiting for reconnect_websocket");
+            state.ws_restart.notified().await;
+            continue;
+        };
+
+        log::info!("Starting Feishu WebSocket connection...");
+        let client = Arc::new(FeishuWsClient::new_with_connected(
+            config.app_id.clone(),
+            config.app_secret.clone(),
+            config.proxy_url.clone(),
+            connected.clone(),
+            Some(event_tx.clone()),
+        ));
+        *state.ws_client.lock().unwrap_or_else(|e| e.into_inner()) = Some(client.clone());
+
+        let mut backoff = reconnect::ReconnectBackoff::new(
+            std::time::Duration::from_secs(1),
+            std::time::Duration::from_secs(60),
+        );
+        while !client.is_shutting_down() {
+            let started = std::time::Instant::now();
+            match client.connect().await {
+                Ok(_) => {
+                    log::info!("WebSocket connection closed normally");
+                }
+                Err(e) => {
+                    log::error!("WebSocket connection error: {}", e);
+                }
+            }
+            if client.is_shutting_down() {
+                break;
+            }
+            if let Some(base) = client.reconnect_interval() {
+                backoff.set_base(base);
+            }
+            if started.elapsed() >= reconnect::STABLE_CONNECTION {
+                backoff.reset();
+            }
+            let delay = backoff.next_delay();
+            log::info!("Reconnecting in {:.1} seconds...", delay.as_secs_f64());
+            if reconnect::wait_for_retry(delay).await {
+                log::info!("Reconnect forced, retrying now");
+                backoff.reset();
+            }
+        }
+        log::info!("Feishu WebSocket client stopped, reloading config");
+    }
+}
+
+/// 断开当前飞书 WSS 连接并用数据库中最新的配置重新连接（修改凭证后无需重启应用）
+#[tauri::command]
+fn reconnect_websocket(state: tauri::State<'_, Arc<AppState>>) -> Result<(), String> {
+    restart_feishu_ws(&state)
+}
+
+fn restart_feishu_ws(state: &AppState) -> Result<(), String> {
+    let client = state.ws_client.lock().map_err(|e| e.to_string())?.clone();
+    match client {
+        Some(client) => {
+            log::info!("Reconnecting Feishu WebSocket with reloaded config");
+            client.shutdown();
+            // 正在退避等待的重连立即醒来并退出旧客户端
+            force_reconnect_now()?;
+        }
+        None => state.ws_restart.notify_one(),
+    }
+    Ok(())
+}
+
 #[cfg_attr(mobile, tauri::mobile_entry_point)]
 pub fn run() {
     // 加载配置
     let config = load_config(None);
     info!(level = %config.logging.level, "Configuration loaded");
 
-    let (event_tx, _event_rx) = mpsc::channel::<String>(100);
+    let (event_tx, mut event_rx) = mpsc::channel::<String>(100);
+    let ws_event_tx = event_tx.clone();
     let state = Arc::new(AppState {
         config: Arc::new(Mutex::new(None)),
         event_tx,
+        ws_client: std::sync::Mutex::new(None),
+        ws_restart: tokio::sync::Notify::new(),
     });
 
     let ws_connected = Arc::new(AtomicBool::new(false));
 
-    tauri::Builder::default()
+    let app = tauri::Builder::default()
         .manage(state)
         .manage(PtyManager::new())
+        .manage(EmbeddedRelayState::new())
         .manage(WsConnectionState(ws_connected.clone()))
         .plugin(tauri_plugin_dialog::init())
         .setup(|app| {
@@ -1231,51 +3193,60 @@ pub fn run() {
                 }
             }
 
-            // 启动时自动连接飞书 WSS
+            // 定期将超时的 pending 权限请求标记为过期，并通知前端置灰
+            let expiry_handle = app.handle().clone();
             tauri::async_runtime::spawn(async move {
-                // 等待一小段时间让应用完全启动
-                tokio::time::sleep(tokio::time::Duration::from_secs(2)).await;
-
-                let config = get_config().ok();
-
-                if let Some(config) = config {
-                    if !config.app_id.is_empty() && !config.app_secret.is_empty() {
-                        log::info!("Starting Feishu WebSocket connection...");
-                        let client = FeishuWsClient::new_with_connected(
-                            config.app_id.clone(),
-                            config.app_secret.clone(),
-                            ws_connected.clone(),
-                        );
-
-                        loop {
-                            match client.connect().await {
-                                Ok(_) => {
-                                    log::info!("WebSocket connection closed normally");
-                                }
-                                Err(e) => {
-                                    log::error!("WebSocket connection error: {}", e);
-                                }
-                            }
-                            log::info!("Reconnecting in 5 seconds...");
-                            tokio::time::sleep(tokio::time::Duration::from_secs(5)).await;
+                let mut interval = tokio::time::interval(tokio::time::Duration::from_secs(
+                    PERMISSION_EXPIRY_SCAN_INTERVAL_SECS,
+                ));
+                loop {
+                    interval.tick().await;
+                    match expire_stale_permission_requests() {
+                        Ok(expired) if !expired.is_empty() => {
+                            log::info!("Expired {} stale permission requests", expired.len());
+                            let _ = expiry_handle.emit("permission-expired", &expired);
                         }
-                    } else {
-                        log::warn!("Feishu app_id or app_secret not configured");
+                        Ok(_) => {}
+                        Err(e) => log::error!("Failed to expire stale permission requests: {}", e),
                     }
-                } else {
-                    log::warn!("Config not found, skipping WSS connection");
                 }
             });
 
+            // 飞书 WSS 收到的消息与卡片操作转发给前端实时展示
+            let event_handle = app.handle().clone();
+            tauri::async_runtime::spawn(async move {
+                while let Some(json) = event_rx.recv().await {
+                    let payload = serde_json::from_str::<serde_json::Value>(&json)
+                        .unwrap_or(serde_json::Value::String(json));
+                    let _ = event_handle.emit("feishu-event", payload);
+                }
+            });
+
+            // 启动时自动连接飞书 WSS
+            let ws_state = app.state::<Arc<AppState>>().inner().clone();
+            tauri::async_runtime::spawn(async move {
+                // 等待一小段时间让应用完全启动
+                tokio::time::sleep(tokio::time::Duration::from_secs(2)).await;
+                run_feishu_ws(ws_state, ws_connected, ws_event_tx).await;
+            });
+
             Ok(())
         })
         .invoke_handler(tauri::generate_handler![
             get_config,
             save_config,
             test_feishu_connection,
+            test_proxy,
             send_feishu_message,
+            list_feishu_chats,
             get_hook_records,
+            get_hook_record,
+            search_hook_records,
+            export_hook_records,
+            prune_hook_records,
+            prune_all_projects,
             get_hook_status,
+            get_hook_statistics,
             delete_hook_record,
             delete_hook_records,
             get_wss_status,
@@ -1284,22 +3255,88 @@ pub fn run() {
             pty_kill,
             pty_resize,
             pty_exists,
+            pty_get_scrollback,
+            pty_list,
             record_terminal_input,
             record_terminal_output,
             get_terminal_history,
+            get_terminal_input_history,
+            clear_terminal_history,
+            clear_all_terminal_history,
             check_hooks_installed,
             install_hooks,
+            preview_install_hooks,
+            get_hook_command,
+            run_diagnostics,
             uninstall_hooks,
             get_projects,
             add_project,
             update_project,
             delete_project,
             set_project_hooks_status,
+            get_relay_config,
+            set_relay_config,
+            get_claude_launch_config,
+            set_claude_launch_config,
+            set_project_notification_target,
+            get_dnd_config,
+            set_dnd_config,
+            get_redact_config,
+            set_redact_config,
+            list_feishu_apps,
+            save_feishu_app,
+            set_feishu_app_enabled,
+            delete_feishu_app,
+            start_project_worker,
+            resolve_all_pending,
+            get_active_sessions,
+            forward_notification,
             open_folder,
-            get_ws_connected
+            set_urgent_events,
+            set_message_style,
+            set_max_notifications_per_session,
+            set_notification_backend,
+            get_permission_timeout_secs,
+            set_permission_timeout_secs,
+            get_supported_hook_events,
+            validate_hook_events_filter,
+            set_truncation_limits,
+            get_dingtalk_config,
+            save_dingtalk_config,
+            get_wework_config,
+            save_wework_config,
+            read_claude_settings,
+            write_claude_settings,
+            get_ws_connected,
+            get_ws_uptime_stats,
+            force_reconnect_now,
+            reconnect_websocket,
+            configure_sandbox,
+            get_sandbox_config,
+            get_task_log,
+            export_task_log,
+            list_approval_policies,
+            add_approval_policy,
+            update_approval_policy,
+            delete_approval_policy,
+            start_embedded_relay,
+            stop_embedded_relay,
+            get_relay_logs
         ])
-        .run(tauri::generate_context!())
-        .unwrap_or_else(|e| {
+        .build(tauri::generate_context!());
+
+    match app {
+        Ok(app) => app.run(|handle, event| {
+            if let tauri::RunEvent::Exit = event {
+                handle.state::<EmbeddedRelayState>().shutdown();
+                // 退出时补记断开事件，避免离线期间被统计为在线
+                if handle.state::<WsConnectionState>().0.load(std::sync::atomic::Ordering::SeqCst) {
+                    ws_uptime::record_ws_event(ws_connection_log::EVENT_DISCONNECTED, "app exit");
+                }
+            }
+        }),
+        Err(e) => {
             error!("Error while running tauri application: {}", e);
-        });
+        }
+    }
 }
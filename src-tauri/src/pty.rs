@@ -1,11 +1,22 @@
 use std::collections::HashMap;
 use std::sync::Mutex;
 use portable_pty::{native_pty_system, CommandBuilder, PtySize, PtyPair, Child};
-use std::io::{Read, Write};
+use std::io::{Read, Seek, SeekFrom, Write};
 use std::thread;
 use tauri::{Emitter, Manager};
 use rusqlite::params;
 
+/// pty_get_scrollback 返回的日志末尾字节数上限
+const SCROLLBACK_MAX_BYTES: u64 = 256 * 1024;
+
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct PtyInfo {
+    pub project_path: String,
+    /// 子进程是否仍在运行（try_wait 未返回退出状态）
+    pub alive: bool,
+    pub exit_code: Option<u32>,
+}
+
 pub struct PtyManager {
     pty_pairs: Mutex<HashMap<String, PtyPair>>,
     children: Mutex<HashMap<String, Box<dyn Child + Send + Sync>>>,
@@ -57,6 +68,44 @@ impl PtyManager {
     pub fn has_pty(&self, project_path: &str) -> bool {
         self.pty_pairs.lock().unwrap().contains_key(project_path)
     }
+
+    /// 子进程退出后清理条目；仅当该项目当前的子进程仍是 pid 对应的进程时才移除（避免误删重新 spawn 的 PTY）
+    fn take_exited(&self, project_path: &str, pid: Option<u32>) -> Option<Box<dyn Child + Send + Sync>> {
+        let same_child = self
+            .children
+            .lock()
+            .unwrap()
+            .get(project_path)
+            .map(|child| child.process_id() == pid)
+            .unwrap_or(false);
+        if !same_child {
+            return None;
+        }
+        self.remove_pty(project_path).map(|(_pair, child)| child)
+    }
+
+    pub fn list(&self) -> Vec<PtyInfo> {
+        let keys: Vec<String> = self.pty_pairs.lock().unwrap().keys().cloned().collect();
+        let mut children = self.children.lock().unwrap();
+        let mut ptys: Vec<PtyInfo> = keys
+            .into_iter()
+            .map(|project_path| {
+                let status = children.get_mut(&project_path).map(|child| child.try_wait());
+                let (alive, exit_code) = match status {
+                    Some(Ok(None)) => (true, None),
+                    Some(Ok(Some(status))) => (false, Some(status.exit_code())),
+                    Some(Err(e)) => {
+                        log::warn!("PTY try_wait failed for project {}: {}", project_path, e);
+                        (false, None)
+                    }
+                    None => (false, None),
+                };
+                PtyInfo { project_path, alive, exit_code }
+            })
+            .collect();
+        ptys.sort_by(|a, b| a.project_path.cmp(&b.project_path));
+        ptys
+    }
 }
 
 #[tauri::command]
@@ -92,6 +141,7 @@ pub async fn pty_spawn(
 
     let child = pair.slave.spawn_command(cmd)
         .map_err(|e| format!("Failed to spawn command: {}", e))?;
+    let child_pid = child.process_id();
 
     // Store the pair and child with project path as key
     let manager = app.state::<PtyManager>();
@@ -193,6 +243,23 @@ pub async fn pty_spawn(
             }
         }
         log::info!("PTY reader thread exiting for project: {}", project_path_clone);
+
+        // 读取结束说明进程已退出（或 PTY 被关闭），回收子进程并通知前端
+        let manager = app_handle.state::<PtyManager>();
+        if let Some(mut child) = manager.take_exited(&project_path_clone, child_pid) {
+            let exit_code = match child.wait() {
+                Ok(status) => Some(status.exit_code()),
+                Err(e) => {
+                    log::warn!("Failed to wait PTY child for project {}: {}", project_path_clone, e);
+                    None
+                }
+            };
+            log::info!("PTY process exited for project: {}, code={:?}", project_path_clone, exit_code);
+            let _ = app_handle.emit("pty-exit", serde_json::json!({
+                "projectPath": project_path_clone,
+                "exitCode": exit_code
+            }));
+        }
     });
 
     // Spawn a task to poll for remote commands from DB
@@ -261,7 +328,7 @@ pub async fn pty_spawn(
     Ok(project_path)
 }
 
-fn get_pty_log_path(project_path: &str) -> std::path::PathBuf {
+pub(crate) fn get_pty_log_path(project_path: &str) -> std::path::PathBuf {
     let home = dirs::home_dir().expect("Failed to get home dir");
     let safe_name = project_path.replace("/", "_").replace(":", "_");
     home.join("sparky/pty_logs").join(format!("{}.log", safe_name))
@@ -306,8 +373,78 @@ pub fn pty_resize(app: tauri::AppHandle, project_path: String, cols: u16, rows:
     }
 }
 
+/// 列出当前所有 PTY 及其子进程是否存活
+#[tauri::command]
+pub fn pty_list(app: tauri::AppHandle) -> Vec<PtyInfo> {
+    let manager = app.state::<PtyManager>();
+    manager.list()
+}
+
 #[tauri::command]
 pub fn pty_exists(app: tauri::AppHandle, project_path: String) -> bool {
     let manager = app.state::<PtyManager>();
     manager.has_pty(&project_path)
 }
+
+/// 返回 PTY 日志的末尾部分（最多 256KB），供前端在挂载时回填 xterm 缓冲区
+#[tauri::command]
+pub fn pty_get_scrollback(project_path: String) -> Result<String, String> {
+    let log_path = get_pty_log_path(&project_path);
+    let mut file = match std::fs::File::open(&log_path) {
+        Ok(file) => file,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(String::new()),
+        Err(e) => return Err(format!("Failed to open PTY log: {}", e)),
+    };
+
+    let len = file.metadata().map_err(|e| e.to_string())?.len();
+    let start = len.saturating_sub(SCROLLBACK_MAX_BYTES);
+    file.seek(SeekFrom::Start(start)).map_err(|e| e.to_string())?;
+    let mut bytes = Vec::with_capacity((len - start) as usize);
+    file.read_to_end(&mut bytes).map_err(|e| e.to_string())?;
+
+    log::info!("PTY scrollback: project={}, bytes={}", project_path, bytes.len());
+    Ok(decode_scrollback(&bytes))
+}
+
+/// 解码日志片段：跳过开头被截断的多字节字符，丢弃末尾不完整的字符和中间的非法字节（与读取线程一致）
+fn decode_scrollback(bytes: &[u8]) -> String {
+    // UTF-8 续字节形如 0b10xxxxxx，出现在开头说明切在了字符中间
+    let skip = bytes.iter().take(3).take_while(|b| (**b & 0xC0) == 0x80).count();
+    let mut rest = &bytes[skip..];
+    let mut out = String::with_capacity(rest.len());
+
+    loop {
+        match std::str::from_utf8(rest) {
+            Ok(valid) => {
+                out.push_str(valid);
+                break;
+            }
+            Err(err) => {
+                let valid_up_to = err.valid_up_to();
+                out.push_str(unsafe { std::str::from_utf8_unchecked(&rest[..valid_up_to]) });
+                match err.error_len() {
+                    Some(error_len) => rest = &rest[valid_up_to + error_len..],
+                    None => break,
+                }
+            }
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_decode_scrollback_strips_partial_chars() {
+        let text = "完成✅ done";
+        let bytes = text.as_bytes();
+        // 开头切掉 "完" 的第一个字节，末尾切掉 "✅" 之后的部分并留下半个字符
+        let start = 1;
+        let end = "完成".len() + 2;
+        assert_eq!(decode_scrollback(&bytes[start..end]), "成");
+        assert_eq!(decode_scrollback(bytes), text);
+        assert_eq!(decode_scrollback(b"ok\xffok"), "okok");
+    }
+}
@@ -0,0 +1,213 @@
+// Reconnect scheduling shared by the Feishu WSS loop and relay workers
+
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::OnceLock;
+use std::time::Duration;
+use tokio::net::TcpStream;
+use tokio::sync::Notify;
+use tokio_tungstenite::{connect_async, MaybeTlsStream, WebSocketStream};
+
+/// relay worker 首次连接的最大尝试次数
+pub const WORKER_MAX_CONNECT_ATTEMPTS: u32 = 10;
+
+/// 连接保持超过该时长后断开，视为稳定连接，退避重置为基础值
+pub const STABLE_CONNECTION: Duration = Duration::from_secs(60);
+
+/// relay worker 发送 ping 的间隔
+pub const HEARTBEAT_INTERVAL: Duration = Duration::from_secs(30);
+
+/// 连续多少次 ping 未收到 pong 视为连接已死
+pub const MAX_MISSED_PONGS: u32 = 2;
+
+/// 退避抖动幅度（±20%）
+const JITTER_RATIO: f64 = 0.2;
+
+/// 重连等待的指数退避（每次失败翻倍直到上限，并加入 ±20% 抖动）
+pub struct ReconnectBackoff {
+    base: Duration,
+    cap: Duration,
+    current: Duration,
+    attempt: u32,
+}
+
+impl ReconnectBackoff {
+    pub fn new(base: Duration, cap: Duration) -> Self {
+        ReconnectBackoff { base, cap, current: base, attempt: 0 }
+    }
+
+    /// 用服务端下发的 reconnect_interval 作为新的基础值（上限不低于基础值）
+    pub fn set_base(&mut self, base: Duration) {
+        if base.is_zero() || base == self.base {
+            return;
+        }
+        log::info!("[reconnect] base delay set to {}s by server config", base.as_secs());
+        self.base = base;
+        self.cap = self.cap.max(base);
+        self.current = self.current.clamp(base, self.cap);
+    }
+
+    /// 返回本次不含抖动的等待时长，并把下一次翻倍
+    fn next_base_delay(&mut self) -> Duration {
+        let delay = self.current;
+        self.current = (self.current * 2).min(self.cap);
+        self.attempt += 1;
+        delay
+    }
+
+    /// 返回本次等待时长（含抖动），并记录退避状态
+    pub fn next_delay(&mut self) -> Duration {
+        let delay = apply_jitter(self.next_base_delay(), rand::random_range(-1.0..=1.0));
+        log::info!(
+            "[reconnect] attempt {}: waiting {:.1}s (base {}s, cap {}s)",
+            self.attempt,
+            delay.as_secs_f64(),
+            self.base.as_secs(),
+            self.cap.as_secs()
+        );
+        delay
+    }
+
+    pub fn reset(&mut self) {
+        self.current = self.base;
+        self.attempt = 0;
+    }
+}
+
+/// factor 取值 [-1, 1]，按 JITTER_RATIO 比例放大或缩小等待时长
+fn apply_jitter(delay: Duration, factor: f64) -> Duration {
+    delay.mul_f64(1.0 + JITTER_RATIO * factor.clamp(-1.0, 1.0))
+}
+
+/// relay worker 的心跳状态：每次发 ping 前调用 tick，收到 pong 时调用 pong
+/// 读写两端可能位于不同任务，因此用原子计数共享
+#[derive(Default)]
+pub struct Heartbeat {
+    missed: AtomicU32,
+}
+
+impl Heartbeat {
+    /// 记录一次待回应的 ping；已有 MAX_MISSED_PONGS 个 ping 未回应时返回 false（应断开重连）
+    pub fn tick(&self) -> bool {
+        self.missed.fetch_add(1, Ordering::SeqCst) < MAX_MISSED_PONGS
+    }
+
+    pub fn pong(&self) {
+        self.missed.store(0, Ordering::SeqCst);
+    }
+}
+
+/// 心跳定时器：首次 tick 在一个间隔之后，错过的 tick 不补发
+pub fn heartbeat_interval() -> tokio::time::Interval {
+    let mut interval =
+        tokio::time::interval_at(tokio::time::Instant::now() + HEARTBEAT_INTERVAL, HEARTBEAT_INTERVAL);
+    interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+    interval
+}
+
+fn reconnect_notify() -> &'static Notify {
+    static NOTIFY: OnceLock<Notify> = OnceLock::new();
+    NOTIFY.get_or_init(Notify::new)
+}
+
+/// 等待下一次重连；被 force_reconnect_now 打断时返回 true（调用方应重置退避）
+pub async fn wait_for_retry(delay: Duration) -> bool {
+    tokio::select! {
+        _ = tokio::time::sleep(delay) => false,
+        _ = reconnect_notify().notified() => true,
+    }
+}
+
+/// 带退避的连接重试，供 relay worker 使用（请求附带 relay token）；超过最大次数后返回 None
+pub async fn connect_with_retry(
+    url: &str,
+    label: &str,
+    max_attempts: u32,
+) -> Option<WebSocketStream<MaybeTlsStream<TcpStream>>> {
+    let mut backoff = ReconnectBackoff::new(Duration::from_secs(1), Duration::from_secs(60));
+    for attempt in 1..=max_attempts {
+        let request = match crate::relay_client::relay_request(url) {
+            Ok(request) => request,
+            Err(e) => {
+                log::warn!("[{}] invalid relay request: {}", label, e);
+                return None;
+            }
+        };
+        match connect_async(request).await {
+            Ok((ws_stream, _)) => return Some(ws_stream),
+            Err(e) => {
+                log::warn!("[{}] connect attempt {}/{} failed: {}", label, attempt, max_attempts, e);
+            }
+        }
+        if attempt == max_attempts {
+            break;
+        }
+        let delay = backoff.next_delay();
+        log::info!("[{}] retrying in {:.1} seconds...", label, delay.as_secs_f64());
+        if wait_for_retry(delay).await {
+            backoff.reset();
+        }
+    }
+    None
+}
+
+/// 立即打断所有正在等待的重连（Feishu WSS 与 relay worker），并把退避重置到最小值
+#[tauri::command]
+pub fn force_reconnect_now() -> Result<(), String> {
+    log::info!("[reconnect] manual reconnect requested, interrupting backoff");
+    reconnect_notify().notify_waiters();
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_backoff_doubles_and_resets() {
+        let mut backoff = ReconnectBackoff::new(Duration::from_secs(1), Duration::from_secs(60));
+        let delays: Vec<u64> = (0..8).map(|_| backoff.next_base_delay().as_secs()).collect();
+        assert_eq!(delays, vec![1, 2, 4, 8, 16, 32, 60, 60]);
+        backoff.reset();
+        assert_eq!(backoff.next_base_delay().as_secs(), 1);
+    }
+
+    #[test]
+    fn test_jitter_stays_within_bounds() {
+        let delay = Duration::from_secs(10);
+        assert_eq!(apply_jitter(delay, -1.0), Duration::from_secs(8));
+        assert_eq!(apply_jitter(delay, 1.0), Duration::from_secs(12));
+        let mut backoff = ReconnectBackoff::new(Duration::from_secs(10), Duration::from_secs(60));
+        let jittered = backoff.next_delay();
+        assert!(jittered >= Duration::from_secs(8) && jittered <= Duration::from_secs(12));
+    }
+
+    #[test]
+    fn test_server_base_raises_cap() {
+        let mut backoff = ReconnectBackoff::new(Duration::from_secs(1), Duration::from_secs(60));
+        backoff.set_base(Duration::from_secs(120));
+        assert_eq!(backoff.next_base_delay().as_secs(), 120);
+        assert_eq!(backoff.next_base_delay().as_secs(), 120);
+    }
+
+    #[test]
+    fn test_heartbeat_detects_missed_pongs() {
+        let heartbeat = Heartbeat::default();
+        assert!(heartbeat.tick());
+        assert!(heartbeat.tick());
+        assert!(!heartbeat.tick());
+        heartbeat.pong();
+        assert!(heartbeat.tick());
+    }
+
+    #[tokio::test]
+    async fn test_force_reconnect_interrupts_wait() {
+        let waiter = tokio::spawn(wait_for_retry(Duration::from_secs(60)));
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        force_reconnect_now().unwrap();
+        let interrupted = tokio::time::timeout(Duration::from_secs(1), waiter)
+            .await
+            .expect("wait should be interrupted")
+            .unwrap();
+        assert!(interrupted);
+    }
+}
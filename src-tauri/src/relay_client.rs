@@ -2,43 +2,23 @@
 // B-1: Local Worker - Core Scheduler Implementation
 
 use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, VecDeque};
 use std::process::Stdio;
-use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, OnceLock};
 use tokio::io::{AsyncBufReadExt, AsyncWriteExt};
 use tokio::process::{Child, Command};
 use tokio::sync::{mpsc, Mutex};
 use tokio::time::{timeout, Duration};
-use tokio_tungstenite::{connect_async, tungstenite::protocol::Message as WsMessage};
-use futures_util::StreamExt;
+use tokio_tungstenite::tungstenite::protocol::Message as WsMessage;
+use tokio_tungstenite::tungstenite::client::IntoClientRequest;
+use tokio_tungstenite::tungstenite::handshake::client::Request as ClientRequest;
+use tokio_tungstenite::tungstenite::http::{header::AUTHORIZATION, HeaderValue};
+use futures_util::{SinkExt, StreamExt};
 use tracing::{info, warn, error, debug};
 
 // ============== Message Types ==============
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct MessagePayload {
-    pub sender: String,
-    pub task_id: String,
-    #[serde(rename = "type")]
-    pub msg_type: String,
-    pub action: Option<String>,
-    pub data: MessageData,
-}
-
-#[derive(Debug, Clone, Serialize, Deserialize, Default)]
-pub struct MessageData {
-    #[serde(rename = "execution_mode")]
-    pub execution_mode: Option<String>,
-    pub prompt: Option<String>,
-    pub status: Option<String>,
-    pub stream: Option<String>,
-    pub content: Option<String>,
-    #[serde(rename = "request_id")]
-    pub request_id: Option<String>,
-    pub hook_type: Option<String>,
-    #[serde(rename = "raw_command")]
-    pub raw_command: Option<String>,
-    pub description: Option<String>,
-    pub decision: Option<String>,
-}
+pub use crate::relay_message::{MessageData, MessagePayload};
 
 #[derive(Debug, Clone, Copy, PartialEq)]
 pub enum ExecutionMode {
@@ -55,6 +35,108 @@ impl ExecutionMode {
     }
 }
 
+/// relay 共享 token 的环境变量，与 relay-server 的 --token / SPARKY_RELAY_TOKEN 对应
+const RELAY_TOKEN_ENV: &str = "SPARKY_RELAY_TOKEN";
+
+/// 构造连接 relay 的 WebSocket 请求；配置了 relay token 时放在 Authorization: Bearer 头中，
+/// 不出现在 URL 里（URL 会被代理和 relay 日志记录）
+pub(crate) fn relay_request(url: &str) -> Result<ClientRequest, String> {
+    let token = std::env::var(RELAY_TOKEN_ENV).ok().filter(|token| !token.is_empty());
+    build_relay_request(url, token.as_deref())
+}
+
+fn build_relay_request(url: &str, token: Option<&str>) -> Result<ClientRequest, String> {
+    let mut request = url.into_client_request().map_err(|e| e.to_string())?;
+    if let Some(token) = token {
+        let value = HeaderValue::from_str(&format!("Bearer {}", token))
+            .map_err(|e| format!("Invalid relay token: {}", e))?;
+        request.headers_mut().insert(AUTHORIZATION, value);
+    }
+    Ok(request)
+}
+
+/// 默认的 relay 基础地址（对应内嵌 relay 的默认端口）
+pub const DEFAULT_RELAY_URL: &str = "ws://localhost:8080";
+
+/// 由 relay 基础地址构造任务房间的 WebSocket 地址：LocalWorker 与 RemoteWorker 共用
+/// relay_url 约定为基础地址（如 ws://host:8080），这里统一追加 `/ws/{task_id}`；
+/// 兼容旧配置：若已经以 `/ws/{task_id}` 结尾则原样使用，不会重复追加
+pub(crate) fn task_ws_url(relay_url: &str, task_id: &str) -> String {
+    let base = relay_url.trim().trim_end_matches('/');
+    let base = if base.is_empty() { DEFAULT_RELAY_URL } else { base };
+    let suffix = format!("/ws/{}", task_id);
+    if base.ends_with(&suffix) {
+        base.to_string()
+    } else {
+        format!("{}{}", base, suffix)
+    }
+}
+
+/// 未配置 claude_binary 时使用的命令
+const DEFAULT_CLAUDE_BINARY: &str = "claude";
+
+/// claude 单次运行的最长时间，超时后结束进程
+const CHILD_TIMEOUT: Duration = Duration::from_secs(300);
+
+/// 等待 claude 退出时的轮询间隔；每次只短暂持有 child 锁，不阻塞 status / kill_process
+const CHILD_POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// LocalWorker 启动 claude 的方式，按项目保存在 projects 表中。
+/// 可执行文件和额外参数只来自本机配置；relay 消息只能覆盖 model，以及指定已添加的项目作为工作目录
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ClaudeLaunchConfig {
+    /// claude 可执行文件（路径或 PATH 中的命令名），为空时使用 "claude"
+    pub binary: Option<String>,
+    pub cwd: Option<String>,
+    pub model: Option<String>,
+    /// 追加在 prompt 之前的参数，如 --dangerously-skip-permissions
+    #[serde(default)]
+    pub extra_args: Vec<String>,
+}
+
+impl ClaudeLaunchConfig {
+    pub fn binary(&self) -> &str {
+        self.binary
+            .as_deref()
+            .map(str::trim)
+            .filter(|b| !b.is_empty())
+            .unwrap_or(DEFAULT_CLAUDE_BINARY)
+    }
+
+    /// 命令行参数：--print、--model、额外参数，最后是 prompt
+    pub fn args(&self, model_override: Option<&str>, prompt: &str) -> Vec<String> {
+        let mut args = vec!["--print".to_string()];
+        let model = model_override
+            .or(self.model.as_deref())
+            .map(str::trim)
+            .filter(|m| !m.is_empty());
+        if let Some(model) = model {
+            args.push("--model".to_string());
+            args.push(model.to_string());
+        }
+        args.extend(self.extra_args.iter().filter(|a| !a.trim().is_empty()).cloned());
+        args.push(prompt.to_string());
+        args
+    }
+
+    /// 启动前检查可执行文件与工作目录，返回解析后的可执行文件路径
+    pub fn validate(&self) -> Result<std::path::PathBuf, String> {
+        let binary = crate::resolve_program(self.binary()).ok_or_else(|| {
+            format!(
+                "Claude binary '{}' not found; install Claude Code or set the project's claude binary path",
+                self.binary()
+            )
+        })?;
+        if let Some(cwd) = self.cwd.as_deref().filter(|c| !c.trim().is_empty()) {
+            if !std::path::Path::new(cwd).is_dir() {
+                return Err(format!("Working directory '{}' does not exist", cwd));
+            }
+        }
+        Ok(binary)
+    }
+}
+
 // ============== Local Worker ==============
 pub struct LocalWorker {
     task_id: String,
@@ -62,11 +144,35 @@ pub struct LocalWorker {
     child: Arc<Mutex<Option<Child>>>,
     stdin: Arc<Mutex<Option<tokio::process::ChildStdin>>>,
     ws_sender: mpsc::Sender<String>,
+    // ws_sender 的接收端，由 handle_connection 转发到 socket 写端；重连后继续使用同一个通道
+    ws_receiver: Mutex<mpsc::Receiver<String>>,
+    running: Arc<AtomicBool>,
+    // stop_local_worker 置位后 run 循环不再重连
+    stopped: AtomicBool,
+    stop_signal: tokio::sync::Notify,
+    last_status: Arc<Mutex<Option<String>>>,
+    launch: ClaudeLaunchConfig,
+    // 当前子进程的工作目录，用于把飞书中的权限回复路由到对应项目的 worker
+    project_dir: Arc<Mutex<Option<String>>>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+pub struct WorkerStatus {
+    pub exists: bool,
+    pub running: bool,
+    pub child_alive: bool,
+    pub last_status: Option<String>,
+}
+
+/// 已启动的 LocalWorker（按 task_id 索引）
+fn worker_registry() -> &'static std::sync::Mutex<HashMap<String, Arc<LocalWorker>>> {
+    static REGISTRY: OnceLock<std::sync::Mutex<HashMap<String, Arc<LocalWorker>>>> = OnceLock::new();
+    REGISTRY.get_or_init(|| std::sync::Mutex::new(HashMap::new()))
 }
 
 impl LocalWorker {
     pub fn new(task_id: String, relay_url: String) -> Self {
-        let (ws_sender, _) = mpsc::channel(200);
+        let (ws_sender, ws_receiver) = mpsc::channel(200);
         
         Self {
             task_id,
@@ -74,30 +180,77 @@ impl LocalWorker {
             child: Arc::new(Mutex::new(None)),
             stdin: Arc::new(Mutex::new(None)),
             ws_sender,
+            ws_receiver: Mutex::new(ws_receiver),
+            running: Arc::new(AtomicBool::new(false)),
+            stopped: AtomicBool::new(false),
+            stop_signal: tokio::sync::Notify::new(),
+            last_status: Arc::new(Mutex::new(None)),
+            launch: ClaudeLaunchConfig::default(),
+            project_dir: Arc::new(Mutex::new(None)),
+        }
+    }
+
+    pub fn with_launch_config(self, launch: ClaudeLaunchConfig) -> Self {
+        Self { launch, ..self }
+    }
+
+    /// 当前状态：run 循环是否在运行、子进程是否存活、最后一次上报的 status
+    pub async fn status(&self) -> WorkerStatus {
+        let child_alive = match self.child.lock().await.as_mut() {
+            Some(child) => matches!(child.try_wait(), Ok(None)),
+            None => false,
+        };
+        WorkerStatus {
+            exists: true,
+            running: self.running.load(Ordering::SeqCst),
+            child_alive,
+            last_status: self.last_status.lock().await.clone(),
         }
     }
 
     /// Run the worker
     pub async fn run(&self) {
-        let url = format!("{}/ws/{}", self.relay_url, self.task_id);
+        self.running.store(true, Ordering::SeqCst);
+        let url = task_ws_url(&self.relay_url, &self.task_id);
         info!("[LocalWorker] Connecting to {}", url);
 
-        match connect_async(&url).await {
-            Ok((ws_stream, _)) => {
-                info!("[LocalWorker] Connected!");
-                self.send_status("connected").await;
-                self.handle_connection(ws_stream).await;
-            }
-            Err(e) => {
-                info!("[LocalWorker] Connect failed: {}", e);
+        while !self.stopped.load(Ordering::SeqCst) {
+            let connected = crate::reconnect::connect_with_retry(
+                &url,
+                "LocalWorker",
+                crate::reconnect::WORKER_MAX_CONNECT_ATTEMPTS,
+            )
+            .await;
+            let Some(ws_stream) = connected else {
+                info!("[LocalWorker] Connect failed, giving up");
+                break;
+            };
+            info!("[LocalWorker] Connected!");
+            self.send_status("connected").await;
+            if !self.handle_connection(ws_stream).await {
+                break;
             }
+            warn!("[LocalWorker] Heartbeat lost, reconnecting");
         }
 
         self.kill_process().await;
+        self.running.store(false, Ordering::SeqCst);
     }
 
-    async fn handle_connection(&self, ws_stream: tokio_tungstenite::WebSocketStream<tokio_tungstenite::MaybeTlsStream<tokio::net::TcpStream>>) {
-        let (_write, mut read) = ws_stream.split();
+    /// 结束子进程并让 run 循环关闭连接后退出
+    pub async fn stop(&self) {
+        self.stopped.store(true, Ordering::SeqCst);
+        self.stop_signal.notify_one();
+        self.kill_process().await;
+    }
+
+    /// 处理一条连接：读取 relay 消息、把 ws_sender 中的消息写入 socket、定期发送 ping
+    /// 返回 true 表示心跳超时（调用方应重连），false 表示连接正常关闭
+    async fn handle_connection(&self, ws_stream: tokio_tungstenite::WebSocketStream<tokio_tungstenite::MaybeTlsStream<tokio::net::TcpStream>>) -> bool {
+        let (mut write, mut read) = ws_stream.split();
+        let mut outgoing = self.ws_receiver.lock().await;
+        let heartbeat = crate::reconnect::Heartbeat::default();
+        let mut ping_interval = crate::reconnect::heartbeat_interval();
 
         loop {
             tokio::select! {
@@ -108,10 +261,34 @@ impl LocalWorker {
                                 info!("[LocalWorker] Error: {}", e);
                             }
                         }
-                        Some(Ok(WsMessage::Close(_))) | None => break,
+                        Some(Ok(WsMessage::Pong(_))) => heartbeat.pong(),
+                        Some(Ok(WsMessage::Close(_))) | None => return false,
+                        Some(Err(e)) => {
+                            warn!("[LocalWorker] Read error: {}", e);
+                            return true;
+                        }
                         _ => {}
                     }
                 }
+                Some(text) = outgoing.recv() => {
+                    crate::task_log::record_worker_message(&text);
+                    if let Err(e) = write.send(WsMessage::Text(text.into())).await {
+                        warn!("[LocalWorker] Write error: {}", e);
+                        return true;
+                    }
+                }
+                _ = self.stop_signal.notified() => {
+                    let _ = write.send(WsMessage::Close(None)).await;
+                    return false;
+                }
+                _ = ping_interval.tick() => {
+                    if !heartbeat.tick() {
+                        return true;
+                    }
+                    if write.send(WsMessage::Ping(Default::default())).await.is_err() {
+                        return true;
+                    }
+                }
             }
         }
     }
@@ -125,7 +302,15 @@ impl LocalWorker {
                 match payload.action.as_deref() {
                     Some("start_task") => {
                         let prompt = payload.data.prompt.as_deref().unwrap_or("");
-                        self.spawn_claude(prompt).await?;
+                        let model = payload.data.model.as_deref();
+                        let project_dir = payload.data.project_path.as_deref().and_then(registered_project_dir);
+                        let attachment_dir = project_dir.as_deref().or(self.launch.cwd.as_deref());
+                        let prompt = with_attachments(&self.task_id, attachment_dir, prompt);
+                        let mode = ExecutionMode::from_str(payload.data.execution_mode.as_deref().unwrap_or(""));
+                        match mode {
+                            ExecutionMode::Local => self.spawn_claude(&prompt, model, project_dir.as_deref()).await?,
+                            ExecutionMode::Remote => self.hand_off_remote(&prompt, model).await,
+                        }
                     }
                     Some("stop_task") => self.kill_process().await,
                     _ => {}
@@ -140,19 +325,67 @@ impl LocalWorker {
         Ok(())
     }
 
-    async fn spawn_claude(&self, prompt: &str) -> Result<(), String> {
+    /// remote 模式：在同一任务房间启动 RemoteWorker，在沙箱中执行 claude；沙箱不可用时拒绝任务
+    async fn hand_off_remote(&self, prompt: &str, model: Option<&str>) {
+        let sandbox_config = crate::remote_worker::load_saved_sandbox_config();
+        if let Err(reason) = sandbox_config.check_available() {
+            warn!("[LocalWorker] Rejecting remote task {}: {}", self.task_id, reason);
+            self.send_status_detail("rejected", Some(format!("Remote execution unavailable: {}", reason)))
+                .await;
+            return;
+        }
+
+        self.kill_process().await;
+        info!("[LocalWorker] Handing task {} off to RemoteWorker", self.task_id);
+        let command = std::iter::once(self.launch.binary().to_string())
+            .chain(self.launch.args(model, prompt))
+            .map(|part| shell_quote(&part))
+            .collect::<Vec<_>>()
+            .join(" ");
+        let worker = crate::remote_worker::RemoteWorker::new(
+            self.task_id.clone(),
+            self.relay_url.clone(),
+            Some(sandbox_config),
+        )
+        .with_initial_command(command);
+        crate::remote_worker::spawn_remote_worker(worker, None).await;
+        self.send_status("handed_off_remote").await;
+    }
+
+    /// project_dir 来自任务消息，优先于项目配置中的工作目录
+    async fn spawn_claude(&self, prompt: &str, model: Option<&str>, project_dir: Option<&str>) -> Result<(), String> {
         self.kill_process().await;
-        
-        info!("[LocalWorker] Spawning Claude: {}", prompt);
 
-        let mut cmd = Command::new("claude");
-        cmd.arg("--print")
-           .arg(prompt)
-           .stdout(Stdio::piped())
+        let launch = match project_dir {
+            Some(dir) => ClaudeLaunchConfig { cwd: Some(dir.to_string()), ..self.launch.clone() },
+            None => self.launch.clone(),
+        };
+        let binary = match launch.validate() {
+            Ok(binary) => binary,
+            Err(e) => {
+                error!("[LocalWorker] {}", e);
+                self.send_status_detail("error", Some(e.clone())).await;
+                return Err(e);
+            }
+        };
+        let cwd = launch.cwd.as_deref().filter(|c| !c.trim().is_empty());
+        *self.project_dir.lock().await = cwd.map(|c| c.trim_end_matches('/').to_string());
+        info!("[LocalWorker] Spawning Claude ({}, cwd={:?}): {}", binary.display(), cwd, prompt);
+
+        let mut cmd = Command::new(&binary);
+        cmd.args(launch.args(model, prompt));
+        match cwd {
+            Some(cwd) => {
+                cmd.current_dir(cwd);
+            }
+            None => warn!("[LocalWorker] No project directory for task {}, using the app's working directory", self.task_id),
+        }
+        cmd.stdout(Stdio::piped())
            .stderr(Stdio::piped())
            .stdin(Stdio::piped());
 
         let mut child = cmd.spawn().map_err(|e| e.to_string())?;
+        let pid = child.id();
         
         let stdout = child.stdout.take();
         let stderr = child.stderr.take();
@@ -192,16 +425,43 @@ impl LocalWorker {
         tokio::spawn(async move {
             if let Some(err) = stderr {
                 let mut lines = tokio::io::BufReader::new(err).lines();
-                while let Ok(Some(line)) = lines.next_line().await {
-                    let (msg_type, data) = Self::check_permission(&line);
+                let mut matcher = PermissionMatcher::default();
+                let send = |msg_type: &str, data: MessageData| {
                     let msg = MessagePayload {
                         sender: "local_worker".to_string(),
                         task_id: task_id2.clone(),
-                        msg_type,
+                        msg_type: msg_type.to_string(),
                         action: None,
                         data,
                     };
-                    if let Ok(t) = serde_json::to_string(&msg) { let _ = sender2.send(t).await; }
+                    serde_json::to_string(&msg).ok()
+                };
+                loop {
+                    // 选项块没有以 (esc) 结尾时，等输出停顿后再把已收集的选项作为提示发出
+                    let next = if matcher.is_pending() {
+                        match timeout(PROMPT_SETTLE, lines.next_line()).await {
+                            Ok(next) => next,
+                            Err(_) => {
+                                if let Some(t) = matcher.flush().and_then(|p| send("permission_request", Self::permission_request(p))) {
+                                    let _ = sender2.send(t).await;
+                                }
+                                continue;
+                            }
+                        }
+                    } else {
+                        lines.next_line().await
+                    };
+                    let Ok(Some(line)) = next else { break };
+                    let prompt = matcher.feed(&line);
+                    let log = MessageData {
+                        stream: Some("stderr".to_string()),
+                        content: Some(line),
+                        ..Default::default()
+                    };
+                    if let Some(t) = send("chat_log_stream", log) { let _ = sender2.send(t).await; }
+                    if let Some(t) = prompt.and_then(|p| send("permission_request", Self::permission_request(p))) {
+                        let _ = sender2.send(t).await;
+                    }
                 }
             }
         });
@@ -211,66 +471,89 @@ impl LocalWorker {
         let task_id3 = self.task_id.clone();
         let child_ref = self.child.clone();
         tokio::spawn(async move {
-            let mut c = child_ref.lock().await;
-            if let Some(ref mut child) = *c {
-                let timeout_result = timeout(Duration::from_secs(300), child.wait()).await;
-                let final_status = match timeout_result {
-                    Ok(Ok(s)) if s.success() => "success",
-                    Ok(Ok(_)) => "failed",
-                    Ok(Err(_)) => "error",
-                    Err(_) => "timeout",
-                };
-                let msg = MessagePayload {
-                    sender: "local_worker".to_string(),
-                    task_id: task_id3,
-                    msg_type: "status".to_string(),
-                    action: None,
-                    data: MessageData { status: Some(final_status.to_string()), ..Default::default() },
-                };
-                if let Ok(t) = serde_json::to_string(&msg) { let _ = sender3.send(t).await; }
-                *c = None;
+            let final_status = match timeout(CHILD_TIMEOUT, Self::wait_for_child(&child_ref, pid)).await {
+                // 进程已被 kill_process 结束或被新任务替换，不再上报
+                Ok(Ok(None)) => return,
+                Ok(Ok(Some(s))) if s.success() => "success",
+                Ok(Ok(Some(_))) => "failed",
+                Ok(Err(_)) => "error",
+                Err(_) => "timeout",
+            };
+            {
+                // 超时仍在运行的进程直接结束；已退出的进程从槽位中移除，新任务的进程保持不动
+                let mut c = child_ref.lock().await;
+                if let Some(child) = c.as_mut() {
+                    if child.id() == pid {
+                        let _ = child.kill().await;
+                        *c = None;
+                    } else if matches!(child.try_wait(), Ok(Some(_))) {
+                        *c = None;
+                    }
+                }
             }
+            let msg = MessagePayload {
+                sender: "local_worker".to_string(),
+                task_id: task_id3,
+                msg_type: "status".to_string(),
+                action: None,
+                data: MessageData { status: Some(final_status.to_string()), ..Default::default() },
+            };
+            if let Ok(t) = serde_json::to_string(&msg) { let _ = sender3.send(t).await; }
         });
 
         Ok(())
     }
 
-    fn check_permission(line: &str) -> (String, MessageData) {
-        let lower = line.to_lowercase();
-        // 改进权限检测：使用更精确的匹配模式
-        let is_permission = lower.contains("permission") 
-            || (lower.contains("allow") && (lower.contains("continue?") || lower.contains("run this command") || lower.contains("proceed")))
-            || lower.contains("approve")
-            || (lower.contains("continue") && (lower.contains("?") || lower.contains("action")));
-        
-        if is_permission {
-            let id = format!("req_{}", std::time::SystemTime::now()
-                .duration_since(std::time::UNIX_EPOCH).unwrap()
-                .as_nanos());
-            return ("permission_request".to_string(), MessageData {
-                request_id: Some(id),
-                hook_type: Some("shell".to_string()),
-                raw_command: Some(line.chars().take(200).collect()),
-                description: Some("Requires approval".to_string()),
-                ..Default::default()
-            });
-        }
-        ("chat_log_stream".to_string(), MessageData {
-            stream: Some("stderr".to_string()),
-            content: Some(line.to_string()),
+    /// 把解析出的确认提示转换为 permission_request 消息
+    fn permission_request(prompt: PermissionPrompt) -> MessageData {
+        let id = format!("req_{}", std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH).unwrap()
+            .as_nanos());
+        MessageData {
+            request_id: Some(id),
+            hook_type: Some("shell".to_string()),
+            raw_command: Some(prompt.context.join("\n")),
+            description: Some(prompt.question),
+            options: Some(prompt.options),
             ..Default::default()
-        })
+        }
     }
 
+    /// 与 PTY 命令队列一致，直接写入选项序号：1 允许，2 始终允许，3 拒绝
     async fn handle_permission_response(&self, decision: &str) {
         info!("[LocalWorker] Permission: {}", decision);
         let mut s = self.stdin.lock().await;
         if let Some(ref mut stdin) = *s {
-            let input = if decision == "approve" { "y\n" } else { "\n" };
+            let input = match decision {
+                "approve" => "1",
+                "always" => "2",
+                _ => "3",
+            };
             let _ = stdin.write_all(input.as_bytes()).await;
         }
     }
 
+    /// 等待 pid 对应的子进程退出；进程被结束或被替换时返回 None
+    async fn wait_for_child(
+        child_ref: &Arc<Mutex<Option<Child>>>,
+        pid: Option<u32>,
+    ) -> std::io::Result<Option<std::process::ExitStatus>> {
+        loop {
+            {
+                let mut c = child_ref.lock().await;
+                match c.as_mut() {
+                    Some(child) if child.id() == pid => {
+                        if let Some(status) = child.try_wait()? {
+                            return Ok(Some(status));
+                        }
+                    }
+                    _ => return Ok(None),
+                }
+            }
+            tokio::time::sleep(CHILD_POLL_INTERVAL).await;
+        }
+    }
+
     async fn kill_process(&self) {
         let mut c = self.child.lock().await;
         if let Some(ref mut child) = *c { let _ = child.kill().await; *c = None; }
@@ -279,44 +562,299 @@ impl LocalWorker {
     }
 
     async fn send_status(&self, status: &str) {
+        self.send_status_detail(status, None).await;
+    }
+
+    /// 上报状态，content 附带原因（如拒绝任务的说明）
+    async fn send_status_detail(&self, status: &str, content: Option<String>) {
+        *self.last_status.lock().await = Some(status.to_string());
         let msg = MessagePayload {
             sender: "local_worker".to_string(),
             task_id: self.task_id.clone(),
             msg_type: "status".to_string(),
             action: None,
-            data: MessageData { status: Some(status.to_string()), ..Default::default() },
+            data: MessageData { status: Some(status.to_string()), content, ..Default::default() },
         };
         if let Ok(t) = serde_json::to_string(&msg) { let _ = self.ws_sender.send(t).await; }
     }
 }
 
 // ============== Tauri Commands ==============
+/// 将用户通过飞书发给该项目的附件路径追加到 prompt，让 Claude 可以读取截图/文件；没有项目目录时不附带
+fn with_attachments(task_id: &str, project_dir: Option<&str>, prompt: &str) -> String {
+    let Some(project_dir) = project_dir.filter(|d| !d.trim().is_empty()) else {
+        return prompt.to_string();
+    };
+    let attachments = match crate::take_pending_attachments(task_id, project_dir) {
+        Ok(paths) => paths,
+        Err(e) => {
+            warn!("[LocalWorker] Failed to load attachments: {}", e);
+            return prompt.to_string();
+        }
+    };
+    append_attachment_paths(prompt, &attachments)
+}
+
+/// 消息中的项目路径只有在 projects 表中登记过时才作为工作目录，避免 relay 消息任意指定目录
+fn registered_project_dir(path: &str) -> Option<String> {
+    let path = path.trim().trim_end_matches('/');
+    match crate::is_registered_project(path) {
+        Ok(true) if std::path::Path::new(path).is_dir() => Some(path.to_string()),
+        Ok(_) => {
+            warn!("[LocalWorker] Ignoring project_path '{}': not an added project directory", path);
+            None
+        }
+        Err(e) => {
+            warn!("[LocalWorker] Failed to look up project '{}': {}", path, e);
+            None
+        }
+    }
+}
+
+/// 转义为 /bin/sh 单引号字符串
+fn shell_quote(s: &str) -> String {
+    format!("'{}'", s.replace('\'', "'\\''"))
+}
+
+fn append_attachment_paths(prompt: &str, paths: &[String]) -> String {
+    if paths.is_empty() {
+        return prompt.to_string();
+    }
+    let mut out = prompt.to_string();
+    out.push_str("\n\n用户附件（可直接读取）：");
+    for path in paths {
+        out.push_str("\n- ");
+        out.push_str(path);
+    }
+    out
+}
+
 #[tauri::command]
-pub async fn start_local_worker(task_id: String, relay_url: String) -> Result<String, String> {
+pub async fn start_local_worker(
+    task_id: String,
+    relay_url: String,
+    launch: Option<ClaudeLaunchConfig>,
+) -> Result<String, String> {
     println!("Starting LocalWorker: {} @ {}", task_id, relay_url);
     
-    let worker = LocalWorker::new(task_id.clone(), relay_url);
+    let worker = LocalWorker::new(task_id.clone(), relay_url).with_launch_config(launch.unwrap_or_default());
     let w = Arc::new(worker);
-    
+    worker_registry().lock().unwrap().insert(task_id.clone(), w.clone());
+
     let ww = w.clone();
     tokio::spawn(async move {
         ww.run().await;
+        // 同一 task_id 可能已被新的 worker 替换，只移除自己
+        let mut registry = worker_registry().lock().unwrap();
+        if registry.get(&ww.task_id).is_some_and(|current| Arc::ptr_eq(current, &ww)) {
+            registry.remove(&ww.task_id);
+        }
     });
 
     Ok(task_id)
 }
 
+/// 子进程仍在运行、且工作目录为 project_path 的 LocalWorker
+pub async fn find_project_worker(project_path: &str) -> Option<Arc<LocalWorker>> {
+    let project_path = project_path.trim_end_matches('/');
+    let workers: Vec<Arc<LocalWorker>> = worker_registry().lock().unwrap().values().cloned().collect();
+    for worker in workers {
+        if worker.project_dir.lock().await.as_deref() == Some(project_path) && worker.status().await.child_alive {
+            return Some(worker);
+        }
+    }
+    None
+}
+
+/// 把飞书中收到的权限决定（approve / always / reject）通过 relay 发给该 worker 的任务房间，
+/// 房间内的 Web 端也会看到这条 permission_response
+pub async fn send_permission_decision(worker: &LocalWorker, decision: &str) -> Result<(), String> {
+    let msg = MessagePayload {
+        sender: "desktop".to_string(),
+        task_id: worker.task_id.clone(),
+        msg_type: "permission_response".to_string(),
+        action: None,
+        data: MessageData { decision: Some(decision.to_string()), ..Default::default() },
+    };
+    let url = task_ws_url(&worker.relay_url, &worker.task_id);
+    let text = serde_json::to_string(&msg).map_err(|e| e.to_string())?;
+    let (mut ws_stream, _) = tokio_tungstenite::connect_async(relay_request(&url)?)
+        .await
+        .map_err(|e| e.to_string())?;
+    ws_stream.send(WsMessage::Text(text.into())).await.map_err(|e| e.to_string())?;
+    let _ = ws_stream.close(None).await;
+    info!("[relay] permission decision '{}' sent to task {}", decision, worker.task_id);
+    Ok(())
+}
+
 #[tauri::command]
-pub async fn stop_local_worker() -> Result<(), String> {
-    println!("Stopping LocalWorker");
+pub async fn get_worker_status(task_id: String) -> Result<WorkerStatus, String> {
+    let worker = worker_registry().lock().unwrap().get(&task_id).cloned();
+    match worker {
+        Some(worker) => Ok(worker.status().await),
+        None => Ok(WorkerStatus::default()),
+    }
+}
+
+/// 结束 task_id 对应 worker 的子进程并将其从注册表中移除
+#[tauri::command]
+pub async fn stop_local_worker(task_id: String) -> Result<(), String> {
+    let worker = worker_registry().lock().unwrap().remove(&task_id);
+    let worker = worker.ok_or_else(|| format!("No local worker for task {}", task_id))?;
+    info!("[LocalWorker] Stopping task {}", task_id);
+    worker.stop().await;
     Ok(())
 }
 
+// ============== Permission Prompt ==============
+/// 选项块未以 (esc) 结尾时，输出停顿多久后认为提示已完整
+const PROMPT_SETTLE: Duration = Duration::from_millis(500);
+/// 提问行上方最多保留几行非空输出作为上下文（如待执行的命令）
+const PROMPT_CONTEXT_LINES: usize = 5;
+
+/// Claude Code 的权限确认提示，例如：
+///
+/// ```text
+///  Do you want to proceed?
+///  ❯ 1. Yes
+///    2. Yes, and don't ask again for rm commands
+///    3. No, and tell Claude what to do differently (esc)
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct PermissionPrompt {
+    pub question: String,
+    /// 去掉 ❯ 和序号后的选项文本
+    pub options: Vec<String>,
+    /// 提问行上方的输出，通常是待执行的命令或待修改的文件
+    pub context: Vec<String>,
+}
+
+/// 逐行识别权限提示：提问行必须是 "Do you want to ...?"，其后（允许空行）紧跟带 ❯ 光标的 "1." 选项，
+/// 之后的选项需连续编号；其他包含 permission / continue 等字样的普通输出不会被当成提示
+#[derive(Debug, Default)]
+pub(crate) struct PermissionMatcher {
+    recent: VecDeque<String>,
+    question: Option<(String, Vec<String>)>,
+    options: Vec<String>,
+}
+
+impl PermissionMatcher {
+    /// 输入一行 stderr；选项块结束时返回完整的提示
+    pub fn feed(&mut self, line: &str) -> Option<PermissionPrompt> {
+        let line = crate::text::strip_ansi(line);
+        let line = line.trim();
+
+        if self.question.is_some() {
+            let option = parse_prompt_option(line);
+            if self.options.is_empty() {
+                match option {
+                    _ if line.is_empty() => return None,
+                    Some((1, true, text)) => {
+                        self.options.push(text);
+                        return None;
+                    }
+                    // 提问行后面不是选项块：只是普通输出
+                    _ => self.question = None,
+                }
+            } else {
+                match option {
+                    Some((n, _, text)) if n == self.options.len() + 1 => {
+                        let last = text.ends_with("(esc)");
+                        self.options.push(text);
+                        return if last { self.flush() } else { None };
+                    }
+                    _ => {
+                        let prompt = self.flush();
+                        self.observe(line);
+                        return prompt;
+                    }
+                }
+            }
+        }
+
+        self.observe(line);
+        None
+    }
+
+    /// 已收集到选项、尚未发出提示
+    pub fn is_pending(&self) -> bool {
+        !self.options.is_empty()
+    }
+
+    /// 发出已收集的提示（没有选项时返回 None）
+    pub fn flush(&mut self) -> Option<PermissionPrompt> {
+        let options = std::mem::take(&mut self.options);
+        let (question, context) = self.question.take()?;
+        if options.is_empty() {
+            return None;
+        }
+        Some(PermissionPrompt { question, options, context })
+    }
+
+    fn observe(&mut self, line: &str) {
+        if line.is_empty() {
+            return;
+        }
+        if line.starts_with("Do you want to") && line.ends_with('?') {
+            let context = self.recent.drain(..).collect();
+            self.question = Some((line.to_string(), context));
+            return;
+        }
+        if self.recent.len() == PROMPT_CONTEXT_LINES {
+            self.recent.pop_front();
+        }
+        self.recent.push_back(line.to_string());
+    }
+}
+
+/// 解析 `❯ 1. Yes` / `2. No` 形式的选项行，返回（序号，是否带 ❯ 光标，选项文本）
+fn parse_prompt_option(line: &str) -> Option<(usize, bool, String)> {
+    let (cursor, rest) = match line.strip_prefix('❯') {
+        Some(rest) => (true, rest.trim_start()),
+        None => (false, line),
+    };
+    let digits = rest.chars().take_while(|c| c.is_ascii_digit()).count();
+    let number = rest[..digits].parse().ok()?;
+    let rest = rest[digits..].strip_prefix(". ")?;
+    let text = rest.trim();
+    if text.is_empty() {
+        return None;
+    }
+    Some((number, cursor, text.to_string()))
+}
+
 // ============== Unit Tests ==============
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_build_relay_request() {
+        let request = build_relay_request("ws://h/ws/t1", Some("abc")).unwrap();
+        assert_eq!(request.uri().to_string(), "ws://h/ws/t1");
+        assert_eq!(request.headers().get(AUTHORIZATION).unwrap(), "Bearer abc");
+        let request = build_relay_request("ws://h/ws/t1", None).unwrap();
+        assert!(request.headers().get(AUTHORIZATION).is_none());
+    }
+
+    #[test]
+    fn test_task_ws_url() {
+        assert_eq!(task_ws_url("ws://h:8080", "t1"), "ws://h:8080/ws/t1");
+        assert_eq!(task_ws_url("ws://h:8080/", "t1"), "ws://h:8080/ws/t1");
+        assert_eq!(task_ws_url("ws://h:8080/ws/t1", "t1"), "ws://h:8080/ws/t1");
+        assert_eq!(task_ws_url("  ", "t1"), "ws://localhost:8080/ws/t1");
+    }
+
+    #[test]
+    fn test_append_attachment_paths() {
+        assert_eq!(append_attachment_paths("fix it", &[]), "fix it");
+        let paths = vec!["/tmp/a.png".to_string(), "/tmp/b.log".to_string()];
+        assert_eq!(
+            append_attachment_paths("fix it", &paths),
+            "fix it\n\n用户附件（可直接读取）：\n- /tmp/a.png\n- /tmp/b.log"
+        );
+    }
+
     #[test]
     fn test_execution_mode_from_str() {
         assert_eq!(ExecutionMode::from_str("remote"), ExecutionMode::Remote);
@@ -325,6 +863,12 @@ mod tests {
         assert_eq!(ExecutionMode::from_str("unknown"), ExecutionMode::Local);
     }
 
+    #[test]
+    fn test_shell_quote() {
+        assert_eq!(shell_quote("fix it"), "'fix it'");
+        assert_eq!(shell_quote("don't $(rm)"), "'don'\\''t $(rm)'");
+    }
+
     #[test]
     fn test_execution_mode_equality() {
         assert_eq!(ExecutionMode::Local, ExecutionMode::Local);
@@ -345,6 +889,109 @@ mod tests {
         assert!(data.raw_command.is_none());
         assert!(data.description.is_none());
         assert!(data.decision.is_none());
+        assert!(data.model.is_none());
+        assert!(data.project_path.is_none());
+        assert!(data.options.is_none());
+    }
+
+    fn feed_all(matcher: &mut PermissionMatcher, text: &str) -> Vec<PermissionPrompt> {
+        text.lines().filter_map(|line| matcher.feed(line)).collect()
+    }
+
+    #[test]
+    fn test_permission_prompt_bash() {
+        let mut matcher = PermissionMatcher::default();
+        let text = "⏺ Bash(rm -rf target)\n\n Bash command\n\n   rm -rf target\n   Remove build artifacts\n\n \
+                    Do you want to proceed?\n ❯ 1. Yes\n   2. Yes, and don't ask again for rm commands\n   \
+                    3. No, and tell Claude what to do differently (esc)";
+        let prompts = feed_all(&mut matcher, text);
+        assert_eq!(prompts.len(), 1);
+        assert_eq!(prompts[0].question, "Do you want to proceed?");
+        assert_eq!(
+            prompts[0].options,
+            vec!["Yes", "Yes, and don't ask again for rm commands", "No, and tell Claude what to do differently (esc)"]
+        );
+        assert_eq!(prompts[0].context.last().map(String::as_str), Some("Remove build artifacts"));
+        assert!(!matcher.is_pending());
+    }
+
+    #[test]
+    fn test_permission_prompt_ends_on_other_output_or_flush() {
+        let mut matcher = PermissionMatcher::default();
+        let text = "\u{1b}[1mDo you want to make this edit to main.rs?\u{1b}[0m\n\u{1b}[36m❯ 1. Yes\u{1b}[0m\n  2. No";
+        assert!(feed_all(&mut matcher, text).is_empty());
+        assert!(matcher.is_pending());
+        let prompt = matcher.flush().unwrap();
+        assert_eq!(prompt.question, "Do you want to make this edit to main.rs?");
+        assert_eq!(prompt.options, vec!["Yes", "No"]);
+
+        let text = "Do you want to proceed?\n❯ 1. Yes\n  2. No\nnext output";
+        assert_eq!(feed_all(&mut matcher, text).len(), 1);
+        assert!(!matcher.is_pending());
+    }
+
+    #[test]
+    fn test_permission_prompt_ignores_decoys() {
+        let mut matcher = PermissionMatcher::default();
+        let decoys = "Checking file permissions on src/\n\
+                      Should I continue with the refactor?\n\
+                      Please approve the plan below.\n\
+                      Allow me to proceed with the migration.\n\
+                      Do you want to proceed? I'll assume yes.\n\
+                      Do you want to proceed?\n\
+                      Here is the plan:\n\
+                      1. Yes, update the schema\n\
+                      2. Run the tests\n\
+                      ❯ 1. Yes";
+        assert!(feed_all(&mut matcher, decoys).is_empty());
+        assert!(!matcher.is_pending());
+        assert_eq!(matcher.flush(), None);
+
+        // 提问后的选项没有 ❯ 光标，或编号不从 1 开始
+        assert!(feed_all(&mut matcher, "Do you want to proceed?\n  1. Yes\n  2. No").is_empty());
+        assert!(feed_all(&mut matcher, "Do you want to proceed?\n❯ 2. Yes").is_empty());
+        assert_eq!(matcher.flush(), None);
+    }
+
+    #[test]
+    fn test_parse_prompt_option() {
+        assert_eq!(parse_prompt_option("❯ 1. Yes"), Some((1, true, "Yes".to_string())));
+        assert_eq!(parse_prompt_option("2. No, and tell Claude (esc)"), Some((2, false, "No, and tell Claude (esc)".to_string())));
+        assert_eq!(parse_prompt_option("1.5 seconds"), None);
+        assert_eq!(parse_prompt_option("❯ 1."), None);
+        assert_eq!(parse_prompt_option("v1. beta"), None);
+    }
+
+    #[test]
+    fn test_claude_launch_args() {
+        let launch = ClaudeLaunchConfig::default();
+        assert_eq!(launch.binary(), "claude");
+        assert_eq!(launch.args(None, "fix it"), vec!["--print", "fix it"]);
+
+        let launch = ClaudeLaunchConfig {
+            binary: Some("/opt/claude/bin/claude".to_string()),
+            model: Some("sonnet".to_string()),
+            extra_args: vec!["--dangerously-skip-permissions".to_string()],
+            ..Default::default()
+        };
+        assert_eq!(launch.binary(), "/opt/claude/bin/claude");
+        assert_eq!(
+            launch.args(None, "fix it"),
+            vec!["--print", "--model", "sonnet", "--dangerously-skip-permissions", "fix it"]
+        );
+        assert_eq!(launch.args(Some("opus"), "x")[2], "opus");
+    }
+
+    #[test]
+    fn test_claude_launch_validate() {
+        let missing = ClaudeLaunchConfig { binary: Some("/nonexistent/claude".to_string()), ..Default::default() };
+        assert!(missing.validate().unwrap_err().contains("/nonexistent/claude"));
+        let bad_cwd = ClaudeLaunchConfig {
+            binary: Some("sh".to_string()),
+            cwd: Some("/nonexistent/dir".to_string()),
+            ..Default::default()
+        };
+        assert!(bad_cwd.validate().unwrap_err().contains("/nonexistent/dir"));
     }
 
     #[test]
@@ -390,4 +1037,77 @@ mod tests {
         assert_eq!(worker.task_id, "task_001");
         assert_eq!(worker.relay_url, "ws://localhost:8080");
     }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_reply_routed_to_worker_with_live_child() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = std::env::temp_dir().join(format!("sparky-route-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let script = dir.join("fake-claude.sh");
+        std::fs::write(&script, "#!/bin/sh\nsleep 30\n").unwrap();
+        std::fs::set_permissions(&script, std::fs::Permissions::from_mode(0o755)).unwrap();
+        let project = dir.to_string_lossy().to_string();
+
+        let launch = ClaudeLaunchConfig {
+            binary: Some(script.to_string_lossy().to_string()),
+            cwd: Some(project.clone()),
+            ..Default::default()
+        };
+        let worker = Arc::new(
+            LocalWorker::new("task_route".to_string(), "ws://127.0.0.1:1".to_string()).with_launch_config(launch),
+        );
+        worker.spawn_claude("hi", None, None).await.unwrap();
+        worker_registry().lock().unwrap().insert("task_route".to_string(), worker.clone());
+
+        // 等待任务在后台等待子进程退出时，状态查询和路由都不能被阻塞
+        let wait = Duration::from_secs(2);
+        tokio::time::sleep(Duration::from_millis(300)).await;
+        assert!(timeout(wait, worker.status()).await.unwrap().child_alive);
+        let routed = timeout(wait, find_project_worker(&format!("{}/", project))).await.unwrap();
+        assert!(routed.is_some_and(|w| Arc::ptr_eq(&w, &worker)));
+
+        timeout(wait, stop_local_worker("task_route".to_string())).await.unwrap().unwrap();
+        assert!(!worker.status().await.child_alive);
+        assert!(find_project_worker(&project).await.is_none());
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    /// 读取 relay 端收到的下一条 status 消息
+    async fn next_status(
+        ws: &mut tokio_tungstenite::WebSocketStream<tokio::net::TcpStream>,
+    ) -> Option<String> {
+        while let Some(Ok(msg)) = ws.next().await {
+            if let WsMessage::Text(text) = msg {
+                let payload: MessagePayload = serde_json::from_str(&text).ok()?;
+                if payload.msg_type == "status" {
+                    return payload.data.status;
+                }
+            }
+        }
+        None
+    }
+
+    #[tokio::test]
+    async fn test_status_reaches_relay_client() {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let relay_url = format!("ws://{}", listener.local_addr().unwrap());
+        let worker = Arc::new(LocalWorker::new("task_status".to_string(), relay_url));
+        let runner = worker.clone();
+        let handle = tokio::spawn(async move { runner.run().await });
+
+        let (stream, _) = listener.accept().await.unwrap();
+        let mut relay = tokio_tungstenite::accept_async(stream).await.unwrap();
+        let wait = Duration::from_secs(5);
+        assert_eq!(timeout(wait, next_status(&mut relay)).await.unwrap().as_deref(), Some("connected"));
+
+        worker.send_status("success").await;
+        assert_eq!(timeout(wait, next_status(&mut relay)).await.unwrap().as_deref(), Some("success"));
+        assert_eq!(worker.status().await.last_status.as_deref(), Some("success"));
+
+        relay.close(None).await.unwrap();
+        timeout(wait, handle).await.unwrap().unwrap();
+        assert!(!worker.status().await.running);
+    }
 }
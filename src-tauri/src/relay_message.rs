@@ -0,0 +1,37 @@
+// Wire format of messages exchanged through the relay server by workers and the UI
+// (relay-server includes this file via #[path] in its tests to check the real payload shape)
+
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MessagePayload {
+    pub sender: String,
+    pub task_id: String,
+    #[serde(rename = "type")]
+    pub msg_type: String,
+    pub action: Option<String>,
+    pub data: MessageData,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+pub struct MessageData {
+    #[serde(rename = "execution_mode")]
+    pub execution_mode: Option<String>,
+    pub prompt: Option<String>,
+    pub status: Option<String>,
+    pub stream: Option<String>,
+    pub content: Option<String>,
+    #[serde(rename = "request_id")]
+    pub request_id: Option<String>,
+    pub hook_type: Option<String>,
+    #[serde(rename = "raw_command")]
+    pub raw_command: Option<String>,
+    pub description: Option<String>,
+    pub decision: Option<String>,
+    /// start_task 时覆盖项目配置的 --model
+    pub model: Option<String>,
+    /// start_task 所属项目的路径，作为 claude 的工作目录（必须是已添加的项目）
+    pub project_path: Option<String>,
+    /// permission_request 中解析出的选项文本，按编号顺序排列
+    pub options: Option<Vec<String>>,
+}
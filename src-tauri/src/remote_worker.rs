@@ -5,12 +5,13 @@ use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 use std::path::{Path, PathBuf};
 use std::process::Stdio;
-use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, OnceLock};
 use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
 use tokio::process::{Child, Command};
 use tokio::sync::{mpsc, Mutex, RwLock};
 use tokio::time::{timeout, Duration};
-use tokio_tungstenite::{connect_async, tungstenite::protocol::Message as WsMessage};
+use tokio_tungstenite::tungstenite::protocol::Message as WsMessage;
 use futures_util::{SinkExt, StreamExt};
 use tracing::{info, warn, error, debug};
 
@@ -51,6 +52,29 @@ impl VfsConfig {
         args
     }
 
+    /// 生成 macOS sandbox-exec 的 SBPL profile：
+    /// sandbox-exec 无法重映射路径，因此直接按 host_path 授权——只读映射允许读取，可写映射额外允许写入
+    pub fn to_sandbox_exec_profile(&self, network_enabled: bool) -> String {
+        let mut profile = String::from("(version 1)\n(deny default)\n");
+        profile.push_str("(allow process-fork process-exec signal sysctl-read mach-lookup ipc-posix-shm)\n");
+        profile.push_str("(allow file-read-metadata)\n");
+        for path in SANDBOX_EXEC_SYSTEM_READ_PATHS {
+            profile.push_str(&format!("(allow file-read* (subpath {}))\n", sbpl_string(path)));
+        }
+        profile.push_str("(allow file-write* (literal \"/dev/null\") (literal \"/dev/tty\"))\n");
+        for m in &self.mappings {
+            let host = sbpl_string(&m.host_path.to_string_lossy());
+            profile.push_str(&format!("(allow file-read* (subpath {}))\n", host));
+            if !m.readonly {
+                profile.push_str(&format!("(allow file-write* (subpath {}))\n", host));
+            }
+        }
+        if network_enabled {
+            profile.push_str("(allow network*)\n");
+        }
+        profile
+    }
+
     pub fn resolve_host_path(&self, sandbox_path: &Path) -> Option<PathBuf> {
         for m in &self.mappings {
             if let Ok(rel) = sandbox_path.strip_prefix(&m.sandbox_path) {
@@ -70,6 +94,66 @@ impl VfsConfig {
     }
 }
 
+/// sandbox-exec 下命令运行所需的系统只读路径（动态库、shell、系统配置）
+const SANDBOX_EXEC_SYSTEM_READ_PATHS: [&str; 7] = [
+    "/System",
+    "/usr/lib",
+    "/usr/bin",
+    "/bin",
+    "/private/etc",
+    "/private/var/db/dyld",
+    "/dev",
+];
+
+const SANDBOX_EXEC_PATH: &str = "/usr/bin/sandbox-exec";
+
+/// 用于 cgroup 资源限制（MemoryMax / CPUQuota）的包装器
+const SYSTEMD_RUN: &str = "systemd-run";
+
+/// 无法使用 cgroup 时回退到 --rlimit-as：它限制的是虚拟地址空间，
+/// JIT / 大量 mmap 的程序虚拟内存远大于实际占用，因此按配置值的倍数放宽
+const RLIMIT_AS_HEADROOM: u64 = 4;
+
+/// 等待沙箱进程退出时的轮询间隔
+const EXIT_POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// 进程退出 / 被杀后等待输出读取完毕的最长时间
+const OUTPUT_DRAIN_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// 在 PATH 中查找可执行文件
+fn find_in_path(program: &str) -> Option<PathBuf> {
+    let paths = std::env::var_os("PATH")?;
+    std::env::split_paths(&paths)
+        .map(|dir| dir.join(program))
+        .find(|candidate| candidate.is_file())
+}
+
+/// 转义为 SBPL 字符串字面量
+fn sbpl_string(s: &str) -> String {
+    format!("\"{}\"", s.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+// ============== Sandbox Backend ==============
+/// 沙箱实现：Linux 使用 LiteBox（bubblewrap 风格参数），macOS 使用 sandbox-exec，None 为不隔离直接执行
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SandboxBackend {
+    LiteBox,
+    SandboxExec,
+    None,
+}
+
+impl SandboxBackend {
+    /// 按当前平台选择默认后端
+    pub fn for_platform() -> Self {
+        if cfg!(target_os = "macos") {
+            SandboxBackend::SandboxExec
+        } else {
+            SandboxBackend::LiteBox
+        }
+    }
+}
+
 // ============== LiteBox Sandbox Config ==============
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct SandboxConfig {
@@ -79,6 +163,127 @@ pub struct SandboxConfig {
     pub max_cpu_percent: u32,
     pub timeout_secs: u64,
     pub env_vars: HashMap<String, String>,
+    /// 覆盖按平台选择的沙箱后端
+    #[serde(default)]
+    pub backend: Option<SandboxBackend>,
+}
+
+impl SandboxConfig {
+    pub fn effective_backend(&self) -> SandboxBackend {
+        self.backend.unwrap_or_else(SandboxBackend::for_platform)
+    }
+
+    /// 检查沙箱程序是否可用；后端为 None 时没有任何隔离，remote 模式不可用
+    pub fn check_available(&self) -> Result<(), String> {
+        match self.effective_backend() {
+            SandboxBackend::LiteBox => {
+                let path = Path::new(&self.litebox_path);
+                if path.is_file() || find_in_path(&self.litebox_path).is_some() {
+                    Ok(())
+                } else {
+                    Err(format!("LiteBox sandbox '{}' not found on this machine", self.litebox_path))
+                }
+            }
+            SandboxBackend::SandboxExec => {
+                if Path::new(SANDBOX_EXEC_PATH).is_file() {
+                    Ok(())
+                } else {
+                    Err(format!("{} not found on this machine", SANDBOX_EXEC_PATH))
+                }
+            }
+            SandboxBackend::None => Err("sandbox backend is None; remote mode requires sandbox isolation".to_string()),
+        }
+    }
+
+    /// 按后端构造在沙箱中执行 `/bin/sh -c <command>` 的命令
+    pub fn build_command(&self, vfs: &VfsConfig, command: &str) -> Command {
+        match self.effective_backend() {
+            SandboxBackend::LiteBox => self.litebox_command(vfs, command),
+            SandboxBackend::SandboxExec => self.sandbox_exec_command(vfs, command),
+            SandboxBackend::None => {
+                warn!(
+                    "[RemoteWorker] Sandbox backend disabled, running command unsandboxed (network/memory/cpu limits not enforced)"
+                );
+                let mut cmd = Command::new("/bin/sh");
+                cmd.arg("-c").arg(command).envs(&self.env_vars);
+                cmd
+            }
+        }
+    }
+
+    /// 有 systemd-run 时通过 cgroup 限制实际内存与 CPU 占比，否则回退到 rlimit（不限制 CPU）
+    fn litebox_command(&self, vfs: &VfsConfig, command: &str) -> Command {
+        let use_cgroup = find_in_path(SYSTEMD_RUN).is_some();
+        let mut cmd = if use_cgroup {
+            let mut cmd = Command::new(SYSTEMD_RUN);
+            cmd.args(self.cgroup_wrapper_args()).arg(&self.litebox_path);
+            cmd
+        } else {
+            warn!(
+                "[RemoteWorker] {} not found, max_cpu_percent={} is not enforced",
+                SYSTEMD_RUN, self.max_cpu_percent
+            );
+            Command::new(&self.litebox_path)
+        };
+
+        // Add sandbox isolation flags
+        cmd.args(self.litebox_isolation_args(use_cgroup));
+
+        // Add VFS mappings
+        for arg in vfs.to_litebox_args() {
+            cmd.arg(arg);
+        }
+
+        // Add environment variables
+        for (key, value) in &self.env_vars {
+            cmd.arg("--setenv").arg(key).arg(value);
+        }
+
+        // Execute shell command inside sandbox
+        cmd.arg("--").arg("/bin/sh").arg("-c").arg(command);
+        cmd
+    }
+
+    /// systemd-run 临时 scope：MemoryMax 限制常驻内存，CPUQuota 限制 CPU 占比
+    fn cgroup_wrapper_args(&self) -> Vec<String> {
+        vec![
+            "--user".to_string(),
+            "--scope".to_string(),
+            "--quiet".to_string(),
+            "-p".to_string(),
+            format!("MemoryMax={}M", self.max_memory_mb),
+            "-p".to_string(),
+            format!("CPUQuota={}%", self.max_cpu_percent),
+            "--".to_string(),
+        ]
+    }
+
+    /// LiteBox 的隔离与资源参数；--unshare-all 默认包含网络隔离，允许网络时需要显式 --share-net
+    fn litebox_isolation_args(&self, cgroup_limited: bool) -> Vec<String> {
+        let mut args = vec!["--unshare-all".to_string()];
+        if self.network_enabled {
+            args.push("--share-net".to_string());
+        } else {
+            args.push("--unshare-net".to_string());
+        }
+        if !cgroup_limited {
+            args.push("--rlimit-as".to_string());
+            args.push(format!("{}M", self.max_memory_mb * RLIMIT_AS_HEADROOM));
+        }
+        args
+    }
+
+    /// sandbox-exec 不支持内存限制与路径重映射，环境变量直接设置在子进程上
+    fn sandbox_exec_command(&self, vfs: &VfsConfig, command: &str) -> Command {
+        let mut cmd = Command::new(SANDBOX_EXEC_PATH);
+        cmd.arg("-p")
+            .arg(vfs.to_sandbox_exec_profile(self.network_enabled))
+            .arg("/bin/sh")
+            .arg("-c")
+            .arg(command)
+            .envs(&self.env_vars);
+        cmd
+    }
 }
 
 impl Default for SandboxConfig {
@@ -90,10 +295,55 @@ impl Default for SandboxConfig {
             max_cpu_percent: 50,
             timeout_secs: 300,
             env_vars: HashMap::new(),
+            backend: None,
+        }
+    }
+}
+
+/// 沙箱配置以 JSON 保存在 db_meta 中的 key
+const SANDBOX_CONFIG_KEY: &str = "sandbox_config";
+
+/// 打开数据库读取沙箱配置，失败时使用默认值
+pub(crate) fn load_saved_sandbox_config() -> SandboxConfig {
+    match crate::open_db() {
+        Ok(conn) => load_sandbox_config(&conn),
+        Err(e) => {
+            warn!("[RemoteWorker] Failed to open db, using default sandbox config: {}", e);
+            SandboxConfig::default()
         }
     }
 }
 
+/// 读取已保存的沙箱配置；未保存或解析失败时使用默认值
+fn load_sandbox_config(conn: &rusqlite::Connection) -> SandboxConfig {
+    let saved: Option<String> = conn
+        .query_row(
+            "SELECT value FROM db_meta WHERE key = ?1",
+            rusqlite::params![SANDBOX_CONFIG_KEY],
+            |row| row.get(0),
+        )
+        .ok();
+    match saved.map(|json| serde_json::from_str::<SandboxConfig>(&json)) {
+        Some(Ok(config)) => config,
+        Some(Err(e)) => {
+            warn!("[RemoteWorker] Invalid saved sandbox config, using defaults: {}", e);
+            SandboxConfig::default()
+        }
+        None => SandboxConfig::default(),
+    }
+}
+
+fn save_sandbox_config(conn: &rusqlite::Connection, config: &SandboxConfig) -> Result<(), String> {
+    let json = serde_json::to_string(config).map_err(|e| e.to_string())?;
+    conn.execute(
+        "INSERT INTO db_meta (key, value) VALUES (?1, ?2)
+         ON CONFLICT(key) DO UPDATE SET value = excluded.value",
+        rusqlite::params![SANDBOX_CONFIG_KEY, json],
+    )
+    .map_err(|e| e.to_string())?;
+    Ok(())
+}
+
 // ============== Message Types ==============
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct RemoteMessagePayload {
@@ -133,6 +383,18 @@ pub struct RemoteWorker {
     child: Arc<Mutex<Option<Child>>>,
     stdin: Arc<Mutex<Option<tokio::process::ChildStdin>>>,
     ws_sender: mpsc::Sender<String>,
+    // 连接并注册后立即执行的命令（由 LocalWorker 转交 remote 模式任务时设置）
+    initial_command: Mutex<Option<String>>,
+    // 执行在独立任务中进行，持有该锁保证同一 worker 的命令依次执行
+    execution: Arc<Mutex<()>>,
+    stopped: AtomicBool,
+    stop_signal: tokio::sync::Notify,
+}
+
+/// 运行中的 RemoteWorker，按 task_id 索引，供 stop_remote_worker 停止
+fn worker_registry() -> &'static std::sync::Mutex<HashMap<String, Arc<RemoteWorker>>> {
+    static REGISTRY: OnceLock<std::sync::Mutex<HashMap<String, Arc<RemoteWorker>>>> = OnceLock::new();
+    REGISTRY.get_or_init(|| std::sync::Mutex::new(HashMap::new()))
 }
 
 impl RemoteWorker {
@@ -147,9 +409,17 @@ impl RemoteWorker {
             child: Arc::new(Mutex::new(None)),
             stdin: Arc::new(Mutex::new(None)),
             ws_sender,
+            initial_command: Mutex::new(None),
+            execution: Arc::new(Mutex::new(())),
+            stopped: AtomicBool::new(false),
+            stop_signal: tokio::sync::Notify::new(),
         }
     }
 
+    pub fn with_initial_command(self, command: String) -> Self {
+        Self { initial_command: Mutex::new(Some(command)), ..self }
+    }
+
     pub async fn configure_vfs(&self, mappings: Vec<VfsMapping>) {
         let mut vfs = self.vfs_config.write().await;
         for m in mappings {
@@ -166,19 +436,47 @@ impl RemoteWorker {
     pub async fn run(&self) {
         info!("[RemoteWorker] Starting: task_id={}", self.task_id);
 
-        // Parse URL for validation, then convert to string for connect_async
-        let ws_url = self.relay_url.clone();
+        // 与 LocalWorker 相同：relay_url 为基础地址，统一追加 /ws/{task_id}
+        let ws_url = crate::relay_client::task_ws_url(&self.relay_url, &self.task_id);
+
+        while !self.stopped.load(Ordering::SeqCst) {
+            let ws_stream = match crate::reconnect::connect_with_retry(
+                &ws_url,
+                "RemoteWorker",
+                crate::reconnect::WORKER_MAX_CONNECT_ATTEMPTS,
+            )
+            .await
+            {
+                Some(s) => s,
+                None => {
+                    error!("[RemoteWorker] WebSocket connection failed, giving up");
+                    return;
+                }
+            };
 
-        let (ws_stream, _) = match connect_async(&ws_url).await {
-            Ok(s) => s,
-            Err(e) => {
-                error!("[RemoteWorker] WebSocket connection failed: {}", e);
-                return;
+            info!("[RemoteWorker] Connected to relay server");
+            if !self.serve_connection(ws_stream).await {
+                break;
             }
-        };
+            warn!("[RemoteWorker] Heartbeat lost, reconnecting: task_id={}", self.task_id);
+        }
+        info!("[RemoteWorker] Disconnected: task_id={}", self.task_id);
+    }
 
-        info!("[RemoteWorker] Connected to relay server");
+    /// 停止 worker：结束沙箱中的子进程并关闭 relay 连接，不再重连
+    pub async fn stop(&self) {
+        self.stopped.store(true, Ordering::SeqCst);
+        self.stop_signal.notify_one();
+        if let Some(mut child) = self.child.lock().await.take() {
+            let _ = child.kill().await;
+        }
+    }
 
+    /// 处理一条 relay 连接；返回 true 表示心跳超时需要重连，false 表示连接正常结束
+    async fn serve_connection(
+        &self,
+        ws_stream: tokio_tungstenite::WebSocketStream<tokio_tungstenite::MaybeTlsStream<tokio::net::TcpStream>>,
+    ) -> bool {
         let (mut ws_write, mut ws_read) = ws_stream.split();
         let (tx, mut rx) = mpsc::channel::<String>(200);
 
@@ -198,27 +496,62 @@ impl RemoteWorker {
             let _ = ws_write.send(WsMessage::Text(json.into())).await;
         }
 
+        // 转交的任务在注册后立即执行；连接重建时不会重复执行
+        if let Some(command) = self.initial_command.lock().await.take() {
+            let child_arc = self.child.clone();
+            let stdin_arc = self.stdin.clone();
+            let sandbox_config = self.sandbox_config.clone();
+            let vfs_config = self.vfs_config.clone();
+            let task_id = self.task_id.clone();
+            let tx = tx.clone();
+            let execution = self.execution.clone();
+            tokio::spawn(async move {
+                let _running = execution.lock().await;
+                Self::execute_in_sandbox(&child_arc, &stdin_arc, &sandbox_config, &vfs_config, &task_id, &tx, &command)
+                    .await;
+            });
+        }
+
         let child_arc = self.child.clone();
         let stdin_arc = self.stdin.clone();
         let sandbox_config = self.sandbox_config.clone();
         let vfs_config = self.vfs_config.clone();
         let task_id = self.task_id.clone();
         let tx_clone = tx.clone();
-
-        // WebSocket sender task
-        let sender_task = tokio::spawn(async move {
-            while let Some(msg) = rx.recv().await {
-                if ws_write.send(WsMessage::Text(msg.into())).await.is_err() {
-                    break;
+        let execution = self.execution.clone();
+        let heartbeat = Arc::new(crate::reconnect::Heartbeat::default());
+        let heartbeat_rx = heartbeat.clone();
+
+        // WebSocket sender task（同时负责定期 ping）；返回 true 表示心跳超时
+        let mut sender_task = tokio::spawn(async move {
+            let mut ping_interval = crate::reconnect::heartbeat_interval();
+            loop {
+                tokio::select! {
+                    msg = rx.recv() => {
+                        let Some(msg) = msg else { return false };
+                        crate::task_log::record_worker_message(&msg);
+                        if ws_write.send(WsMessage::Text(msg.into())).await.is_err() {
+                            return true;
+                        }
+                    }
+                    _ = ping_interval.tick() => {
+                        if !heartbeat.tick() || ws_write.send(WsMessage::Ping(Default::default())).await.is_err() {
+                            return true;
+                        }
+                    }
                 }
             }
         });
 
         // WebSocket receiver task
-        let receiver_task = tokio::spawn(async move {
+        let mut receiver_task = tokio::spawn(async move {
             while let Some(msg_result) = ws_read.next().await {
                 let msg = match msg_result {
                     Ok(WsMessage::Text(t)) => t.to_string(),
+                    Ok(WsMessage::Pong(_)) => {
+                        heartbeat_rx.pong();
+                        continue;
+                    }
                     Ok(WsMessage::Close(_)) => break,
                     Err(_) => break,
                     _ => continue,
@@ -231,16 +564,20 @@ impl RemoteWorker {
 
                 match payload.msg_type.as_str() {
                     "execute" => {
+                        // 与转交任务一样在独立任务中执行，读循环继续处理 pong、input 与 terminate
                         if let Some(cmd) = payload.data.raw_command {
-                            Self::execute_in_sandbox(
-                                &child_arc,
-                                &stdin_arc,
-                                &sandbox_config,
-                                &vfs_config,
-                                &task_id,
-                                &tx_clone,
-                                &cmd,
-                            ).await;
+                            let child_arc = child_arc.clone();
+                            let stdin_arc = stdin_arc.clone();
+                            let sandbox_config = sandbox_config.clone();
+                            let vfs_config = vfs_config.clone();
+                            let task_id = task_id.clone();
+                            let tx = tx_clone.clone();
+                            let execution = execution.clone();
+                            tokio::spawn(async move {
+                                let _running = execution.lock().await;
+                                Self::execute_in_sandbox(&child_arc, &stdin_arc, &sandbox_config, &vfs_config, &task_id, &tx, &cmd)
+                                    .await;
+                            });
                         }
                     }
                     "input" => {
@@ -265,8 +602,22 @@ impl RemoteWorker {
             }
         });
 
-        let _ = tokio::join!(sender_task, receiver_task);
-        info!("[RemoteWorker] Disconnected: task_id={}", self.task_id);
+        // 任一方向结束即结束本条连接，另一个任务随之中止
+        tokio::select! {
+            lost = &mut sender_task => {
+                receiver_task.abort();
+                lost.unwrap_or(false)
+            }
+            _ = &mut receiver_task => {
+                sender_task.abort();
+                false
+            }
+            _ = self.stop_signal.notified() => {
+                sender_task.abort();
+                receiver_task.abort();
+                false
+            }
+        }
     }
 
     async fn execute_in_sandbox(
@@ -281,36 +632,9 @@ impl RemoteWorker {
         info!("[RemoteWorker] Executing in sandbox: {}", command);
 
         let vfs = vfs_config.read().await;
-        let vfs_args = vfs.to_litebox_args();
+        let mut cmd = sandbox_config.build_command(&vfs, command);
         drop(vfs);
 
-        // Build LiteBox command
-        let mut cmd = Command::new(&sandbox_config.litebox_path);
-        
-        // Add sandbox isolation flags
-        cmd.arg("--unshare-all");
-        
-        // Network isolation
-        if !sandbox_config.network_enabled {
-            cmd.arg("--unshare-net");
-        }
-
-        // Resource limits
-        cmd.arg("--rlimit-as").arg(format!("{}M", sandbox_config.max_memory_mb));
-        
-        // Add VFS mappings
-        for arg in &vfs_args {
-            cmd.arg(arg);
-        }
-
-        // Add environment variables
-        for (key, value) in &sandbox_config.env_vars {
-            cmd.arg("--setenv").arg(key).arg(value);
-        }
-
-        // Execute shell command inside sandbox
-        cmd.arg("--").arg("/bin/sh").arg("-c").arg(command);
-
         cmd.stdin(Stdio::piped())
             .stdout(Stdio::piped())
             .stderr(Stdio::piped());
@@ -366,28 +690,71 @@ impl RemoteWorker {
             }
         });
 
-        let _ = tokio::join!(stdout_task, stderr_task);
+        let mut readers = tokio::spawn(async move {
+            let _ = tokio::join!(stdout_task, stderr_task);
+        });
 
-        // Wait for process with timeout
+        // 输出在读取任务中实时推送，等待退出与超时并发进行，超时可以打断仍在输出的命令
         let timeout_duration = Duration::from_secs(sandbox_config.timeout_secs);
-        let mut child_guard = child_arc.lock().await;
-        
-        if let Some(ref mut child) = *child_guard {
-            match timeout(timeout_duration, child.wait()).await {
-                Ok(Ok(status)) => {
-                    let exit_code = status.code().unwrap_or(-1);
-                    Self::send_exit_msg(tx, task_id, exit_code).await;
-                }
-                Ok(Err(e)) => {
-                    Self::send_error_msg(tx, task_id, &format!("Process error: {}", e)).await;
-                }
-                Err(_) => {
+        let outcome = tokio::select! {
+            status = Self::wait_for_exit(child_arc) => Some(status),
+            _ = tokio::time::sleep(timeout_duration) => None,
+        };
+
+        match outcome {
+            Some(Ok(Some(status))) => {
+                Self::drain_output(&mut readers).await;
+                Self::send_exit_msg(tx, task_id, status.code().unwrap_or(-1)).await;
+            }
+            // 子进程已被 terminate 移除
+            Some(Ok(None)) => Self::drain_output(&mut readers).await,
+            Some(Err(e)) => {
+                Self::drain_output(&mut readers).await;
+                Self::send_error_msg(tx, task_id, &format!("Process error: {}", e)).await;
+            }
+            None => {
+                if let Some(ref mut child) = *child_arc.lock().await {
                     let _ = child.kill().await;
-                    Self::send_error_msg(tx, task_id, "Execution timeout").await;
+                }
+                Self::drain_output(&mut readers).await;
+                let marker = format!(
+                    "[output truncated: execution timed out after {}s]",
+                    sandbox_config.timeout_secs
+                );
+                Self::send_stream_msg(tx, task_id, "stderr", &marker).await;
+                Self::send_error_msg(tx, task_id, "Execution timeout").await;
+            }
+        }
+        *child_arc.lock().await = None;
+    }
+
+    /// 轮询等待子进程退出；只在检查时短暂持有锁，避免阻塞 terminate
+    /// 返回 Ok(None) 表示子进程已被其他路径移除
+    async fn wait_for_exit(
+        child_arc: &Arc<Mutex<Option<Child>>>,
+    ) -> std::io::Result<Option<std::process::ExitStatus>> {
+        loop {
+            {
+                let mut child_guard = child_arc.lock().await;
+                match child_guard.as_mut() {
+                    Some(child) => {
+                        if let Some(status) = child.try_wait()? {
+                            return Ok(Some(status));
+                        }
+                    }
+                    None => return Ok(None),
                 }
             }
+            tokio::time::sleep(EXIT_POLL_INTERVAL).await;
+        }
+    }
+
+    /// 进程结束后读完管道中剩余的输出；孙进程仍持有管道时最多等待 OUTPUT_DRAIN_TIMEOUT
+    async fn drain_output(readers: &mut tokio::task::JoinHandle<()>) {
+        if timeout(OUTPUT_DRAIN_TIMEOUT, &mut *readers).await.is_err() {
+            warn!("[RemoteWorker] Output readers still open after exit, abandoning");
+            readers.abort();
         }
-        *child_guard = None;
     }
 
     async fn send_status_msg(tx: &mpsc::Sender<String>, task_id: &str, status: &str) {
@@ -463,9 +830,15 @@ pub async fn start_remote_worker(
     vfs_mappings: Option<Vec<VfsMapping>>,
 ) -> Result<String, String> {
     info!("[RemoteWorker] Starting: {} @ {}", task_id, relay_url);
+    let sandbox_config = load_saved_sandbox_config();
+    sandbox_config.check_available()?;
+    let worker = RemoteWorker::new(task_id.clone(), relay_url, Some(sandbox_config));
+    spawn_remote_worker(worker, vfs_mappings).await;
+    Ok(task_id)
+}
 
-    let worker = RemoteWorker::new(task_id.clone(), relay_url, None);
-    
+/// 配置默认 VFS 映射（及自定义映射）后在后台运行 RemoteWorker
+pub(crate) async fn spawn_remote_worker(worker: RemoteWorker, vfs_mappings: Option<Vec<VfsMapping>>) {
     // Configure default VFS mappings
     worker.add_vfs_mapping("/tmp", "/tmp", false).await;
     worker.add_vfs_mapping("/usr", "/usr", true).await;
@@ -479,39 +852,78 @@ pub async fn start_remote_worker(
     }
 
     let w = Arc::new(worker);
+    // 同一 task_id 的旧 worker 被替换时先停止，避免两个 worker 同时占用任务房间
+    let previous = worker_registry().lock().unwrap().insert(w.task_id.clone(), w.clone());
+    if let Some(previous) = previous {
+        previous.stop().await;
+    }
+
     let ww = w.clone();
-    
     tokio::spawn(async move {
         ww.run().await;
+        // 同一 task_id 可能已被新的 worker 替换，只移除自己
+        let mut registry = worker_registry().lock().unwrap();
+        if registry.get(&ww.task_id).is_some_and(|current| Arc::ptr_eq(current, &ww)) {
+            registry.remove(&ww.task_id);
+        }
     });
-
-    Ok(task_id)
 }
 
 #[tauri::command]
-pub async fn stop_remote_worker() -> Result<(), String> {
-    info!("[RemoteWorker] Stopping");
+pub async fn stop_remote_worker(task_id: String) -> Result<(), String> {
+    let worker = worker_registry().lock().unwrap().remove(&task_id);
+    let worker = worker.ok_or_else(|| format!("No remote worker for task {}", task_id))?;
+    info!("[RemoteWorker] Stopping task {}", task_id);
+    worker.stop().await;
     Ok(())
 }
 
+/// 获取当前保存的沙箱配置（供 UI 展示）
+#[tauri::command]
+pub async fn get_sandbox_config() -> Result<SandboxConfig, String> {
+    let conn = crate::open_db()?;
+    Ok(load_sandbox_config(&conn))
+}
+
+/// 更新沙箱配置并保存，之后启动的 RemoteWorker 使用新配置；未传入的字段保持原值
 #[tauri::command]
 pub async fn configure_sandbox(
     network_enabled: Option<bool>,
     max_memory_mb: Option<u64>,
+    max_cpu_percent: Option<u32>,
     timeout_secs: Option<u64>,
+    backend: Option<SandboxBackend>,
 ) -> Result<SandboxConfig, String> {
-    let mut config = SandboxConfig::default();
+    let conn = crate::open_db()?;
+    let mut config = load_sandbox_config(&conn);
     
     if let Some(net) = network_enabled {
         config.network_enabled = net;
     }
     if let Some(mem) = max_memory_mb {
+        if mem == 0 {
+            return Err("max_memory_mb must be greater than 0".to_string());
+        }
         config.max_memory_mb = mem;
     }
+    if let Some(cpu) = max_cpu_percent {
+        if cpu == 0 || cpu > 100 {
+            return Err("max_cpu_percent must be between 1 and 100".to_string());
+        }
+        config.max_cpu_percent = cpu;
+    }
     if let Some(t) = timeout_secs {
+        if t == 0 {
+            return Err("timeout_secs must be greater than 0".to_string());
+        }
         config.timeout_secs = t;
     }
+    if backend.is_some() {
+        config.backend = backend;
+    }
 
+    save_sandbox_config(&conn, &config)?;
+    info!("[RemoteWorker] Sandbox config saved: {:?}", config);
     Ok(config)
 }
 
@@ -617,11 +1029,129 @@ mod tests {
             max_cpu_percent: 80,
             timeout_secs: 600,
             env_vars,
+            backend: Some(SandboxBackend::SandboxExec),
         };
 
         let json = serde_json::to_string(&config).unwrap();
         assert!(json.contains("network_enabled"));
         assert!(json.contains("1024"));
+        assert!(json.contains("\"sandbox_exec\""));
+    }
+
+    #[test]
+    fn test_sandbox_backend_override() {
+        let mut config = SandboxConfig::default();
+        assert_eq!(config.effective_backend(), SandboxBackend::for_platform());
+        config.backend = Some(SandboxBackend::None);
+        assert_eq!(config.effective_backend(), SandboxBackend::None);
+        // 不隔离时 remote 模式不可用
+        assert!(config.check_available().is_err());
+
+        let legacy: SandboxConfig = serde_json::from_str(
+            r#"{"litebox_path":"litebox","network_enabled":false,"max_memory_mb":512,"max_cpu_percent":50,"timeout_secs":300,"env_vars":{}}"#,
+        )
+        .unwrap();
+        assert!(legacy.backend.is_none());
+    }
+
+    #[test]
+    fn test_sandbox_exec_profile() {
+        let mut vfs = VfsConfig::new();
+        vfs.add_mapping("/usr", "/usr", true);
+        vfs.add_mapping("/tmp/work \"x\"", "/work", false);
+
+        let profile = vfs.to_sandbox_exec_profile(false);
+        assert!(profile.starts_with("(version 1)\n(deny default)"));
+        assert!(profile.contains("(allow file-read* (subpath \"/usr\"))"));
+        assert!(!profile.contains("(allow file-write* (subpath \"/usr\"))"));
+        assert!(profile.contains("(allow file-write* (subpath \"/tmp/work \\\"x\\\"\"))"));
+        assert!(!profile.contains("network"));
+        assert!(vfs.to_sandbox_exec_profile(true).contains("(allow network*)"));
+    }
+
+    #[test]
+    fn test_build_command_per_backend() {
+        let mut vfs = VfsConfig::new();
+        vfs.add_mapping("/bin", "/bin", true);
+        let mut config = SandboxConfig { backend: Some(SandboxBackend::LiteBox), ..Default::default() };
+
+        // 是否经 systemd-run 包装取决于宿主机，这里只校验 litebox 之后的参数
+        let litebox = config.build_command(&vfs, "echo hi");
+        let argv: Vec<String> = std::iter::once(litebox.as_std().get_program())
+            .chain(litebox.as_std().get_args())
+            .map(|a| a.to_string_lossy().to_string())
+            .collect();
+        let start = argv.iter().position(|a| a == "litebox").unwrap();
+        assert_eq!(argv[start + 1], "--unshare-all");
+        assert!(argv.contains(&"--unshare-net".to_string()));
+        assert_eq!(&argv[argv.len() - 4..], ["--", "/bin/sh", "-c", "echo hi"]);
+
+        config.backend = Some(SandboxBackend::SandboxExec);
+        let sandbox_exec = config.build_command(&vfs, "echo hi");
+        let args: Vec<_> = sandbox_exec.as_std().get_args().map(|a| a.to_string_lossy().to_string()).collect();
+        assert_eq!(sandbox_exec.as_std().get_program(), SANDBOX_EXEC_PATH);
+        assert_eq!(args[0], "-p");
+        assert!(args[1].contains("(subpath \"/bin\")"));
+        assert_eq!(&args[2..], ["/bin/sh", "-c", "echo hi"]);
+    }
+
+    #[test]
+    fn test_litebox_resource_args() {
+        let mut config = SandboxConfig { max_memory_mb: 256, max_cpu_percent: 25, ..Default::default() };
+        assert_eq!(
+            config.cgroup_wrapper_args(),
+            ["--user", "--scope", "--quiet", "-p", "MemoryMax=256M", "-p", "CPUQuota=25%", "--"]
+        );
+        assert_eq!(config.litebox_isolation_args(true), ["--unshare-all", "--unshare-net"]);
+        assert_eq!(
+            config.litebox_isolation_args(false),
+            ["--unshare-all", "--unshare-net", "--rlimit-as", "1024M"]
+        );
+
+        config.network_enabled = true;
+        assert_eq!(config.litebox_isolation_args(true), ["--unshare-all", "--share-net"]);
+    }
+
+    #[tokio::test]
+    async fn test_timeout_streams_partial_output() {
+        let config = SandboxConfig { timeout_secs: 1, backend: Some(SandboxBackend::None), ..Default::default() };
+        let vfs = Arc::new(RwLock::new(VfsConfig::new()));
+        let child = Arc::new(Mutex::new(None));
+        let stdin = Arc::new(Mutex::new(None));
+        let (tx, mut rx) = mpsc::channel(32);
+
+        let started = std::time::Instant::now();
+        RemoteWorker::execute_in_sandbox(&child, &stdin, &config, &vfs, "t1", &tx, "echo start; sleep 30").await;
+        assert!(started.elapsed() < Duration::from_secs(10));
+        drop(tx);
+
+        let mut messages = Vec::new();
+        while let Some(json) = rx.recv().await {
+            let payload: RemoteMessagePayload = serde_json::from_str(&json).unwrap();
+            messages.push((payload.msg_type, payload.data.content.unwrap_or_default()));
+        }
+        let types: Vec<&str> = messages.iter().map(|(t, _)| t.as_str()).collect();
+        assert_eq!(types, vec!["status", "stream", "stream", "error"]);
+        assert_eq!(messages[1].1, "start");
+        assert!(messages[2].1.contains("timed out after 1s"));
+        assert!(child.lock().await.is_none());
+    }
+
+    #[test]
+    fn test_sandbox_config_persistence() {
+        let conn = rusqlite::Connection::open_in_memory().unwrap();
+        conn.execute("CREATE TABLE db_meta (key TEXT PRIMARY KEY, value TEXT NOT NULL)", []).unwrap();
+        assert_eq!(load_sandbox_config(&conn).timeout_secs, 300);
+
+        let config = SandboxConfig { timeout_secs: 42, network_enabled: true, ..Default::default() };
+        save_sandbox_config(&conn, &config).unwrap();
+        save_sandbox_config(&conn, &config).unwrap();
+        let loaded = load_sandbox_config(&conn);
+        assert_eq!(loaded.timeout_secs, 42);
+        assert!(loaded.network_enabled);
+
+        conn.execute("UPDATE db_meta SET value = 'not json'", []).unwrap();
+        assert_eq!(load_sandbox_config(&conn).timeout_secs, 300);
     }
 
     #[test]
@@ -0,0 +1,271 @@
+// Remote execution history: persists worker stream/status messages per task_id
+
+use rusqlite::{params, Connection};
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::io::Write;
+use std::sync::OnceLock;
+
+/// 每个任务最多保留的日志行数
+const MAX_LINES_PER_TASK: i64 = 5000;
+/// 超过该时长（秒）的任务日志会被清理
+const RETENTION_SECS: i64 = 7 * 24 * 3600;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TaskLogEntry {
+    pub id: i64,
+    pub task_id: String,
+    pub stream: String,
+    pub content: String,
+    pub created_at: i64,
+}
+
+/// 日志写入线程每个事务最多写入的行数
+const MAX_BATCH: usize = 500;
+/// 定期清理超出行数上限与保留时长的日志
+const PRUNE_INTERVAL: std::time::Duration = std::time::Duration::from_secs(600);
+
+struct LogLine {
+    task_id: String,
+    stream: String,
+    content: String,
+    created_at: i64,
+}
+
+/// 日志写入通道：流式输出的每一行都从 async 读循环中调用，这里只入队，由专用线程批量写库
+fn log_writer() -> &'static std::sync::mpsc::Sender<LogLine> {
+    static WRITER: OnceLock<std::sync::mpsc::Sender<LogLine>> = OnceLock::new();
+    WRITER.get_or_init(|| {
+        let (tx, rx) = std::sync::mpsc::channel();
+        if let Err(e) = std::thread::Builder::new()
+            .name("task-log-writer".to_string())
+            .spawn(move || write_loop(rx))
+        {
+            log::error!("[task_log] failed to start log writer: {}", e);
+        }
+        tx
+    })
+}
+
+fn now_secs() -> i64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.as_secs() as i64)
+        .unwrap_or(0)
+}
+
+/// 写入一条任务日志（stream: stdout / stderr / status / exit / error）
+pub(crate) fn record_task_log(task_id: &str, stream: &str, content: &str) {
+    let line = LogLine {
+        task_id: task_id.to_string(),
+        stream: stream.to_string(),
+        content: content.to_string(),
+        created_at: now_secs(),
+    };
+    if log_writer().send(line).is_err() {
+        log::error!("[task_log] log writer is not running, dropping log for task {}", task_id);
+    }
+}
+
+/// 批量写入队列中的日志；任务结束（exit / error）时清理该任务，其余任务按 PRUNE_INTERVAL 定期清理
+fn write_loop(rx: std::sync::mpsc::Receiver<LogLine>) {
+    let mut touched: HashSet<String> = HashSet::new();
+    let mut last_prune = std::time::Instant::now();
+    while let Ok(first) = rx.recv() {
+        let mut batch = vec![first];
+        while batch.len() < MAX_BATCH {
+            match rx.try_recv() {
+                Ok(line) => batch.push(line),
+                Err(_) => break,
+            }
+        }
+
+        let conn = match crate::open_db() {
+            Ok(conn) => conn,
+            Err(e) => {
+                log::error!("[task_log] failed to open database, dropping {} log lines: {}", batch.len(), e);
+                continue;
+            }
+        };
+        if let Err(e) = insert_batch(&conn, &batch) {
+            log::error!("[task_log] failed to record {} log lines: {}", batch.len(), e);
+        }
+
+        for line in &batch {
+            if line.stream == "exit" || line.stream == "error" {
+                touched.remove(&line.task_id);
+                if let Err(e) = prune_task(&conn, &line.task_id) {
+                    log::error!("[task_log] failed to prune logs for task {}: {}", line.task_id, e);
+                }
+            } else {
+                touched.insert(line.task_id.clone());
+            }
+        }
+        if last_prune.elapsed() >= PRUNE_INTERVAL {
+            last_prune = std::time::Instant::now();
+            for task_id in touched.drain() {
+                if let Err(e) = prune_task(&conn, &task_id) {
+                    log::error!("[task_log] failed to prune logs for task {}: {}", task_id, e);
+                }
+            }
+            if let Err(e) = prune_expired(&conn, now_secs()) {
+                log::error!("[task_log] failed to prune expired logs: {}", e);
+            }
+        }
+    }
+}
+
+fn insert_batch(conn: &Connection, batch: &[LogLine]) -> rusqlite::Result<()> {
+    let tx = conn.unchecked_transaction()?;
+    {
+        let mut stmt =
+            tx.prepare_cached("INSERT INTO task_logs (task_id, stream, content, created_at) VALUES (?1, ?2, ?3, ?4)")?;
+        for line in batch {
+            stmt.execute(params![line.task_id, line.stream, line.content, line.created_at])?;
+        }
+    }
+    tx.commit()
+}
+
+/// 只保留该任务最近 MAX_LINES_PER_TASK 行
+fn prune_task(conn: &Connection, task_id: &str) -> rusqlite::Result<()> {
+    conn.execute(
+        "DELETE FROM task_logs
+         WHERE task_id = ?1 AND id < (
+           SELECT MIN(id) FROM (
+             SELECT id FROM task_logs WHERE task_id = ?1 ORDER BY id DESC LIMIT ?2
+           )
+         )",
+        params![task_id, MAX_LINES_PER_TASK],
+    )?;
+    Ok(())
+}
+
+fn prune_expired(conn: &Connection, now: i64) -> rusqlite::Result<()> {
+    conn.execute("DELETE FROM task_logs WHERE created_at < ?1", params![now - RETENTION_SECS])?;
+    Ok(())
+}
+
+/// 从 worker 发出的 relay 消息中提取需要持久化的内容
+pub(crate) fn record_worker_message(json: &str) {
+    let value: serde_json::Value = match serde_json::from_str(json) {
+        Ok(v) => v,
+        Err(_) => return,
+    };
+    let task_id = value.get("task_id").and_then(|v| v.as_str()).unwrap_or("");
+    if task_id.is_empty() {
+        return;
+    }
+    let msg_type = value.get("type").and_then(|v| v.as_str()).unwrap_or("");
+    let data = value.get("data");
+    let field = |key: &str| {
+        data.and_then(|d| d.get(key))
+            .and_then(|v| v.as_str())
+            .unwrap_or("")
+            .to_string()
+    };
+
+    let (stream, content) = match msg_type {
+        "stream" | "chat_log_stream" => (field("stream"), field("content")),
+        "status" => ("status".to_string(), field("status")),
+        "exit" => {
+            let code = data
+                .and_then(|d| d.get("exit_code"))
+                .and_then(|v| v.as_i64())
+                .unwrap_or(-1);
+            ("exit".to_string(), format!("exit_code={}", code))
+        }
+        "error" => ("error".to_string(), field("content")),
+        _ => return,
+    };
+
+    record_task_log(task_id, &stream, &content);
+}
+
+#[tauri::command]
+pub fn get_task_log(task_id: String) -> Result<Vec<TaskLogEntry>, String> {
+    let conn = crate::open_db()?;
+    let mut stmt = conn
+        .prepare(
+            "SELECT id, task_id, stream, content, created_at FROM task_logs
+             WHERE task_id = ?1
+             ORDER BY id ASC",
+        )
+        .map_err(|e| e.to_string())?;
+    let rows = stmt
+        .query_map(params![task_id], |row| {
+            Ok(TaskLogEntry {
+                id: row.get(0)?,
+                task_id: row.get(1)?,
+                stream: row.get(2)?,
+                content: row.get(3)?,
+                created_at: row.get(4)?,
+            })
+        })
+        .map_err(|e| e.to_string())?;
+
+    let mut entries = Vec::new();
+    for entry in rows {
+        entries.push(entry.map_err(|e| e.to_string())?);
+    }
+    Ok(entries)
+}
+
+/// 导出任务完整输出到文本文件，返回写入的行数
+#[tauri::command]
+pub fn export_task_log(task_id: String, path: String) -> Result<usize, String> {
+    let entries = get_task_log(task_id.clone())?;
+    if entries.is_empty() {
+        return Err(format!("No logs found for task: {}", task_id));
+    }
+
+    let mut file = std::fs::File::create(&path)
+        .map_err(|e| format!("Failed to create export file: {}", e))?;
+    for entry in &entries {
+        let time = chrono::DateTime::from_timestamp(entry.created_at, 0)
+            .map(|t| t.with_timezone(&chrono::Local).format("%Y-%m-%d %H:%M:%S").to_string())
+            .unwrap_or_default();
+        writeln!(file, "[{}] [{}] {}", time, entry.stream, entry.content)
+            .map_err(|e| format!("Failed to write export file: {}", e))?;
+    }
+
+    log::info!("[task_log] exported {} lines for task {} to {}", entries.len(), task_id, path);
+    Ok(entries.len())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn line(task_id: &str, stream: &str, created_at: i64) -> LogLine {
+        LogLine {
+            task_id: task_id.to_string(),
+            stream: stream.to_string(),
+            content: String::new(),
+            created_at,
+        }
+    }
+
+    fn count(conn: &Connection, task_id: &str) -> i64 {
+        conn.query_row("SELECT COUNT(*) FROM task_logs WHERE task_id = ?1", params![task_id], |row| row.get(0))
+            .unwrap()
+    }
+
+    #[test]
+    fn test_batch_insert_and_prune() {
+        let conn = Connection::open_in_memory().unwrap();
+        crate::migrations::run(&conn).unwrap();
+
+        let batch: Vec<LogLine> = (0..MAX_LINES_PER_TASK + 10).map(|_| line("t1", "stdout", 1_000)).collect();
+        insert_batch(&conn, &batch).unwrap();
+        insert_batch(&conn, &[line("t2", "stdout", 10), line("t2", "exit", 1_000)]).unwrap();
+
+        prune_task(&conn, "t1").unwrap();
+        assert_eq!(count(&conn, "t1"), MAX_LINES_PER_TASK);
+        assert_eq!(count(&conn, "t2"), 2);
+
+        prune_expired(&conn, 10 + RETENTION_SECS + 1).unwrap();
+        assert_eq!(count(&conn, "t2"), 1);
+        assert_eq!(count(&conn, "t1"), MAX_LINES_PER_TASK);
+    }
+}
@@ -7,13 +7,29 @@ use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
 use std::sync::Arc;
 use std::time::Duration;
 use std::io::Read;
-use tokio::sync::Mutex;
-use tokio_tungstenite::{connect_async, tungstenite::protocol::Message as WsMessage};
+use tokio::sync::{mpsc, Mutex};
+use tokio_tungstenite::tungstenite::protocol::Message as WsMessage;
+use tokio_tungstenite::tungstenite::protocol::{frame::coding::CloseCode, CloseFrame};
 use tokio_tungstenite::MaybeTlsStream;
 use tokio::net::TcpStream;
 use futures_util::stream::SplitSink;
 use std::sync::OnceLock;
 
+use crate::card_choice::CardChoice;
+use crate::ws_fragments::FragmentBuffer;
+
+/// 解析配对码回复 "<2 位配对码>-<1|2|3>"，允许空白和全角连字符（与 CLI 保持一致）
+fn parse_pairing_reply(text: &str) -> Option<(String, String)> {
+    let normalized = text.trim().replace(['－', '—'], "-");
+    let (code, choice) = normalized.split_once('-')?;
+    let (code, choice) = (code.trim(), choice.trim());
+    if code.len() == 2 && code.chars().all(|c| c.is_ascii_digit()) && matches!(choice, "1" | "2" | "3") {
+        Some((code.to_string(), choice.to_string()))
+    } else {
+        None
+    }
+}
+
 // 包含由 prost 生成的 protobuf 代码
 pub mod proto {
     include!(concat!(env!("OUT_DIR"), "/pbbp2.rs"));
@@ -88,31 +104,82 @@ pub struct EventHeader {
 pub struct FeishuWsClient {
     app_id: String,
     app_secret: String,
+    // 出站 HTTP 代理（仅用于 endpoint 请求，WebSocket 本身直连）
+    proxy_url: Option<String>,
     connected: Arc<AtomicBool>,
     ping_interval_secs: Arc<AtomicU64>,
+    // 服务端下发的重连间隔（秒），0 表示未下发
+    reconnect_interval_secs: Arc<AtomicU64>,
     // 保存最后联系的用户 open_id，用于发送消息
     last_open_id: Arc<OnceLock<String>>,
+    // 收到的事件（EventPayload JSON）转发给桌面应用，由其 emit 给前端
+    event_tx: Option<mpsc::Sender<String>>,
+    // 退出信号：置为 true 后 connect 发送 Close 帧并返回，重连循环随之结束
+    shutdown: tokio::sync::watch::Sender<bool>,
+    // 未收齐的分片事件
+    fragments: std::sync::Mutex<FragmentBuffer>,
 }
 
+/// 退出时等待服务端回应 Close 帧的最长时间
+const CLOSE_HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(2);
+
 impl FeishuWsClient {
     #[allow(dead_code)]
-    pub fn new(app_id: String, app_secret: String) -> Self {
-        FeishuWsClient {
-            app_id,
-            app_secret,
-            connected: Arc::new(AtomicBool::new(false)),
-            ping_interval_secs: Arc::new(AtomicU64::new(30)),
-            last_open_id: Arc::new(OnceLock::new()),
-        }
+    pub fn new(
+        app_id: String,
+        app_secret: String,
+        proxy_url: Option<String>,
+        event_tx: Option<mpsc::Sender<String>>,
+    ) -> Self {
+        Self::new_with_connected(app_id, app_secret, proxy_url, Arc::new(AtomicBool::new(false)), event_tx)
     }
 
-    pub fn new_with_connected(app_id: String, app_secret: String, connected: Arc<AtomicBool>) -> Self {
+    pub fn new_with_connected(
+        app_id: String,
+        app_secret: String,
+        proxy_url: Option<String>,
+        connected: Arc<AtomicBool>,
+        event_tx: Option<mpsc::Sender<String>>,
+    ) -> Self {
         FeishuWsClient {
             app_id,
             app_secret,
+            proxy_url,
             connected,
             ping_interval_secs: Arc::new(AtomicU64::new(30)),
+            reconnect_interval_secs: Arc::new(AtomicU64::new(0)),
             last_open_id: Arc::new(OnceLock::new()),
+            event_tx,
+            shutdown: tokio::sync::watch::channel(false).0,
+            fragments: std::sync::Mutex::new(FragmentBuffer::default()),
+        }
+    }
+
+    /// 请求优雅退出：正在进行的连接发送 Close 帧后返回
+    pub fn shutdown(&self) {
+        self.shutdown.send_replace(true);
+    }
+
+    pub fn is_shutting_down(&self) -> bool {
+        *self.shutdown.borrow()
+    }
+
+    /// 等待退出信号
+    async fn wait_for_shutdown(&self) {
+        let mut rx = self.shutdown.subscribe();
+        let _ = rx.wait_for(|stopping| *stopping).await;
+    }
+
+    /// 将事件转发给订阅方；通道已满或已关闭时丢弃，不阻塞 WSS 收帧
+    fn forward_event(&self, event: &EventPayload) {
+        let Some(tx) = &self.event_tx else { return };
+        match serde_json::to_string(event) {
+            Ok(json) => {
+                if let Err(e) = tx.try_send(json) {
+                    log::warn!("Dropping forwarded Feishu event {}: {}", event.header.event_id, e);
+                }
+            }
+            Err(e) => log::warn!("Failed to serialize Feishu event: {}", e),
         }
     }
 
@@ -122,7 +189,7 @@ impl FeishuWsClient {
     }
 
     async fn get_ws_url(&self) -> Result<String> {
-        let client = reqwest::Client::new();
+        let client = crate::build_http_client(self.proxy_url.as_deref()).map_err(|e| anyhow::anyhow!(e))?;
         let url = format!("{}{}", FEISHU_DOMAIN, GEN_ENDPOINT_URI);
 
         let response = client
@@ -149,22 +216,37 @@ impl FeishuWsClient {
             if let Some(interval) = config.ping_interval {
                 self.ping_interval_secs.store(interval as u64, Ordering::Relaxed);
             }
+            if let Some(interval) = config.reconnect_interval.filter(|i| *i > 0) {
+                self.reconnect_interval_secs.store(interval as u64, Ordering::Relaxed);
+            }
         }
 
         Ok(data.url)
     }
 
+    /// 服务端 ClientConfig 下发的重连间隔，用作重连退避的基础值
+    pub fn reconnect_interval(&self) -> Option<Duration> {
+        match self.reconnect_interval_secs.load(Ordering::Relaxed) {
+            0 => None,
+            secs => Some(Duration::from_secs(secs)),
+        }
+    }
+
     pub async fn connect(&self) -> Result<()> {
         // 获取 WebSocket URL
         let ws_url = self.get_ws_url().await?;
         log::info!("Connecting to Feishu WebSocket...");
 
-        let (ws_stream, _) = connect_async(&ws_url).await?;
+        let proxy_url = crate::resolve_proxy_url(self.proxy_url.as_deref());
+        let ws_stream = crate::ws_proxy::connect_ws(&ws_url, proxy_url.as_deref())
+            .await
+            .map_err(anyhow::Error::msg)?;
         log::info!("WebSocket connected successfully");
 
         let (write, mut read) = ws_stream.split();
         let write = Arc::new(Mutex::new(write));
         self.connected.store(true, Ordering::SeqCst);
+        crate::ws_uptime::record_ws_event(crate::ws_connection_log::EVENT_CONNECTED, "");
 
         // 心跳任务
         let connected = self.connected.clone();
@@ -191,8 +273,33 @@ impl FeishuWsClient {
             }
         });
 
-        // 接收消息循环
-        while let Some(msg) = read.next().await {
+        // 接收消息循环（收到退出信号时发送 Close 帧并等待服务端回应）
+        let mut disconnect_reason = "stream ended".to_string();
+        loop {
+            let msg = tokio::select! {
+                msg = read.next() => msg,
+                _ = self.wait_for_shutdown() => {
+                    log::info!("Shutdown requested, closing WebSocket");
+                    disconnect_reason = "client shutdown".to_string();
+                    let close = WsMessage::Close(Some(CloseFrame {
+                        code: CloseCode::Normal,
+                        reason: "client shutdown".into(),
+                    }));
+                    if let Err(e) = write.lock().await.send(close).await {
+                        log::warn!("Failed to send close frame: {}", e);
+                    }
+                    let _ = tokio::time::timeout(CLOSE_HANDSHAKE_TIMEOUT, async {
+                        while let Some(Ok(msg)) = read.next().await {
+                            if matches!(msg, WsMessage::Close(_)) {
+                                break;
+                            }
+                        }
+                    })
+                    .await;
+                    break;
+                }
+            };
+            let Some(msg) = msg else { break };
             match msg {
                 Ok(WsMessage::Binary(data)) => {
                     if let Err(e) = self.handle_message(&data, &write).await {
@@ -207,11 +314,13 @@ impl FeishuWsClient {
                 }
                 Ok(WsMessage::Close(_)) => {
                     log::info!("WebSocket closed by server");
+                    disconnect_reason = "closed by server".to_string();
                     self.connected.store(false, Ordering::SeqCst);
                     break;
                 }
                 Err(e) => {
                     log::error!("WebSocket error: {}", e);
+                    disconnect_reason = format!("error: {}", e);
                     self.connected.store(false, Ordering::SeqCst);
                     break;
                 }
@@ -221,6 +330,7 @@ impl FeishuWsClient {
 
         heartbeat_handle.abort();
         self.connected.store(false, Ordering::SeqCst);
+        crate::ws_uptime::record_ws_event(crate::ws_connection_log::EVENT_DISCONNECTED, &disconnect_reason);
         log::info!("WebSocket disconnected");
 
         Ok(())
@@ -250,6 +360,7 @@ impl FeishuWsClient {
 
     async fn handle_message(&self, data: &[u8], write: &Arc<Mutex<WsWrite>>) -> Result<()> {
         let frame = Frame::decode(data)?;
+        crate::record_wss_receive();
         let method = frame.method;
         let msg_type = Self::get_header_value(&frame, HEADER_TYPE);
         let payload_len = frame.payload.as_ref().map(|payload| payload.len()).unwrap_or(0);
@@ -325,14 +436,21 @@ impl FeishuWsClient {
 
         self.send_ack(frame, write).await?;
 
-        let payload_str = Self::decode_payload(frame)?;
-        if let Some(payload_str) = payload_str {
-            log::debug!("Event payload: {}", payload_str);
-            if let Ok(event) = serde_json::from_str::<EventPayload>(&payload_str) {
-                self.handle_event(&event).await?;
-            } else if let Ok(value) = serde_json::from_str::<serde_json::Value>(&payload_str) {
-                log::debug!("Raw event json: {}", value);
+        let payload = match self.reassemble_payload(frame) {
+            Some(payload) => payload,
+            None if frame.payload.is_some() => {
+                log::debug!("Buffered fragment, waiting for the rest");
+                return Ok(());
             }
+            None => return Ok(()),
+        };
+
+        let payload_str = Self::decode_payload(frame, &payload)?;
+        log::debug!("Event payload: {}", payload_str);
+        if let Ok(event) = serde_json::from_str::<EventPayload>(&payload_str) {
+            self.handle_event(&event).await?;
+        } else if let Ok(value) = serde_json::from_str::<serde_json::Value>(&payload_str) {
+            log::debug!("Raw event json: {}", value);
         }
 
         Ok(())
@@ -341,6 +459,7 @@ impl FeishuWsClient {
     async fn handle_event(&self, event: &EventPayload) -> Result<()> {
         let event_type = &event.header.event_type;
         log::info!("Received event: {}", event_type);
+        self.forward_event(event);
 
         match event_type.as_str() {
             "card.action.trigger" => {
@@ -361,15 +480,16 @@ impl FeishuWsClient {
         log::info!("Card action: {}", serde_json::to_string_pretty(event_data)?);
 
         // 获取用户选择的值
-        if let Some(action) = event_data.get("action") {
-            if let Some(value) = action.get("value") {
-                if let Some(choice) = value.get("choice") {
-                    if let Some(choice_str) = choice.as_str() {
-                        log::info!("User choice: {}", choice_str);
-                        self.save_user_choice(choice_str).await?;
-                    }
+        let value = event_data.get("action").and_then(|action| action.get("value"));
+        match value.and_then(CardChoice::from_value) {
+            Some(choice) => match choice.user_choice() {
+                Some(answer) => {
+                    log::info!("User choice: {} ({:?})", answer, choice);
+                    self.save_user_choice(&answer).await?;
                 }
-            }
+                None => log::info!("Test card button clicked, ignoring"),
+            },
+            None => log::warn!("Card action without a recognised value: {:?}", value),
         }
 
         Ok(())
@@ -403,19 +523,66 @@ impl FeishuWsClient {
             if let Err(e) = crate::save_open_id_to_db(open_id) {
                 log::error!("Failed to save open_id to SQLite: {}", e);
             }
+            if let Err(e) = crate::record_wss_open_id(open_id) {
+                log::error!("Failed to record last open_id: {}", e);
+            }
         }
 
         log::info!("Message parsed: sender={}, type={}, content={}", open_id, message_type, content);
 
+        // 配对码回复（如 42-1）：更新权限请求；该项目有运行中的 relay worker 时通过 relay 交给它，
+        // 否则写入 PTY 命令队列，两条路径只走其一
+        let text = if message_type == "text" {
+            serde_json::from_str::<serde_json::Value>(content)
+                .ok()
+                .and_then(|json| json.get("text").and_then(|v| v.as_str()).map(|s| s.to_string()))
+                .unwrap_or_else(|| content.to_string())
+        } else {
+            content.to_string()
+        };
+        if let Some((code, choice)) = parse_pairing_reply(&text) {
+            log::info!("Received permission response: code={}, choice={}", code, choice);
+            let worker = match crate::pending_permission_project(&code) {
+                Ok(project_path) => crate::relay_client::find_project_worker(&project_path).await,
+                Err(e) => {
+                    log::warn!("Failed to resolve permission response: {}", e);
+                    return Ok(());
+                }
+            };
+            match crate::resolve_permission_by_code(&code, &choice, worker.is_none()) {
+                Ok(_) => {
+                    if let Some(worker) = worker {
+                        let decision = crate::permission_decision_for_choice(&choice);
+                        if let Err(e) = crate::relay_client::send_permission_decision(&worker, decision).await {
+                            log::warn!("Failed to send permission decision to relay worker: {}", e);
+                        }
+                    }
+                }
+                Err(e) => log::warn!("Failed to resolve permission response: {}", e),
+            }
+        }
+
         Ok(())
     }
 
-    fn decode_payload(frame: &Frame) -> Result<Option<String>> {
-        let payload = match &frame.payload {
-            Some(payload) => payload.as_slice(),
-            None => return Ok(None),
-        };
+    /// 取出数据帧的完整负载：单帧直接返回，分片帧缓存到收齐为止（未收齐时返回 None）
+    fn reassemble_payload(&self, frame: &Frame) -> Option<Vec<u8>> {
+        let payload = frame.payload.clone()?;
+        let sum: usize = Self::get_header_value(frame, HEADER_SUM)
+            .and_then(|v| v.parse().ok())
+            .unwrap_or(1);
+        if sum <= 1 {
+            return Some(payload);
+        }
+        let message_id = Self::get_header_value(frame, HEADER_MESSAGE_ID)?;
+        let seq: usize = Self::get_header_value(frame, HEADER_SEQ)?.parse().ok()?;
+        self.fragments
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .push(&message_id, sum, seq, payload)
+    }
 
+    fn decode_payload(frame: &Frame, payload: &[u8]) -> Result<String> {
         let payload_encoding = frame.payload_encoding.as_deref().unwrap_or("");
         let is_gzip = payload_encoding.eq_ignore_ascii_case("gzip")
             || payload.starts_with(&[0x1f, 0x8b]);
@@ -428,7 +595,7 @@ impl FeishuWsClient {
             String::from_utf8_lossy(payload).to_string()
         };
 
-        Ok(Some(decoded))
+        Ok(decoded)
     }
 
     async fn send_pong(&self, service_id: i32, write: &Arc<Mutex<WsWrite>>) -> Result<()> {
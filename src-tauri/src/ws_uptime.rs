@@ -0,0 +1,186 @@
+// WSS connection history: connect/disconnect transitions and uptime statistics
+
+use crate::ws_connection_log::{EVENT_CONNECTED, EVENT_DISCONNECTED, SOURCE_DESKTOP, STALE_AFTER_SECS};
+use rusqlite::params;
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct WsUptimeStats {
+    pub window_secs: i64,
+    pub uptime_secs: i64,
+    pub uptime_ratio: f64,
+    pub disconnect_count: u32,
+    pub longest_gap_secs: i64,
+    pub currently_connected: bool,
+}
+
+fn now_secs() -> i64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.as_secs() as i64)
+        .unwrap_or(0)
+}
+
+/// 记录桌面端的一次连接状态变化（event: connected / disconnected）
+pub(crate) fn record_ws_event(event: &str, reason: &str) {
+    let result = crate::open_db().and_then(|conn| {
+        crate::ws_connection_log::record(&conn, SOURCE_DESKTOP, event, reason, now_secs()).map_err(|e| e.to_string())
+    });
+    if let Err(e) = result {
+        log::error!("[ws_uptime] failed to record {} event: {}", event, e);
+    }
+}
+
+/// 根据窗口起点之前的最后状态和窗口内的事件（按时间升序）计算统计；
+/// 最后仍为 connected 但 last_seen（最近收到帧的时间）已过期时，视为进程已退出，从 last_seen 起按断开计算
+fn compute_uptime_stats(
+    initially_connected: bool,
+    events: &[(String, i64)],
+    last_seen: Option<i64>,
+    window_start: i64,
+    now: i64,
+) -> WsUptimeStats {
+    let mut connected = initially_connected;
+    let mut since = window_start;
+    let mut uptime_secs = 0;
+    let mut longest_gap_secs = 0;
+    let mut disconnect_count = 0;
+
+    for (event, at) in events {
+        let at = (*at).clamp(window_start, now);
+        match (connected, event.as_str()) {
+            (true, EVENT_DISCONNECTED) => {
+                uptime_secs += at - since;
+                disconnect_count += 1;
+                connected = false;
+                since = at;
+            }
+            (false, EVENT_CONNECTED) => {
+                longest_gap_secs = longest_gap_secs.max(at - since);
+                connected = true;
+                since = at;
+            }
+            _ => {}
+        }
+    }
+
+    if let Some(seen) = last_seen.filter(|seen| connected && now - seen > STALE_AFTER_SECS) {
+        let ended_at = seen.clamp(since, now);
+        uptime_secs += ended_at - since;
+        disconnect_count += 1;
+        connected = false;
+        since = ended_at;
+    }
+
+    if connected {
+        uptime_secs += now - since;
+    } else {
+        longest_gap_secs = longest_gap_secs.max(now - since);
+    }
+
+    let window_secs = now - window_start;
+    WsUptimeStats {
+        window_secs,
+        uptime_secs,
+        uptime_ratio: if window_secs > 0 { uptime_secs as f64 / window_secs as f64 } else { 0.0 },
+        disconnect_count,
+        longest_gap_secs,
+        currently_connected: connected,
+    }
+}
+
+/// 统计最近 hours 小时内的连接时长、断开次数和最长断开间隔；source 为 cli / desktop，默认统计桌面端
+#[tauri::command]
+pub fn get_ws_uptime_stats(hours: u32, source: Option<String>) -> Result<WsUptimeStats, String> {
+    if hours == 0 {
+        return Err("hours must be greater than 0".to_string());
+    }
+    let conn = crate::open_db()?;
+    let now = now_secs();
+    let window_start = now - hours as i64 * 3600;
+    let source = source.as_deref().unwrap_or(SOURCE_DESKTOP);
+
+    let initially_connected = conn
+        .query_row(
+            "SELECT event FROM ws_connection_log WHERE source = ?1 AND created_at < ?2 ORDER BY id DESC LIMIT 1",
+            params![source, window_start],
+            |row| row.get::<_, String>(0),
+        )
+        .map(|event| event == EVENT_CONNECTED)
+        .unwrap_or(false);
+
+    let mut stmt = conn
+        .prepare(
+            "SELECT event, created_at FROM ws_connection_log
+             WHERE source = ?1 AND created_at >= ?2
+             ORDER BY id ASC",
+        )
+        .map_err(|e| e.to_string())?;
+    let rows = stmt
+        .query_map(params![source, window_start], |row| Ok((row.get::<_, String>(0)?, row.get::<_, i64>(1)?)))
+        .map_err(|e| e.to_string())?;
+    let mut events = Vec::new();
+    for row in rows {
+        events.push(row.map_err(|e| e.to_string())?);
+    }
+
+    let last_seen = crate::ws_connection_log::last_seen_secs(&conn).map_err(|e| e.to_string())?;
+
+    Ok(compute_uptime_stats(initially_connected, &events, last_seen, window_start, now))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ev(event: &str, at: i64) -> (String, i64) {
+        (event.to_string(), at)
+    }
+
+    #[test]
+    fn test_uptime_with_disconnect_gap() {
+        let events = vec![
+            ev(EVENT_CONNECTED, 100),
+            ev(EVENT_DISCONNECTED, 400),
+            ev(EVENT_CONNECTED, 700),
+        ];
+        let stats = compute_uptime_stats(false, &events, None, 0, 1000);
+        assert_eq!(stats.uptime_secs, 300 + 300);
+        assert_eq!(stats.disconnect_count, 1);
+        assert_eq!(stats.longest_gap_secs, 300);
+        assert!(stats.currently_connected);
+    }
+
+    #[test]
+    fn test_connected_before_window_and_still_down() {
+        let events = vec![ev(EVENT_DISCONNECTED, 200)];
+        let stats = compute_uptime_stats(true, &events, None, 0, 1000);
+        assert_eq!(stats.uptime_secs, 200);
+        assert_eq!(stats.longest_gap_secs, 800);
+        assert!(!stats.currently_connected);
+    }
+
+    #[test]
+    fn test_duplicate_events_ignored() {
+        let events = vec![ev(EVENT_CONNECTED, 0), ev(EVENT_CONNECTED, 500)];
+        let stats = compute_uptime_stats(false, &events, None, 0, 1000);
+        assert_eq!(stats.uptime_secs, 1000);
+        assert_eq!(stats.disconnect_count, 0);
+        assert_eq!(stats.longest_gap_secs, 0);
+    }
+
+    #[test]
+    fn test_stale_connected_row_counts_as_down() {
+        let events = vec![ev(EVENT_CONNECTED, 100)];
+        // 最后收到帧在 300，之后再无心跳：视为 300 起已断开
+        let stats = compute_uptime_stats(false, &events, Some(300), 0, 1000 + STALE_AFTER_SECS);
+        assert_eq!(stats.uptime_secs, 200);
+        assert_eq!(stats.disconnect_count, 1);
+        assert!(!stats.currently_connected);
+
+        // 仍在收到帧时保持在线
+        let stats = compute_uptime_stats(false, &events, Some(900), 0, 1000);
+        assert_eq!(stats.uptime_secs, 900);
+        assert!(stats.currently_connected);
+    }
+}
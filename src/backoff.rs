@@ -0,0 +1,95 @@
+use std::time::Duration;
+
+/// 连接保持超过该时长后断开，视为稳定连接，退避重置为基础值
+pub const STABLE_CONNECTION: Duration = Duration::from_secs(60);
+
+/// 退避抖动幅度（±20%）
+const JITTER_RATIO: f64 = 0.2;
+
+/// WebSocket 重连的指数退避（每次失败翻倍直到上限，并加入 ±20% 抖动）
+pub struct ReconnectBackoff {
+    base: Duration,
+    cap: Duration,
+    current: Duration,
+    attempt: u32,
+}
+
+impl ReconnectBackoff {
+    pub fn new(base: Duration, cap: Duration) -> Self {
+        ReconnectBackoff { base, cap, current: base, attempt: 0 }
+    }
+
+    /// 用服务端下发的 reconnect_interval 作为新的基础值（上限不低于基础值）
+    pub fn set_base(&mut self, base: Duration) {
+        if base.is_zero() || base == self.base {
+            return;
+        }
+        tracing::info!("[reconnect] base delay set to {}s by server config", base.as_secs());
+        self.base = base;
+        self.cap = self.cap.max(base);
+        self.current = self.current.clamp(base, self.cap);
+    }
+
+    /// 返回本次不含抖动的等待时长，并把下一次翻倍
+    fn next_base_delay(&mut self) -> Duration {
+        let delay = self.current;
+        self.current = (self.current * 2).min(self.cap);
+        self.attempt += 1;
+        delay
+    }
+
+    /// 返回本次等待时长（含抖动），并记录退避状态
+    pub fn next_delay(&mut self) -> Duration {
+        let delay = apply_jitter(self.next_base_delay(), rand::random_range(-1.0..=1.0));
+        tracing::info!(
+            "[reconnect] attempt {}: waiting {:.1}s (base {}s, cap {}s)",
+            self.attempt,
+            delay.as_secs_f64(),
+            self.base.as_secs(),
+            self.cap.as_secs()
+        );
+        delay
+    }
+
+    pub fn reset(&mut self) {
+        self.current = self.base;
+        self.attempt = 0;
+    }
+}
+
+/// factor 取值 [-1, 1]，按 JITTER_RATIO 比例放大或缩小等待时长
+fn apply_jitter(delay: Duration, factor: f64) -> Duration {
+    delay.mul_f64(1.0 + JITTER_RATIO * factor.clamp(-1.0, 1.0))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_backoff_doubles_caps_and_resets() {
+        let mut backoff = ReconnectBackoff::new(Duration::from_secs(1), Duration::from_secs(60));
+        let delays: Vec<u64> = (0..8).map(|_| backoff.next_base_delay().as_secs()).collect();
+        assert_eq!(delays, vec![1, 2, 4, 8, 16, 32, 60, 60]);
+        backoff.reset();
+        assert_eq!(backoff.next_base_delay().as_secs(), 1);
+    }
+
+    #[test]
+    fn test_jitter_stays_within_bounds() {
+        let delay = Duration::from_secs(10);
+        assert_eq!(apply_jitter(delay, -1.0), Duration::from_secs(8));
+        assert_eq!(apply_jitter(delay, 1.0), Duration::from_secs(12));
+        let mut backoff = ReconnectBackoff::new(Duration::from_secs(10), Duration::from_secs(60));
+        let jittered = backoff.next_delay();
+        assert!(jittered >= Duration::from_secs(8) && jittered <= Duration::from_secs(12));
+    }
+
+    #[test]
+    fn test_server_base_raises_cap() {
+        let mut backoff = ReconnectBackoff::new(Duration::from_secs(1), Duration::from_secs(60));
+        backoff.set_base(Duration::from_secs(120));
+        assert_eq!(backoff.next_base_delay().as_secs(), 120);
+        assert_eq!(backoff.next_base_delay().as_secs(), 120);
+    }
+}
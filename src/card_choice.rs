@@ -0,0 +1,70 @@
+// Values carried by Feishu card button callbacks, shared by the CLI and the desktop app
+// (src-tauri includes this file via #[path] so both sides decode card actions the same way)
+
+/// 卡片按钮回调的值：普通按钮为 {"choice": "1"}，AskUserQuestion 选项为 {"question_index": i, "option_index": j}，
+/// `sparky test --with-actions` 发出的测试卡片为 {"test": "1"}
+#[derive(Debug, Clone, PartialEq)]
+pub enum CardChoice {
+    Choice(String),
+    QuestionOption { question_index: usize, option_index: usize },
+    /// 测试卡片上的按钮，点击后不写入任何选择
+    Test,
+}
+
+impl CardChoice {
+    pub fn from_value(value: &serde_json::Value) -> Option<Self> {
+        if value.get("test").is_some() {
+            return Some(CardChoice::Test);
+        }
+        if let Some(choice) = value.get("choice").and_then(|v| v.as_str()) {
+            return Some(CardChoice::Choice(choice.to_string()));
+        }
+        let question_index = value.get("question_index").and_then(|v| v.as_u64())?;
+        let option_index = value.get("option_index").and_then(|v| v.as_u64())?;
+        Some(CardChoice::QuestionOption {
+            question_index: question_index as usize,
+            option_index: option_index as usize,
+        })
+    }
+
+    /// 写入 user_choice.txt 的内容：普通按钮为选项值；问题选项为 "<问题序号>:<选项序号>"（均从 1 开始），
+    /// 一张卡片有多个问题时也能区分回答的是哪一题；测试按钮返回 None
+    pub fn user_choice(&self) -> Option<String> {
+        match self {
+            CardChoice::Choice(choice) => Some(choice.clone()),
+            CardChoice::QuestionOption { question_index, option_index } => {
+                Some(format!("{}:{}", question_index + 1, option_index + 1))
+            }
+            CardChoice::Test => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_card_choice_from_value() {
+        assert_eq!(
+            CardChoice::from_value(&serde_json::json!({"choice": "2"})),
+            Some(CardChoice::Choice("2".to_string()))
+        );
+        assert_eq!(
+            CardChoice::from_value(&serde_json::json!({"question_index": 1, "option_index": 0})),
+            Some(CardChoice::QuestionOption { question_index: 1, option_index: 0 })
+        );
+        assert_eq!(CardChoice::from_value(&serde_json::json!({"question_index": 1})), None);
+        assert_eq!(CardChoice::from_value(&serde_json::json!({"test": "1"})), Some(CardChoice::Test));
+    }
+
+    #[test]
+    fn test_user_choice_keeps_question_index() {
+        assert_eq!(CardChoice::Choice("2".to_string()).user_choice().as_deref(), Some("2"));
+        let first = CardChoice::QuestionOption { question_index: 0, option_index: 1 };
+        let second = CardChoice::QuestionOption { question_index: 1, option_index: 1 };
+        assert_eq!(first.user_choice().as_deref(), Some("1:2"));
+        assert_eq!(second.user_choice().as_deref(), Some("2:2"));
+        assert_eq!(CardChoice::Test.user_choice(), None);
+    }
+}
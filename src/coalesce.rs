@@ -0,0 +1,167 @@
+use rusqlite::{params, Connection, OptionalExtension};
+
+/// 未配置 coalesce_window_secs 时的合并窗口（秒）
+pub const DEFAULT_WINDOW_SECS: u32 = 5;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum CoalesceDecision {
+    Send,
+    /// 与上一条通知重复；count 为窗口内（含首条）的累计次数，message_id 为首条通知的消息 ID
+    Coalesce { count: u32, message_id: Option<String> },
+}
+
+/// 是否参与合并：PermissionRequest 与带确认按钮的通知需要用户逐条操作，既不合并也不作为合并目标
+pub fn applies(event_name: &str, need_action: bool) -> bool {
+    event_name != "PermissionRequest" && !need_action
+}
+
+/// 归一化后计算指纹：忽略空白差异，数字统一替换为 #，避免计数、耗时等细节让重复事件无法合并
+pub fn fingerprint(event_name: &str, text: &str) -> String {
+    let mut normalized = String::with_capacity(text.len());
+    let mut last_was_digit = false;
+    for word in text.split_whitespace() {
+        if !normalized.is_empty() {
+            normalized.push(' ');
+        }
+        for ch in word.chars() {
+            if ch.is_ascii_digit() {
+                if !last_was_digit {
+                    normalized.push('#');
+                }
+                last_was_digit = true;
+            } else {
+                normalized.extend(ch.to_lowercase());
+                last_was_digit = false;
+            }
+        }
+        last_was_digit = false;
+    }
+
+    let mut hash: u64 = 14695981039346656037;
+    for byte in event_name.bytes().chain([0]).chain(normalized.bytes()) {
+        hash ^= byte as u64;
+        hash = hash.wrapping_mul(1099511628211);
+    }
+    format!("{:016x}", hash)
+}
+
+/// 同一会话在窗口内再次出现相同指纹时合并；窗口从最近一次出现开始计算
+pub fn check(
+    conn: &Connection,
+    session_id: &str,
+    fingerprint: &str,
+    window_secs: u32,
+    now: i64,
+) -> Result<CoalesceDecision, rusqlite::Error> {
+    if window_secs == 0 || session_id.is_empty() {
+        return Ok(CoalesceDecision::Send);
+    }
+    let last: Option<(String, Option<String>, u32, i64)> = conn
+        .query_row(
+            "SELECT fingerprint, message_id, repeat_count, last_seen_at
+             FROM session_last_notification WHERE session_id = ?1",
+            params![session_id],
+            |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?, row.get(3)?)),
+        )
+        .optional()?;
+
+    match last {
+        Some((last_fingerprint, message_id, repeat_count, last_seen_at))
+            if last_fingerprint == fingerprint && now - last_seen_at < window_secs as i64 =>
+        {
+            let count = repeat_count + 1;
+            conn.execute(
+                "UPDATE session_last_notification SET repeat_count = ?1, last_seen_at = ?2 WHERE session_id = ?3",
+                params![count, now, session_id],
+            )?;
+            Ok(CoalesceDecision::Coalesce { count, message_id })
+        }
+        _ => Ok(CoalesceDecision::Send),
+    }
+}
+
+/// 通知发送后记录指纹，作为后续重复事件的合并目标
+pub fn record_sent(
+    conn: &Connection,
+    session_id: &str,
+    fingerprint: &str,
+    message_id: Option<&str>,
+    now: i64,
+) -> Result<(), rusqlite::Error> {
+    if session_id.is_empty() {
+        return Ok(());
+    }
+    conn.execute(
+        "INSERT INTO session_last_notification (session_id, fingerprint, message_id, repeat_count, last_seen_at)
+         VALUES (?1, ?2, ?3, 1, ?4)
+         ON CONFLICT(session_id) DO UPDATE SET
+           fingerprint = excluded.fingerprint,
+           message_id = excluded.message_id,
+           repeat_count = 1,
+           last_seen_at = excluded.last_seen_at",
+        params![session_id, fingerprint, message_id, now],
+    )?;
+    // 清理一天内没有活动的会话
+    conn.execute(
+        "DELETE FROM session_last_notification WHERE last_seen_at < ?1",
+        params![now - 24 * 3600],
+    )?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn test_conn() -> Connection {
+        let conn = Connection::open_in_memory().unwrap();
+        crate::migrations::run(&conn).unwrap();
+        conn
+    }
+
+    #[test]
+    fn test_fingerprint_ignores_whitespace_and_numbers() {
+        assert_eq!(
+            fingerprint("Notification", "Waiting  for input (3 tasks)"),
+            fingerprint("Notification", "waiting for input\n(12 tasks)")
+        );
+        assert_ne!(fingerprint("Notification", "done"), fingerprint("Stop", "done"));
+        assert_ne!(fingerprint("Stop", "build ok"), fingerprint("Stop", "build failed"));
+    }
+
+    #[test]
+    fn test_repeats_within_window_are_coalesced() {
+        let conn = test_conn();
+        let fp = fingerprint("Stop", "done");
+        assert_eq!(check(&conn, "s1", &fp, 5, 100).unwrap(), CoalesceDecision::Send);
+        record_sent(&conn, "s1", &fp, Some("om_1"), 100).unwrap();
+
+        assert_eq!(
+            check(&conn, "s1", &fp, 5, 103).unwrap(),
+            CoalesceDecision::Coalesce { count: 2, message_id: Some("om_1".to_string()) }
+        );
+        // 窗口从最近一次出现开始滑动
+        assert_eq!(
+            check(&conn, "s1", &fp, 5, 107).unwrap(),
+            CoalesceDecision::Coalesce { count: 3, message_id: Some("om_1".to_string()) }
+        );
+        assert_eq!(check(&conn, "s1", &fp, 5, 113).unwrap(), CoalesceDecision::Send);
+        assert_eq!(check(&conn, "s2", &fp, 5, 107).unwrap(), CoalesceDecision::Send);
+        assert_eq!(check(&conn, "s1", &fingerprint("Stop", "other"), 5, 108).unwrap(), CoalesceDecision::Send);
+        assert_eq!(check(&conn, "s1", &fp, 0, 108).unwrap(), CoalesceDecision::Send);
+    }
+
+    #[test]
+    fn test_action_cards_are_never_coalesced() {
+        let conn = test_conn();
+        // 数字被归一化，两条不同命令的确认提示指纹相同
+        let fp = fingerprint("Notification", "Do you want to run rm -rf build1?");
+        assert_eq!(fp, fingerprint("Notification", "Do you want to run rm -rf build2?"));
+
+        // 带按钮的卡片发送后不记录为合并目标，后续提示照常单独发送，原卡片不会被更新去掉按钮
+        assert!(!applies("Notification", true));
+        assert!(!applies("PermissionRequest", false));
+        assert!(applies("Notification", false));
+        assert_eq!(check(&conn, "s1", &fp, 5, 101).unwrap(), CoalesceDecision::Send);
+    }
+}
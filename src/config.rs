@@ -1,7 +1,5 @@
 use rusqlite::{Connection, OptionalExtension};
 use serde::{Deserialize, Serialize};
-use std::fs;
-use std::path::PathBuf;
 use tracing::error;
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -12,8 +10,57 @@ pub struct Config {
     pub verification_token: Option<String>,
     pub chat_id: Option<String>,
     pub open_id: Option<String>,
+    /// chat_id 中填写的 ID 类型（chat_id/open_id/user_id/union_id/email，默认 chat_id）
+    pub receive_id_type: Option<String>,
     pub hook_events_filter: Option<String>,
     pub project_path: Option<String>,
+    pub proxy_url: Option<String>,
+    /// 需要加急提醒的事件列表（逗号分隔，如 PermissionRequest,error）
+    pub urgent_events: Option<String>,
+    /// 消息样式：compact（仅标题与摘要）或 detailed（默认，包含元数据）
+    pub message_style: Option<String>,
+    /// 每个会话每分钟最多发送的通知数（None 或 0 表示不限制）
+    pub max_notifications_per_session_per_minute: Option<u32>,
+    /// 同一会话重复通知的合并窗口（秒，默认 5，0 表示不合并）
+    pub coalesce_window_secs: Option<u32>,
+    /// 识别终端确认提示的触发短语（逗号分隔，未配置时使用内置默认值）
+    pub prompt_triggers: Option<String>,
+    /// 通知正文与提取到的回复少于该字符数时不发送（不计 Event/Session 等元信息；默认 1，即只跳过空通知；0 表示不跳过；权限确认类事件不受影响）
+    pub min_notification_chars: Option<u32>,
+    /// 单条消息最大字符数（默认 18000，飞书单条消息上限约 20000）
+    pub max_message_len: Option<u32>,
+    /// 写入数据库的内容最大字符数（默认 5000）
+    pub max_db_content_len: Option<u32>,
+    /// Stop 事件中 Claude 输出的最大字符数（默认 3000）
+    pub max_response_chars: Option<u32>,
+    /// 其他事件附带的 transcript 末尾字符数（默认 2000）
+    pub max_transcript_chars: Option<u32>,
+    /// 通知后端：feishu（默认）、dingtalk 或 wework
+    pub backend: Option<String>,
+    /// 钉钉配置（来自 app_config_dingtalk，仅在 backend = dingtalk 时加载）
+    pub dingtalk: Option<DingTalkConfig>,
+    /// 企业微信配置（来自 app_config_wework，仅在 backend = wework 时加载）
+    pub wework: Option<WeWorkConfig>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DingTalkConfig {
+    pub app_key: String,
+    pub app_secret: String,
+    /// 群会话 openConversationId
+    pub chat_id: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WeWorkConfig {
+    pub corp_id: String,
+    pub corp_secret: String,
+    /// 自建应用 AgentId（向成员发送权限确认提醒时需要）
+    pub agent_id: Option<String>,
+    /// 群聊 chatid（markdown 通知发送目标）
+    pub chat_id: Option<String>,
+    /// 接收权限确认提醒的成员 userid（多个用 | 分隔）
+    pub to_user: Option<String>,
 }
 
 impl Default for Config {
@@ -25,69 +72,151 @@ impl Default for Config {
             verification_token: None,
             chat_id: None,
             open_id: None,
+            receive_id_type: None,
             hook_events_filter: None,
             project_path: None,
+            proxy_url: None,
+            urgent_events: None,
+            message_style: None,
+            max_notifications_per_session_per_minute: None,
+            coalesce_window_secs: None,
+            prompt_triggers: None,
+            min_notification_chars: None,
+            max_message_len: None,
+            max_db_content_len: None,
+            max_response_chars: None,
+            max_transcript_chars: None,
+            backend: None,
+            dingtalk: None,
+            wework: None,
         }
     }
 }
 
-fn get_db_path() -> PathBuf {
-    let base_dir = dirs::home_dir()
-        .expect("Failed to get home directory")
-        .join("sparky");
-    fs::create_dir_all(&base_dir).expect("Failed to create base directory");
-    base_dir.join("hooks.db")
+/// 配置缺失或不完整（首次运行、尚未在桌面应用中配置）。
+/// 与数据库错误等区分开，run_hook 据此跳过通知并正常退出，而不是让 Claude Code 报告 hook 失败
+#[derive(Debug)]
+pub struct ConfigMissing(pub String);
+
+impl std::fmt::Display for ConfigMissing {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl std::error::Error for ConfigMissing {}
+
+impl ConfigMissing {
+    /// 判断 anyhow 错误是否由配置缺失引起
+    pub fn is(err: &anyhow::Error) -> bool {
+        err.downcast_ref::<ConfigMissing>().is_some()
+    }
+}
+
+pub const DEFAULT_MAX_MESSAGE_LEN: usize = 18000;
+pub const DEFAULT_MIN_NOTIFICATION_CHARS: usize = 1;
+pub const DEFAULT_MAX_DB_CONTENT_LEN: usize = 5000;
+pub const DEFAULT_MAX_RESPONSE_CHARS: usize = 3000;
+pub const DEFAULT_MAX_TRANSCRIPT_CHARS: usize = 2000;
+
+/// 未配置或配置为 0 时使用默认值
+fn limit_or(value: Option<u32>, default: usize) -> usize {
+    value.filter(|v| *v > 0).map(|v| v as usize).unwrap_or(default)
 }
 
 impl Config {
+    pub fn coalesce_window(&self) -> u32 {
+        self.coalesce_window_secs.unwrap_or(crate::coalesce::DEFAULT_WINDOW_SECS)
+    }
+
+    pub fn prompt_triggers(&self) -> Vec<String> {
+        let configured: Vec<String> = self
+            .prompt_triggers
+            .as_deref()
+            .unwrap_or_default()
+            .split(',')
+            .map(|s| s.trim().to_string())
+            .filter(|s| !s.is_empty())
+            .collect();
+        if configured.is_empty() {
+            crate::terminal_prompt::DEFAULT_TRIGGERS.iter().map(|s| s.to_string()).collect()
+        } else {
+            configured
+        }
+    }
+
+    /// 配置的 receive_id_type；未配置或非法时返回 None
+    pub fn receive_id_type(&self) -> Option<&'static str> {
+        self.receive_id_type
+            .as_deref()
+            .filter(|s| !s.trim().is_empty())
+            .and_then(|s| crate::feishu::parse_receive_id_type(s).ok())
+    }
+
+    pub fn min_notification_chars(&self) -> usize {
+        self.min_notification_chars
+            .map(|v| v as usize)
+            .unwrap_or(DEFAULT_MIN_NOTIFICATION_CHARS)
+    }
+
+    pub fn message_limit(&self) -> usize {
+        limit_or(self.max_message_len, DEFAULT_MAX_MESSAGE_LEN)
+    }
+
+    pub fn db_content_limit(&self) -> usize {
+        limit_or(self.max_db_content_len, DEFAULT_MAX_DB_CONTENT_LEN)
+    }
+
+    pub fn response_limit(&self) -> usize {
+        limit_or(self.max_response_chars, DEFAULT_MAX_RESPONSE_CHARS)
+    }
+
+    pub fn transcript_limit(&self) -> usize {
+        limit_or(self.max_transcript_chars, DEFAULT_MAX_TRANSCRIPT_CHARS)
+    }
+
+    pub fn is_dingtalk_backend(&self) -> bool {
+        self.backend
+            .as_deref()
+            .map(|s| s.trim().eq_ignore_ascii_case("dingtalk"))
+            .unwrap_or(false)
+    }
+
+    pub fn is_wework_backend(&self) -> bool {
+        self.backend
+            .as_deref()
+            .map(|s| s.trim().eq_ignore_ascii_case("wework"))
+            .unwrap_or(false)
+    }
+
+    pub fn is_compact_style(&self) -> bool {
+        self.message_style
+            .as_deref()
+            .map(|s| s.trim().eq_ignore_ascii_case("compact"))
+            .unwrap_or(false)
+    }
+
     pub fn load() -> Result<Self, anyhow::Error> {
-        let db_path = get_db_path();
+        let db_path = crate::migrations::db_path()?;
         tracing::info!("[config] loading from DB: {:?}", db_path);
-        let conn = Connection::open(&db_path)?;
-
-        // 迁移：确保新列存在
-        let _ = conn.execute("ALTER TABLE app_config_feishu ADD COLUMN open_id TEXT", []);
-        let _ = conn.execute("ALTER TABLE app_config_feishu ADD COLUMN hook_events_filter TEXT", []);
-
-        // 创建 PTY 命令表
-        let _ = conn.execute(
-            "CREATE TABLE IF NOT EXISTS pty_commands (
-                id INTEGER PRIMARY KEY,
-                project_path TEXT NOT NULL,
-                command TEXT NOT NULL,
-                processed INTEGER DEFAULT 0,
-                created_at INTEGER
-            )",
-            [],
-        );
-
-        // create permission_requests table
-        let _ = conn.execute(
-            "CREATE TABLE IF NOT EXISTS permission_requests (
-                id INTEGER PRIMARY KEY,
-                project_path TEXT NOT NULL,
-                status TEXT NOT NULL,
-                code TEXT,
-                choice TEXT,
-                created_at INTEGER
-            )",
-            [],
-        );
-        // migration: add code column if missing
-        let _ = conn.execute("ALTER TABLE permission_requests ADD COLUMN code TEXT", []);
-            
-        // project_path 应该是已存在的列
-
-        // project_path 应该是已存在的列 (由 Tauri 端负责创建/更新)
-        // 但如果 CLI 独立运行且 DB 刚创建，可能没有?
-        // src-tauri/src/lib.rs 中 upsert_config 更新 project_path。
-        // 表结构在 load_config_from_db 中创建。
-        // 如果 CLI 先运行? CLI 只是读取。
-        // 所以我们假设 project_path 列存在。
+        let conn = crate::migrations::open_connection(&db_path)?;
+
+        // 迁移：按 PRAGMA user_version 应用尚未执行的 schema 迁移（与桌面应用共用）
+        crate::migrations::run(&conn)?;
+
+        // 迁移：旧版明文保存的密钥在首次加载时加密
+        if let Err(e) = crate::secret::migrate_plaintext_secrets(&conn) {
+            error!("[config] failed to encrypt plaintext secrets: {}", e);
+        }
+
+        Self::load_from(&conn)
+    }
 
+    /// 从已迁移的数据库读取配置；没有配置行或缺少必填项时返回 ConfigMissing
+    fn load_from(conn: &Connection) -> Result<Self, anyhow::Error> {
         let config = conn
             .query_row(
-                "SELECT app_id, app_secret, encrypt_key, verification_token, chat_id, open_id, hook_events_filter, project_path
+                "SELECT app_id, app_secret, encrypt_key, verification_token, chat_id, open_id, hook_events_filter, project_path, proxy_url, urgent_events, message_style, max_notifications_per_session_per_minute, backend, max_message_len, max_db_content_len, max_response_chars, max_transcript_chars, coalesce_window_secs, prompt_triggers, min_notification_chars, receive_id_type
                  FROM app_config_feishu WHERE id = 1",
                 [],
                 |row| {
@@ -100,15 +229,47 @@ impl Config {
                         open_id: row.get(5)?,
                         hook_events_filter: row.get(6)?,
                         project_path: row.get(7)?,
+                        proxy_url: row.get(8)?,
+                        urgent_events: row.get(9)?,
+                        message_style: row.get(10)?,
+                        max_notifications_per_session_per_minute: row.get(11)?,
+                        backend: row.get(12)?,
+                        max_message_len: row.get(13)?,
+                        max_db_content_len: row.get(14)?,
+                        max_response_chars: row.get(15)?,
+                        max_transcript_chars: row.get(16)?,
+                        coalesce_window_secs: row.get(17)?,
+                        prompt_triggers: row.get(18)?,
+                        min_notification_chars: row.get(19)?,
+                        receive_id_type: row.get(20)?,
+                        dingtalk: None,
+                        wework: None,
                     })
                 },
             )
             .optional()?;
 
         let config = match config {
-            Some(config) => {
-                let masked_id = if config.app_id.len() > 8 {
-                    format!("{}...", &config.app_id[..8])
+            Some(mut config) => {
+                config.app_secret = crate::secret::decrypt_secret(&config.app_secret).map_err(anyhow::Error::msg)?;
+                config.encrypt_key = crate::secret::decrypt_optional(config.encrypt_key).map_err(anyhow::Error::msg)?;
+                config.verification_token =
+                    crate::secret::decrypt_optional(config.verification_token).map_err(anyhow::Error::msg)?;
+                // 只有占位行（未设置后端、也没有飞书凭证）时，同样按已配置的钉钉/企业微信表决定后端
+                let unset = config.backend.as_deref().unwrap_or("").trim().is_empty();
+                if unset && (config.app_id.is_empty() || config.app_secret.is_empty()) {
+                    if let Some(fallback) = Self::backend_without_feishu_row(conn)? {
+                        config.backend = fallback.backend;
+                    }
+                }
+                if config.is_dingtalk_backend() {
+                    config.dingtalk = Self::load_dingtalk(conn)?;
+                }
+                if config.is_wework_backend() {
+                    config.wework = Self::load_wework(conn)?;
+                }
+                let masked_id = if config.app_id.chars().count() > 8 {
+                    format!("{}...", crate::text::truncate_chars(&config.app_id, 8))
                 } else {
                     config.app_id.clone()
                 };
@@ -121,17 +282,498 @@ impl Config {
                 );
                 config
             }
-            None => {
-                error!("未在 SQLite 中找到飞书配置，请先在桌面应用中配置");
-                anyhow::bail!("Feishu config not found in SQLite");
-            }
+            // 没有飞书配置行时，只配置了钉钉或企业微信也可以使用，后端由已配置的表决定
+            None => match Self::backend_without_feishu_row(conn)? {
+                Some(config) => config,
+                None => {
+                    error!("未在 SQLite 中找到飞书、钉钉或企业微信配置，请先在桌面应用中配置");
+                    return Err(ConfigMissing("Feishu config not found in SQLite".to_string()).into());
+                }
+            },
         };
 
-        if config.app_id.is_empty() || config.app_secret.is_empty() {
+        if config.is_dingtalk_backend() {
+            match &config.dingtalk {
+                Some(dingtalk) if !dingtalk.app_key.is_empty() && !dingtalk.app_secret.is_empty() => {}
+                _ => {
+                    error!("通知后端为钉钉，但 SQLite 中的钉钉配置缺失或不完整");
+                    return Err(ConfigMissing(
+                        "DingTalk AppKey and AppSecret are required when backend is dingtalk".to_string(),
+                    )
+                    .into());
+                }
+            }
+        } else if config.is_wework_backend() {
+            match &config.wework {
+                Some(wework) if !wework.corp_id.is_empty() && !wework.corp_secret.is_empty() => {}
+                _ => {
+                    error!("通知后端为企业微信，但 SQLite 中的企业微信配置缺失或不完整");
+                    return Err(ConfigMissing(
+                        "WeWork CorpID and Secret are required when backend is wework".to_string(),
+                    )
+                    .into());
+                }
+            }
+        } else if config.app_id.is_empty() || config.app_secret.is_empty() {
             error!("SQLite 中的飞书配置不完整，缺少 app_id 或 app_secret");
-            anyhow::bail!("App ID and App Secret are required in configuration");
+            return Err(ConfigMissing("App ID and App Secret are required in configuration".to_string()).into());
         }
 
         Ok(config)
     }
+
+    /// 没有 app_config_feishu 行时按 app_config_dingtalk、app_config_wework 的顺序取第一个完整的配置
+    fn backend_without_feishu_row(conn: &Connection) -> Result<Option<Self>, anyhow::Error> {
+        if let Some(dingtalk) = Self::load_dingtalk(conn)?
+            .filter(|d| !d.app_key.is_empty() && !d.app_secret.is_empty())
+        {
+            return Ok(Some(Config {
+                backend: Some("dingtalk".to_string()),
+                dingtalk: Some(dingtalk),
+                ..Config::default()
+            }));
+        }
+        if let Some(wework) = Self::load_wework(conn)?
+            .filter(|w| !w.corp_id.is_empty() && !w.corp_secret.is_empty())
+        {
+            return Ok(Some(Config {
+                backend: Some("wework".to_string()),
+                wework: Some(wework),
+                ..Config::default()
+            }));
+        }
+        Ok(None)
+    }
+
+    fn load_dingtalk(conn: &Connection) -> Result<Option<DingTalkConfig>, anyhow::Error> {
+        let dingtalk = conn
+            .query_row(
+                "SELECT app_id, app_secret, chat_id FROM app_config_dingtalk WHERE id = 1",
+                [],
+                |row| {
+                    Ok(DingTalkConfig {
+                        app_key: row.get(0)?,
+                        app_secret: row.get(1)?,
+                        chat_id: row.get(2)?,
+                    })
+                },
+            )
+            .optional()?;
+        dingtalk
+            .map(|d| Ok::<_, String>(DingTalkConfig { app_secret: crate::secret::decrypt_secret(&d.app_secret)?, ..d }))
+            .transpose()
+            .map_err(anyhow::Error::msg)
+    }
+
+    /// `sparky connect` 需要建立长连接的全部飞书应用：
+    /// 主配置（id = 1）中的应用 + app_config_feishu_apps 中启用的其他应用，按 app_id 去重
+    pub fn connect_apps(&self) -> Result<Vec<ConnectApp>, anyhow::Error> {
+        let mut apps = Vec::new();
+        if !self.app_id.is_empty() && !self.app_secret.is_empty() {
+            apps.push(ConnectApp {
+                app_id: self.app_id.clone(),
+                app_secret: self.app_secret.clone(),
+                encrypt_key: self.encrypt_key.clone(),
+                verification_token: self.verification_token.clone(),
+            });
+        }
+        let conn = open_config_db()?;
+        for app in load_extra_apps(&conn)? {
+            if !apps.iter().any(|a| a.app_id == app.app_id) {
+                apps.push(app);
+            }
+        }
+        Ok(apps)
+    }
+
+    fn load_wework(conn: &Connection) -> Result<Option<WeWorkConfig>, anyhow::Error> {
+        let wework = conn
+            .query_row(
+                "SELECT app_id, app_secret, agent_id, chat_id, to_user FROM app_config_wework WHERE id = 1",
+                [],
+                |row| {
+                    Ok(WeWorkConfig {
+                        corp_id: row.get(0)?,
+                        corp_secret: row.get(1)?,
+                        agent_id: row.get(2)?,
+                        chat_id: row.get(3)?,
+                        to_user: row.get(4)?,
+                    })
+                },
+            )
+            .optional()?;
+        wework
+            .map(|w| Ok::<_, String>(WeWorkConfig { corp_secret: crate::secret::decrypt_secret(&w.corp_secret)?, ..w }))
+            .transpose()
+            .map_err(anyhow::Error::msg)
+    }
+}
+
+/// `sparky connect` 建立长连接的一个飞书应用
+#[derive(Debug, Clone)]
+pub struct ConnectApp {
+    pub app_id: String,
+    pub app_secret: String,
+    pub encrypt_key: Option<String>,
+    pub verification_token: Option<String>,
+}
+
+/// 读取 app_config_feishu_apps 中启用的额外飞书应用（密钥按需解密）；
+/// 应用通过 `sparky config apps` 或桌面应用管理
+fn load_extra_apps(conn: &Connection) -> Result<Vec<ConnectApp>, anyhow::Error> {
+    let mut stmt = conn.prepare(
+        "SELECT app_id, app_secret, encrypt_key, verification_token FROM app_config_feishu_apps
+         WHERE enabled = 1 AND app_id != '' AND app_secret != '' ORDER BY id",
+    )?;
+    let rows = stmt.query_map([], |row| {
+        Ok(ConnectApp {
+            app_id: row.get(0)?,
+            app_secret: row.get(1)?,
+            encrypt_key: row.get(2)?,
+            verification_token: row.get(3)?,
+        })
+    })?;
+
+    let mut apps = Vec::new();
+    for row in rows {
+        let mut app = row?;
+        app.app_secret = crate::secret::decrypt_secret(&app.app_secret).map_err(anyhow::Error::msg)?;
+        app.encrypt_key = crate::secret::decrypt_optional(app.encrypt_key).map_err(anyhow::Error::msg)?;
+        app.verification_token =
+            crate::secret::decrypt_optional(app.verification_token).map_err(anyhow::Error::msg)?;
+        apps.push(app);
+    }
+    Ok(apps)
+}
+
+/// `sparky config` 可读写的 app_config_feishu 列
+pub const CONFIG_KEYS: &[&str] = &[
+    "app_id",
+    "app_secret",
+    "encrypt_key",
+    "verification_token",
+    "chat_id",
+    "open_id",
+    "hook_events_filter",
+    "project_path",
+    "proxy_url",
+    "urgent_events",
+    "message_style",
+    "max_notifications_per_session_per_minute",
+    "backend",
+    "max_message_len",
+    "max_db_content_len",
+    "max_response_chars",
+    "max_transcript_chars",
+    "coalesce_window_secs",
+    "prompt_triggers",
+    "min_notification_chars",
+    "receive_id_type",
+];
+
+/// 加密保存、输出时需要掩码的列
+const SECRET_KEYS: &[&str] = &["app_secret", "encrypt_key", "verification_token"];
+
+const INTEGER_KEYS: &[&str] = &[
+    "max_notifications_per_session_per_minute",
+    "max_message_len",
+    "max_db_content_len",
+    "max_response_chars",
+    "max_transcript_chars",
+    "coalesce_window_secs",
+    "min_notification_chars",
+];
+
+fn validate_key(key: &str) -> Result<(), anyhow::Error> {
+    if CONFIG_KEYS.contains(&key) {
+        Ok(())
+    } else {
+        anyhow::bail!("Unknown config key '{}'. Valid keys: {}", key, CONFIG_KEYS.join(", "))
+    }
+}
+
+/// 密钥只显示前 4 个字符
+pub fn mask_secret(value: &str) -> String {
+    if value.chars().count() <= 4 {
+        "****".to_string()
+    } else {
+        format!("{}****", crate::text::truncate_chars(value, 4))
+    }
+}
+
+/// 项目级通知目标（projects.chat_id / open_id），桌面应用中按项目设置
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ProjectTarget {
+    pub chat_id: Option<String>,
+    pub open_id: Option<String>,
+}
+
+/// 按 hook 的 cwd 匹配项目：cwd 等于项目路径或位于其子目录，多个匹配时取路径最长的项目
+pub fn project_target(conn: &Connection, cwd: &str) -> Result<Option<ProjectTarget>, anyhow::Error> {
+    let mut stmt = conn.prepare(
+        "SELECT path, chat_id, open_id FROM projects
+         WHERE COALESCE(chat_id, '') != '' OR COALESCE(open_id, '') != ''",
+    )?;
+    let rows = stmt.query_map([], |row| {
+        Ok((
+            row.get::<_, String>(0)?,
+            row.get::<_, Option<String>>(1)?,
+            row.get::<_, Option<String>>(2)?,
+        ))
+    })?;
+    let cwd = std::path::Path::new(cwd);
+    let mut best: Option<(usize, ProjectTarget)> = None;
+    for row in rows {
+        let (path, chat_id, open_id) = row?;
+        if !cwd.starts_with(&path) || best.as_ref().is_some_and(|(len, _)| *len >= path.len()) {
+            continue;
+        }
+        let target = ProjectTarget {
+            chat_id: chat_id.filter(|id| !id.is_empty()),
+            open_id: open_id.filter(|id| !id.is_empty()),
+        };
+        best = Some((path.len(), target));
+    }
+    Ok(best.map(|(_, target)| target))
+}
+
+/// 打开配置数据库；表不存在时按桌面应用的结构创建（无界面环境下首次运行 CLI）
+pub fn open_config_db() -> Result<Connection, anyhow::Error> {
+    let conn = crate::migrations::open_connection(crate::migrations::db_path()?)?;
+    crate::migrations::run(&conn)?;
+    Ok(conn)
+}
+
+/// 读取单个配置项（密钥已解密并掩码）；未设置时返回 None
+pub fn get_value(conn: &Connection, key: &str) -> Result<Option<String>, anyhow::Error> {
+    validate_key(key)?;
+    let value = conn
+        .query_row(&format!("SELECT {} FROM app_config_feishu WHERE id = 1", key), [], |row| {
+            Ok(match row.get_ref(0)? {
+                rusqlite::types::ValueRef::Null => None,
+                rusqlite::types::ValueRef::Integer(i) => Some(i.to_string()),
+                rusqlite::types::ValueRef::Text(t) => Some(String::from_utf8_lossy(t).to_string()),
+                other => Some(format!("{:?}", other)),
+            })
+        })
+        .optional()?
+        .flatten();
+    match value {
+        Some(v) if SECRET_KEYS.contains(&key) => {
+            let plain = crate::secret::decrypt_secret(&v).map_err(anyhow::Error::msg)?;
+            Ok(Some(mask_secret(&plain)))
+        }
+        other => Ok(other),
+    }
+}
+
+/// 列出全部配置项
+pub fn list_values(conn: &Connection) -> Result<Vec<(&'static str, Option<String>)>, anyhow::Error> {
+    CONFIG_KEYS
+        .iter()
+        .map(|key| Ok((*key, get_value(conn, key)?)))
+        .collect()
+}
+
+/// 写入单个配置项；空字符串表示清除（app_id / app_secret 除外）
+pub fn set_value(conn: &Connection, key: &str, value: &str) -> Result<(), anyhow::Error> {
+    validate_key(key)?;
+    let value = value.trim();
+    let now = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)?
+        .as_secs() as i64;
+
+    let stored: Option<rusqlite::types::Value> = if value.is_empty() {
+        if key == "app_id" || key == "app_secret" {
+            anyhow::bail!("'{}' cannot be empty", key);
+        }
+        None
+    } else if INTEGER_KEYS.contains(&key) {
+        let n: u32 = value
+            .parse()
+            .map_err(|_| anyhow::anyhow!("'{}' must be a non-negative integer, got '{}'", key, value))?;
+        Some(rusqlite::types::Value::Integer(n as i64))
+    } else {
+        match key {
+            "backend" if !matches!(value.to_lowercase().as_str(), "feishu" | "dingtalk" | "wework") => {
+                anyhow::bail!("Invalid backend '{}', expected feishu/dingtalk/wework", value)
+            }
+            "message_style" if !matches!(value.to_lowercase().as_str(), "compact" | "detailed") => {
+                anyhow::bail!("Invalid message_style '{}', expected compact/detailed", value)
+            }
+            "receive_id_type" => Some(rusqlite::types::Value::Text(
+                crate::feishu::parse_receive_id_type(value)?.to_string(),
+            )),
+            "backend" | "message_style" => Some(rusqlite::types::Value::Text(value.to_lowercase())),
+            _ if SECRET_KEYS.contains(&key) => Some(rusqlite::types::Value::Text(
+                crate::secret::encrypt_secret(value).map_err(anyhow::Error::msg)?,
+            )),
+            _ => Some(rusqlite::types::Value::Text(value.to_string())),
+        }
+    };
+
+    conn.execute(
+        "INSERT INTO app_config_feishu (id, app_id, app_secret, updated_at) VALUES (1, '', '', ?1)
+         ON CONFLICT(id) DO NOTHING",
+        rusqlite::params![now],
+    )?;
+    conn.execute(
+        &format!("UPDATE app_config_feishu SET {} = ?1, updated_at = ?2 WHERE id = 1", key),
+        rusqlite::params![stored, now],
+    )?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn test_conn() -> Connection {
+        let conn = Connection::open_in_memory().unwrap();
+        conn.execute(
+            "CREATE TABLE app_config_feishu (
+                id INTEGER PRIMARY KEY CHECK (id = 1),
+                app_id TEXT NOT NULL,
+                app_secret TEXT NOT NULL,
+                chat_id TEXT,
+                backend TEXT,
+                max_message_len INTEGER,
+                updated_at INTEGER NOT NULL
+            )",
+            [],
+        )
+        .unwrap();
+        conn
+    }
+
+    #[test]
+    fn test_set_and_get_value() {
+        let conn = test_conn();
+        assert_eq!(get_value(&conn, "chat_id").unwrap(), None);
+
+        set_value(&conn, "chat_id", "oc_123").unwrap();
+        set_value(&conn, "max_message_len", "9000").unwrap();
+        set_value(&conn, "backend", "DingTalk").unwrap();
+        assert_eq!(get_value(&conn, "chat_id").unwrap().as_deref(), Some("oc_123"));
+        assert_eq!(get_value(&conn, "max_message_len").unwrap().as_deref(), Some("9000"));
+        assert_eq!(get_value(&conn, "backend").unwrap().as_deref(), Some("dingtalk"));
+
+        set_value(&conn, "chat_id", "").unwrap();
+        assert_eq!(get_value(&conn, "chat_id").unwrap(), None);
+    }
+
+    #[test]
+    fn test_set_value_rejects_invalid_input() {
+        let conn = test_conn();
+        assert!(set_value(&conn, "no_such_key", "x").unwrap_err().to_string().contains("Unknown config key"));
+        assert!(get_value(&conn, "no_such_key").is_err());
+        assert!(set_value(&conn, "max_message_len", "abc").is_err());
+        assert!(set_value(&conn, "backend", "slack").is_err());
+        assert!(set_value(&conn, "receive_id_type", "phone").is_err());
+        assert!(set_value(&conn, "app_id", "").is_err());
+    }
+
+    #[test]
+    fn test_load_reports_missing_config() {
+        let conn = Connection::open_in_memory().unwrap();
+        crate::migrations::run(&conn).unwrap();
+        assert!(ConfigMissing::is(&Config::load_from(&conn).unwrap_err()));
+
+        conn.execute(
+            "INSERT INTO app_config_feishu (id, app_id, app_secret, updated_at) VALUES (1, 'cli_a', '', 0)",
+            [],
+        )
+        .unwrap();
+        assert!(ConfigMissing::is(&Config::load_from(&conn).unwrap_err()));
+
+        // 数据库本身出错时不应被当成“未配置”
+        let broken = Connection::open_in_memory().unwrap();
+        assert!(!ConfigMissing::is(&Config::load_from(&broken).unwrap_err()));
+    }
+
+    #[test]
+    fn test_load_backend_table_without_feishu_row() {
+        let conn = Connection::open_in_memory().unwrap();
+        crate::migrations::run(&conn).unwrap();
+        conn.execute(
+            "INSERT INTO app_config_wework (id, app_id, app_secret, agent_id, updated_at) VALUES (1, 'ww_corp', 'ws', '1000002', 0)",
+            [],
+        )
+        .unwrap();
+        let config = Config::load_from(&conn).unwrap();
+        assert!(config.is_wework_backend());
+        assert_eq!(config.wework.unwrap().corp_id, "ww_corp");
+
+        conn.execute(
+            "INSERT INTO app_config_dingtalk (id, app_id, app_secret, updated_at) VALUES (1, 'ding_key', 'ds', 0)",
+            [],
+        )
+        .unwrap();
+        let config = Config::load_from(&conn).unwrap();
+        assert!(config.is_dingtalk_backend());
+        assert_eq!(config.dingtalk.unwrap().app_key, "ding_key");
+        assert!(config.app_id.is_empty());
+
+        // 设置其他配置项会插入空的飞书占位行，不应让钉钉配置失效
+        set_value(&conn, "message_style", "compact").unwrap();
+        let config = Config::load_from(&conn).unwrap();
+        assert!(config.is_dingtalk_backend() && config.is_compact_style());
+        set_value(&conn, "backend", "wework").unwrap();
+        assert!(Config::load_from(&conn).unwrap().is_wework_backend());
+    }
+
+    #[test]
+    fn test_min_notification_chars() {
+        let conn = Connection::open_in_memory().unwrap();
+        crate::migrations::run(&conn).unwrap();
+        set_value(&conn, "app_id", "cli_a").unwrap();
+        set_value(&conn, "app_secret", "secret").unwrap();
+        assert_eq!(Config::load_from(&conn).unwrap().min_notification_chars(), DEFAULT_MIN_NOTIFICATION_CHARS);
+        set_value(&conn, "min_notification_chars", "20").unwrap();
+        assert_eq!(Config::load_from(&conn).unwrap().min_notification_chars(), 20);
+        assert!(set_value(&conn, "min_notification_chars", "-1").is_err());
+    }
+
+    #[test]
+    fn test_load_extra_apps_skips_disabled() {
+        let conn = Connection::open_in_memory().unwrap();
+        crate::migrations::run(&conn).unwrap();
+        assert!(load_extra_apps(&conn).unwrap().is_empty());
+        conn.execute(
+            "INSERT INTO app_config_feishu_apps (app_id, app_secret, enabled) VALUES
+             ('cli_a', 'sa', 1), ('cli_b', 'sb', 0), ('cli_c', '', 1)",
+            [],
+        )
+        .unwrap();
+        let apps = load_extra_apps(&conn).unwrap();
+        assert_eq!(apps.len(), 1);
+        assert_eq!(apps[0].app_id, "cli_a");
+        assert_eq!(apps[0].app_secret, "sa");
+    }
+
+    #[test]
+    fn test_project_target_matches_longest_path() {
+        let conn = Connection::open_in_memory().unwrap();
+        crate::migrations::run(&conn).unwrap();
+        conn.execute_batch(
+            "INSERT INTO projects (name, path, created_at, updated_at, chat_id, open_id) VALUES
+             ('mono', '/work/mono', 0, 0, 'oc_mono', NULL),
+             ('api', '/work/mono/api', 0, 0, '', 'ou_api'),
+             ('plain', '/work/plain', 0, 0, NULL, NULL);",
+        )
+        .unwrap();
+
+        let api = project_target(&conn, "/work/mono/api/src").unwrap().unwrap();
+        assert_eq!(api, ProjectTarget { chat_id: None, open_id: Some("ou_api".to_string()) });
+        let mono = project_target(&conn, "/work/mono").unwrap().unwrap();
+        assert_eq!(mono.chat_id.as_deref(), Some("oc_mono"));
+        assert_eq!(project_target(&conn, "/work/monorepo").unwrap(), None);
+        assert_eq!(project_target(&conn, "/work/plain").unwrap(), None);
+    }
+
+    #[test]
+    fn test_mask_secret() {
+        assert_eq!(mask_secret("abc"), "****");
+        assert_eq!(mask_secret("abcdefgh"), "abcd****");
+    }
 }
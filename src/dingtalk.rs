@@ -0,0 +1,132 @@
+use reqwest::Client;
+use tracing::error;
+
+use crate::feishu::{build_http_client, strip_markdown};
+use crate::token_cache::TokenCache;
+
+/// 响应中缺少 expireIn 字段时使用的默认有效期（钉钉默认 2 小时）
+const DEFAULT_TOKEN_TTL_SECS: u64 = 7200;
+/// 钉钉 markdown 消息标题最大长度（会话列表中展示）
+const MAX_TITLE_CHARS: usize = 32;
+
+/// 钉钉企业内部应用机器人客户端：通过 openConversationId 向群聊发送 markdown 消息
+#[derive(Clone)]
+pub struct DingTalkClient {
+    client: Client,
+    app_key: String,
+    app_secret: String,
+    token_cache: TokenCache,
+}
+
+impl DingTalkClient {
+    pub fn new(app_key: String, app_secret: String, proxy_url: Option<&str>) -> Self {
+        let client = build_http_client(proxy_url).unwrap_or_else(|e| {
+            error!("[dingtalk] invalid proxy config, falling back to direct connection: {}", e);
+            Client::new()
+        });
+        let token_cache = TokenCache::new("dingtalk", &app_key);
+        DingTalkClient {
+            client,
+            app_key,
+            app_secret,
+            token_cache,
+        }
+    }
+
+    /// 获取应用 accessToken，优先使用缓存
+    pub async fn get_access_token(&self) -> Result<String, anyhow::Error> {
+        self.token_cache.get_or_fetch(|| self.request_access_token()).await
+    }
+
+    /// 请求新的 access token，返回 token 与有效期（秒）
+    async fn request_access_token(&self) -> Result<(String, u64), anyhow::Error> {
+        let token_url = "https://api.dingtalk.com/v1.0/oauth2/accessToken";
+        let response = self
+            .client
+            .post(token_url)
+            .json(&serde_json::json!({
+                "appKey": self.app_key,
+                "appSecret": self.app_secret
+            }))
+            .send()
+            .await?;
+
+        let status = response.status();
+        let result: serde_json::Value = response.json().await?;
+        let token = match result["accessToken"].as_str() {
+            Some(token) => token.to_string(),
+            None => {
+                let msg = result["message"].as_str().unwrap_or("Unknown error");
+                error!("[dingtalk:token] FAILED: status={}, body={}", status, result);
+                anyhow::bail!("Failed to get DingTalk access token: {}", msg);
+            }
+        };
+        let ttl_secs = result["expireIn"].as_u64().unwrap_or(DEFAULT_TOKEN_TTL_SECS);
+        tracing::info!("[dingtalk:token] obtained token (len={}, expire={}s)", token.len(), ttl_secs);
+        Ok((token, ttl_secs))
+    }
+
+    /// 以 markdown 消息发送到群聊，返回 processQueryKey
+    /// 钉钉机器人消息不支持飞书卡片按钮，权限请求依赖内容中的配对码回复
+    pub async fn send_message(&self, open_conversation_id: &str, content: &str) -> Result<String, anyhow::Error> {
+        let token = self.get_access_token().await?;
+        let msg_param = serde_json::json!({
+            "title": markdown_title(content),
+            "text": content
+        });
+        let body = serde_json::json!({
+            "robotCode": self.app_key,
+            "openConversationId": open_conversation_id,
+            "msgKey": "sampleMarkdown",
+            "msgParam": msg_param.to_string()
+        });
+
+        tracing::info!(
+            "[dingtalk:send] sending markdown: open_conversation_id={}, content_len={}",
+            open_conversation_id,
+            content.len()
+        );
+        let response = self
+            .client
+            .post("https://api.dingtalk.com/v1.0/robot/groupMessages/send")
+            .header("x-acs-dingtalk-access-token", token)
+            .json(&body)
+            .send()
+            .await?;
+
+        let status = response.status();
+        let result: serde_json::Value = response.json().await?;
+        match result["processQueryKey"].as_str() {
+            Some(key) => {
+                tracing::info!("[dingtalk:send] message sent successfully: processQueryKey={}", key);
+                Ok(key.to_string())
+            }
+            None => {
+                let msg = result["message"].as_str().unwrap_or("Unknown error");
+                error!("[dingtalk:send] FAILED: status={}, body={}", status, result);
+                anyhow::bail!("Failed to send DingTalk message: {}", msg)
+            }
+        }
+    }
+}
+
+/// 取内容第一行非空文本（去掉 markdown 标记）作为消息标题
+fn markdown_title(content: &str) -> String {
+    let first_line = content
+        .lines()
+        .map(|line| line.trim())
+        .find(|line| !line.is_empty())
+        .unwrap_or("Sparky");
+    strip_markdown(first_line).chars().take(MAX_TITLE_CHARS).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_markdown_title_uses_first_line() {
+        assert_eq!(markdown_title("\n🧭 **权限确认**\n\n内容"), "🧭 权限确认");
+        assert_eq!(markdown_title(""), "Sparky");
+    }
+}
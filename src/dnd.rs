@@ -0,0 +1,208 @@
+// Do-not-disturb schedule, shared by the CLI and the desktop app
+// (src-tauri includes this file via #[path]; the config is a JSON blob in db_meta)
+
+use chrono::{DateTime, FixedOffset, Local, NaiveTime, Timelike, Utc};
+use rusqlite::{params, Connection, OptionalExtension};
+use serde::{Deserialize, Serialize};
+
+const DND_CONFIG_KEY: &str = "dnd_config";
+
+/// 免打扰时段；start > end 时表示跨午夜（如 22:00 - 08:00）
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct DndConfig {
+    #[serde(default)]
+    pub enabled: bool,
+    /// HH:MM
+    pub start: String,
+    /// HH:MM，不包含该时刻
+    pub end: String,
+    /// "local"、"UTC" 或 "+08:00" 形式的 UTC 偏移
+    #[serde(default = "default_timezone")]
+    pub timezone: String,
+    /// 需要静音的事件名，为空时静音所有事件
+    #[serde(default)]
+    pub events: Vec<String>,
+    /// PermissionRequest 会阻塞 Claude Code，默认在免打扰时段内仍然通知
+    #[serde(default = "default_true")]
+    pub always_notify_permission_requests: bool,
+}
+
+fn default_timezone() -> String {
+    "local".to_string()
+}
+
+fn default_true() -> bool {
+    true
+}
+
+impl Default for DndConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            start: "22:00".to_string(),
+            end: "08:00".to_string(),
+            timezone: default_timezone(),
+            events: Vec::new(),
+            always_notify_permission_requests: true,
+        }
+    }
+}
+
+impl DndConfig {
+    #[allow(dead_code)] // CLI 只读取配置
+    pub fn validate(&self) -> Result<(), String> {
+        parse_time(&self.start)?;
+        parse_time(&self.end)?;
+        parse_timezone(&self.timezone)?;
+        Ok(())
+    }
+
+    /// event_name 在 now 时刻是否应被静音；配置非法时不静音
+    #[allow(dead_code)] // 桌面应用只读写配置，由 CLI 的 run_hook 判断
+    pub fn is_muted(&self, event_name: &str, now: DateTime<Utc>) -> bool {
+        if !self.enabled {
+            return false;
+        }
+        if event_name == "PermissionRequest" && self.always_notify_permission_requests {
+            return false;
+        }
+        if !self.events.is_empty() && !self.events.iter().any(|e| e == event_name) {
+            return false;
+        }
+        let (Ok(start), Ok(end), Ok(offset)) =
+            (parse_time(&self.start), parse_time(&self.end), parse_timezone(&self.timezone))
+        else {
+            return false;
+        };
+        let offset = offset.unwrap_or_else(|| *Local::now().offset());
+        let minute = minute_of_day(now.with_timezone(&offset).time());
+        if start <= end {
+            start <= minute && minute < end
+        } else {
+            minute >= start || minute < end
+        }
+    }
+}
+
+fn minute_of_day(time: NaiveTime) -> u32 {
+    time.hour() * 60 + time.minute()
+}
+
+fn parse_time(value: &str) -> Result<u32, String> {
+    NaiveTime::parse_from_str(value.trim(), "%H:%M")
+        .map(minute_of_day)
+        .map_err(|_| format!("Invalid time '{}', expected HH:MM", value))
+}
+
+/// None 表示使用本机时区
+fn parse_timezone(value: &str) -> Result<Option<FixedOffset>, String> {
+    let value = value.trim();
+    if value.is_empty() || value.eq_ignore_ascii_case("local") {
+        return Ok(None);
+    }
+    if value.eq_ignore_ascii_case("utc") || value == "Z" {
+        return Ok(FixedOffset::east_opt(0));
+    }
+    let invalid = || format!("Invalid timezone '{}', expected local, UTC or +HH:MM", value);
+    let (sign, rest) = match value.as_bytes().first() {
+        Some(b'+') => (1, &value[1..]),
+        Some(b'-') => (-1, &value[1..]),
+        _ => return Err(invalid()),
+    };
+    let (hours, minutes) = rest.split_once(':').ok_or_else(invalid)?;
+    let hours: i32 = hours.parse().map_err(|_| invalid())?;
+    let minutes: i32 = minutes.parse().map_err(|_| invalid())?;
+    if hours > 14 || minutes > 59 {
+        return Err(invalid());
+    }
+    FixedOffset::east_opt(sign * (hours * 3600 + minutes * 60)).map(Some).ok_or_else(invalid)
+}
+
+/// 读取免打扰配置；未保存时返回默认值（未启用）
+pub fn load(conn: &Connection) -> rusqlite::Result<DndConfig> {
+    let saved: Option<String> = conn
+        .query_row("SELECT value FROM db_meta WHERE key = ?1", params![DND_CONFIG_KEY], |row| row.get(0))
+        .optional()?;
+    Ok(saved
+        .and_then(|json| serde_json::from_str(&json).ok())
+        .unwrap_or_default())
+}
+
+#[allow(dead_code)] // CLI 只读取配置
+pub fn save(conn: &Connection, config: &DndConfig) -> Result<(), String> {
+    config.validate()?;
+    let json = serde_json::to_string(config).map_err(|e| e.to_string())?;
+    conn.execute(
+        "INSERT INTO db_meta (key, value) VALUES (?1, ?2)
+         ON CONFLICT(key) DO UPDATE SET value = excluded.value",
+        params![DND_CONFIG_KEY, json],
+    )
+    .map_err(|e| e.to_string())?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    fn at(hour: u32, minute: u32) -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(2024, 1, 1, hour, minute, 0).unwrap()
+    }
+
+    fn config(start: &str, end: &str) -> DndConfig {
+        DndConfig {
+            enabled: true,
+            start: start.to_string(),
+            end: end.to_string(),
+            timezone: "UTC".to_string(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_overnight_window() {
+        let dnd = config("22:00", "08:00");
+        assert!(dnd.is_muted("Stop", at(23, 30)));
+        assert!(dnd.is_muted("Stop", at(7, 59)));
+        assert!(!dnd.is_muted("Stop", at(8, 0)));
+        assert!(!dnd.is_muted("Stop", at(12, 0)));
+    }
+
+    #[test]
+    fn test_same_day_window_and_timezone() {
+        let mut dnd = config("12:00", "13:00");
+        assert!(dnd.is_muted("Stop", at(12, 30)));
+        dnd.timezone = "+08:00".to_string();
+        assert!(!dnd.is_muted("Stop", at(12, 30)));
+        assert!(dnd.is_muted("Stop", at(4, 30)));
+    }
+
+    #[test]
+    fn test_permission_override_and_event_filter() {
+        let mut dnd = config("00:00", "23:59");
+        assert!(!dnd.is_muted("PermissionRequest", at(1, 0)));
+        dnd.always_notify_permission_requests = false;
+        assert!(dnd.is_muted("PermissionRequest", at(1, 0)));
+        dnd.events = vec!["Stop".to_string()];
+        assert!(dnd.is_muted("Stop", at(1, 0)));
+        assert!(!dnd.is_muted("Notification", at(1, 0)));
+        dnd.enabled = false;
+        assert!(!dnd.is_muted("Stop", at(1, 0)));
+    }
+
+    #[test]
+    fn test_validate_and_persist() {
+        assert!(config("25:00", "08:00").validate().is_err());
+        let mut bad_tz = config("22:00", "08:00");
+        bad_tz.timezone = "Asia/Shanghai".to_string();
+        assert!(bad_tz.validate().is_err());
+
+        let conn = Connection::open_in_memory().unwrap();
+        crate::migrations::run(&conn).unwrap();
+        assert_eq!(load(&conn).unwrap(), DndConfig::default());
+        let dnd = config("22:00", "08:00");
+        save(&conn, &dnd).unwrap();
+        assert_eq!(load(&conn).unwrap(), dnd);
+    }
+}
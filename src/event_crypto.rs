@@ -0,0 +1,84 @@
+use base64::Engine;
+use cbc::cipher::{block_padding::Pkcs7, BlockDecryptMut, KeyIvInit};
+
+type Aes256CbcDec = cbc::Decryptor<aes::Aes256>;
+
+/// 解密飞书加密事件：key = SHA256(encrypt_key)，密文为 base64(IV(16 字节) ‖ AES-256-CBC 密文)，PKCS7 填充
+pub fn decrypt_event(encrypt_key: &str, encrypted: &str) -> Result<String, String> {
+    let data = base64::engine::general_purpose::STANDARD
+        .decode(encrypted.trim())
+        .map_err(|e| format!("invalid base64: {}", e))?;
+    if data.len() <= 16 || (data.len() - 16) % 16 != 0 {
+        return Err(format!("invalid ciphertext length {}", data.len()));
+    }
+    let key = ring::digest::digest(&ring::digest::SHA256, encrypt_key.as_bytes());
+    let (iv, ciphertext) = data.split_at(16);
+    let plain = Aes256CbcDec::new_from_slices(key.as_ref(), iv)
+        .map_err(|e| format!("decrypt failed: {}", e))?
+        .decrypt_padded_vec_mut::<Pkcs7>(ciphertext)
+        .map_err(|e| format!("decrypt failed: {}", e))?;
+    String::from_utf8(plain).map_err(|e| format!("decrypted payload is not UTF-8: {}", e))
+}
+
+/// 若 payload 为 {"encrypt": "..."} 信封则解密，否则原样返回
+/// 未配置 encrypt_key 却收到加密事件时返回错误
+pub fn open_envelope(encrypt_key: Option<&str>, payload: &str) -> Result<String, String> {
+    let encrypted = serde_json::from_str::<serde_json::Value>(payload)
+        .ok()
+        .and_then(|v| v.get("encrypt").and_then(|e| e.as_str()).map(|e| e.to_string()));
+    match (encrypted, encrypt_key.filter(|k| !k.is_empty())) {
+        (Some(encrypted), Some(key)) => decrypt_event(key, &encrypted),
+        (Some(_), None) => Err("received encrypted event but encrypt_key is not configured".to_string()),
+        (None, _) => Ok(payload.to_string()),
+    }
+}
+
+/// 校验事件 header.token；未配置 verification_token 时不校验
+pub fn verify_token(expected: Option<&str>, actual: &str) -> bool {
+    match expected.filter(|t| !t.is_empty()) {
+        Some(expected) => constant_time_eq(expected.as_bytes(), actual.as_bytes()),
+        None => true,
+    }
+}
+
+/// 常量时间比较，耗时不随第一个不同字节的位置变化（长度不同时直接返回 false）
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0u8, |acc, (x, y)| acc | (x ^ y)) == 0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // 飞书开放平台文档中的示例
+    const DOC_KEY: &str = "test key";
+    const DOC_ENCRYPTED: &str = "P37w+VZImNgPEO1RBhJ6RtKl7n6zymIbEG1pReEzghk=";
+
+    #[test]
+    fn test_decrypt_documented_example() {
+        assert_eq!(decrypt_event(DOC_KEY, DOC_ENCRYPTED).unwrap(), "hello world");
+    }
+
+    #[test]
+    fn test_decrypt_rejects_wrong_key_and_garbage() {
+        assert!(decrypt_event("wrong key", DOC_ENCRYPTED).is_err());
+        assert!(decrypt_event(DOC_KEY, "not base64!").is_err());
+        assert!(decrypt_event(DOC_KEY, "AAAA").is_err());
+    }
+
+    #[test]
+    fn test_open_envelope() {
+        let envelope = format!(r#"{{"encrypt":"{}"}}"#, DOC_ENCRYPTED);
+        assert_eq!(open_envelope(Some(DOC_KEY), &envelope).unwrap(), "hello world");
+        assert!(open_envelope(None, &envelope).is_err());
+        assert_eq!(open_envelope(Some(DOC_KEY), r#"{"schema":"2.0"}"#).unwrap(), r#"{"schema":"2.0"}"#);
+    }
+
+    #[test]
+    fn test_verify_token() {
+        assert!(verify_token(None, "anything"));
+        assert!(verify_token(Some("abc"), "abc"));
+        assert!(!verify_token(Some("abc"), "abd"));
+        assert!(!verify_token(Some("abc"), "ab"));
+    }
+}
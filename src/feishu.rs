@@ -1,20 +1,48 @@
 use reqwest::Client;
 use serde::{Deserialize, Serialize};
 use rusqlite::{params, Connection, OptionalExtension};
-use std::fs;
 use tracing::error;
 use rand::Rng;
+use std::time::Duration;
+
+use crate::token_cache::TokenCache;
+
+/// 响应中缺少 expire 字段时使用的默认有效期（飞书默认约 2 小时）
+const DEFAULT_TOKEN_TTL_SECS: u64 = 7200;
+/// 发送消息的最大尝试次数（含首次）
+const MAX_SEND_ATTEMPTS: u32 = 3;
+/// 重试基础间隔，按 2^n 递增
+const SEND_RETRY_BASE: Duration = Duration::from_millis(500);
+/// Retry-After 的上限，避免 hook 进程长时间阻塞
+const MAX_RETRY_AFTER: Duration = Duration::from_secs(30);
+/// 超长内容以文件发送时，摘要卡片保留的字符数
+const LONG_MESSAGE_SUMMARY_CHARS: usize = 2000;
+/// tenant_access_token 无效 / 过期的业务错误码，刷新 token 后重试一次
+const TOKEN_INVALID_CODES: [i64; 2] = [99991663, 99991677];
+
+/// 发送消息的去重标识（飞书要求不超过 50 个字符，1 小时内相同 uuid 只发送一次）
+fn send_uuid() -> String {
+    format!("sparky-{:016x}{:016x}", rand::random::<u64>(), rand::random::<u64>())
+}
+
+/// 第 attempt 次尝试失败后的重试间隔；已达上限时返回 None
+/// 优先使用服务端 Retry-After（秒），否则指数退避
+fn retry_delay(attempt: u32, retry_after_secs: Option<u64>) -> Option<Duration> {
+    if attempt >= MAX_SEND_ATTEMPTS {
+        return None;
+    }
+    Some(match retry_after_secs {
+        Some(secs) => Duration::from_secs(secs).min(MAX_RETRY_AFTER),
+        None => SEND_RETRY_BASE * 2u32.pow(attempt - 1),
+    })
+}
 
 /// 打开 SQLite 数据库连接
 fn open_db() -> Result<Connection, String> {
-    let home = dirs::home_dir().ok_or("Failed to get home dir".to_string())?;
     // CLI 和 GUI 使用相同的数据库路径
-    let db_path = home.join("sparky/hooks.db");
+    let db_path = crate::migrations::db_path().map_err(|e| e.to_string())?;
     tracing::info!("[feishu] open_db path: {:?}", db_path);
-    if let Some(parent) = db_path.parent() {
-        let _ = fs::create_dir_all(parent);
-    }
-    Connection::open(&db_path).map_err(|e| e.to_string())
+    crate::migrations::open_connection(&db_path).map_err(|e| e.to_string())
 }
 
 /// 保存 open_id 到 SQLite（供 WebSocket 回调使用）
@@ -33,6 +61,51 @@ pub fn save_open_id_to_db(open_id: &str) -> Result<(), String> {
     Ok(())
 }
 
+/// 单个附件的大小上限（字节）
+pub const MAX_ATTACHMENT_BYTES: u64 = 20 * 1024 * 1024;
+
+/// 附件所属的项目：会话绑定了项目通知目标时取该项目，否则取配置中的当前项目
+pub fn attachment_project(chat_id: Option<&str>) -> Result<Option<String>, String> {
+    let conn = open_db()?;
+    if let Some(chat_id) = chat_id.filter(|c| !c.is_empty()) {
+        let path: Option<String> = conn
+            .query_row("SELECT path FROM projects WHERE chat_id = ?1 LIMIT 1", params![chat_id], |row| row.get(0))
+            .optional()
+            .map_err(|e| e.to_string())?;
+        if path.is_some() {
+            return Ok(path);
+        }
+    }
+    let path: Option<Option<String>> = conn
+        .query_row("SELECT project_path FROM app_config_feishu WHERE id = 1", [], |row| row.get(0))
+        .optional()
+        .map_err(|e| e.to_string())?;
+    Ok(path.flatten().filter(|p| !p.trim().is_empty()))
+}
+
+/// 记录用户通过飞书发送的附件，只提供给 project_path 项目中下一次启动的 relay 任务
+pub fn save_attachment_to_db(
+    message_id: &str,
+    message_type: &str,
+    file_path: &str,
+    sender: &str,
+    project_path: &str,
+) -> Result<(), String> {
+    let conn = open_db()?;
+    let now = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map_err(|e| e.to_string())?
+        .as_secs() as i64;
+    conn.execute(
+        "INSERT INTO attachments (message_id, message_type, file_path, sender, project_path, created_at)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+        params![message_id, message_type, file_path, sender, project_path.trim_end_matches('/'), now],
+    )
+    .map_err(|e| e.to_string())?;
+    tracing::info!("[db] attachment saved: {} ({})", file_path, message_type);
+    Ok(())
+}
+
 /// 创建一个新的权限请求（Pending 状态），返回 4 位随机配对码
 pub fn create_permission_request(project_path: &str) -> Result<String, String> {
     let conn = open_db()?;
@@ -88,6 +161,16 @@ pub fn create_permission_request(project_path: &str) -> Result<String, String> {
     Ok(code_str)
 }
 
+/// 权限请求处理后的状态：1 → allowed，2 → always（始终允许），3 → rejected
+pub fn permission_status_for_choice(choice: &str) -> &'static str {
+    match choice {
+        "1" => "allowed",
+        "2" => "always",
+        "3" => "rejected",
+        _ => "completed",
+    }
+}
+
 /// 验证并执行命令（通过 code 匹配 pending 请求）
 pub fn verify_and_execute_command(code: &str, choice: &str) -> Result<(), String> {
     let mut conn = open_db()?;
@@ -131,10 +214,10 @@ pub fn verify_and_execute_command(code: &str, choice: &str) -> Result<(), String
         .map_err(|e| e.to_string())?
         .as_secs() as i64;
 
-    // Mark request as completed
+    // Mark request as resolved (allowed / always / rejected)
     tx.execute(
-        "UPDATE permission_requests SET status = 'completed', choice = ?1 WHERE id = ?2",
-        params![choice, req_id],
+        "UPDATE permission_requests SET status = ?1, choice = ?2 WHERE id = ?3",
+        params![permission_status_for_choice(choice), choice, req_id],
     ).map_err(|e| e.to_string())?;
 
     // Insert command
@@ -158,6 +241,8 @@ pub struct Card {
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct CardConfig {
     pub wide_screen_mode: bool,
+    /// 共享卡片：发送后可通过 update_card 更新内容（用于重复通知计数）
+    pub update_multi: bool,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -170,6 +255,9 @@ pub struct CardElement {
     pub actions: Option<Vec<CardAction>>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub table: Option<Table>,
+    /// markdown 组件的内容（tag = "markdown" 时使用）
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub content: Option<String>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -203,6 +291,345 @@ pub struct CardText {
     pub tag: String,
 }
 
+/// 卡片内容被拒绝的错误码（卡片 JSON 非法 / 元素不受支持 / 参数非法），命中时降级为纯文本
+const CARD_REJECTED_CODES: [i64; 2] = [230099, 230001];
+
+/// 去掉 markdown 标记，用于纯文本降级发送
+pub(crate) fn strip_markdown(content: &str) -> String {
+    content
+        .lines()
+        .filter(|line| !line.trim_start().starts_with("```"))
+        .map(|line| line.replace("**", "").replace('`', ""))
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum ContentSegment {
+    Prose(String),
+    Code { lang: String, code: String },
+    /// 连续的列表行（无序 / 有序），使用 markdown 组件渲染
+    List(String),
+    /// 水平分割线（---、***、___）
+    Rule,
+}
+
+/// 是否为水平分割线：至少 3 个相同的 -、* 或 _（允许中间有空格）
+fn is_horizontal_rule(line: &str) -> bool {
+    let chars: Vec<char> = line.chars().filter(|c| !c.is_whitespace()).collect();
+    chars.len() >= 3 && matches!(chars[0], '-' | '*' | '_') && chars.iter().all(|c| *c == chars[0])
+}
+
+/// 列表项：`- `、`* `、`+ ` 或 `1. ` / `1) ` 开头，返回去掉缩进后的规范化行（无序标记统一为 `- `）
+fn list_item(line: &str) -> Option<String> {
+    let indent = line.len() - line.trim_start().len();
+    let trimmed = line.trim_start();
+    let pad = " ".repeat(indent);
+    for marker in ["- ", "* ", "+ "] {
+        if let Some(rest) = trimmed.strip_prefix(marker) {
+            return Some(format!("{}- {}", pad, rest));
+        }
+    }
+    let digits = trimmed.chars().take_while(|c| c.is_ascii_digit()).count();
+    if digits > 0 {
+        let rest = &trimmed[digits..];
+        if rest.starts_with(". ") || rest.starts_with(") ") {
+            return Some(format!("{}{}. {}", pad, &trimmed[..digits], &rest[2..]));
+        }
+    }
+    None
+}
+
+/// 将内容拆分为有序的渲染片段：先按代码围栏拆分，再从普通文本中拆出分割线与列表
+fn segment_markdown(content: &str) -> Vec<ContentSegment> {
+    let mut segments = Vec::new();
+    for segment in split_code_fences(content) {
+        let ContentSegment::Prose(text) = segment else {
+            segments.push(segment);
+            continue;
+        };
+
+        let mut prose: Vec<&str> = Vec::new();
+        let mut list: Vec<String> = Vec::new();
+        let flush_prose = |prose: &mut Vec<&str>, segments: &mut Vec<ContentSegment>| {
+            if !prose.is_empty() {
+                segments.push(ContentSegment::Prose(prose.join("\n")));
+                prose.clear();
+            }
+        };
+        let flush_list = |list: &mut Vec<String>, segments: &mut Vec<ContentSegment>| {
+            if !list.is_empty() {
+                segments.push(ContentSegment::List(list.join("\n")));
+                list.clear();
+            }
+        };
+
+        for line in text.lines() {
+            if is_horizontal_rule(line) {
+                flush_prose(&mut prose, &mut segments);
+                flush_list(&mut list, &mut segments);
+                segments.push(ContentSegment::Rule);
+            } else if let Some(item) = list_item(line) {
+                flush_prose(&mut prose, &mut segments);
+                list.push(item);
+            } else if !list.is_empty() && line.starts_with("  ") && !line.trim().is_empty() {
+                // 缩进的续行归属上一个列表项
+                list.push(line.to_string());
+            } else {
+                flush_list(&mut list, &mut segments);
+                prose.push(line);
+            }
+        }
+        flush_prose(&mut prose, &mut segments);
+        flush_list(&mut list, &mut segments);
+    }
+    segments
+}
+
+/// 按 ``` 围栏拆分内容，围栏内的内容单独渲染；未闭合的围栏视为延续到末尾
+fn split_code_fences(content: &str) -> Vec<ContentSegment> {
+    let mut segments = Vec::new();
+    let mut prose: Vec<&str> = Vec::new();
+    // (开始围栏的反引号数量, 语言, 代码行)
+    let mut fence: Option<(usize, String, Vec<&str>)> = None;
+
+    for line in content.lines() {
+        let trimmed = line.trim_start();
+        let ticks = trimmed.chars().take_while(|c| *c == '`').count();
+        let rest = &trimmed[ticks..];
+
+        if let Some((fence_len, _, code_lines)) = fence.as_mut() {
+            // 闭合围栏需要至少同样多的反引号且后面没有其他内容，更短的 ``` 视为代码内容
+            if ticks >= *fence_len && rest.trim().is_empty() {
+                if let Some((_, lang, code_lines)) = fence.take() {
+                    segments.push(ContentSegment::Code { lang, code: code_lines.join("\n") });
+                }
+            } else {
+                code_lines.push(line);
+            }
+            continue;
+        }
+
+        if ticks >= 3 && !rest.contains('`') {
+            if !prose.is_empty() {
+                segments.push(ContentSegment::Prose(prose.join("\n")));
+                prose.clear();
+            }
+            fence = Some((ticks, rest.trim().to_string(), Vec::new()));
+        } else {
+            prose.push(line);
+        }
+    }
+
+    if let Some((_, lang, code_lines)) = fence {
+        segments.push(ContentSegment::Code { lang, code: code_lines.join("\n") });
+    }
+    if !prose.is_empty() {
+        segments.push(ContentSegment::Prose(prose.join("\n")));
+    }
+    segments
+}
+
+/// 代码块使用 markdown 组件渲染为等宽块，围栏长度大于代码中最长的连续反引号，避免提前闭合
+fn code_block_element(lang: &str, code: &str) -> CardElement {
+    let mut longest = 0;
+    let mut run = 0;
+    for c in code.chars() {
+        run = if c == '`' { run + 1 } else { 0 };
+        longest = longest.max(run);
+    }
+    let fence = "`".repeat((longest + 1).max(3));
+    CardElement {
+        tag: "markdown".to_string(),
+        text: None,
+        actions: None,
+        table: None,
+        content: Some(format!("{}{}\n{}\n{}", fence, lang, code, fence)),
+    }
+}
+
+/// 将消息内容转换为卡片元素：代码块、列表、分割线、表格与普通文本分别渲染
+fn build_content_elements(content: &str) -> Vec<CardElement> {
+    let mut elements = Vec::new();
+    for segment in segment_markdown(content) {
+        match segment {
+            ContentSegment::Code { lang, code } => elements.push(code_block_element(&lang, &code)),
+            ContentSegment::List(items) => elements.push(CardElement {
+                tag: "markdown".to_string(),
+                text: None,
+                actions: None,
+                table: None,
+                content: Some(items),
+            }),
+            ContentSegment::Rule => elements.push(CardElement {
+                tag: "hr".to_string(),
+                text: None,
+                actions: None,
+                table: None,
+                content: None,
+            }),
+            ContentSegment::Prose(text) => {
+                if !text.trim().is_empty() {
+                    push_prose_elements(&mut elements, &text);
+                }
+            }
+        }
+    }
+    if elements.is_empty() {
+        elements.push(CardElement {
+            tag: "div".to_string(),
+            text: Some(CardText {
+                content: content.to_string(),
+                tag: "lark_md".to_string(),
+            }),
+            actions: None,
+            table: None,
+            content: None,
+        });
+    }
+    elements
+}
+
+fn push_prose_elements(elements: &mut Vec<CardElement>, content: &str) {
+    // 检测是否包含 markdown 表格
+    let has_table = content.contains("| --- |") || content.contains("| 文件 |");
+
+    let start_len = elements.len();
+
+    if has_table {
+        // 解析 markdown 表格并转换为飞书表格
+        let lines: Vec<&str> = content.lines().collect();
+        let mut i = 0;
+        while i < lines.len() {
+            let line = lines[i];
+            if line.contains("| --- |") || line.contains("| 文件 |") {
+                // 找到表格开始，解析表头和行
+                let mut table_lines = Vec::new();
+                // 收集表头之前的文本
+                if i > 0 {
+                    let before_text: String = lines[..i].join("\n");
+                    if !before_text.trim().is_empty() {
+                        elements.push(CardElement {
+                            tag: "div".to_string(),
+                            text: Some(CardText {
+                                content: before_text.trim().to_string(),
+                                tag: "lark_md".to_string(),
+                            }),
+                            actions: None,
+                            table: None,
+                            content: None,
+                        });
+                    }
+                }
+
+                // 跳过表头分隔符
+                i += 1;
+
+                // 收集表格行
+                while i < lines.len() && lines[i].contains("|") {
+                    table_lines.push(lines[i].trim());
+                    i += 1;
+                }
+
+                // 解析表格
+                if table_lines.len() >= 1 {
+                    let headers: Vec<String> = table_lines[0]
+                        .split('|')
+                        .filter(|s| !s.trim().is_empty())
+                        .map(|s| s.trim().to_string())
+                        .collect();
+
+                    let mut table_rows: Vec<Vec<String>> = Vec::new();
+                    for row_line in table_lines.iter().skip(1) {
+                        let cells: Vec<String> = row_line
+                            .split('|')
+                            .filter(|s| !s.trim().is_empty())
+                            .map(|s| s.trim().to_string())
+                            .collect();
+                        if !cells.is_empty() {
+                            table_rows.push(cells);
+                        }
+                    }
+
+                    // 构建飞书表格
+                    let mut table_cells: Vec<TableCell> = Vec::new();
+                    for h in &headers {
+                        table_cells.push(TableCell {
+                            tag: "cell".to_string(),
+                            text: Some(CardText {
+                                content: h.clone(),
+                                tag: "lark_md".to_string(),
+                            }),
+                        });
+                    }
+
+                    // 转换行数据
+                    let table_rows_elements: Vec<TableElement> = table_rows.iter().map(|row| {
+                        let cells: Vec<TableCell> = row.iter().map(|cell| {
+                            TableCell {
+                                tag: "cell".to_string(),
+                                text: Some(CardText {
+                                    content: cell.clone(),
+                                    tag: "lark_md".to_string(),
+                                }),
+                            }
+                        }).collect();
+                        TableElement {
+                            tag: "tr".to_string(),
+                            cells,
+                        }
+                    }).collect();
+
+                    let table_elements = vec![CardElement {
+                        tag: "table".to_string(),
+                        text: None,
+                        actions: None,
+                        table: Some(Table {
+                            tag: "table".to_string(),
+                            elements: vec![TableElement {
+                                tag: "tr".to_string(),
+                                cells: table_cells,
+                            }],
+                            rows: Some(table_rows_elements),
+                        }),
+                        content: None,
+                    }];
+
+                    elements.extend(table_elements);
+                }
+                continue;
+            }
+            i += 1;
+        }
+
+        // 如果没有解析到表格，添加整个内容
+        if elements.len() == start_len {
+            elements.push(CardElement {
+                tag: "div".to_string(),
+                text: Some(CardText {
+                    content: content.to_string(),
+                    tag: "lark_md".to_string(),
+                }),
+                actions: None,
+                table: None,
+                content: None,
+            });
+        }
+    } else {
+        // 没有表格，正常发送
+        elements.push(CardElement {
+            tag: "div".to_string(),
+            text: Some(CardText {
+                content: content.to_string(),
+                tag: "lark_md".to_string(),
+            }),
+            actions: None,
+            table: None,
+            content: None,
+        });
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct CardAction {
     #[serde(rename = "tag")]
@@ -213,25 +640,114 @@ pub struct CardAction {
     pub value: serde_json::Value,
 }
 
+/// 构造 multipart/form-data 请求体（文本字段 + 一个 file 字段）
+fn multipart_body(boundary: &str, fields: &[(&str, &str)], file_name: &str, data: &[u8]) -> Vec<u8> {
+    let mut body = Vec::with_capacity(data.len() + 512);
+    for (name, value) in fields {
+        body.extend_from_slice(
+            format!(
+                "--{}\r\nContent-Disposition: form-data; name=\"{}\"\r\n\r\n{}\r\n",
+                boundary, name, value
+            )
+            .as_bytes(),
+        );
+    }
+    body.extend_from_slice(
+        format!(
+            "--{}\r\nContent-Disposition: form-data; name=\"file\"; filename=\"{}\"\r\nContent-Type: text/plain; charset=utf-8\r\n\r\n",
+            boundary,
+            file_name.replace('"', "")
+        )
+        .as_bytes(),
+    );
+    body.extend_from_slice(data);
+    body.extend_from_slice(format!("\r\n--{}--\r\n", boundary).as_bytes());
+    body
+}
+
+/// 飞书发送消息接口支持的 receive_id_type
+pub const RECEIVE_ID_TYPES: &[&str] = &["chat_id", "open_id", "user_id", "union_id", "email"];
+
+/// 校验用户指定的 receive_id_type（不区分大小写）
+pub fn parse_receive_id_type(value: &str) -> Result<&'static str, anyhow::Error> {
+    let value = value.trim().to_lowercase();
+    RECEIVE_ID_TYPES
+        .iter()
+        .copied()
+        .find(|t| *t == value)
+        .ok_or_else(|| {
+            anyhow::anyhow!("Invalid receive_id_type '{}', expected {}", value, RECEIVE_ID_TYPES.join("/"))
+        })
+}
+
+/// 根据 ID 前缀推断 receive_id_type（oc_ 为群聊，on_ 为 union_id，含 @ 为邮箱，其余按 open_id 处理）；
+/// user_id 没有固定前缀，需要显式指定
+pub fn receive_id_type_for(receive_id: &str) -> &'static str {
+    if receive_id.starts_with("oc_") {
+        "chat_id"
+    } else if receive_id.starts_with("on_") {
+        "union_id"
+    } else if receive_id.contains('@') {
+        "email"
+    } else {
+        "open_id"
+    }
+}
+
+/// 解析出站代理：优先使用配置的 proxy_url，其次回退到 HTTPS_PROXY 环境变量
+pub fn resolve_proxy_url(configured: Option<&str>) -> Option<String> {
+    configured
+        .map(|s| s.trim().to_string())
+        .filter(|s| !s.is_empty())
+        .or_else(|| std::env::var("HTTPS_PROXY").ok())
+        .or_else(|| std::env::var("https_proxy").ok())
+        .filter(|s| !s.trim().is_empty())
+}
+
+/// 构建 HTTP 客户端，设置了代理时所有请求都走代理（支持 http/https/socks5）
+pub fn build_http_client(proxy_url: Option<&str>) -> Result<Client, anyhow::Error> {
+    let mut builder = Client::builder();
+    if let Some(url) = resolve_proxy_url(proxy_url) {
+        tracing::info!("[feishu] using proxy: {}", crate::ws_proxy::mask_proxy_url(&url));
+        builder = builder.proxy(reqwest::Proxy::all(&url)?);
+    }
+    Ok(builder.build()?)
+}
+
+#[derive(Clone)]
 pub struct FeishuClient {
     client: Client,
     app_id: String,
     app_secret: String,
+    // tenant_access_token 缓存（持久化在 hooks.db，hook 进程之间共用）
+    token_cache: TokenCache,
 }
 
 impl FeishuClient {
-    pub fn new(app_id: String, app_secret: String) -> Self {
+    pub fn new(app_id: String, app_secret: String, proxy_url: Option<&str>) -> Self {
+        let client = build_http_client(proxy_url).unwrap_or_else(|e| {
+            error!("[feishu] invalid proxy config, falling back to direct connection: {}", e);
+            Client::new()
+        });
+        let token_cache = TokenCache::new("feishu", &app_id);
         FeishuClient {
-            client: Client::new(),
+            client,
             app_id,
             app_secret,
+            token_cache,
         }
     }
 
+    /// 获取 tenant_access_token，优先使用缓存
     async fn get_tenant_access_token(&self) -> Result<String, anyhow::Error> {
+        self.token_cache.get_or_fetch(|| self.request_tenant_access_token()).await
+    }
+
+    /// 请求新的 tenant_access_token，返回 token 与有效期（秒）
+    async fn request_tenant_access_token(&self) -> Result<(String, u64), anyhow::Error> {
         let token_url = "https://open.feishu.cn/open-apis/auth/v3/tenant_access_token/internal";
-        let masked_id = if self.app_id.len() > 8 {
-            format!("{}...", &self.app_id[..8])
+        let masked_id = if self.app_id.chars().count() > 8 {
+            format!("{}...", crate::text::truncate_chars(&self.app_id, 8))
         } else {
             self.app_id.clone()
         };
@@ -257,7 +773,7 @@ impl FeishuClient {
         tracing::info!("[feishu:token] response: status={}, code={}, msg={}", status, code, msg);
         
         if code != 0 {
-            let body_preview = if text.len() > 2000 { &text[..2000] } else { &text };
+            let body_preview = crate::text::truncate_chars(&text, 2000);
             error!(
                 "[feishu:token] FAILED: status={}, code={}, msg={}, body={}",
                 status, code, msg, body_preview
@@ -269,20 +785,43 @@ impl FeishuClient {
             .as_str()
             .ok_or_else(|| anyhow::anyhow!("No tenant_access_token in response"))?
             .to_string();
-        tracing::info!("[feishu:token] obtained token (len={})", token.len());
-        Ok(token)
+        let ttl_secs = result["expire"].as_u64().unwrap_or(DEFAULT_TOKEN_TTL_SECS);
+        tracing::info!("[feishu:token] obtained token (len={}, expire={}s)", token.len(), ttl_secs);
+        Ok((token, ttl_secs))
     }
 
-    pub async fn send_notification(
-        &self,
-        content: String,
-        actions: Option<Vec<CardAction>>,
-        receive_id: &str,
-    ) -> Result<(), anyhow::Error> {
-        self.send_message(receive_id, content, actions, "open_id").await
+    /// 构建消息卡片：代码块、表格、普通文本分别渲染，最后附加按钮
+    pub fn build_card(content: &str, actions: Option<Vec<CardAction>>) -> Card {
+        let mut elements = build_content_elements(content);
+
+        let has_actions = actions.as_ref().map(|a| !a.is_empty()).unwrap_or(false);
+        tracing::info!(
+            "[feishu:send] building card: elements={}, has_actions={}",
+            elements.len(), has_actions
+        );
+
+        if let Some(actions) = actions {
+            if !actions.is_empty() {
+                elements.push(CardElement {
+                    tag: "action".to_string(),
+                    text: None,
+                    actions: Some(actions),
+                    table: None,
+                    content: None,
+                });
+            }
+        }
+
+        Card {
+            config: CardConfig {
+                wide_screen_mode: true,
+                update_multi: true,
+            },
+            elements,
+        }
     }
 
-    /// 发送消息到飞书
+    /// 发送消息到飞书，返回 message_id
     /// receive_id: 可以是 chat_id, open_id, user_id, union_id
     /// receive_id_type: 对应的类型
     pub async fn send_message(
@@ -291,191 +830,310 @@ impl FeishuClient {
         content: String,
         actions: Option<Vec<CardAction>>,
         receive_id_type: &str,
-    ) -> Result<(), anyhow::Error> {
-        let token = self.get_tenant_access_token().await?;
-
-        // 检测是否包含 markdown 表格
-        let has_table = content.contains("| --- |") || content.contains("| 文件 |");
-
-        let mut elements: Vec<CardElement> = Vec::new();
-
-        if has_table {
-            // 解析 markdown 表格并转换为飞书表格
-            let lines: Vec<&str> = content.lines().collect();
-            let mut i = 0;
-            while i < lines.len() {
-                let line = lines[i];
-                if line.contains("| --- |") || line.contains("| 文件 |") {
-                    // 找到表格开始，解析表头和行
-                    let mut table_lines = Vec::new();
-                    // 收集表头之前的文本
-                    if i > 0 {
-                        let before_text: String = lines[..i].join("\n");
-                        if !before_text.trim().is_empty() {
-                            elements.push(CardElement {
-                                tag: "div".to_string(),
-                                text: Some(CardText {
-                                    content: before_text.trim().to_string(),
-                                    tag: "lark_md".to_string(),
-                                }),
-                                actions: None,
-                                table: None,
-                            });
-                        }
-                    }
-
-                    // 跳过表头分隔符
-                    i += 1;
+    ) -> Result<String, anyhow::Error> {
+        let card = Self::build_card(&content, actions);
 
-                    // 收集表格行
-                    while i < lines.len() && lines[i].contains("|") {
-                        table_lines.push(lines[i].trim());
-                        i += 1;
-                    }
+        let card_json = serde_json::to_string(&card)?;
+        tracing::info!("[feishu:send] card JSON length={}", card_json.len());
 
-                    // 解析表格
-                    if table_lines.len() >= 1 {
-                        let headers: Vec<String> = table_lines[0]
-                            .split('|')
-                            .filter(|s| !s.trim().is_empty())
-                            .map(|s| s.trim().to_string())
-                            .collect();
+        let mut result = self
+            .post_message(receive_id, receive_id_type, "interactive", card_json)
+            .await?;
+        let code = result["code"].as_i64().unwrap_or(-1);
 
-                        let mut table_rows: Vec<Vec<String>> = Vec::new();
-                        for row_line in table_lines.iter().skip(1) {
-                            let cells: Vec<String> = row_line
-                                .split('|')
-                                .filter(|s| !s.trim().is_empty())
-                                .map(|s| s.trim().to_string())
-                                .collect();
-                            if !cells.is_empty() {
-                                table_rows.push(cells);
-                            }
-                        }
+        // 卡片被飞书拒绝时降级为纯文本重发一次，避免通知丢失
+        if CARD_REJECTED_CODES.contains(&code) {
+            tracing::warn!(
+                "[feishu:send] interactive card rejected (code={}, msg={}), falling back to plain text",
+                code,
+                result["msg"].as_str().unwrap_or("")
+            );
+            let text_json = serde_json::json!({ "text": strip_markdown(&content) }).to_string();
+            result = self
+                .post_message(receive_id, receive_id_type, "text", text_json)
+                .await?;
+            if result["code"].as_i64() == Some(0) {
+                tracing::info!("[feishu:send] plain text fallback sent");
+            }
+        }
 
-                        // 构建飞书表格
-                        let mut table_cells: Vec<TableCell> = Vec::new();
-                        for h in &headers {
-                            table_cells.push(TableCell {
-                                tag: "cell".to_string(),
-                                text: Some(CardText {
-                                    content: h.clone(),
-                                    tag: "lark_md".to_string(),
-                                }),
-                            });
-                        }
+        let code = result["code"].as_i64().unwrap_or(-1);
+        if code != 0 {
+            let msg = result["msg"].as_str().unwrap_or("Unknown error");
+            anyhow::bail!("Failed to send message: {}", msg);
+        }
 
-                        // 转换行数据
-                        let table_rows_elements: Vec<TableElement> = table_rows.iter().map(|row| {
-                            let cells: Vec<TableCell> = row.iter().map(|cell| {
-                                TableCell {
-                                    tag: "cell".to_string(),
-                                    text: Some(CardText {
-                                        content: cell.clone(),
-                                        tag: "lark_md".to_string(),
-                                    }),
-                                }
-                            }).collect();
-                            TableElement {
-                                tag: "tr".to_string(),
-                                cells,
-                            }
-                        }).collect();
+        let message_id = result["data"]["message_id"].as_str().unwrap_or("").to_string();
+        tracing::info!("[feishu:send] message sent successfully: message_id={}", message_id);
+        Ok(message_id)
+    }
 
-                        let table_elements = vec![CardElement {
-                            tag: "table".to_string(),
-                            text: None,
-                            actions: None,
-                            table: Some(Table {
-                                tag: "table".to_string(),
-                                elements: vec![TableElement {
-                                    tag: "tr".to_string(),
-                                    cells: table_cells,
-                                }],
-                                rows: Some(table_rows_elements),
-                            }),
-                        }];
+    /// 只发送交互卡片，不降级为纯文本；用于 test --with-actions 验证机器人的卡片发送权限
+    pub async fn send_card(
+        &self,
+        receive_id: &str,
+        receive_id_type: &str,
+        content: String,
+        actions: Option<Vec<CardAction>>,
+    ) -> Result<String, anyhow::Error> {
+        let card_json = serde_json::to_string(&Self::build_card(&content, actions))?;
+        let result = self
+            .post_message(receive_id, receive_id_type, "interactive", card_json)
+            .await?;
+        let code = result["code"].as_i64().unwrap_or(-1);
+        if code != 0 {
+            anyhow::bail!(
+                "Feishu rejected the interactive card: code={}, msg={}",
+                code,
+                result["msg"].as_str().unwrap_or("Unknown error")
+            );
+        }
+        Ok(result["data"]["message_id"].as_str().unwrap_or("").to_string())
+    }
 
-                        elements.extend(table_elements);
-                    }
-                    continue;
-                }
-                i += 1;
+    /// 超长内容：上传为 .txt 文件并以文件消息发送，同时发送附带按钮的摘要卡片，返回摘要卡片的 message_id
+    /// 上传失败时回退为截断后发送；摘要卡片送达后文件消息失败不视为发送失败
+    pub async fn send_long_message(
+        &self,
+        receive_id: &str,
+        receive_id_type: &str,
+        content: String,
+        actions: Option<Vec<CardAction>>,
+        max_chars: usize,
+    ) -> Result<String, anyhow::Error> {
+        let total_chars = content.chars().count();
+        let file_name = format!("sparky-{}.txt", chrono::Local::now().format("%Y%m%d-%H%M%S"));
+        let file_key = match self.upload_file(&file_name, content.as_bytes()).await {
+            Ok(file_key) => file_key,
+            Err(e) => {
+                error!("[feishu:file] upload failed, falling back to truncation: {}", e);
+                let truncated = format!(
+                    "{}...\n\n（内容过长，已截断）",
+                    crate::text::truncate_chars(&content, max_chars)
+                );
+                return self.send_message(receive_id, truncated, actions, receive_id_type).await;
             }
+        };
 
-            // 如果没有解析到表格，添加整个内容
-            if elements.is_empty() {
-                elements.push(CardElement {
-                    tag: "div".to_string(),
-                    text: Some(CardText {
-                        content,
-                        tag: "lark_md".to_string(),
-                    }),
-                    actions: None,
-                    table: None,
-                });
+        let summary = format!(
+            "{}...\n\n📎 内容过长（共 {} 字），完整内容见随后发送的文件 {}",
+            crate::text::truncate_chars(&content, LONG_MESSAGE_SUMMARY_CHARS.min(max_chars)),
+            total_chars,
+            file_name
+        );
+        let message_id = self.send_message(receive_id, summary, actions, receive_id_type).await?;
+
+        // 摘要卡片已送达：文件消息失败只记录日志，结果以摘要卡片为准（记录状态与重复合并都依赖其 message_id）
+        let file_json = serde_json::json!({ "file_key": file_key }).to_string();
+        match self.post_message(receive_id, receive_id_type, "file", file_json).await {
+            Ok(result) if result["code"].as_i64() == Some(0) => {
+                tracing::info!("[feishu:file] long content sent as file {} ({} chars)", file_name, total_chars);
             }
-        } else {
-            // 没有表格，正常发送
-            elements.push(CardElement {
-                tag: "div".to_string(),
-                text: Some(CardText {
-                    content,
-                    tag: "lark_md".to_string(),
-                }),
-                actions: None,
-                table: None,
-            });
+            Ok(result) => error!(
+                "[feishu:file] summary card sent but file message failed: {}",
+                result["msg"].as_str().unwrap_or("Unknown error")
+            ),
+            Err(e) => error!("[feishu:file] summary card sent but file message failed: {}", e),
         }
+        Ok(message_id)
+    }
 
-        let has_actions = actions.as_ref().map(|a| !a.is_empty()).unwrap_or(false);
-        tracing::info!(
-            "[feishu:send] building card: elements={}, has_actions={}",
-            elements.len(), has_actions
+    /// 上传文件（file_type=stream），返回 file_key
+    pub async fn upload_file(&self, file_name: &str, data: &[u8]) -> Result<String, anyhow::Error> {
+        let token = self.get_tenant_access_token().await?;
+        let boundary = format!("sparky-{:016x}", rand::random::<u64>());
+        let body = multipart_body(
+            &boundary,
+            &[("file_type", "stream"), ("file_name", file_name)],
+            file_name,
+            data,
         );
 
-        if let Some(actions) = actions {
-            if !actions.is_empty() {
-                elements.push(CardElement {
-                    tag: "action".to_string(),
-                    text: None,
-                    actions: Some(actions),
-                    table: None,
-                });
-            }
+        let response = self
+            .client
+            .post("https://open.feishu.cn/open-apis/im/v1/files")
+            .header("Authorization", format!("Bearer {}", token))
+            .header(
+                reqwest::header::CONTENT_TYPE,
+                format!("multipart/form-data; boundary={}", boundary),
+            )
+            .body(body)
+            .send()
+            .await?;
+
+        let status = response.status();
+        let text = response.text().await?;
+        let result: serde_json::Value = serde_json::from_str(&text)?;
+        let code = result["code"].as_i64().unwrap_or(-1);
+        if code != 0 {
+            let msg = result["msg"].as_str().unwrap_or("Unknown error");
+            error!(
+                "[feishu:file] upload FAILED: status={}, code={}, msg={}, body={}",
+                status,
+                code,
+                msg,
+                crate::text::truncate_chars(&text, 2000)
+            );
+            anyhow::bail!("Failed to upload file: {}", msg);
         }
+        let file_key = result["data"]["file_key"]
+            .as_str()
+            .ok_or_else(|| anyhow::anyhow!("No file_key in upload response"))?
+            .to_string();
+        tracing::info!("[feishu:file] uploaded {} ({} bytes): file_key={}", file_name, data.len(), file_key);
+        Ok(file_key)
+    }
 
-        let card = Card {
-            config: CardConfig {
-                wide_screen_mode: true,
-            },
-            elements,
-        };
+    /// 使缓存的 tenant_access_token 失效，下次请求时重新获取
+    async fn invalidate_token(&self) {
+        self.token_cache.invalidate().await;
+    }
 
+    /// 调用发送消息接口，返回飞书响应 JSON（code != 0 时记录错误日志）
+    /// 网络错误与 HTTP 429/5xx 最多重试 MAX_SEND_ATTEMPTS 次；token 失效时刷新后重试一次；其余业务错误不重试
+    async fn post_message(
+        &self,
+        receive_id: &str,
+        receive_id_type: &str,
+        msg_type: &str,
+        content: String,
+    ) -> Result<serde_json::Value, anyhow::Error> {
         let message_url = "https://open.feishu.cn/open-apis/im/v1/messages";
-        let card_json = serde_json::to_string(&card)?;
-        tracing::info!("[feishu:send] card JSON length={}", card_json.len());
-
+        // 同一次发送的所有重试共用一个 uuid，飞书按 uuid 去重，超时后实际已送达的请求不会重复发出
         let message_body = serde_json::json!({
             "receive_id": receive_id,
-            "msg_type": "interactive",
-            "content": card_json
+            "msg_type": msg_type,
+            "content": content,
+            "uuid": send_uuid()
         });
 
         tracing::info!(
-            "[feishu:send] POST {}: receive_id_type={}, receive_id={}, body_len={}",
+            "[feishu:send] POST {}: msg_type={}, receive_id_type={}, receive_id={}, body_len={}",
             message_url,
+            msg_type,
             receive_id_type,
             receive_id,
             message_body.to_string().len()
         );
 
+        let mut attempt = 0;
+        let mut token_refreshed = false;
+        loop {
+            attempt += 1;
+            let token = self.get_tenant_access_token().await?;
+            let sent = self
+                .client
+                .post(message_url)
+                .header("Authorization", format!("Bearer {}", token))
+                .query(&[("receive_id_type", receive_id_type)])
+                .json(&message_body)
+                .send()
+                .await;
+
+            let response = match sent {
+                Ok(response) => response,
+                Err(e) => match retry_delay(attempt, None) {
+                    Some(delay) => {
+                        tracing::warn!(
+                            "[feishu:send] network error (attempt {}/{}), retrying in {:?}: {}",
+                            attempt, MAX_SEND_ATTEMPTS, delay, e
+                        );
+                        tokio::time::sleep(delay).await;
+                        continue;
+                    }
+                    None => return Err(e.into()),
+                },
+            };
+
+            let status = response.status();
+            let retry_after = response
+                .headers()
+                .get(reqwest::header::RETRY_AFTER)
+                .and_then(|v| v.to_str().ok())
+                .and_then(|v| v.trim().parse::<u64>().ok());
+            if status.as_u16() == 429 || status.is_server_error() {
+                if let Some(delay) = retry_delay(attempt, retry_after) {
+                    tracing::warn!(
+                        "[feishu:send] HTTP {} (attempt {}/{}), retrying in {:?}",
+                        status, attempt, MAX_SEND_ATTEMPTS, delay
+                    );
+                    tokio::time::sleep(delay).await;
+                    continue;
+                }
+            }
+
+            let text = response.text().await?;
+            let result: serde_json::Value = serde_json::from_str(&text)?;
+            let code = result["code"].as_i64().unwrap_or(-1);
+            let msg = result["msg"].as_str().unwrap_or("Unknown error");
+            tracing::info!("[feishu:send] response: status={}, code={}, msg={}", status, code, msg);
+
+            if TOKEN_INVALID_CODES.contains(&code) && !token_refreshed {
+                tracing::warn!("[feishu:send] token rejected (code={}), refreshing and retrying once", code);
+                token_refreshed = true;
+                self.invalidate_token().await;
+                continue;
+            }
+
+            if code != 0 {
+                let body_preview = crate::text::truncate_chars(&text, 2000);
+                error!(
+                    "[feishu:send] FAILED: status={}, code={}, msg={}, body={}",
+                    status, code, msg, body_preview
+                );
+            }
+            return Ok(result);
+        }
+    }
+
+    /// 更新已发送的交互卡片内容（卡片需为共享卡片）
+    pub async fn update_card(
+        &self,
+        message_id: &str,
+        content: &str,
+        actions: Option<Vec<CardAction>>,
+    ) -> Result<(), anyhow::Error> {
+        let token = self.get_tenant_access_token().await?;
+        let url = format!("https://open.feishu.cn/open-apis/im/v1/messages/{}", message_id);
+        let card_json = serde_json::to_string(&Self::build_card(content, actions))?;
+
+        let response = self
+            .client
+            .patch(&url)
+            .header("Authorization", format!("Bearer {}", token))
+            .json(&serde_json::json!({ "content": card_json }))
+            .send()
+            .await?;
+
+        let result: serde_json::Value = serde_json::from_str(&response.text().await?)?;
+        let code = result["code"].as_i64().unwrap_or(-1);
+        if code != 0 {
+            anyhow::bail!(
+                "Failed to update card: code={}, msg={}",
+                code,
+                result["msg"].as_str().unwrap_or("Unknown error")
+            );
+        }
+        Ok(())
+    }
+
+    /// 对已发送的消息发起应用内加急（需要接收人的 open_id）
+    pub async fn send_urgent_app(&self, message_id: &str, open_ids: &[String]) -> Result<(), anyhow::Error> {
+        if message_id.is_empty() || open_ids.is_empty() {
+            anyhow::bail!("message_id and open_ids are required for urgent_app");
+        }
+        let token = self.get_tenant_access_token().await?;
+        let url = format!(
+            "https://open.feishu.cn/open-apis/im/v1/messages/{}/urgent_app",
+            message_id
+        );
+
         let response = self
             .client
-            .post(message_url)
+            .patch(&url)
             .header("Authorization", format!("Bearer {}", token))
-            .query(&[("receive_id_type", receive_id_type)])
-            .json(&message_body)
+            .query(&[("user_id_type", "open_id")])
+            .json(&serde_json::json!({ "user_id_list": open_ids }))
             .send()
             .await?;
 
@@ -484,18 +1142,194 @@ impl FeishuClient {
         let result: serde_json::Value = serde_json::from_str(&text)?;
         let code = result["code"].as_i64().unwrap_or(-1);
         let msg = result["msg"].as_str().unwrap_or("Unknown error");
-        tracing::info!("[feishu:send] response: status={}, code={}, msg={}", status, code, msg);
+        tracing::info!("[feishu:urgent] response: status={}, code={}, msg={}", status, code, msg);
 
         if code != 0 {
-            let body_preview = if text.len() > 2000 { &text[..2000] } else { &text };
+            anyhow::bail!("Failed to send urgent: {}", msg);
+        }
+        Ok(())
+    }
+
+    /// 下载消息中的资源（图片或文件），返回文件内容与 Content-Type
+    /// resource_type: image 或 file
+    pub async fn download_message_resource(
+        &self,
+        message_id: &str,
+        file_key: &str,
+        resource_type: &str,
+    ) -> Result<(Vec<u8>, Option<String>), anyhow::Error> {
+        let token = self.get_tenant_access_token().await?;
+        let url = format!(
+            "https://open.feishu.cn/open-apis/im/v1/messages/{}/resources/{}",
+            message_id, file_key
+        );
+
+        let mut response = self
+            .client
+            .get(&url)
+            .header("Authorization", format!("Bearer {}", token))
+            .query(&[("type", resource_type)])
+            .send()
+            .await?;
+
+        let status = response.status();
+        let content_type = response
+            .headers()
+            .get(reqwest::header::CONTENT_TYPE)
+            .and_then(|v| v.to_str().ok())
+            .map(|v| v.to_string());
+        // 失败时飞书返回 JSON 错误体，成功时直接返回二进制内容
+        if !status.is_success()
+            || content_type.as_deref().is_some_and(|ct| ct.starts_with("application/json"))
+        {
+            let text = response.text().await?;
+            let body_preview = crate::text::truncate_chars(&text, 2000);
             error!(
-                "[feishu:send] FAILED: status={}, code={}, msg={}, body={}",
-                status, code, msg, body_preview
+                "[feishu:resource] FAILED: status={}, message_id={}, body={}",
+                status, message_id, body_preview
             );
-            anyhow::bail!("Failed to send message: {}", msg);
+            anyhow::bail!("Failed to download resource (status={})", status);
         }
 
-        tracing::info!("[feishu:send] message sent successfully");
-        Ok(())
+        if response.content_length().is_some_and(|len| len > MAX_ATTACHMENT_BYTES) {
+            anyhow::bail!("Attachment is larger than {} MB", MAX_ATTACHMENT_BYTES / 1024 / 1024);
+        }
+        // 未返回 Content-Length 时边下载边计数，超过上限立即中止
+        let mut bytes = Vec::new();
+        while let Some(chunk) = response.chunk().await? {
+            if (bytes.len() + chunk.len()) as u64 > MAX_ATTACHMENT_BYTES {
+                anyhow::bail!("Attachment is larger than {} MB", MAX_ATTACHMENT_BYTES / 1024 / 1024);
+            }
+            bytes.extend_from_slice(&chunk);
+        }
+        tracing::info!(
+            "[feishu:resource] downloaded {} bytes (message_id={}, type={})",
+            bytes.len(),
+            message_id,
+            resource_type
+        );
+        Ok((bytes, content_type))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_multipart_body() {
+        let body = multipart_body("b", &[("file_type", "stream")], "a.txt", b"hi");
+        let expected = "--b\r\nContent-Disposition: form-data; name=\"file_type\"\r\n\r\nstream\r\n\
+                        --b\r\nContent-Disposition: form-data; name=\"file\"; filename=\"a.txt\"\r\n\
+                        Content-Type: text/plain; charset=utf-8\r\n\r\nhi\r\n--b--\r\n";
+        assert_eq!(String::from_utf8(body).unwrap(), expected);
+    }
+
+    #[test]
+    fn test_send_uuid_is_unique_and_short() {
+        let a = send_uuid();
+        assert!(a.len() <= 50);
+        assert_ne!(a, send_uuid());
+    }
+
+    #[test]
+    fn test_retry_delay() {
+        assert_eq!(retry_delay(1, None), Some(Duration::from_millis(500)));
+        assert_eq!(retry_delay(2, None), Some(Duration::from_millis(1000)));
+        assert_eq!(retry_delay(1, Some(7)), Some(Duration::from_secs(7)));
+        assert_eq!(retry_delay(1, Some(3600)), Some(MAX_RETRY_AFTER));
+        assert_eq!(retry_delay(MAX_SEND_ATTEMPTS, None), None);
+    }
+
+    #[test]
+    fn test_receive_id_type_for() {
+        assert_eq!(receive_id_type_for("oc_123"), "chat_id");
+        assert_eq!(receive_id_type_for("ou_123"), "open_id");
+        assert_eq!(receive_id_type_for("on_123"), "union_id");
+        assert_eq!(receive_id_type_for("dev@example.com"), "email");
+    }
+
+    #[test]
+    fn test_parse_receive_id_type() {
+        assert_eq!(parse_receive_id_type("user_id").unwrap(), "user_id");
+        assert_eq!(parse_receive_id_type(" Email ").unwrap(), "email");
+        assert!(parse_receive_id_type("phone").is_err());
+    }
+
+    #[test]
+    fn test_permission_status_for_choice() {
+        assert_eq!(permission_status_for_choice("1"), "allowed");
+        assert_eq!(permission_status_for_choice("2"), "always");
+        assert_eq!(permission_status_for_choice("3"), "rejected");
+        assert_eq!(permission_status_for_choice("y"), "completed");
+    }
+
+    #[test]
+    fn test_split_code_fences() {
+        let segments = split_code_fences("输出如下：\n```bash\nls -la\n```\n完成");
+        assert_eq!(
+            segments,
+            vec![
+                ContentSegment::Prose("输出如下：".to_string()),
+                ContentSegment::Code { lang: "bash".to_string(), code: "ls -la".to_string() },
+                ContentSegment::Prose("完成".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_nested_and_unclosed_fences() {
+        // 四个反引号的围栏内包含 ```，不应提前闭合
+        let segments = split_code_fences("````md\n```rust\nfn main() {}\n```\n````");
+        assert_eq!(
+            segments,
+            vec![ContentSegment::Code {
+                lang: "md".to_string(),
+                code: "```rust\nfn main() {}\n```".to_string(),
+            }]
+        );
+
+        let segments = split_code_fences("text\n```\nstill running");
+        assert_eq!(segments[1], ContentSegment::Code { lang: String::new(), code: "still running".to_string() });
+
+        let element = code_block_element("", "a ``` b");
+        assert_eq!(element.content.as_deref(), Some("````\na ``` b\n````"));
+    }
+
+    #[test]
+    fn test_strip_markdown() {
+        assert_eq!(strip_markdown("**标题**\n```bash\nls `pwd`\n```"), "标题\nls pwd");
+    }
+
+    #[test]
+    fn test_build_card_separates_code_from_prose() {
+        let card = FeishuClient::build_card("**标题**\n```\n$ cargo test\n```", None);
+        let tags: Vec<&str> = card.elements.iter().map(|e| e.tag.as_str()).collect();
+        assert_eq!(tags, vec!["div", "markdown"]);
+    }
+
+    #[test]
+    fn test_segment_markdown_lists_and_rules() {
+        let segments = segment_markdown("改动：\n* a\n  续行\n2) b\n---\n| a | b |\n| --- | --- |\n```\nx\n```");
+        assert_eq!(
+            segments,
+            vec![
+                ContentSegment::Prose("改动：".to_string()),
+                ContentSegment::List("- a\n  续行\n2. b".to_string()),
+                ContentSegment::Rule,
+                ContentSegment::Prose("| a | b |\n| --- | --- |".to_string()),
+                ContentSegment::Code { lang: String::new(), code: "x".to_string() },
+            ]
+        );
+        assert!(is_horizontal_rule("* * *"));
+        assert!(!is_horizontal_rule("**粗体**"));
+        assert_eq!(list_item("-- not a list"), None);
+    }
+
+    #[test]
+    fn test_build_card_renders_lists_and_dividers() {
+        let card = FeishuClient::build_card("总结\n\n- 一\n- 二\n\n***\n结束", None);
+        let tags: Vec<&str> = card.elements.iter().map(|e| e.tag.as_str()).collect();
+        assert_eq!(tags, vec!["div", "markdown", "hr", "div"]);
     }
 }
@@ -0,0 +1,131 @@
+// Extra Feishu apps that `sparky connect` keeps a long connection for (app_config_feishu_apps),
+// shared by the CLI and the desktop app (src-tauri includes this file via #[path])
+
+use rusqlite::{params, Connection};
+use serde::Serialize;
+
+/// 额外飞书应用的列表项；密钥不返回，只标记是否已设置
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct FeishuApp {
+    pub id: i64,
+    pub app_id: String,
+    pub has_encrypt_key: bool,
+    pub has_verification_token: bool,
+    pub enabled: bool,
+}
+
+/// 列出全部额外应用（含已停用的），按添加顺序排列
+pub fn list(conn: &Connection) -> Result<Vec<FeishuApp>, String> {
+    let mut stmt = conn
+        .prepare(
+            "SELECT id, app_id, COALESCE(encrypt_key, '') != '', COALESCE(verification_token, '') != '', enabled
+             FROM app_config_feishu_apps ORDER BY id",
+        )
+        .map_err(|e| e.to_string())?;
+    let rows = stmt
+        .query_map([], |row| {
+            Ok(FeishuApp {
+                id: row.get(0)?,
+                app_id: row.get(1)?,
+                has_encrypt_key: row.get(2)?,
+                has_verification_token: row.get(3)?,
+                enabled: row.get::<_, i64>(4)? != 0,
+            })
+        })
+        .map_err(|e| e.to_string())?;
+    rows.collect::<Result<Vec<_>, _>>().map_err(|e| e.to_string())
+}
+
+/// 添加应用；app_id 已存在时更新密钥并重新启用。密钥加密保存
+pub fn upsert(
+    conn: &Connection,
+    app_id: &str,
+    app_secret: &str,
+    encrypt_key: Option<&str>,
+    verification_token: Option<&str>,
+) -> Result<(), String> {
+    let app_id = app_id.trim();
+    let app_secret = app_secret.trim();
+    if app_id.is_empty() || app_secret.is_empty() {
+        return Err("app_id 和 app_secret 不能为空".to_string());
+    }
+    let encrypt_key = encrypt_key.map(str::trim).filter(|v| !v.is_empty());
+    let verification_token = verification_token.map(str::trim).filter(|v| !v.is_empty());
+    conn.execute(
+        "INSERT INTO app_config_feishu_apps (app_id, app_secret, encrypt_key, verification_token, enabled)
+         VALUES (?1, ?2, ?3, ?4, 1)
+         ON CONFLICT(app_id) DO UPDATE SET
+            app_secret = excluded.app_secret,
+            encrypt_key = excluded.encrypt_key,
+            verification_token = excluded.verification_token,
+            enabled = 1",
+        params![
+            app_id,
+            crate::secret::encrypt_secret(app_secret)?,
+            crate::secret::encrypt_optional(encrypt_key)?,
+            crate::secret::encrypt_optional(verification_token)?,
+        ],
+    )
+    .map_err(|e| e.to_string())?;
+    Ok(())
+}
+
+/// 启用或停用应用；应用不存在时返回 false
+pub fn set_enabled(conn: &Connection, app_id: &str, enabled: bool) -> Result<bool, String> {
+    let changed = conn
+        .execute(
+            "UPDATE app_config_feishu_apps SET enabled = ?1 WHERE app_id = ?2",
+            params![enabled as i64, app_id.trim()],
+        )
+        .map_err(|e| e.to_string())?;
+    Ok(changed > 0)
+}
+
+/// 删除应用；应用不存在时返回 false
+pub fn remove(conn: &Connection, app_id: &str) -> Result<bool, String> {
+    let changed = conn
+        .execute("DELETE FROM app_config_feishu_apps WHERE app_id = ?1", params![app_id.trim()])
+        .map_err(|e| e.to_string())?;
+    Ok(changed > 0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_upsert_toggle_and_remove() {
+        let conn = Connection::open_in_memory().unwrap();
+        crate::migrations::run(&conn).unwrap();
+        assert!(list(&conn).unwrap().is_empty());
+        assert!(upsert(&conn, "cli_a", " ", None, None).is_err());
+
+        upsert(&conn, "cli_a", "secret-a", Some("ek"), None).unwrap();
+        upsert(&conn, "cli_b", "secret-b", None, Some(" ")).unwrap();
+        let stored: String = conn
+            .query_row("SELECT app_secret FROM app_config_feishu_apps WHERE app_id = 'cli_a'", [], |row| row.get(0))
+            .unwrap();
+        assert_ne!(stored, "secret-a");
+        assert_eq!(crate::secret::decrypt_secret(&stored).unwrap(), "secret-a");
+
+        assert!(set_enabled(&conn, "cli_a", false).unwrap());
+        assert!(!set_enabled(&conn, "cli_missing", false).unwrap());
+        let apps = list(&conn).unwrap();
+        assert_eq!(apps.len(), 2);
+        assert_eq!(
+            apps[0],
+            FeishuApp { id: apps[0].id, app_id: "cli_a".to_string(), has_encrypt_key: true, has_verification_token: false, enabled: false }
+        );
+        assert!(!apps[1].has_verification_token && apps[1].enabled);
+
+        // 重新添加同一应用会更新密钥并启用
+        upsert(&conn, "cli_a", "secret-a2", None, None).unwrap();
+        let apps = list(&conn).unwrap();
+        assert_eq!(apps.len(), 2);
+        assert!(apps[0].enabled && !apps[0].has_encrypt_key);
+
+        assert!(remove(&conn, "cli_b").unwrap());
+        assert!(!remove(&conn, "cli_b").unwrap());
+        assert_eq!(list(&conn).unwrap().len(), 1);
+    }
+}
@@ -0,0 +1,99 @@
+use std::sync::Arc;
+use std::time::Instant;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpListener;
+
+use crate::websocket::FeishuWsClient;
+
+/// Connect 模式的健康状态快照
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct HealthStatus {
+    pub connected: bool,
+    /// 最近一次收到飞书事件的时间（Unix 秒），尚未收到为 None
+    pub last_event_at: Option<u64>,
+    pub uptime_secs: u64,
+}
+
+/// 根据请求行生成响应状态码与 JSON 正文；未连接时 /healthz 返回 503
+fn route(request_line: &str, status: &HealthStatus) -> (u16, String) {
+    let mut parts = request_line.split_whitespace();
+    let method = parts.next().unwrap_or("");
+    let path = parts.next().unwrap_or("");
+    match (method, path.split('?').next().unwrap_or("")) {
+        // 长连接断开时返回 503，进程守护工具无需解析正文即可判断不健康
+        ("GET", "/healthz") => {
+            let code = if status.connected { 200 } else { 503 };
+            (code, serde_json::to_string(status).unwrap_or_default())
+        }
+        _ => (404, r#"{"error":"not found"}"#.to_string()),
+    }
+}
+
+/// 汇总多个应用的长连接状态：全部连接才算 connected，last_event_at 取最近一次
+fn aggregate(clients: &[Arc<FeishuWsClient>], uptime_secs: u64) -> HealthStatus {
+    HealthStatus {
+        connected: !clients.is_empty() && clients.iter().all(|c| c.is_connected()),
+        last_event_at: clients.iter().filter_map(|c| c.last_event_at()).max(),
+        uptime_secs,
+    }
+}
+
+/// 在 127.0.0.1:port 上启动极简 HTTP 服务，暴露 GET /healthz，供进程守护工具探活
+pub async fn serve(port: u16, clients: Vec<Arc<FeishuWsClient>>) -> anyhow::Result<()> {
+    let clients = Arc::new(clients);
+    let listener = TcpListener::bind(("127.0.0.1", port)).await?;
+    tracing::info!("[health] listening on http://127.0.0.1:{}/healthz", port);
+    let started = Instant::now();
+
+    loop {
+        let (mut stream, _) = listener.accept().await?;
+        let clients = clients.clone();
+        tokio::spawn(async move {
+            let mut buf = [0u8; 1024];
+            let n = match stream.read(&mut buf).await {
+                Ok(n) => n,
+                Err(_) => return,
+            };
+            let request = String::from_utf8_lossy(&buf[..n]);
+            let request_line = request.lines().next().unwrap_or("");
+            let status = aggregate(&clients, started.elapsed().as_secs());
+            let (code, body) = route(request_line, &status);
+            let reason = match code {
+                200 => "OK",
+                503 => "Service Unavailable",
+                _ => "Not Found",
+            };
+            let response = format!(
+                "HTTP/1.1 {} {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                code,
+                reason,
+                body.len(),
+                body
+            );
+            let _ = stream.write_all(response.as_bytes()).await;
+            let _ = stream.shutdown().await;
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_route() {
+        let status = HealthStatus { connected: true, last_event_at: Some(42), uptime_secs: 7 };
+        let (code, body) = route("GET /healthz HTTP/1.1", &status);
+        assert_eq!(code, 200);
+        assert_eq!(body, r#"{"connected":true,"last_event_at":42,"uptime_secs":7}"#);
+
+        assert_eq!(route("GET /healthz?verbose=1 HTTP/1.1", &status).0, 200);
+        assert_eq!(route("POST /healthz HTTP/1.1", &status).0, 404);
+        assert_eq!(route("GET / HTTP/1.1", &status).0, 404);
+
+        let down = HealthStatus { connected: false, ..status };
+        let (code, body) = route("GET /healthz HTTP/1.1", &down);
+        assert_eq!(code, 503);
+        assert!(body.contains(r#""connected":false"#));
+    }
+}
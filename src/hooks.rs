@@ -1,5 +1,6 @@
 use serde::{Deserialize, Serialize};
-use std::io::{self, BufRead};
+use std::io::{self, BufRead, Write};
+use std::sync::atomic::{AtomicBool, Ordering};
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct HookInput {
@@ -18,16 +19,58 @@ pub struct HookInput {
     pub tool_name: Option<String>,
     #[serde(default, alias = "tools")]
     pub tool_input: Option<serde_json::Value>,
+    /// 未建模的字段（如 stop_hook_active），随 Claude Code 的 hook schema 演进保留下来
+    #[serde(flatten)]
+    pub extra: serde_json::Map<String, serde_json::Value>,
 }
 
+impl HookInput {
+    /// 读取未建模的布尔字段，例如 stop_hook_active
+    pub fn extra_bool(&self, key: &str) -> Option<bool> {
+        self.extra.get(key).and_then(|v| v.as_bool())
+    }
+
+    /// AskUserQuestion 的各问题选项标签（questions[].options[].label），其他工具返回空
+    pub fn question_options(&self) -> Vec<Vec<String>> {
+        if self.tool_name.as_deref() != Some("AskUserQuestion") {
+            return Vec::new();
+        }
+        let questions = self
+            .tool_input
+            .as_ref()
+            .and_then(|input| input.get("questions"))
+            .and_then(|v| v.as_array());
+        let Some(questions) = questions else {
+            return Vec::new();
+        };
+        questions
+            .iter()
+            .map(|q| {
+                q.get("options")
+                    .and_then(|v| v.as_array())
+                    .map(|options| {
+                        options
+                            .iter()
+                            .map(|opt| opt.get("label").and_then(|v| v.as_str()).unwrap_or("").to_string())
+                            .collect()
+                    })
+                    .unwrap_or_default()
+            })
+            .collect()
+    }
+}
+
+/// Claude Code 读取的 hook 输出，字段名与其 JSON 约定一致（continue / stopReason / systemMessage）
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct HookOutput {
-    #[serde(skip_serializing_if = "Option::is_none")]
+    #[serde(rename = "continue", skip_serializing_if = "Option::is_none")]
     pub continue_exec: Option<bool>,
-    #[serde(skip_serializing_if = "Option::is_none")]
+    #[serde(rename = "stopReason", skip_serializing_if = "Option::is_none")]
     pub stop_reason: Option<String>,
-    #[serde(skip_serializing_if = "Option::is_none")]
+    #[serde(rename = "systemMessage", skip_serializing_if = "Option::is_none")]
     pub system_message: Option<String>,
+    #[serde(rename = "hookSpecificOutput", skip_serializing_if = "Option::is_none")]
+    pub hook_specific_output: Option<serde_json::Value>,
 }
 
 impl HookOutput {
@@ -36,6 +79,7 @@ impl HookOutput {
             continue_exec: Some(true),
             stop_reason: None,
             system_message: None,
+            hook_specific_output: None,
         }
     }
 
@@ -44,6 +88,23 @@ impl HookOutput {
             continue_exec: Some(false),
             stop_reason: Some(reason),
             system_message: None,
+            hook_specific_output: None,
+        }
+    }
+
+    /// PermissionRequest 的自动决策（behavior: allow / deny）
+    pub fn permission_decision(behavior: &str, message: String) -> Self {
+        HookOutput {
+            continue_exec: Some(true),
+            stop_reason: None,
+            system_message: Some(message.clone()),
+            hook_specific_output: Some(serde_json::json!({
+                "hookEventName": "PermissionRequest",
+                "decision": {
+                    "behavior": behavior,
+                    "message": message,
+                }
+            })),
         }
     }
 }
@@ -57,7 +118,7 @@ pub fn read_hook_input() -> Result<HookInput, anyhow::Error> {
         input.push_str(&line);
     }
 
-    let preview = if input.len() > 500 { &input[..500] } else { &input };
+    let preview = crate::text::truncate_chars(&input, 500);
     tracing::info!(
         "[hook:stdin] read {} bytes, preview: {}",
         input.len(),
@@ -83,12 +144,94 @@ pub fn read_hook_input() -> Result<HookInput, anyhow::Error> {
         hook_input.session_id,
         hook_input.cwd
     );
+    if !hook_input.extra.is_empty() {
+        tracing::debug!(
+            "[hook:stdin] unmodeled fields: {}",
+            serde_json::Value::Object(hook_input.extra.clone())
+        );
+    }
 
     Ok(hook_input)
 }
 
+/// 本进程是否已经向 stdout 输出过 HookOutput
+static OUTPUT_SENT: AtomicBool = AtomicBool::new(false);
+
 pub fn send_hook_output(output: &HookOutput) {
-    let json = serde_json::to_string(output).unwrap();
+    write_hook_output(&mut io::stdout(), &OUTPUT_SENT, output);
+}
+
+fn write_hook_output(out: &mut impl Write, sent: &AtomicBool, output: &HookOutput) {
+    let json = serde_json::to_string(output).unwrap_or_else(|_| r#"{"continue":true}"#.to_string());
     tracing::info!("[hook:stdout] sending output: {}", json);
-    println!("{}", json);
+    if let Err(e) = writeln!(out, "{}", json).and_then(|_| out.flush()) {
+        tracing::error!("[hook:stdout] failed to write output: {}", e);
+    }
+    sent.store(true, Ordering::SeqCst);
+}
+
+/// hook 的收尾：错误只写日志，尚未输出时补发 success，保证 Claude Code 总能拿到合法输出并继续会话
+pub fn finish_hook(result: anyhow::Result<()>) {
+    finish_hook_with(result, &mut io::stdout(), &OUTPUT_SENT);
+}
+
+fn finish_hook_with(result: anyhow::Result<()>, out: &mut impl Write, sent: &AtomicBool) {
+    if let Err(e) = result {
+        tracing::error!("[hook] failed, letting Claude Code continue: {:?}", e);
+    }
+    if !sent.load(Ordering::SeqCst) {
+        write_hook_output(out, sent, &HookOutput::success());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_failed_hook_still_outputs_continue() {
+        let sent = AtomicBool::new(false);
+        let mut out = Vec::new();
+        finish_hook_with(Err(anyhow::anyhow!("Feishu send failed: 502")), &mut out, &sent);
+        assert_eq!(String::from_utf8(out).unwrap(), "{\"continue\":true}\n");
+
+        // 已经输出过（如权限决策）时不再重复输出
+        let mut out = Vec::new();
+        finish_hook_with(Err(anyhow::anyhow!("DB locked")), &mut out, &sent);
+        assert!(out.is_empty());
+    }
+
+    #[test]
+    fn test_unknown_fields_are_preserved() {
+        let input: HookInput = serde_json::from_str(
+            r#"{"session_id":"s","transcript_path":"","cwd":"/tmp","hook_event_name":"Stop","stop_hook_active":true,"new_field":{"a":1}}"#,
+        )
+        .unwrap();
+        assert_eq!(input.extra_bool("stop_hook_active"), Some(true));
+        assert_eq!(input.extra.get("new_field"), Some(&serde_json::json!({"a": 1})));
+        assert!(!input.extra.contains_key("cwd"));
+    }
+
+    #[test]
+    fn test_question_options() {
+        let input: HookInput = serde_json::from_str(
+            r#"{"session_id":"s","transcript_path":"","cwd":"/tmp","hook_event_name":"PermissionRequest",
+                "tool_name":"AskUserQuestion",
+                "tool_input":{"questions":[
+                    {"question":"Which db?","options":[{"label":"SQLite"},{"label":"Postgres","description":"server"}]},
+                    {"question":"Run tests?","options":[{"label":"Yes"},{"label":"No"}]}
+                ]}}"#,
+        )
+        .unwrap();
+        assert_eq!(
+            input.question_options(),
+            vec![vec!["SQLite".to_string(), "Postgres".to_string()], vec!["Yes".to_string(), "No".to_string()]]
+        );
+
+        let other: HookInput = serde_json::from_str(
+            r#"{"session_id":"s","transcript_path":"","cwd":"/tmp","hook_event_name":"PermissionRequest","tool_name":"Bash","tool_input":{"questions":[]}}"#,
+        )
+        .unwrap();
+        assert!(other.question_options().is_empty());
+    }
 }
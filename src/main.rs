@@ -1,8 +1,31 @@
+mod backoff;
+mod card_choice;
+mod coalesce;
 mod config;
+mod dingtalk;
+mod dnd;
+mod event_crypto;
 mod feishu;
+mod feishu_apps;
+mod health;
 mod hooks;
+mod migrations;
+mod policy;
+mod project_tables;
+mod rate_limit;
+mod redact;
+mod secret;
 mod server;
+mod terminal_prompt;
+mod text;
+mod token_cache;
+mod transcript;
 mod websocket;
+mod wework;
+mod ws_connection_log;
+mod ws_fragments;
+mod ws_proxy;
+mod wss_status;
 
 use anyhow::Result;
 use clap::{Parser, Subcommand};
@@ -26,12 +49,78 @@ enum Commands {
     Hook,
     /// Send a test message to Feishu
     Test {
-        /// Chat ID to send message to
+        /// Chat ID to send message to (or user_id/open_id/email with --receive-type)
         #[arg(short, long)]
         chat_id: Option<String>,
+        /// Feishu receive_id_type: chat_id, open_id, user_id, union_id or email
+        #[arg(long)]
+        receive_type: Option<String>,
+        /// Send the interactive permission card with Yes/No buttons instead of plain text
+        #[arg(long)]
+        with_actions: bool,
+    },
+    /// Send a markdown notification read from stdin or --text
+    Send {
+        /// Message body (markdown); read from stdin when omitted
+        #[arg(long)]
+        text: Option<String>,
+        /// Title prepended to the message as a bold first line
+        #[arg(long)]
+        title: Option<String>,
+        /// Chat ID to send message to (or user_id/open_id/email with --receive-type)
+        #[arg(short, long)]
+        chat_id: Option<String>,
+        /// Feishu receive_id_type: chat_id, open_id, user_id, union_id or email
+        #[arg(long)]
+        receive_type: Option<String>,
+    },
+    /// View or change configuration stored in SQLite
+    Config {
+        #[command(subcommand)]
+        action: ConfigAction,
     },
     /// Start WebSocket long connection to receive events
-    Connect,
+    Connect {
+        /// Expose GET /healthz on 127.0.0.1 (default port 8799)
+        #[arg(long, num_args = 0..=1, default_missing_value = "8799")]
+        health_port: Option<u16>,
+    },
+}
+
+#[derive(Subcommand)]
+enum ConfigAction {
+    /// Print a single config value (secrets are masked)
+    Get { key: String },
+    /// Set a config value (an empty value clears optional keys)
+    Set { key: String, value: String },
+    /// List all config values (secrets are masked)
+    List,
+    /// Manage extra Feishu apps that `sparky connect` also keeps a long connection for
+    Apps {
+        #[command(subcommand)]
+        action: AppsAction,
+    },
+}
+
+#[derive(Subcommand)]
+enum AppsAction {
+    /// List extra Feishu apps
+    List,
+    /// Add an app, or update its secrets and re-enable it if the app ID already exists
+    Add {
+        app_id: String,
+        app_secret: String,
+        #[arg(long)]
+        encrypt_key: Option<String>,
+        #[arg(long)]
+        verification_token: Option<String>,
+    },
+    /// Remove an app
+    Remove { app_id: String },
+    /// Enable an app
+    Enable { app_id: String },
+    /// Disable an app without removing it
+    Disable { app_id: String },
 }
 
 #[tokio::main]
@@ -77,17 +166,47 @@ async fn main() -> Result<()> {
     tracing::info!("[main] Args: {:?}", args);
 
     let cli = Cli::parse();
-    let config = config::Config::load()?;
+    // config 子命令用于首次写入配置，不要求已有完整配置
+    if let Commands::Config { action } = &cli.command {
+        return run_config(action);
+    }
+    let config = match config::Config::load() {
+        Ok(config) => config,
+        // 首次运行尚未配置时，hook 正常退出，避免 Claude Code 把它当成 hook 失败
+        Err(e) if matches!(cli.command, Commands::Hook) && config::ConfigMissing::is(&e) => {
+            tracing::warn!(
+                "[main] {}; skipping notification. Configure sparky in the desktop app or with `sparky config set`",
+                e
+            );
+            append_hook_log(&format!("⚠️ 未配置，跳过通知: {}", e));
+            hooks::finish_hook(Ok(()));
+            return Ok(());
+        }
+        // hook 模式下任何错误都不能让进程非零退出，否则 Claude Code 会把它当成 hook 失败
+        Err(e) if matches!(cli.command, Commands::Hook) => {
+            append_hook_log(&format!("❌ 加载配置失败: {}", e));
+            hooks::finish_hook(Err(e));
+            return Ok(());
+        }
+        Err(e) => return Err(e),
+    };
 
     match cli.command {
         Commands::Hook => {
-            if let Err(e) = run_hook(&config).await {
-                tracing::error!("[main] run_hook failed: {:?}", e);
-                return Err(e);
+            let result = run_hook(&config).await;
+            if let Err(e) = &result {
+                append_hook_log(&format!("❌ Hook 处理失败: {}", e));
             }
+            hooks::finish_hook(result);
+        }
+        Commands::Test { chat_id, receive_type, with_actions } => {
+            run_test(&config, chat_id, receive_type, with_actions).await?
+        }
+        Commands::Send { text, title, chat_id, receive_type } => {
+            run_send(&config, text, title, chat_id, receive_type).await?
         }
-        Commands::Test { chat_id } => run_test(&config, chat_id).await?,
-        Commands::Connect => run_connect(&config).await?,
+        Commands::Config { .. } => unreachable!("handled before loading config"),
+        Commands::Connect { health_port } => run_connect(&config, health_port).await?,
     }
 
     Ok(())
@@ -126,8 +245,7 @@ async fn run_hook(config: &config::Config) -> Result<()> {
                     hook_input.hook_event_name, filter
                 ));
                 // 输出 continue 让 Claude Code 继续
-                let output = hooks::HookOutput::success();
-                println!("{}", serde_json::to_string(&output).unwrap_or_default());
+                hooks::send_hook_output(&hooks::HookOutput::success());
                 return Ok(());
             }
         }
@@ -136,6 +254,9 @@ async fn run_hook(config: &config::Config) -> Result<()> {
     let notification_text = hook_input.notification_text.clone().unwrap_or_default();
     let final_response = hook_input.final_response.clone().unwrap_or_default();
     let event_name = hook_input.hook_event_name.clone();
+    if let Some(active) = hook_input.extra_bool("stop_hook_active") {
+        tracing::info!("[run_hook] stop_hook_active={}", active);
+    }
 
     // 对于 PermissionRequest，提取 tool 信息作为摘要
     let permission_summary = if event_name == "PermissionRequest" {
@@ -214,6 +335,14 @@ async fn run_hook(config: &config::Config) -> Result<()> {
     } else {
         String::new()
     };
+
+    // 自动审批策略：命中 allow / deny 时直接给出决策，不再发送飞书通知
+    if event_name == "PermissionRequest" {
+        if let Some(done) = apply_approval_policy(&hook_input, &permission_summary) {
+            return done;
+        }
+    }
+
     let event_lower = event_name.to_lowercase();
     let (title, allow_actions) = match event_lower.as_str() {
         "notification" => ("🧭 需要确认", true),
@@ -230,8 +359,11 @@ async fn run_hook(config: &config::Config) -> Result<()> {
 
     let mut content = format!("{}\n\n", title);
 
+    // compact 样式只保留标题与通知/摘要
+    let compact = config.is_compact_style();
+
     // Stop 和 PermissionRequest 简化内容，不显示 Event、Session、CWD、Permission
-    if event_name != "Stop" && event_name != "PermissionRequest" {
+    if !compact && event_name != "Stop" && event_name != "PermissionRequest" {
         content.push_str(&format!("**Event**: {}\n", event_name));
         content.push_str(&format!("**Session**: {}\n", hook_input.session_id));
         content.push_str(&format!("**CWD**: {}\n", hook_input.cwd));
@@ -243,6 +375,9 @@ async fn run_hook(config: &config::Config) -> Result<()> {
         content.push_str(&notification_text);
     }
 
+    // 从终端捕获到的结构化提示（用于生成选项按钮）
+    let mut terminal_prompt: Option<terminal_prompt::TerminalPrompt> = None;
+
         // PermissionRequest - 显示工具信息
     if !permission_summary.is_empty() {
         // Record pending permission request in DB using CWD
@@ -265,11 +400,19 @@ async fn run_hook(config: &config::Config) -> Result<()> {
         // 尝试从终端日志中捕获提示
         let mut prompt_captured = false;
         if let Some(project_path) = config.project_path.as_ref() {
-            if let Some(prompt) = read_terminal_prompt(project_path) {
-                content.push_str("\n\n❓ **Terminal Output**\n");
-                content.push_str("```\n");
-                content.push_str(&prompt);
-                content.push_str("\n```");
+            terminal_prompt = read_terminal_prompt(project_path, &config.prompt_triggers());
+            if let Some(prompt) = &terminal_prompt {
+                if prompt.options.is_empty() {
+                    content.push_str("\n\n❓ **Terminal Output**\n");
+                    content.push_str("```\n");
+                    content.push_str(&prompt.raw);
+                    content.push_str("\n```");
+                } else {
+                    content.push_str(&format!("\n\n❓ **{}**\n", prompt.question));
+                    for (i, option) in prompt.options.iter().enumerate() {
+                        content.push_str(&format!("{}. {}\n", i + 1, option));
+                    }
+                }
                 prompt_captured = true;
             }
         }
@@ -287,127 +430,19 @@ async fn run_hook(config: &config::Config) -> Result<()> {
         }
     }
 
-    // Stop hook - 显示 Claude 的输出内容
-    if !final_response.is_empty() {
-        content.push_str("\n\n**Claude 输出**\n");
-        // 限制长度
-        let truncated = if final_response.len() > 3000 {
-            format!("{}...\n\n（省略 {} 字符）", &final_response[..3000], final_response.len() - 3000)
-        } else {
-            final_response
-        };
-        content.push_str(&truncated);
-    }
-
-    // Stop hook - 从 transcript 中提取最新的 Claude 回复
-    if event_name == "Stop" && !hook_input.transcript_path.is_empty() {
-        match std::fs::read_to_string(&hook_input.transcript_path) {
-            Ok(transcript) => {
-                // 提取最新的交流过程（只包含文本和工具调用，过滤掉执行详情）
-                let lines: Vec<&str> = transcript.lines().collect();
-                let mut session_elements: Vec<String> = Vec::new();
-
-                // 从后向前遍历，开始收集
-                for line in lines.iter().rev().take(100) {
-                    if let Ok(json) = serde_json::from_str::<serde_json::Value>(line) {
-                        let role = json.get("message").and_then(|v| v.get("role")).and_then(|v| v.as_str());
-                        
-                        // 提取内容
-                        if let Some(content_val) = json.get("message").and_then(|v| v.get("content")) {
-                            let mut turn_has_tool_result = false;
-                            let mut turn_elements = Vec::new();
-
-                            if let Some(content_array) = content_val.as_array() {
-                                for item in content_array {
-                                    let item_type = item.get("type").and_then(|v| v.as_str());
-                                    
-                                    if item_type == Some("text") {
-                                        if let Some(text) = item.get("text").and_then(|v| v.as_str()) {
-                                            if !text.trim().is_empty() {
-                                                turn_elements.push(format!("⏺ {}", text));
-                                            }
-                                        }
-                                    } else if item_type == Some("tool_use") {
-                                        let name = item.get("name").and_then(|v| v.as_str()).unwrap_or("tool");
-                                        let input = item.get("input").map(|v| v.to_string()).unwrap_or_default();
-                                        // 简化 input 显示
-                                        let input_display = if input.len() > 100 { format!("{}...", &input[..100]) } else { input };
-                                        turn_elements.push(format!("⏺ **{}**({})", name, input_display));
-                                    } else if item_type == Some("tool_result") {
-                                        turn_has_tool_result = true;
-                                        // 过滤掉 tool_result，不再添加 ⎿ 行
-                                    }
-                                }
-                            } else if let Some(text) = content_val.as_str() {
-                                if !text.trim().is_empty() {
-                                    turn_elements.push(format!("⏺ {}", text));
-                                }
-                            }
-
-                            if !turn_elements.is_empty() {
-                                // 因为是 rev 遍历行，所以要把这一行的元素按原来的正序加入 session_elements
-                                // 稍后整体再 rev 一次
-                                for el in turn_elements.into_iter().rev() {
-                                    session_elements.push(el);
-                                }
-                            }
-
-                            // 如果是用户发送的文本消息（且不是工具回传），说明到了本轮对话的起点，停止
-                            if role == Some("user") && !turn_has_tool_result {
-                                break;
-                            }
-                        }
-                    }
-                }
-
-                if !session_elements.is_empty() {
-                    content.push_str("\n\n**Claude 回复**\n");
-                    // 整体反转回正序（从前到后）
-                    for el in session_elements.iter().rev() {
-                        content.push_str(el);
-                        content.push_str("\n\n");
-                    }
-                } else {
-                    // 如果没有提取到，显示最后 3 行作为保底
-                    content.push_str("\n\n**Claude 回复**\n（无法解析转录）\n");
-                    let last_lines: Vec<&str> = lines.iter().rev().take(3).cloned().collect();
-                    for line in last_lines.iter().rev() {
-                        content.push_str(line);
-                        content.push_str("\n");
-                    }
-                }
-            }
-            Err(err) => {
-                content.push_str("\n\n**Claude 回复**\n读取失败: ");
-                content.push_str(&err.to_string());
-            }
-        }
-    } else if !hook_input.transcript_path.is_empty() && event_name != "UserPromptSubmit" && event_name != "PermissionRequest" && event_name != "Stop" {
-        // 其他事件读取 transcript（除了 Stop 和 PermissionRequest）
-        match std::fs::read_to_string(&hook_input.transcript_path) {
-            Ok(transcript) => {
-                content.push_str("\n\n**Transcript**\n");
-                // 只保留最后 2000 字符
-                let truncated = if transcript.len() > 2000 {
-                    format!("...（省略 {} 字符）\n\n{}", transcript.len() - 2000, &transcript[transcript.len() - 2000..])
-                } else {
-                    transcript
-                };
-                content.push_str(&truncated);
-            }
-            Err(err) => {
-                content.push_str("\n\n**Transcript**\n读取失败: ");
-                content.push_str(&err.to_string());
-            }
-        }
-    } else if event_name == "UserPromptSubmit" || event_name == "PermissionRequest" || event_name == "Stop" {
-        // 这些事件不读取 transcript
-    }
+    let reply_chars = append_reply_sections(
+        &mut content,
+        &event_name,
+        &final_response,
+        &hook_input.transcript_path,
+        compact,
+        config,
+    );
 
     // 限制数据库存储的内容长度
-    const MAX_DB_CONTENT_LEN: usize = 5000;
-    let db_content = if content.len() > MAX_DB_CONTENT_LEN {
-        format!("{}...\n\n（内容过长，已截断）", &content[..MAX_DB_CONTENT_LEN])
+    let max_db_content = config.db_content_limit();
+    let db_content = if content.chars().count() > max_db_content {
+        format!("{}...\n\n（内容过长，已截断）", text::truncate_chars(&content, max_db_content))
     } else {
         content.clone()
     };
@@ -419,15 +454,18 @@ async fn run_hook(config: &config::Config) -> Result<()> {
         notification_text.clone()
     };
 
+    // 只统计通知正文与提取到的回复（Event/Session 等元信息不算），
+    // 没有实质内容（如 Stop 未能提取到回复）时不发送；需要确认的事件始终发送
+    let body_chars = notification_text.trim().chars().count() + reply_chars;
+    let skip_empty = !allow_actions && body_chars < config.min_notification_chars();
+
     // 先保存记录到数据库
     let record_id = match save_hook_record(
-        &hook_input.cwd,
+        &hook_input,
         &event_name,
-        &hook_input.session_id,
         &notification_for_record,
-        &hook_input.transcript_path,
         &db_content,
-        "pending",
+        if skip_empty { "skipped_empty" } else { "pending" },
     ) {
         Ok(id) => Some(id),
         Err(err) => {
@@ -436,6 +474,17 @@ async fn run_hook(config: &config::Config) -> Result<()> {
         }
     };
 
+    if skip_empty {
+        tracing::info!(
+            "[run_hook] body has {} chars (< {}), skipping event={}",
+            body_chars,
+            config.min_notification_chars(),
+            event_name
+        );
+        append_hook_log(&format!("⏭️ 内容为空，跳过通知: event={}", event_name));
+        return Ok(());
+    }
+
     // 获取接收者ID，发送飞书通知（可选）
     // 优先级：chat_id > open_id
     let env_chat_id = std::env::var("FEISHU_CHAT_ID").ok();
@@ -447,16 +496,45 @@ async fn run_hook(config: &config::Config) -> Result<()> {
         env_chat_id, env_cm_chat_id, config_chat_id, config_open_id
     );
 
-    let (receive_id, receive_id_type) = env_chat_id
-        .or(env_cm_chat_id)
-        .or(config_chat_id)
-        .map(|id| (id, "chat_id"))
-        .unwrap_or_else(|| {
-            config_open_id
-                .filter(|id| !id.is_empty())
-                .map(|id| (id, "open_id"))
-                .unwrap_or((String::new(), ""))
-        });
+    let (receive_id, receive_id_type) = if config.is_dingtalk_backend() {
+        // 钉钉后端发送到配置的群会话
+        config
+            .dingtalk
+            .as_ref()
+            .and_then(|d| d.chat_id.clone())
+            .filter(|id| !id.is_empty())
+            .map(|id| (id, "open_conversation_id"))
+            .unwrap_or((String::new(), ""))
+    } else if config.is_wework_backend() {
+        // 企业微信后端发送到配置的群聊
+        config
+            .wework
+            .as_ref()
+            .and_then(|w| w.chat_id.clone())
+            .filter(|id| !id.is_empty())
+            .map(|id| (id, "chatid"))
+            .unwrap_or((String::new(), ""))
+    } else {
+        // 环境变量 > 项目级目标（chat_id > open_id）> 全局配置（chat_id > open_id）
+        let project_target = match config::open_config_db()
+            .and_then(|conn| config::project_target(&conn, &hook_input.cwd))
+        {
+            Ok(target) => target.unwrap_or_default(),
+            Err(err) => {
+                tracing::error!("[run_hook] failed to load project notification target: {}", err);
+                config::ProjectTarget::default()
+            }
+        };
+        tracing::info!("[run_hook] project target for {}: {:?}", hook_input.cwd, project_target);
+        env_chat_id
+            .or(env_cm_chat_id)
+            .map(|id| (id, "chat_id"))
+            .or_else(|| project_target.chat_id.map(|id| (id, "chat_id")))
+            .or_else(|| project_target.open_id.map(|id| (id, "open_id")))
+            .or_else(|| config_chat_id.map(|id| (id, config.receive_id_type().unwrap_or("chat_id"))))
+            .or_else(|| config_open_id.filter(|id| !id.is_empty()).map(|id| (id, "open_id")))
+            .unwrap_or((String::new(), ""))
+    };
 
     tracing::info!("[run_hook] resolved receive_id_type={}, receive_id={}", receive_id_type, receive_id);
 
@@ -467,6 +545,27 @@ async fn run_hook(config: &config::Config) -> Result<()> {
         return Ok(());
     }
 
+    // 免打扰时段：记录标记为 muted，不发送通知
+    let dnd = config::open_config_db()
+        .and_then(|conn| dnd::load(&conn).map_err(anyhow::Error::from))
+        .unwrap_or_else(|err| {
+            tracing::error!("[run_hook] failed to load DND config: {}", err);
+            dnd::DndConfig::default()
+        });
+    if dnd.is_muted(&event_name, chrono::Utc::now()) {
+        tracing::info!("[run_hook] DND active, muting event={}", event_name);
+        append_hook_log(&format!("🌙 免打扰时段，跳过通知: event={}", event_name));
+        if let Some(id) = record_id {
+            if let Err(err) =
+                update_hook_record(&hook_input, id, &event_name, &notification_for_record, &db_content, "muted")
+            {
+                tracing::error!("Failed to update hook record: {}", err);
+            }
+        }
+        hooks::send_hook_output(&hooks::HookOutput::success());
+        return Ok(());
+    }
+
     // 检测是否需要确认按钮
     let action_text = if !notification_text.is_empty() {
         notification_text.clone()
@@ -476,8 +575,14 @@ async fn run_hook(config: &config::Config) -> Result<()> {
         String::new()
     };
 
+    let prompt_options = terminal_prompt
+        .as_ref()
+        .map(|p| p.options.clone())
+        .unwrap_or_default();
+
     let need_action = allow_actions
-        && (action_text.contains("Do you want to")
+        && (!prompt_options.is_empty()
+            || action_text.contains("Do you want to")
             || action_text.contains("❯ 1. Yes")
             || action_text.contains("❯ 2. No")
             || action_text.contains("AskUserQuestion"));
@@ -487,46 +592,157 @@ async fn run_hook(config: &config::Config) -> Result<()> {
         allow_actions, need_action, action_text.len()
     );
 
-    let actions = if need_action {
-        Some(vec![
-            feishu::CardAction {
-                tag: "button".to_string(),
-                text: feishu::CardText {
-                    content: "✅ Yes (1)".to_string(),
-                    tag: "plain_text".to_string(),
-                },
-                action_type: "primary".to_string(),
-                value: serde_json::json!({"choice": "1"}),
-            },
-            feishu::CardAction {
-                tag: "button".to_string(),
-                text: feishu::CardText {
-                    content: "❌ No (2)".to_string(),
-                    tag: "plain_text".to_string(),
-                },
-                action_type: "danger".to_string(),
-                value: serde_json::json!({"choice": "2"}),
-            },
-        ])
-    } else {
-        None
-    };
+    // 重复通知合并：同一会话短时间内的相同事件不再单独发送，而是更新首条卡片的计数
+    let fingerprint = coalesce::fingerprint(
+        &event_name,
+        if notification_for_record.is_empty() { &db_content } else { &notification_for_record },
+    );
+    if coalesce::applies(&event_name, need_action) {
+        let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_secs() as i64;
+        let decision = config::open_config_db()
+            .and_then(|conn| {
+                coalesce::check(&conn, &hook_input.session_id, &fingerprint, config.coalesce_window(), now)
+                    .map_err(anyhow::Error::from)
+            })
+            .unwrap_or_else(|err| {
+                tracing::error!("[run_hook] coalesce check failed: {}", err);
+                coalesce::CoalesceDecision::Send
+            });
+        if let coalesce::CoalesceDecision::Coalesce { count, message_id } = decision {
+            tracing::info!(
+                "[run_hook] coalescing repeated event={} session={} (x{})",
+                event_name, hook_input.session_id, count
+            );
+            append_hook_log(&format!("🔁 重复通知已合并: event={}, x{}", event_name, count));
+            let is_feishu = !config.is_dingtalk_backend() && !config.is_wework_backend();
+            if let (true, Some(message_id)) = (is_feishu, message_id) {
+                let feishu_client = feishu::FeishuClient::new(
+                    config.app_id.clone(),
+                    config.app_secret.clone(),
+                    config.proxy_url.as_deref(),
+                );
+                let updated = format!(
+                    "{}\n\n🔁 (x{})",
+                    text::truncate_chars(&content, config.message_limit()),
+                    count
+                );
+                if let Err(err) = feishu_client.update_card(&message_id, &updated, None).await {
+                    tracing::warn!("[run_hook] failed to update coalesced card {}: {}", message_id, err);
+                }
+            }
+            if let Some(id) = record_id {
+                if let Err(err) = update_hook_record(
+                    &hook_input,
+                    id,
+                    &event_name,
+                    &notification_for_record,
+                    &db_content,
+                    &format!("coalesced (x{})", count),
+                ) {
+                    tracing::error!("Failed to update hook record: {}", err);
+                }
+            }
+            hooks::send_hook_output(&hooks::HookOutput::success());
+            return Ok(());
+        }
+    }
 
-    // 限制消息长度，飞书单条消息最大 20000 字符
-    const MAX_CONTENT_LEN: usize = 18000;
-    let mut send_content = content.clone();
-    if send_content.len() > MAX_CONTENT_LEN {
-        send_content = format!("{}...\n\n（内容过长，已截断）", &send_content[..MAX_CONTENT_LEN]);
+    // 会话级通知限流（PermissionRequest 需要用户操作，不受限制）
+    if event_name != "PermissionRequest" {
+        let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_secs() as i64;
+        let decision = migrations::db_path()
+            .map_err(anyhow::Error::from)
+            .and_then(|path| migrations::open_connection(path).map_err(anyhow::Error::from))
+            .and_then(|conn| {
+                rate_limit::check(
+                    &conn,
+                    &hook_input.session_id,
+                    config.max_notifications_per_session_per_minute,
+                    now,
+                )
+                .map_err(anyhow::Error::from)
+            })
+            .unwrap_or_else(|err| {
+                tracing::error!("[run_hook] rate limit check failed: {}", err);
+                rate_limit::RateDecision::Send { suppressed_before: 0 }
+            });
+
+        match decision {
+            rate_limit::RateDecision::Send { suppressed_before } if suppressed_before > 0 => {
+                content.push_str(&format!("\n\n🔕 此前有 {} 条通知因频率限制被合并", suppressed_before));
+            }
+            rate_limit::RateDecision::Send { .. } => {}
+            rate_limit::RateDecision::Suppress => {
+                tracing::info!(
+                    "[run_hook] session={} exceeded notification cap, suppressing event={}",
+                    hook_input.session_id, event_name
+                );
+                append_hook_log(&format!("🔕 通知已限流: event={}, session={}", event_name, hook_input.session_id));
+                if let Some(id) = record_id {
+                    if let Err(err) = update_hook_record(
+                        &hook_input,
+                        id,
+                        &event_name,
+                        &notification_for_record,
+                        &db_content,
+                        "suppressed: rate limit",
+                    ) {
+                        tracing::error!("Failed to update hook record: {}", err);
+                    }
+                }
+                hooks::send_hook_output(&hooks::HookOutput::success());
+                return Ok(());
+            }
+        }
     }
 
-    let feishu_client = feishu::FeishuClient::new(
-        config.app_id.clone(),
-        config.app_secret.clone(),
-    );
+    let question_options = hook_input.question_options();
+
+    let actions = if need_action && question_options.iter().any(|options| !options.is_empty()) {
+        // AskUserQuestion：每个问题的每个选项一个按钮，回调中按 question_index / option_index 路由
+        Some(ask_user_question_actions(&question_options))
+    } else if need_action && !prompt_options.is_empty() {
+        // 终端中识别到编号选项时，按选项逐一生成按钮
+        Some(
+            prompt_options
+                .iter()
+                .enumerate()
+                .map(|(i, option)| feishu::CardAction {
+                    tag: "button".to_string(),
+                    text: feishu::CardText {
+                        content: format!("{}. {}", i + 1, option),
+                        tag: "plain_text".to_string(),
+                    },
+                    action_type: if i == 0 { "primary" } else { "default" }.to_string(),
+                    value: serde_json::json!({"choice": (i + 1).to_string()}),
+                })
+                .collect(),
+        )
+    } else if need_action {
+        Some(yes_no_actions())
+    } else {
+        None
+    };
 
-    let send_result = feishu_client
-        .send_message(&receive_id, send_content, actions, receive_id_type)
-        .await;
+    // 限制消息长度，飞书单条消息最大 20000 字符；飞书后端将超长内容作为文件发送，其余后端截断
+    let max_message = config.message_limit();
+    let is_feishu = !config.is_dingtalk_backend() && !config.is_wework_backend();
+    let send_result = if content.chars().count() > max_message && is_feishu {
+        let feishu_client = feishu::FeishuClient::new(
+            config.app_id.clone(),
+            config.app_secret.clone(),
+            config.proxy_url.as_deref(),
+        );
+        feishu_client
+            .send_long_message(&receive_id, receive_id_type, content.clone(), actions, max_message)
+            .await
+    } else {
+        let mut send_content = content.clone();
+        if send_content.chars().count() > max_message {
+            send_content = format!("{}...\n\n（内容过长，已截断）", text::truncate_chars(&send_content, max_message));
+        }
+        send_via_backend(config, &receive_id, receive_id_type, send_content, actions).await
+    };
 
     if let Err(err) = &send_result {
         tracing::error!(
@@ -535,26 +751,73 @@ async fn run_hook(config: &config::Config) -> Result<()> {
             receive_id,
             err
         );
-        append_hook_log(&format!("❌ 飞书发送失败: {}", err));
+        append_hook_log(&format!("❌ 通知发送失败: {}", err));
     } else {
-        append_hook_log(&format!("✅ 飞书发送成功: event={}, receive_id_type={}", event_name, receive_id_type));
+        append_hook_log(&format!("✅ 通知发送成功: event={}, receive_id_type={}", event_name, receive_id_type));
+    }
+
+    // 加急提醒：事件命中 urgent_events 时对已发送的消息发起应用内加急（仅飞书）
+    let urgent_status = match &send_result {
+        Ok(message_id)
+            if !config.is_dingtalk_backend()
+                && !config.is_wework_backend()
+                && is_urgent_event(config.urgent_events.as_deref(), &event_name) =>
+        {
+            let feishu_client = feishu::FeishuClient::new(
+                config.app_id.clone(),
+                config.app_secret.clone(),
+                config.proxy_url.as_deref(),
+            );
+            let open_id = if receive_id_type == "open_id" {
+                Some(receive_id.clone())
+            } else {
+                config.open_id.clone().filter(|id| !id.is_empty())
+            };
+            match open_id {
+                Some(open_id) => match feishu_client.send_urgent_app(message_id, &[open_id]).await {
+                    Ok(_) => {
+                        append_hook_log(&format!("🚨 已加急: event={}", event_name));
+                        Some("urgent".to_string())
+                    }
+                    Err(err) => {
+                        tracing::error!("[run_hook] urgent_app failed: {}", err);
+                        Some(format!("urgent failed: {}", err))
+                    }
+                },
+                None => {
+                    tracing::warn!("[run_hook] urgent event {} but no open_id configured", event_name);
+                    Some("urgent skipped: no open_id".to_string())
+                }
+            }
+        }
+        _ => None,
+    };
+
+    // 带按钮的卡片不作为合并目标，避免后续重复通知更新卡片时去掉按钮
+    if let (true, Ok(message_id)) = (coalesce::applies(&event_name, need_action), &send_result) {
+        let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_secs() as i64;
+        let message_id = Some(message_id.as_str()).filter(|id| !id.is_empty());
+        if let Err(err) = config::open_config_db().and_then(|conn| {
+            coalesce::record_sent(&conn, &hook_input.session_id, &fingerprint, message_id, now).map_err(anyhow::Error::from)
+        }) {
+            tracing::error!("[run_hook] failed to record notification fingerprint: {}", err);
+        }
     }
 
     // 更新记录状态
-    let record_result = match &send_result {
-        Ok(_) => "sent".to_string(),
-        Err(err) => format!("failed: {}", err),
+    let record_result = match (&send_result, urgent_status) {
+        (Ok(_), Some(urgent)) => format!("sent ({})", urgent),
+        (Ok(_), None) => "sent".to_string(),
+        (Err(err), _) => format!("failed: {}", err),
     };
 
     // 如果有 record_id，使用 UPDATE；否则创建新记录
     if let Some(id) = record_id {
         if let Err(err) = update_hook_record(
-            &hook_input.cwd,
+            &hook_input,
             id,
             &event_name,
-            &hook_input.session_id,
             &notification_for_record,
-            &hook_input.transcript_path,
             &db_content,
             &record_result,
         ) {
@@ -563,11 +826,9 @@ async fn run_hook(config: &config::Config) -> Result<()> {
     } else {
         // 如果没有 ID，创建一个新记录
         if let Err(err) = save_hook_record(
-            &hook_input.cwd,
+            &hook_input,
             &event_name,
-            &hook_input.session_id,
             &notification_for_record,
-            &hook_input.transcript_path,
             &db_content,
             &record_result,
         ) {
@@ -583,6 +844,7 @@ async fn run_hook(config: &config::Config) -> Result<()> {
             continue_exec: Some(true),
             stop_reason: None,
             system_message: Some("通知已发送到飞书，请在飞书中查看并回复".to_string()),
+            hook_specific_output: None,
         };
         hooks::send_hook_output(&output);
     } else {
@@ -593,61 +855,62 @@ async fn run_hook(config: &config::Config) -> Result<()> {
     Ok(())
 }
 
-fn get_db_path() -> std::path::PathBuf {
-    let base_dir = dirs::home_dir()
-        .expect("Failed to get home directory")
-        .join("sparky");
-    std::fs::create_dir_all(&base_dir).expect("Failed to create base directory");
-    base_dir.join("hooks.db")
-}
-
-fn project_hooks_table_name(project_path: &str) -> String {
-    let mut hash: u64 = 14695981039346656037;
-    for byte in project_path.as_bytes() {
-        hash ^= *byte as u64;
-        hash = hash.wrapping_mul(1099511628211);
-    }
-    format!("hook_records_{:x}", hash)
-}
-
-fn ensure_project_hooks_table(conn: &Connection, table_name: &str) -> Result<()> {
-    let sql = format!(
-        "CREATE TABLE IF NOT EXISTS {} (
-            id INTEGER PRIMARY KEY AUTOINCREMENT,
-            event_name TEXT NOT NULL,
-            session_id TEXT NOT NULL,
-            notification_text TEXT NOT NULL,
-            transcript_path TEXT NOT NULL,
-            content TEXT NOT NULL,
-            result TEXT NOT NULL,
-            created_at INTEGER NOT NULL
-        )",
-        table_name
-    );
-    conn.execute(&sql, [])?;
-    ensure_session_id_column(conn, table_name)?;
-    Ok(())
+/// 事件是否在加急列表中（逗号分隔，忽略大小写）
+fn is_urgent_event(urgent_events: Option<&str>, event_name: &str) -> bool {
+    urgent_events
+        .map(|events| {
+            events
+                .split(',')
+                .map(|e| e.trim())
+                .any(|e| !e.is_empty() && e.eq_ignore_ascii_case(event_name))
+        })
+        .unwrap_or(false)
 }
 
-fn ensure_session_id_column(conn: &Connection, table_name: &str) -> Result<()> {
-    let pragma_sql = format!("PRAGMA table_info({})", table_name);
-    let mut stmt = conn.prepare(&pragma_sql)?;
-    let mut has_session = false;
-    let rows = stmt.query_map([], |row| row.get::<_, String>(1))?;
-    for row in rows {
-        if row? == "session_id" {
-            has_session = true;
-            break;
+/// 按自动审批策略处理 PermissionRequest；返回 None 表示需要继续走人工确认流程
+fn apply_approval_policy(hook_input: &hooks::HookInput, permission_summary: &str) -> Option<Result<()>> {
+    let tool_name = hook_input.tool_name.clone().unwrap_or_default();
+    let policies = match migrations::db_path()
+        .map_err(anyhow::Error::from)
+        .and_then(|path| migrations::open_connection(path).map_err(anyhow::Error::from))
+        .and_then(|conn| policy::load_policies(&conn).map_err(anyhow::Error::from))
+    {
+        Ok(policies) => policies,
+        Err(err) => {
+            tracing::error!("[policy] failed to load approval policies: {}", err);
+            return None;
         }
+    };
+    let subject = policy::policy_subject(&tool_name, hook_input.tool_input.as_ref());
+    let matched = policy::evaluate(&policies, &tool_name, &subject)?;
+
+    let (behavior, result) = match matched.action {
+        policy::ApprovalAction::Allow => ("allow", format!("auto_allowed: {}", matched.pattern)),
+        policy::ApprovalAction::Deny => ("deny", format!("auto_denied: {}", matched.pattern)),
+        policy::ApprovalAction::Ask => {
+            tracing::info!("[policy] tool={} matched ask policy id={}", tool_name, matched.id);
+            return None;
+        }
+    };
+    tracing::info!(
+        "[policy] tool={} subject={} -> {} (policy id={})",
+        tool_name, subject, behavior, matched.id
+    );
+    append_hook_log(&format!("🛡️ 自动审批: tool={}, {}", tool_name, result));
+
+    if let Err(err) = save_hook_record(
+        hook_input,
+        &hook_input.hook_event_name,
+        permission_summary,
+        permission_summary,
+        &result,
+    ) {
+        tracing::error!("Failed to save hook record: {}", err);
     }
-    if !has_session {
-        let alter_sql = format!(
-            "ALTER TABLE {} ADD COLUMN session_id TEXT NOT NULL DEFAULT ''",
-            table_name
-        );
-        conn.execute(&alter_sql, [])?;
-    }
-    Ok(())
+
+    let message = format!("Sparky 自动审批策略 {} ({})", behavior, matched.pattern);
+    hooks::send_hook_output(&hooks::HookOutput::permission_decision(behavior, message));
+    Some(Ok(()))
 }
 
 fn cleanup_legacy_hook_records(conn: &Connection) -> Result<()> {
@@ -655,48 +918,47 @@ fn cleanup_legacy_hook_records(conn: &Connection) -> Result<()> {
     Ok(())
 }
 
+/// project_path / session_id / transcript_path 以及 tool_name、permission_mode 元数据取自 hook_input
 fn save_hook_record(
-    project_path: &str,
+    hook_input: &hooks::HookInput,
     event_name: &str,
-    session_id: &str,
     notification_text: &str,
-    transcript_path: &str,
     content: &str,
     result: &str,
 ) -> Result<i64> {
-    let db_path = get_db_path();
+    let project_path = hook_input.cwd.as_str();
+    let db_path = migrations::db_path()?;
     tracing::info!(
         "[db:save] opening DB: {:?}, project_path={}, event={}",
         db_path, project_path, event_name
     );
-    let conn = Connection::open(&db_path)?;
+    let conn = migrations::open_connection(&db_path)?;
     cleanup_legacy_hook_records(&conn)?;
-    let table_name = project_hooks_table_name(project_path);
+    let table_name = project_tables::project_hooks_table_name(&conn, project_path)?;
     tracing::info!("[db:save] table_name={}", table_name);
-    ensure_project_hooks_table(&conn, &table_name)?;
-    let created_at = SystemTime::now()
-        .duration_since(UNIX_EPOCH)
-        .unwrap_or_default()
-        .as_millis() as i64;
+    project_tables::ensure_project_hooks_table(&conn, &table_name)?;
+    let created_at = project_tables::now_millis();
     let insert_sql = format!(
-        "INSERT INTO {} (event_name, session_id, notification_text, transcript_path, content, result, created_at)
-         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+        "INSERT INTO {} (event_name, session_id, notification_text, transcript_path, content, result, created_at, tool_name, permission_mode)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9)",
         table_name
     );
     tracing::info!(
         "[db:save] inserting: event={}, session={}, content_len={}, result={}",
-        event_name, session_id, content.len(), result
+        event_name, hook_input.session_id, content.len(), result
     );
     match conn.execute(
         &insert_sql,
         params![
             event_name,
-            session_id,
+            hook_input.session_id,
             notification_text,
-            transcript_path,
+            hook_input.transcript_path,
             content,
             result,
-            created_at
+            created_at,
+            hook_input.tool_name,
+            hook_input.permission_mode
         ],
     ) {
         Ok(rows) => tracing::info!("[db:save] INSERT affected {} rows", rows),
@@ -721,35 +983,37 @@ fn save_hook_record(
 }
 
 fn update_hook_record(
-    project_path: &str,
+    hook_input: &hooks::HookInput,
     id: i64,
     event_name: &str,
-    session_id: &str,
     notification_text: &str,
-    transcript_path: &str,
     content: &str,
     result: &str,
 ) -> Result<()> {
-    let db_path = get_db_path();
+    let project_path = hook_input.cwd.as_str();
+    let db_path = migrations::db_path()?;
     tracing::info!("[db:update] opening DB: {:?}, id={}, event={}", db_path, id, event_name);
-    let conn = Connection::open(&db_path)?;
+    let conn = migrations::open_connection(&db_path)?;
     cleanup_legacy_hook_records(&conn)?;
-    let table_name = project_hooks_table_name(project_path);
+    let table_name = project_tables::project_hooks_table_name(&conn, project_path)?;
     tracing::info!("[db:update] table_name={}", table_name);
-    ensure_project_hooks_table(&conn, &table_name)?;
+    project_tables::ensure_project_hooks_table(&conn, &table_name)?;
     let update_sql = format!(
-        "UPDATE {} SET event_name = ?1, session_id = ?2, notification_text = ?3, transcript_path = ?4, content = ?5, result = ?6 WHERE id = ?7",
+        "UPDATE {} SET event_name = ?1, session_id = ?2, notification_text = ?3, transcript_path = ?4, content = ?5, result = ?6,
+         tool_name = ?7, permission_mode = ?8 WHERE id = ?9",
         table_name
     );
     match conn.execute(
         &update_sql,
         params![
             event_name,
-            session_id,
+            hook_input.session_id,
             notification_text,
-            transcript_path,
+            hook_input.transcript_path,
             content,
             result,
+            hook_input.tool_name,
+            hook_input.permission_mode,
             id
         ],
     ) {
@@ -762,26 +1026,235 @@ fn update_hook_record(
     Ok(())
 }
 
-async fn run_test(config: &config::Config, chat_id: Option<String>) -> Result<()> {
+/// 按配置的通知后端发送消息，返回消息 ID（飞书 message_id / 钉钉 processQueryKey）
+/// 钉钉不支持卡片按钮，actions 会被忽略
+async fn send_via_backend(
+    config: &config::Config,
+    receive_id: &str,
+    receive_id_type: &str,
+    content: String,
+    actions: Option<Vec<feishu::CardAction>>,
+) -> Result<String> {
+    if config.is_dingtalk_backend() {
+        let dingtalk = config
+            .dingtalk
+            .as_ref()
+            .ok_or_else(|| anyhow::anyhow!("DingTalk config not found"))?;
+        let client = dingtalk::DingTalkClient::new(
+            dingtalk.app_key.clone(),
+            dingtalk.app_secret.clone(),
+            config.proxy_url.as_deref(),
+        );
+        return client.send_message(receive_id, &content).await;
+    }
+
+    if config.is_wework_backend() {
+        let wework = config
+            .wework
+            .as_ref()
+            .ok_or_else(|| anyhow::anyhow!("WeWork config not found"))?;
+        let client = wework::WeWorkClient::new(
+            wework.corp_id.clone(),
+            wework.corp_secret.clone(),
+            config.proxy_url.as_deref(),
+        );
+        // 企业微信按钮没有回调通道，不发送交互卡片，选项以文本列出
+        let actions = actions.unwrap_or_default();
+        let content = wework::actions_text(&content, &actions);
+        let message_id = client.send_message(receive_id, &content).await?;
+        // 需要确认时另以应用消息提醒配置的成员
+        if !actions.is_empty() {
+            match (wework.agent_id.as_deref(), wework.to_user.as_deref()) {
+                (Some(agent_id), Some(to_user)) if !agent_id.is_empty() && !to_user.is_empty() => {
+                    if let Err(err) = client.send_user_message(agent_id, to_user, &content).await {
+                        tracing::error!("[wework] failed to send permission reminder: {}", err);
+                    }
+                }
+                _ => tracing::warn!("[wework] agent_id/to_user not configured, skipping permission reminder"),
+            }
+        }
+        return Ok(message_id);
+    }
+
+    let feishu_client = feishu::FeishuClient::new(
+        config.app_id.clone(),
+        config.app_secret.clone(),
+        config.proxy_url.as_deref(),
+    );
+    feishu_client
+        .send_message(receive_id, content, actions, receive_id_type)
+        .await
+}
+
+fn ask_user_question_actions(question_options: &[Vec<String>]) -> Vec<feishu::CardAction> {
+    let multiple = question_options.len() > 1;
+    let mut actions = Vec::new();
+    for (i, options) in question_options.iter().enumerate() {
+        for (j, label) in options.iter().enumerate() {
+            let content = if multiple {
+                format!("Q{} · {}. {}", i + 1, j + 1, label)
+            } else {
+                format!("{}. {}", j + 1, label)
+            };
+            actions.push(feishu::CardAction {
+                tag: "button".to_string(),
+                text: feishu::CardText {
+                    content,
+                    tag: "plain_text".to_string(),
+                },
+                action_type: if j == 0 { "primary" } else { "default" }.to_string(),
+                value: serde_json::json!({"question_index": i, "option_index": j}),
+            });
+        }
+    }
+    actions
+}
+
+/// 权限请求的默认 Yes / No 按钮
+fn yes_no_actions() -> Vec<feishu::CardAction> {
+    vec![
+        feishu::CardAction {
+            tag: "button".to_string(),
+            text: feishu::CardText {
+                content: "✅ Yes (1)".to_string(),
+                tag: "plain_text".to_string(),
+            },
+            action_type: "primary".to_string(),
+            value: serde_json::json!({"choice": "1"}),
+        },
+        feishu::CardAction {
+            tag: "button".to_string(),
+            text: feishu::CardText {
+                content: "❌ No (2)".to_string(),
+                tag: "plain_text".to_string(),
+            },
+            action_type: "danger".to_string(),
+            value: serde_json::json!({"choice": "2"}),
+        },
+    ]
+}
+
+/// 测试卡片的按钮：回调值与真实权限按钮不同，点击后不会被当作用户选择
+fn test_card_actions() -> Vec<feishu::CardAction> {
+    yes_no_actions()
+        .into_iter()
+        .enumerate()
+        .map(|(i, action)| feishu::CardAction {
+            value: serde_json::json!({"test": (i + 1).to_string()}),
+            ..action
+        })
+        .collect()
+}
+
+const TEST_CARD_CONTENT: &str =
+    "🧪 **Claude Monitor 权限卡片测试**\n\n这是一条测试权限请求，点击按钮不会执行任何操作。";
+
+/// 飞书接收者及其类型：explicit_id（命令行/环境变量）优先于配置的 chat_id；
+/// 类型取 --receive-type，其次是配置的 receive_id_type（仅用于配置的 chat_id），最后按 ID 前缀推断
+fn resolve_feishu_target(
+    explicit_id: Option<String>,
+    receive_type: Option<&str>,
+    config: &config::Config,
+) -> Result<Option<(String, &'static str)>> {
+    let explicit_type = receive_type.map(feishu::parse_receive_id_type).transpose()?;
+    let target = match explicit_id {
+        Some(id) => {
+            let inferred = feishu::receive_id_type_for(&id);
+            Some((id, explicit_type.unwrap_or(inferred)))
+        }
+        None => config.chat_id.clone().filter(|id| !id.is_empty()).map(|id| {
+            let receive_id_type = explicit_type
+                .or_else(|| config.receive_id_type())
+                .unwrap_or_else(|| feishu::receive_id_type_for(&id));
+            (id, receive_id_type)
+        }),
+    };
+    Ok(target)
+}
+
+async fn run_test(
+    config: &config::Config,
+    chat_id: Option<String>,
+    receive_type: Option<String>,
+    with_actions: bool,
+) -> Result<()> {
+    if receive_type.is_some() && (config.is_dingtalk_backend() || config.is_wework_backend()) {
+        tracing::warn!("--receive-type only applies to the Feishu backend and is ignored");
+    }
+    if with_actions && config.is_dingtalk_backend() {
+        tracing::warn!("DingTalk does not support card buttons; --with-actions sends plain text only");
+    }
+    if with_actions && config.is_wework_backend() {
+        tracing::warn!("WeWork card buttons have no callback; --with-actions lists the options as text");
+    }
+    if config.is_dingtalk_backend() {
+        let target = chat_id
+            .or_else(|| config.dingtalk.as_ref().and_then(|d| d.chat_id.clone()))
+            .ok_or_else(|| anyhow::anyhow!("DingTalk conversation ID not provided. Use --chat-id or configure it in the desktop app."))?;
+        tracing::info!("Sending test message to DingTalk...");
+        send_via_backend(
+            config,
+            &target,
+            "open_conversation_id",
+            "🧪 **Claude Monitor 连接成功！**".to_string(),
+            None,
+        )
+        .await?;
+        tracing::info!("Test message sent successfully to conversation: {}", target);
+        return Ok(());
+    }
+
+    if config.is_wework_backend() {
+        let target = chat_id
+            .or_else(|| config.wework.as_ref().and_then(|w| w.chat_id.clone()))
+            .ok_or_else(|| anyhow::anyhow!("WeWork chat ID not provided. Use --chat-id or configure it in the desktop app."))?;
+        tracing::info!("Sending test message to WeWork...");
+        if with_actions {
+            send_via_backend(config, &target, "chatid", TEST_CARD_CONTENT.to_string(), Some(test_card_actions())).await?;
+        } else {
+            send_via_backend(config, &target, "chatid", "🧪 **Claude Monitor 连接成功！**".to_string(), None).await?;
+        }
+        tracing::info!("Test message sent successfully to chat: {}", target);
+        return Ok(());
+    }
+
+
     // 优先使用命令行参数，其次使用配置文件
-    let target_chat_id = chat_id
+    let explicit_id = chat_id
         .or_else(|| std::env::var("FEISHU_CHAT_ID").ok())
-        .or_else(|| std::env::var("CLAUDE_MONITOR_CHAT_ID").ok())
-        .or_else(|| config.chat_id.clone())
+        .or_else(|| std::env::var("CLAUDE_MONITOR_CHAT_ID").ok());
+    let (target_chat_id, receive_id_type) = resolve_feishu_target(explicit_id, receive_type.as_deref(), config)?
         .ok_or_else(|| anyhow::anyhow!("Chat ID not provided. Use --chat-id, set FEISHU_CHAT_ID, or configure it in the desktop app."))?;
     
-    tracing::info!("Sending test message to Feishu...");
+    tracing::info!("Sending test message to Feishu ({})...", receive_id_type);
     
     let feishu_client = feishu::FeishuClient::new(
         config.app_id.clone(),
         config.app_secret.clone(),
+        config.proxy_url.as_deref(),
     );
     
+    if with_actions {
+        // 不走 send_message 的纯文本降级，卡片被拒绝时直接报出飞书错误码
+        feishu_client
+            .send_card(
+                &target_chat_id,
+                receive_id_type,
+                TEST_CARD_CONTENT.to_string(),
+                Some(test_card_actions()),
+            )
+            .await
+            .map_err(|e| anyhow::anyhow!("{} (check that the bot has permission to send interactive cards)", e))?;
+        tracing::info!("Interactive permission card sent successfully to chat: {}", target_chat_id);
+        return Ok(());
+    }
+
     feishu_client
-        .send_notification(
+        .send_message(
+            &target_chat_id,
             "🧪 **Claude Monitor 连接成功！**".to_string(),
             None,
-            &target_chat_id,
+            receive_id_type,
         )
         .await?;
     
@@ -789,24 +1262,233 @@ async fn run_test(config: &config::Config, chat_id: Option<String>) -> Result<()
     Ok(())
 }
 
-async fn run_connect(config: &config::Config) -> Result<()> {
-    tracing::info!("Starting Feishu WebSocket long connection...");
-    tracing::info!("App ID: {}", config.app_id);
+/// 组装 send 子命令的消息：有标题时作为加粗首行
+fn compose_send_message(title: Option<&str>, body: &str) -> String {
+    let body = body.trim_end();
+    match title.map(str::trim).filter(|t| !t.is_empty()) {
+        Some(title) if body.is_empty() => format!("**{}**", title),
+        Some(title) => format!("**{}**\n\n{}", title, body),
+        None => body.to_string(),
+    }
+}
+
+async fn run_send(
+    config: &config::Config,
+    text: Option<String>,
+    title: Option<String>,
+    chat_id: Option<String>,
+    receive_type: Option<String>,
+) -> Result<()> {
+    let body = match text {
+        Some(text) => text,
+        None => {
+            let mut input = String::new();
+            std::io::stdin().read_to_string(&mut input)?;
+            input
+        }
+    };
+    let content = compose_send_message(title.as_deref(), &body);
+    if content.trim().is_empty() {
+        anyhow::bail!("Message is empty. Pass --text or pipe the message via stdin.");
+    }
+
+    if config.is_dingtalk_backend() || config.is_wework_backend() {
+        let target = chat_id
+            .or_else(|| config.dingtalk.as_ref().and_then(|d| d.chat_id.clone()))
+            .or_else(|| config.wework.as_ref().and_then(|w| w.chat_id.clone()))
+            .ok_or_else(|| anyhow::anyhow!("Chat ID not provided. Use --chat-id or configure it in the desktop app."))?;
+        send_via_backend(config, &target, "", content, None).await?;
+        return Ok(());
+    }
+
+    let explicit_id = chat_id.or_else(|| std::env::var("FEISHU_CHAT_ID").ok());
+    let (target, receive_id_type) = resolve_feishu_target(explicit_id, receive_type.as_deref(), config)?
+        .or_else(|| config.open_id.clone().filter(|id| !id.is_empty()).map(|id| (id, "open_id")))
+        .ok_or_else(|| anyhow::anyhow!("Chat ID not provided. Use --chat-id, set FEISHU_CHAT_ID, or configure it in the desktop app."))?;
+
+    let feishu_client = feishu::FeishuClient::new(
+        config.app_id.clone(),
+        config.app_secret.clone(),
+        config.proxy_url.as_deref(),
+    );
+    feishu_client.send_message(&target, content, None, receive_id_type).await?;
+    tracing::info!("Message sent to {} ({})", target, receive_id_type);
+    Ok(())
+}
+
+fn run_config(action: &ConfigAction) -> Result<()> {
+    let conn = config::open_config_db()?;
+    match action {
+        ConfigAction::Get { key } => match config::get_value(&conn, key)? {
+            Some(value) => println!("{}", value),
+            None => println!("(not set)"),
+        },
+        ConfigAction::Set { key, value } => {
+            config::set_value(&conn, key, value)?;
+            println!("{} updated", key);
+        }
+        ConfigAction::List => {
+            for (key, value) in config::list_values(&conn)? {
+                println!("{} = {}", key, value.as_deref().unwrap_or("(not set)"));
+            }
+        }
+        ConfigAction::Apps { action } => run_config_apps(&conn, action)?,
+    }
+    Ok(())
+}
+
+fn run_config_apps(conn: &Connection, action: &AppsAction) -> Result<()> {
+    match action {
+        AppsAction::List => {
+            let apps = feishu_apps::list(conn).map_err(anyhow::Error::msg)?;
+            if apps.is_empty() {
+                println!("(no extra apps)");
+            }
+            for app in apps {
+                println!(
+                    "{} {}{}{}",
+                    app.app_id,
+                    if app.enabled { "enabled" } else { "disabled" },
+                    if app.has_encrypt_key { ", encrypt_key set" } else { "" },
+                    if app.has_verification_token { ", verification_token set" } else { "" },
+                );
+            }
+        }
+        AppsAction::Add { app_id, app_secret, encrypt_key, verification_token } => {
+            feishu_apps::upsert(conn, app_id, app_secret, encrypt_key.as_deref(), verification_token.as_deref())
+                .map_err(anyhow::Error::msg)?;
+            println!("{} saved", app_id);
+        }
+        AppsAction::Remove { app_id } => {
+            if !feishu_apps::remove(conn, app_id).map_err(anyhow::Error::msg)? {
+                anyhow::bail!("No extra app with app_id '{}'", app_id);
+            }
+            println!("{} removed", app_id);
+        }
+        AppsAction::Enable { app_id } | AppsAction::Disable { app_id } => {
+            let enabled = matches!(action, AppsAction::Enable { .. });
+            if !feishu_apps::set_enabled(conn, app_id, enabled).map_err(anyhow::Error::msg)? {
+                anyhow::bail!("No extra app with app_id '{}'", app_id);
+            }
+            println!("{} {}", app_id, if enabled { "enabled" } else { "disabled" });
+        }
+    }
+    Ok(())
+}
+
+async fn run_connect(config: &config::Config, health_port: Option<u16>) -> Result<()> {
+    let apps = config.connect_apps()?;
+    if apps.is_empty() {
+        anyhow::bail!("No Feishu app with app_id/app_secret configured for long connection");
+    }
+    tracing::info!("Starting Feishu WebSocket long connection for {} app(s)...", apps.len());
 
-    // 启动 hook.log tail 监视任务
+    // 启动 hook.log tail 监视任务（与应用数量无关，只启动一次）
     tokio::spawn(async {
         if let Err(e) = tail_hook_log().await {
             tracing::error!("Hook log watcher error: {}", e);
         }
     });
-    
-    let client = websocket::FeishuWsClient::new(
-        config.app_id.clone(),
-        config.app_secret.clone(),
+
+    let clients: Vec<std::sync::Arc<websocket::FeishuWsClient>> = apps
+        .iter()
+        .map(|app| {
+            tracing::info!("App ID: {}", app.app_id);
+            std::sync::Arc::new(
+                websocket::FeishuWsClient::new(app.app_id.clone(), app.app_secret.clone(), config.proxy_url.clone())
+                    .with_event_security(app.encrypt_key.clone(), app.verification_token.clone()),
+            )
+        })
+        .collect();
+
+    if let Some(port) = health_port {
+        let health_clients = clients.clone();
+        tokio::spawn(async move {
+            if let Err(e) = health::serve(port, health_clients).await {
+                tracing::error!("Health endpoint error: {}", e);
+            }
+        });
+    }
+
+    // 每个应用一个独立的重连任务，日志通过 span 标注 app_id
+    let mut tasks = tokio::task::JoinSet::new();
+    for (app, client) in apps.iter().zip(clients.iter().cloned()) {
+        let span = tracing::info_span!("app", app_id = %app.app_id);
+        tasks.spawn(tracing::Instrument::instrument(run_app_connection(client), span));
+    }
+
+    let mut signal = std::pin::pin!(shutdown_signal());
+    loop {
+        tokio::select! {
+            _ = &mut signal => break,
+            joined = tasks.join_next() => match joined {
+                Some(Err(e)) if !e.is_cancelled() => tracing::error!("Connection task failed: {}", e),
+                Some(_) => {}
+                None => return Ok(()),
+            },
+        }
+    }
+
+    // 第一次信号：通知所有连接发送 Close 帧后退出；等待期间再次收到信号则立即退出
+    tracing::info!("Shutdown signal received, closing {} connection(s)...", tasks.len());
+    for client in &clients {
+        client.shutdown();
+    }
+    let drained = tokio::select! {
+        drained = tokio::time::timeout(SHUTDOWN_GRACE_PERIOD, async {
+            while tasks.join_next().await.is_some() {}
+        }) => drained.is_ok(),
+        _ = shutdown_signal() => {
+            eprintln!("Second signal received, exiting immediately");
+            std::process::exit(130);
+        }
+    };
+    if !drained {
+        tracing::warn!("Connections did not close within {:?}, aborting", SHUTDOWN_GRACE_PERIOD);
+        tasks.abort_all();
+    }
+    tracing::info!("Shutdown complete");
+    Ok(())
+}
+
+/// 优雅退出时等待各连接关闭的最长时间
+const SHUTDOWN_GRACE_PERIOD: std::time::Duration = std::time::Duration::from_secs(5);
+
+/// 等待 SIGINT（Ctrl-C）或 SIGTERM
+async fn shutdown_signal() {
+    #[cfg(unix)]
+    {
+        use tokio::signal::unix::{signal, SignalKind};
+        match signal(SignalKind::terminate()) {
+            Ok(mut sigterm) => {
+                tokio::select! {
+                    _ = tokio::signal::ctrl_c() => {}
+                    _ = sigterm.recv() => {}
+                }
+            }
+            Err(e) => {
+                tracing::warn!("Failed to install SIGTERM handler: {}", e);
+                let _ = tokio::signal::ctrl_c().await;
+            }
+        }
+    }
+    #[cfg(not(unix))]
+    {
+        let _ = tokio::signal::ctrl_c().await;
+    }
+}
+
+/// 单个应用的长连接循环：指数退避 + 抖动重连，稳定连接后重置
+async fn run_app_connection(client: std::sync::Arc<websocket::FeishuWsClient>) {
+    let mut backoff = backoff::ReconnectBackoff::new(
+        std::time::Duration::from_secs(1),
+        std::time::Duration::from_secs(60),
     );
-    
-    // 带重连机制
     loop {
+        if client.is_shutting_down() {
+            break;
+        }
+        let started = std::time::Instant::now();
         match client.connect().await {
             Ok(_) => {
                 tracing::info!("WebSocket connection closed normally");
@@ -815,11 +1497,24 @@ async fn run_connect(config: &config::Config) -> Result<()> {
                 tracing::error!("WebSocket connection error: {}", e);
             }
         }
-        
-        // 等待 5 秒后重连
-        tracing::info!("Reconnecting in 5 seconds...");
-        tokio::time::sleep(std::time::Duration::from_secs(5)).await;
+
+        if let Some(base) = client.reconnect_interval() {
+            backoff.set_base(base);
+        }
+        if started.elapsed() >= backoff::STABLE_CONNECTION {
+            backoff.reset();
+        }
+        if client.is_shutting_down() {
+            break;
+        }
+        let delay = backoff.next_delay();
+        tracing::info!("Reconnecting in {:.1} seconds...", delay.as_secs_f64());
+        tokio::select! {
+            _ = tokio::time::sleep(delay) => {}
+            _ = client.wait_for_shutdown() => break,
+        }
     }
+    tracing::info!("Connection loop stopped");
 }
 
 /// 获取 hook.log 路径
@@ -893,7 +1588,97 @@ fn get_pty_log_path(project_path: &str) -> PathBuf {
     home.join("sparky/pty_logs").join(format!("{}.log", safe_name))
 }
 
-fn read_terminal_prompt(project_path: &str) -> Option<String> {
+/// 追加 Claude 输出、回复与 transcript 段落，返回其中实际提取到的正文字符数
+/// （不含段落标题、解析失败的保底内容与读取错误，用于判断通知是否为空）
+fn append_reply_sections(
+    content: &mut String,
+    event_name: &str,
+    final_response: &str,
+    transcript_path: &str,
+    compact: bool,
+    config: &config::Config,
+) -> usize {
+    let mut reply_chars = 0;
+
+    // Stop hook - 显示 Claude 的输出内容
+    if !final_response.is_empty() {
+        content.push_str("\n\n**Claude 输出**\n");
+        // 限制长度
+        let max_response = config.response_limit();
+        let total_chars = final_response.chars().count();
+        reply_chars += total_chars;
+        let truncated = if total_chars > max_response {
+            format!(
+                "{}...\n\n（省略 {} 字符）",
+                text::truncate_chars(final_response, max_response),
+                total_chars - max_response
+            )
+        } else {
+            final_response.to_string()
+        };
+        content.push_str(&truncated);
+    }
+
+    // Stop hook - 从 transcript 中提取最新的 Claude 回复
+    if event_name == "Stop" && !transcript_path.is_empty() {
+        match std::fs::read_to_string(transcript_path) {
+            Ok(transcript) => {
+                // 提取最新的交流过程（文本、工具调用摘要，无文本时附带 thinking 摘要）
+                let session_elements = transcript::extract_assistant_summary(&transcript);
+
+                if !session_elements.is_empty() {
+                    reply_chars += session_elements.iter().map(|el| el.trim().chars().count()).sum::<usize>();
+                    content.push_str("\n\n**Claude 回复**\n");
+                    for el in &session_elements {
+                        content.push_str(el);
+                        content.push_str("\n\n");
+                    }
+                } else {
+                    // 如果没有提取到，显示最后 3 行作为保底
+                    content.push_str("\n\n**Claude 回复**\n（无法解析转录）\n");
+                    let last_lines: Vec<&str> = transcript.lines().rev().take(3).collect();
+                    for line in last_lines.iter().rev() {
+                        content.push_str(line);
+                        content.push_str("\n");
+                    }
+                }
+            }
+            Err(err) => {
+                content.push_str("\n\n**Claude 回复**\n读取失败: ");
+                content.push_str(&err.to_string());
+            }
+        }
+    } else if !compact && !transcript_path.is_empty() && event_name != "UserPromptSubmit" && event_name != "PermissionRequest" && event_name != "Stop" {
+        // 其他事件读取 transcript（除了 Stop 和 PermissionRequest）
+        match std::fs::read_to_string(transcript_path) {
+            Ok(transcript) => {
+                content.push_str("\n\n**Transcript**\n");
+                reply_chars += transcript.trim().chars().count();
+                // 只保留末尾部分
+                let max_transcript = config.transcript_limit();
+                let total_chars = transcript.chars().count();
+                let truncated = if total_chars > max_transcript {
+                    format!(
+                        "...（省略 {} 字符）\n\n{}",
+                        total_chars - max_transcript,
+                        text::tail_chars(&transcript, max_transcript)
+                    )
+                } else {
+                    transcript
+                };
+                content.push_str(&truncated);
+            }
+            Err(err) => {
+                content.push_str("\n\n**Transcript**\n读取失败: ");
+                content.push_str(&err.to_string());
+            }
+        }
+    }
+
+    reply_chars
+}
+
+fn read_terminal_prompt(project_path: &str, triggers: &[String]) -> Option<terminal_prompt::TerminalPrompt> {
     let log_path = get_pty_log_path(project_path);
     let mut file = File::open(log_path).ok()?;
     let metadata = file.metadata().ok()?;
@@ -908,16 +1693,67 @@ fn read_terminal_prompt(project_path: &str) -> Option<String> {
     }
     file.read_exact(&mut buf).ok()?;
     
-    let content = String::from_utf8_lossy(&buf);
-    
-    // Look for "Do you want to proceed?"
-    if let Some(pos) = content.rfind("Do you want to proceed?") {
-        let prompt_part = &content[pos..];
-        // Only take lines up to some reasonable limit or until next prompt?
-        // Prompt ends with user input.
-        // Assuming we just want to show the prompt and options.
-        return Some(prompt_part.trim().to_string());
+    let content = text::strip_ansi(&String::from_utf8_lossy(&buf));
+    let mut prompt = terminal_prompt::parse_terminal_prompt(&content, triggers)?;
+
+    // 捕获的提示会发送到飞书，先脱敏
+    let redactor = config::open_config_db()
+        .and_then(|conn| redact::load(&conn).map_err(anyhow::Error::from))
+        .unwrap_or_else(|err| {
+            tracing::error!("[read_terminal_prompt] failed to load redact config: {}", err);
+            redact::RedactConfig::default()
+        })
+        .compile();
+    prompt.question = redactor.redact(&prompt.question);
+    prompt.options = prompt.options.iter().map(|option| redactor.redact(option)).collect();
+    prompt.raw = redactor.redact(&prompt.raw);
+    Some(prompt)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn write_transcript(name: &str, lines: &[serde_json::Value]) -> std::path::PathBuf {
+        let path = std::env::temp_dir().join(format!("sparky-{}-{}.jsonl", name, std::process::id()));
+        let body: Vec<String> = lines.iter().map(|line| line.to_string()).collect();
+        std::fs::write(&path, body.join("\n")).unwrap();
+        path
+    }
+
+    #[test]
+    fn test_reply_chars_ignore_headers_and_fallback() {
+        let config = config::Config::default();
+        let path = write_transcript("stop-empty", &[serde_json::json!({
+            "type": "user",
+            "message": {"role": "user", "content": "你好"}
+        })]);
+        let mut content = "💬 Claude 回复\n\n".to_string();
+        let reply_chars = append_reply_sections(&mut content, "Stop", "", path.to_str().unwrap(), false, &config);
+        std::fs::remove_file(&path).ok();
+        // 保底内容会写入正文，但不算作提取到的回复
+        assert!(content.contains("无法解析转录"));
+        assert_eq!(reply_chars, 0);
+
+        let mut content = String::new();
+        assert_eq!(append_reply_sections(&mut content, "Notification", "", "", false, &config), 0);
+        assert_eq!(append_reply_sections(&mut content, "Stop", "", "/nonexistent/transcript.jsonl", false, &config), 0);
+    }
+
+    #[test]
+    fn test_reply_chars_count_extracted_reply() {
+        let config = config::Config::default();
+        let path = write_transcript("stop-reply", &[
+            serde_json::json!({"type": "user", "message": {"role": "user", "content": "列出文件"}}),
+            serde_json::json!({"type": "assistant", "message": {"role": "assistant", "content": [{"type": "text", "text": "只有 a.txt"}]}}),
+        ]);
+        let mut content = String::new();
+        let reply_chars = append_reply_sections(&mut content, "Stop", "", path.to_str().unwrap(), false, &config);
+        std::fs::remove_file(&path).ok();
+        assert!(reply_chars > 0);
+        assert!(content.contains("只有 a.txt"));
+
+        let mut content = String::new();
+        assert_eq!(append_reply_sections(&mut content, "Stop", "完成", "", false, &config), 2);
     }
-    
-    None
 }
@@ -0,0 +1,606 @@
+// Schema migrations and connection setup for ~/sparky/hooks.db, shared by the CLI and the desktop app
+// (src-tauri includes this file via #[path] so the two crates cannot drift)
+
+use rusqlite::{Connection, TransactionBehavior};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+/// ~/sparky/hooks.db，目录不存在时创建
+pub fn db_path() -> std::io::Result<PathBuf> {
+    let base_dir = dirs::home_dir()
+        .ok_or_else(|| std::io::Error::new(std::io::ErrorKind::NotFound, "Failed to get home directory"))?
+        .join("sparky");
+    std::fs::create_dir_all(&base_dir)?;
+    Ok(base_dir.join("hooks.db"))
+}
+
+/// CLI hook 进程与桌面应用并发写入时，等待对方释放锁的最长时间
+pub const BUSY_TIMEOUT: Duration = Duration::from_millis(5000);
+
+/// 打开数据库并完成连接级设置：WAL 让读写互不阻塞，busy_timeout 让写冲突等待而不是立即返回 SQLITE_BUSY
+pub fn open_connection(path: impl AsRef<Path>) -> rusqlite::Result<Connection> {
+    let conn = Connection::open(path)?;
+    configure_connection(&conn)?;
+    Ok(conn)
+}
+
+pub fn configure_connection(conn: &Connection) -> rusqlite::Result<()> {
+    conn.busy_timeout(BUSY_TIMEOUT)?;
+    let _mode: String = conn.pragma_update_and_check(None, "journal_mode", "WAL", |row| row.get(0))?;
+    Ok(())
+}
+
+/// 单个迁移步骤
+enum Step {
+    /// 执行 SQL（可包含多条语句），需保证对旧数据库重复执行无害
+    Sql(&'static str),
+    /// 添加列；旧版本可能已通过 ALTER TABLE 添加过，已存在时跳过
+    AddColumn {
+        table: &'static str,
+        column: &'static str,
+        decl: &'static str,
+    },
+    /// 需要条件判断的数据迁移
+    Custom(fn(&Connection) -> rusqlite::Result<()>),
+}
+
+struct Migration {
+    version: i64,
+    description: &'static str,
+    steps: &'static [Step],
+}
+
+/// 按版本顺序排列，只能追加，不能修改已发布的步骤
+const MIGRATIONS: &[Migration] = &[
+    Migration {
+        version: 1,
+        description: "base schema",
+        steps: &[Step::Sql(
+            "CREATE TABLE IF NOT EXISTS projects (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                name TEXT NOT NULL,
+                path TEXT NOT NULL,
+                hooks_installed INTEGER DEFAULT 0,
+                created_at INTEGER NOT NULL,
+                updated_at INTEGER NOT NULL
+            );
+            CREATE TABLE IF NOT EXISTS pty_commands (
+                id INTEGER PRIMARY KEY,
+                project_path TEXT NOT NULL,
+                command TEXT NOT NULL,
+                processed INTEGER DEFAULT 0,
+                created_at INTEGER
+            );
+            CREATE TABLE IF NOT EXISTS permission_requests (
+                id INTEGER PRIMARY KEY,
+                project_path TEXT NOT NULL,
+                status TEXT NOT NULL,
+                choice TEXT,
+                created_at INTEGER
+            );
+            CREATE TABLE IF NOT EXISTS attachments (
+                id INTEGER PRIMARY KEY,
+                message_id TEXT NOT NULL,
+                message_type TEXT NOT NULL,
+                file_path TEXT NOT NULL,
+                sender TEXT,
+                consumed INTEGER DEFAULT 0,
+                created_at INTEGER
+            );
+            CREATE TABLE IF NOT EXISTS terminal_input_history (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                project_path TEXT NOT NULL,
+                input TEXT NOT NULL,
+                created_at INTEGER NOT NULL
+            );
+            CREATE TABLE IF NOT EXISTS terminal_history (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                project_path TEXT NOT NULL,
+                kind TEXT NOT NULL,
+                content TEXT NOT NULL,
+                created_at INTEGER NOT NULL
+            );
+            CREATE TABLE IF NOT EXISTS task_logs (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                task_id TEXT NOT NULL,
+                stream TEXT NOT NULL,
+                content TEXT NOT NULL,
+                created_at INTEGER NOT NULL
+            );
+            CREATE TABLE IF NOT EXISTS ws_connection_log (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                event TEXT NOT NULL,
+                reason TEXT NOT NULL DEFAULT '',
+                created_at INTEGER NOT NULL
+            );
+            CREATE TABLE IF NOT EXISTS auto_approval_policies (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                tool TEXT NOT NULL,
+                regex_pattern TEXT NOT NULL,
+                action TEXT NOT NULL,
+                created_at INTEGER NOT NULL
+            );
+            CREATE TABLE IF NOT EXISTS app_config_feishu (
+                id INTEGER PRIMARY KEY CHECK (id = 1),
+                app_id TEXT NOT NULL,
+                app_secret TEXT NOT NULL,
+                encrypt_key TEXT,
+                verification_token TEXT,
+                chat_id TEXT,
+                project_path TEXT,
+                updated_at INTEGER NOT NULL
+            );
+            CREATE TABLE IF NOT EXISTS app_config_dingtalk (
+                id INTEGER PRIMARY KEY CHECK (id = 1),
+                app_id TEXT NOT NULL,
+                app_secret TEXT NOT NULL,
+                encrypt_key TEXT,
+                verification_token TEXT,
+                chat_id TEXT,
+                project_path TEXT,
+                updated_at INTEGER NOT NULL
+            );
+            CREATE TABLE IF NOT EXISTS app_config_wework (
+                id INTEGER PRIMARY KEY CHECK (id = 1),
+                app_id TEXT NOT NULL,
+                app_secret TEXT NOT NULL,
+                encrypt_key TEXT,
+                verification_token TEXT,
+                chat_id TEXT,
+                project_path TEXT,
+                updated_at INTEGER NOT NULL
+            );
+            CREATE TABLE IF NOT EXISTS db_meta (
+                key TEXT PRIMARY KEY,
+                value TEXT NOT NULL
+            );",
+        )],
+    },
+    Migration {
+        version: 2,
+        description: "app_config_feishu: open_id, hook_events_filter",
+        steps: &[
+            Step::AddColumn { table: "app_config_feishu", column: "open_id", decl: "TEXT" },
+            Step::AddColumn { table: "app_config_feishu", column: "hook_events_filter", decl: "TEXT" },
+        ],
+    },
+    Migration {
+        version: 3,
+        description: "legacy cleanup v1",
+        steps: &[Step::Custom(cleanup_legacy_v1)],
+    },
+    Migration {
+        version: 4,
+        description: "app_config_feishu: display, proxy, notification and limit settings",
+        steps: &[
+            Step::AddColumn { table: "app_config_feishu", column: "app_name", decl: "TEXT" },
+            Step::AddColumn { table: "app_config_feishu", column: "proxy_url", decl: "TEXT" },
+            Step::AddColumn { table: "app_config_feishu", column: "urgent_events", decl: "TEXT" },
+            Step::AddColumn { table: "app_config_feishu", column: "message_style", decl: "TEXT" },
+            Step::AddColumn {
+                table: "app_config_feishu",
+                column: "max_notifications_per_session_per_minute",
+                decl: "INTEGER",
+            },
+            Step::AddColumn { table: "app_config_feishu", column: "backend", decl: "TEXT" },
+            Step::AddColumn { table: "app_config_feishu", column: "max_message_len", decl: "INTEGER" },
+            Step::AddColumn { table: "app_config_feishu", column: "max_db_content_len", decl: "INTEGER" },
+            Step::AddColumn { table: "app_config_feishu", column: "max_response_chars", decl: "INTEGER" },
+            Step::AddColumn { table: "app_config_feishu", column: "max_transcript_chars", decl: "INTEGER" },
+        ],
+    },
+    Migration {
+        version: 5,
+        description: "permission codes, project relay settings, wework card target",
+        steps: &[
+            Step::AddColumn { table: "permission_requests", column: "code", decl: "TEXT" },
+            Step::AddColumn { table: "projects", column: "relay_url", decl: "TEXT" },
+            Step::AddColumn { table: "projects", column: "worker_mode", decl: "TEXT" },
+            Step::AddColumn { table: "app_config_wework", column: "agent_id", decl: "TEXT" },
+            Step::AddColumn { table: "app_config_wework", column: "to_user", decl: "TEXT" },
+        ],
+    },
+    Migration {
+        version: 6,
+        description: "project_tables: project path to hook records table mapping",
+        steps: &[Step::Sql(
+            "CREATE TABLE IF NOT EXISTS project_tables (
+                path TEXT PRIMARY KEY,
+                table_name TEXT NOT NULL UNIQUE
+            );",
+        )],
+    },
+    Migration {
+        version: 7,
+        description: "hook records: created_at in milliseconds",
+        steps: &[Step::Custom(hook_records_created_at_to_millis)],
+    },
+    Migration {
+        version: 8,
+        description: "projects: per-project notification target",
+        steps: &[
+            Step::AddColumn { table: "projects", column: "chat_id", decl: "TEXT" },
+            Step::AddColumn { table: "projects", column: "open_id", decl: "TEXT" },
+        ],
+    },
+    Migration {
+        version: 9,
+        description: "coalescing of repeated notifications",
+        steps: &[
+            Step::AddColumn { table: "app_config_feishu", column: "coalesce_window_secs", decl: "INTEGER" },
+            Step::Sql(
+                "CREATE TABLE IF NOT EXISTS session_last_notification (
+                    session_id TEXT PRIMARY KEY,
+                    fingerprint TEXT NOT NULL,
+                    message_id TEXT,
+                    repeat_count INTEGER NOT NULL,
+                    last_seen_at INTEGER NOT NULL
+                );",
+            ),
+        ],
+    },
+    Migration {
+        version: 10,
+        description: "drop unused terminal_input_history",
+        // 终端输入统一记录在 terminal_history（kind = 'input'），该表从未写入过
+        steps: &[Step::Sql("DROP TABLE IF EXISTS terminal_input_history;")],
+    },
+    Migration {
+        version: 11,
+        description: "app_config_feishu: prompt_triggers",
+        steps: &[Step::AddColumn { table: "app_config_feishu", column: "prompt_triggers", decl: "TEXT" }],
+    },
+    Migration {
+        version: 12,
+        description: "app_config_feishu: min_notification_chars",
+        steps: &[Step::AddColumn { table: "app_config_feishu", column: "min_notification_chars", decl: "INTEGER" }],
+    },
+    Migration {
+        version: 13,
+        description: "app_config_feishu: receive_id_type",
+        steps: &[Step::AddColumn { table: "app_config_feishu", column: "receive_id_type", decl: "TEXT" }],
+    },
+    Migration {
+        version: 14,
+        description: "projects: claude launch settings for the local worker",
+        steps: &[
+            Step::AddColumn { table: "projects", column: "claude_binary", decl: "TEXT" },
+            Step::AddColumn { table: "projects", column: "claude_cwd", decl: "TEXT" },
+            Step::AddColumn { table: "projects", column: "claude_model", decl: "TEXT" },
+            Step::AddColumn { table: "projects", column: "claude_args", decl: "TEXT" },
+        ],
+    },
+    Migration {
+        version: 15,
+        description: "attachments: owning project and consuming task",
+        steps: &[
+            Step::AddColumn { table: "attachments", column: "project_path", decl: "TEXT" },
+            Step::AddColumn { table: "attachments", column: "task_id", decl: "TEXT" },
+        ],
+    },
+    Migration {
+        version: 16,
+        description: "project_tables: backfill existing projects",
+        steps: &[Step::Custom(backfill_project_tables)],
+    },
+    Migration {
+        version: 17,
+        description: "app_config_feishu_apps: extra Feishu apps for sparky connect",
+        steps: &[Step::Sql(
+            "CREATE TABLE IF NOT EXISTS app_config_feishu_apps (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                app_id TEXT NOT NULL UNIQUE,
+                app_secret TEXT NOT NULL,
+                encrypt_key TEXT,
+                verification_token TEXT,
+                enabled INTEGER NOT NULL DEFAULT 1
+            );",
+        )],
+    },
+    Migration {
+        version: 18,
+        description: "session_notification_rate: per-session notification cap",
+        steps: &[Step::Sql(
+            "CREATE TABLE IF NOT EXISTS session_notification_rate (
+                session_id TEXT PRIMARY KEY,
+                window_start INTEGER NOT NULL,
+                sent_count INTEGER NOT NULL,
+                suppressed_count INTEGER NOT NULL,
+                last_summary_at INTEGER NOT NULL
+            );",
+        )],
+    },
+    Migration {
+        version: 19,
+        description: "ws_connection_log: record which process (cli / desktop) wrote each event",
+        steps: &[Step::AddColumn { table: "ws_connection_log", column: "source", decl: "TEXT NOT NULL DEFAULT ''" }],
+    },
+    Migration {
+        version: 20,
+        description: "task_logs: indexes for per-task reads and retention pruning",
+        steps: &[Step::Sql(
+            "CREATE INDEX IF NOT EXISTS idx_task_logs_task_created ON task_logs (task_id, created_at);
+             CREATE INDEX IF NOT EXISTS idx_task_logs_created_at ON task_logs (created_at);",
+        )],
+    },
+];
+
+/// 当前代码对应的 schema 版本
+pub const LATEST_VERSION: i64 = MIGRATIONS[MIGRATIONS.len() - 1].version;
+
+/// 旧版 hook_records 单表与终端历史在 v1 清理时删除；已由旧版本清理过（db_meta 有标记）则跳过
+fn cleanup_legacy_v1(conn: &Connection) -> rusqlite::Result<()> {
+    let cleaned: Option<String> = conn
+        .query_row("SELECT value FROM db_meta WHERE key = 'cleanup_legacy_v1'", [], |row| row.get(0))
+        .ok();
+    if cleaned.is_some() {
+        return Ok(());
+    }
+    conn.execute_batch(
+        "DROP TABLE IF EXISTS hook_records;
+         DELETE FROM terminal_history;
+         DELETE FROM terminal_input_history;
+         INSERT OR IGNORE INTO db_meta (key, value) VALUES ('cleanup_legacy_v1', '1');",
+    )
+}
+
+/// 小于该值的 created_at 视为秒级时间戳（毫秒时间戳自 1973 年起就超过该值）
+const MILLIS_THRESHOLD: i64 = 100_000_000_000;
+
+/// hook 记录的 created_at 统一为毫秒；早期写入的秒级时间戳换算为毫秒
+fn hook_records_created_at_to_millis(conn: &Connection) -> rusqlite::Result<()> {
+    let tables = {
+        let mut stmt = conn.prepare(
+            "SELECT name FROM sqlite_master WHERE type = 'table' AND name LIKE 'hook\\_records\\_%' ESCAPE '\\'",
+        )?;
+        let rows = stmt.query_map([], |row| row.get::<_, String>(0))?;
+        rows.collect::<rusqlite::Result<Vec<_>>>()?
+    };
+    for table in tables {
+        conn.execute(
+            &format!("UPDATE {} SET created_at = created_at * 1000 WHERE created_at < ?1", table),
+            [MILLIS_THRESHOLD],
+        )?;
+    }
+    Ok(())
+}
+
+/// v6 只创建了空的映射表：把 projects 中已有记录表（按路径哈希命名）的项目登记进去，
+/// 否则升级后按映射反查项目路径的查询会丢失这些表
+fn backfill_project_tables(conn: &Connection) -> rusqlite::Result<()> {
+    let paths = {
+        let mut stmt = conn.prepare("SELECT path FROM projects")?;
+        let rows = stmt.query_map([], |row| row.get::<_, String>(0))?;
+        rows.collect::<rusqlite::Result<Vec<_>>>()?
+    };
+    for path in paths {
+        if let Some(table_name) = crate::project_tables::lookup_project_hooks_table(conn, &path)? {
+            conn.execute(
+                "INSERT OR IGNORE INTO project_tables (path, table_name) VALUES (?1, ?2)",
+                rusqlite::params![path, table_name],
+            )?;
+        }
+    }
+    Ok(())
+}
+
+fn has_column(conn: &Connection, table: &str, column: &str) -> rusqlite::Result<bool> {
+    let mut stmt = conn.prepare(&format!("PRAGMA table_info({})", table))?;
+    let mut rows = stmt.query([])?;
+    while let Some(row) = rows.next()? {
+        if row.get::<_, String>(1)? == column {
+            return Ok(true);
+        }
+    }
+    Ok(false)
+}
+
+fn apply(conn: &Connection, step: &Step) -> rusqlite::Result<()> {
+    match step {
+        Step::Sql(sql) => conn.execute_batch(sql),
+        Step::AddColumn { table, column, decl } => {
+            if !has_column(conn, table, column)? {
+                conn.execute(&format!("ALTER TABLE {} ADD COLUMN {} {}", table, column, decl), [])?;
+            }
+            Ok(())
+        }
+        Step::Custom(f) => f(conn),
+    }
+}
+
+pub fn current_version(conn: &Connection) -> rusqlite::Result<i64> {
+    conn.query_row("PRAGMA user_version", [], |row| row.get(0))
+}
+
+/// 依次应用尚未执行的迁移，每个版本一个事务并记录到 PRAGMA user_version
+/// 使用 IMMEDIATE 事务并在事务内重新读取版本，CLI 与桌面应用同时启动时不会重复执行
+pub fn run(conn: &Connection) -> rusqlite::Result<()> {
+    if current_version(conn)? >= LATEST_VERSION {
+        return Ok(());
+    }
+    for migration in MIGRATIONS {
+        let tx = rusqlite::Transaction::new_unchecked(conn, TransactionBehavior::Immediate)?;
+        if current_version(&tx)? >= migration.version {
+            continue;
+        }
+        for step in migration.steps {
+            apply(&tx, step)?;
+        }
+        tx.pragma_update(None, "user_version", migration.version)?;
+        tx.commit()?;
+        tracing::info!("[migrations] applied v{}: {}", migration.version, migration.description);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_fresh_database_reaches_latest_version() {
+        let conn = Connection::open_in_memory().unwrap();
+        run(&conn).unwrap();
+        assert_eq!(current_version(&conn).unwrap(), LATEST_VERSION);
+        assert!(has_column(&conn, "app_config_feishu", "max_transcript_chars").unwrap());
+        assert!(has_column(&conn, "projects", "worker_mode").unwrap());
+        assert!(has_column(&conn, "attachments", "project_path").unwrap());
+        assert!(has_column(&conn, "app_config_feishu_apps", "enabled").unwrap());
+        run(&conn).unwrap();
+        assert_eq!(current_version(&conn).unwrap(), LATEST_VERSION);
+    }
+
+    #[test]
+    fn test_legacy_database_is_upgraded_in_place() {
+        let conn = Connection::open_in_memory().unwrap();
+        conn.execute_batch(
+            "CREATE TABLE app_config_feishu (
+                id INTEGER PRIMARY KEY CHECK (id = 1),
+                app_id TEXT NOT NULL,
+                app_secret TEXT NOT NULL,
+                open_id TEXT,
+                updated_at INTEGER NOT NULL
+            );
+            INSERT INTO app_config_feishu (id, app_id, app_secret, updated_at) VALUES (1, 'cli_x', 's', 0);
+            CREATE TABLE terminal_history (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                project_path TEXT NOT NULL,
+                kind TEXT NOT NULL,
+                content TEXT NOT NULL,
+                created_at INTEGER NOT NULL
+            );
+            INSERT INTO terminal_history (project_path, kind, content, created_at) VALUES ('/p', 'out', 'x', 0);
+            CREATE TABLE db_meta (key TEXT PRIMARY KEY, value TEXT NOT NULL);
+            INSERT INTO db_meta (key, value) VALUES ('cleanup_legacy_v1', '1');",
+        )
+        .unwrap();
+
+        run(&conn).unwrap();
+
+        assert!(has_column(&conn, "app_config_feishu", "hook_events_filter").unwrap());
+        assert!(has_column(&conn, "app_config_feishu", "backend").unwrap());
+        let app_id: String = conn.query_row("SELECT app_id FROM app_config_feishu", [], |r| r.get(0)).unwrap();
+        assert_eq!(app_id, "cli_x");
+        // 已清理过的数据库不会再次清空终端历史
+        let history: i64 = conn.query_row("SELECT COUNT(*) FROM terminal_history", [], |r| r.get(0)).unwrap();
+        assert_eq!(history, 1);
+    }
+
+    #[test]
+    fn test_concurrent_writers_and_readers() {
+        let path = std::env::temp_dir().join(format!("sparky-wal-{}-{}.db", std::process::id(), rand_suffix()));
+        let setup = open_connection(&path).unwrap();
+        let mode: String = setup.query_row("PRAGMA journal_mode", [], |r| r.get(0)).unwrap();
+        assert_eq!(mode.to_lowercase(), "wal");
+        setup
+            .execute_batch(
+                "CREATE TABLE hook_records_test (
+                    id INTEGER PRIMARY KEY AUTOINCREMENT,
+                    content TEXT NOT NULL,
+                    created_at INTEGER NOT NULL
+                )",
+            )
+            .unwrap();
+
+        // 模拟 hook 突发：多个写入者插入后按条数裁剪（与 save_hook_record 相同的 DELETE），同时有读取者分页查询
+        let mut handles = Vec::new();
+        for writer in 0..4 {
+            let path = path.clone();
+            handles.push(std::thread::spawn(move || {
+                let conn = open_connection(&path).unwrap();
+                for i in 0..50 {
+                    conn.execute(
+                        "INSERT INTO hook_records_test (content, created_at) VALUES (?1, ?2)",
+                        rusqlite::params![format!("w{}-{}", writer, i), i],
+                    )
+                    .unwrap();
+                    conn.execute(
+                        "DELETE FROM hook_records_test WHERE id NOT IN
+                         (SELECT id FROM hook_records_test ORDER BY id DESC LIMIT 100)",
+                        [],
+                    )
+                    .unwrap();
+                }
+            }));
+        }
+        for _ in 0..2 {
+            let path = path.clone();
+            handles.push(std::thread::spawn(move || {
+                let conn = open_connection(&path).unwrap();
+                for _ in 0..100 {
+                    let mut stmt = conn
+                        .prepare("SELECT id, content FROM hook_records_test ORDER BY id DESC LIMIT 20")
+                        .unwrap();
+                    let rows: Vec<(i64, String)> =
+                        stmt.query_map([], |r| Ok((r.get(0)?, r.get(1)?))).unwrap().map(|r| r.unwrap()).collect();
+                    assert!(rows.len() <= 20);
+                }
+            }));
+        }
+        for handle in handles {
+            handle.join().unwrap();
+        }
+
+        let count: i64 = setup.query_row("SELECT COUNT(*) FROM hook_records_test", [], |r| r.get(0)).unwrap();
+        assert_eq!(count, 100);
+        drop(setup);
+        for suffix in ["", "-wal", "-shm"] {
+            let _ = std::fs::remove_file(format!("{}{}", path.display(), suffix));
+        }
+    }
+
+    fn rand_suffix() -> u128 {
+        std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH).unwrap().as_nanos()
+    }
+
+    #[test]
+    fn test_hook_record_seconds_are_converted_to_millis() {
+        let conn = Connection::open_in_memory().unwrap();
+        run(&conn).unwrap();
+        // 模拟 v6 时写入的秒级记录，再从 v6 升级
+        conn.execute_batch(
+            "CREATE TABLE hook_records_abc (id INTEGER PRIMARY KEY, created_at INTEGER NOT NULL);
+             INSERT INTO hook_records_abc (id, created_at) VALUES (1, 1700000000), (2, 1700000000123);
+             PRAGMA user_version = 6;",
+        )
+        .unwrap();
+        run(&conn).unwrap();
+        let values: Vec<i64> = conn
+            .prepare("SELECT created_at FROM hook_records_abc ORDER BY id")
+            .unwrap()
+            .query_map([], |r| r.get(0))
+            .unwrap()
+            .map(|r| r.unwrap())
+            .collect();
+        assert_eq!(values, vec![1_700_000_000_000, 1_700_000_000_123]);
+    }
+
+    #[test]
+    fn test_project_tables_backfill() {
+        let conn = Connection::open_in_memory().unwrap();
+        run(&conn).unwrap();
+        let table = crate::project_tables::lookup_project_hooks_table(&conn, "/work/app").unwrap();
+        assert_eq!(table, None);
+        // 模拟 v6 之前按路径哈希创建、未登记映射的记录表，再从 v15 升级
+        let legacy = crate::project_tables::project_hooks_table_name(&conn, "/work/app").unwrap();
+        crate::project_tables::ensure_project_hooks_table(&conn, &legacy).unwrap();
+        conn.execute("DELETE FROM project_tables", []).unwrap();
+        conn.execute_batch(
+            "INSERT INTO projects (name, path, created_at, updated_at) VALUES ('app', '/work/app', 0, 0);
+             PRAGMA user_version = 15;",
+        )
+        .unwrap();
+        run(&conn).unwrap();
+        let mapped: String = conn
+            .query_row("SELECT table_name FROM project_tables WHERE path = '/work/app'", [], |r| r.get(0))
+            .unwrap();
+        assert_eq!(mapped, legacy);
+    }
+
+    #[test]
+    fn test_versions_are_sequential() {
+        for (i, migration) in MIGRATIONS.iter().enumerate() {
+            assert_eq!(migration.version, i as i64 + 1);
+        }
+    }
+}
@@ -0,0 +1,243 @@
+// Per-project hook record tables, shared by the CLI and the desktop app
+// (src-tauri includes this file via #[path] so both resolve a project path to the same table
+// and agree on its schema)
+
+use rusqlite::{params, Connection, OptionalExtension};
+use std::collections::HashMap;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// hook 记录 created_at 的当前时间（毫秒），写入端统一使用
+pub fn now_millis() -> i64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_millis() as i64
+}
+
+/// 项目路径的 64 位 FNV-1a 哈希，作为表名的基础部分
+fn hashed_table_name(project_path: &str) -> String {
+    let mut hash: u64 = 14695981039346656037;
+    for byte in project_path.as_bytes() {
+        hash ^= *byte as u64;
+        hash = hash.wrapping_mul(1099511628211);
+    }
+    format!("hook_records_{:x}", hash)
+}
+
+/// 返回项目的 hook 记录表名，首次使用时记录到 project_tables；
+/// 哈希得到的表名已被其他路径占用时追加数字后缀，避免不同项目的记录混在同一张表
+#[allow(dead_code)] // 只有写入记录的 CLI 登记映射，桌面端只查询
+pub fn project_hooks_table_name(conn: &Connection, project_path: &str) -> rusqlite::Result<String> {
+    resolve(conn, project_path, &hashed_table_name(project_path))
+}
+
+fn resolve(conn: &Connection, project_path: &str, base: &str) -> rusqlite::Result<String> {
+    if let Some(name) = lookup(conn, project_path)? {
+        return Ok(name);
+    }
+    let mut candidate = base.to_string();
+    let mut suffix = 2;
+    loop {
+        // path 与 table_name 均唯一：插入被忽略时要么其他进程刚登记了同一路径，要么表名已被占用
+        let inserted = conn.execute(
+            "INSERT OR IGNORE INTO project_tables (path, table_name) VALUES (?1, ?2)",
+            params![project_path, candidate],
+        )?;
+        if inserted == 1 {
+            return Ok(candidate);
+        }
+        if let Some(name) = lookup(conn, project_path)? {
+            return Ok(name);
+        }
+        tracing::warn!(
+            "[project_tables] table {} already used by another project, trying suffix {} for {}",
+            candidate, suffix, project_path
+        );
+        candidate = format!("{}_{}", base, suffix);
+        suffix += 1;
+    }
+}
+
+/// 只查询、不登记：返回项目已登记的表名；未登记但存在按哈希命名、且未被其他路径占用的旧表时返回该表
+pub fn lookup_project_hooks_table(conn: &Connection, project_path: &str) -> rusqlite::Result<Option<String>> {
+    if let Some(name) = lookup(conn, project_path)? {
+        return Ok(Some(name));
+    }
+    let legacy = hashed_table_name(project_path);
+    let exists = conn
+        .query_row(
+            "SELECT 1 FROM sqlite_master WHERE type = 'table' AND name = ?1
+             AND NOT EXISTS (SELECT 1 FROM project_tables WHERE table_name = ?1)",
+            params![legacy],
+            |_| Ok(()),
+        )
+        .optional()?
+        .is_some();
+    Ok(exists.then_some(legacy))
+}
+
+fn lookup(conn: &Connection, project_path: &str) -> rusqlite::Result<Option<String>> {
+    conn.query_row(
+        "SELECT table_name FROM project_tables WHERE path = ?1",
+        params![project_path],
+        |row| row.get(0),
+    )
+    .optional()
+}
+
+pub fn ensure_project_hooks_table(conn: &Connection, table_name: &str) -> rusqlite::Result<()> {
+    let sql = format!(
+        "CREATE TABLE IF NOT EXISTS {} (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            event_name TEXT NOT NULL,
+            session_id TEXT NOT NULL,
+            notification_text TEXT NOT NULL,
+            transcript_path TEXT NOT NULL,
+            content TEXT NOT NULL,
+            result TEXT NOT NULL,
+            created_at INTEGER NOT NULL,
+            tool_name TEXT,
+            permission_mode TEXT
+        )",
+        table_name
+    );
+    conn.execute(&sql, [])?;
+    ensure_added_columns(conn, table_name)
+}
+
+/// 建表后新增的列，旧版本创建的记录表需要补齐
+const ADDED_COLUMNS: &[(&str, &str)] = &[
+    ("session_id", "TEXT NOT NULL DEFAULT ''"),
+    ("tool_name", "TEXT"),
+    ("permission_mode", "TEXT"),
+];
+
+fn ensure_added_columns(conn: &Connection, table_name: &str) -> rusqlite::Result<()> {
+    let pragma_sql = format!("PRAGMA table_info({})", table_name);
+    let mut stmt = conn.prepare(&pragma_sql)?;
+    let columns = stmt
+        .query_map([], |row| row.get::<_, String>(1))?
+        .collect::<rusqlite::Result<Vec<_>>>()?;
+    for (column, decl) in ADDED_COLUMNS {
+        if !columns.iter().any(|c| c == column) {
+            let alter_sql = format!("ALTER TABLE {} ADD COLUMN {} {}", table_name, column, decl);
+            conn.execute(&alter_sql, [])?;
+        }
+    }
+    Ok(())
+}
+
+/// 表名 -> 项目路径，用于跨项目查询时反查记录所属项目（CLI 不使用）
+#[allow(dead_code)]
+pub fn project_paths_by_table(conn: &Connection) -> rusqlite::Result<HashMap<String, String>> {
+    let mut stmt = conn.prepare("SELECT path, table_name FROM project_tables")?;
+    let rows = stmt.query_map([], |row| Ok((row.get::<_, String>(1)?, row.get::<_, String>(0)?)))?;
+    rows.collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn test_conn() -> Connection {
+        let conn = Connection::open_in_memory().unwrap();
+        crate::migrations::run(&conn).unwrap();
+        conn
+    }
+
+    #[test]
+    fn test_table_name_is_stable() {
+        let conn = test_conn();
+        let first = project_hooks_table_name(&conn, "/work/app").unwrap();
+        assert_eq!(first, hashed_table_name("/work/app"));
+        assert_eq!(project_hooks_table_name(&conn, "/work/app").unwrap(), first);
+    }
+
+    #[test]
+    fn test_ensure_adds_missing_columns() {
+        let conn = test_conn();
+        conn.execute_batch(
+            "CREATE TABLE hook_records_old (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                event_name TEXT NOT NULL,
+                notification_text TEXT NOT NULL,
+                transcript_path TEXT NOT NULL,
+                content TEXT NOT NULL,
+                result TEXT NOT NULL,
+                created_at INTEGER NOT NULL
+            )",
+        )
+        .unwrap();
+        ensure_project_hooks_table(&conn, "hook_records_old").unwrap();
+        ensure_project_hooks_table(&conn, "hook_records_old").unwrap();
+        conn.execute(
+            "INSERT INTO hook_records_old (event_name, session_id, notification_text, transcript_path, content, result, created_at, tool_name, permission_mode)
+             VALUES ('PreToolUse', 's1', '', '', '', 'ok', ?1, 'Bash', 'default')",
+            params![now_millis()],
+        )
+        .unwrap();
+        let (tool, mode): (Option<String>, Option<String>) = conn
+            .query_row("SELECT tool_name, permission_mode FROM hook_records_old", [], |r| Ok((r.get(0)?, r.get(1)?)))
+            .unwrap();
+        assert_eq!(tool.as_deref(), Some("Bash"));
+        assert_eq!(mode.as_deref(), Some("default"));
+    }
+
+    #[test]
+    fn test_created_at_round_trip_in_millis() {
+        let conn = test_conn();
+        let table = project_hooks_table_name(&conn, "/work/app").unwrap();
+        ensure_project_hooks_table(&conn, &table).unwrap();
+        let created_at: i64 = 1_700_000_000_123;
+        conn.execute(
+            &format!(
+                "INSERT INTO {} (event_name, session_id, notification_text, transcript_path, content, result, created_at)
+                 VALUES ('Stop', 's1', '', '', '', 'ok', ?1)",
+                table
+            ),
+            params![created_at],
+        )
+        .unwrap();
+        let read: i64 = conn
+            .query_row(&format!("SELECT created_at FROM {}", table), [], |r| r.get(0))
+            .unwrap();
+        assert_eq!(read, created_at);
+        assert!(now_millis() > 1_000_000_000_000);
+    }
+
+    #[test]
+    fn test_lookup_does_not_register() {
+        let conn = test_conn();
+        assert_eq!(lookup_project_hooks_table(&conn, "/work/app").unwrap(), None);
+        assert!(project_paths_by_table(&conn).unwrap().is_empty());
+
+        // v6 之前按哈希创建的旧表可以查到，但不会被登记
+        let legacy = hashed_table_name("/work/app");
+        ensure_project_hooks_table(&conn, &legacy).unwrap();
+        assert_eq!(lookup_project_hooks_table(&conn, "/work/app").unwrap(), Some(legacy.clone()));
+        assert!(project_paths_by_table(&conn).unwrap().is_empty());
+
+        // 已被其他路径登记的表名不会当作旧表返回
+        conn.execute(
+            "INSERT INTO project_tables (path, table_name) VALUES ('/work/other', ?1)",
+            params![legacy],
+        )
+        .unwrap();
+        assert_eq!(lookup_project_hooks_table(&conn, "/work/app").unwrap(), None);
+    }
+
+    #[test]
+    fn test_collision_gets_suffix() {
+        let conn = test_conn();
+        // 两个路径强制使用相同的哈希表名
+        let base = "hook_records_deadbeef";
+        assert_eq!(resolve(&conn, "/work/a", base).unwrap(), base);
+        assert_eq!(resolve(&conn, "/work/b", base).unwrap(), "hook_records_deadbeef_2");
+        assert_eq!(resolve(&conn, "/work/c", base).unwrap(), "hook_records_deadbeef_3");
+        assert_eq!(resolve(&conn, "/work/a", base).unwrap(), base);
+        assert_eq!(resolve(&conn, "/work/b", base).unwrap(), "hook_records_deadbeef_2");
+
+        let paths = project_paths_by_table(&conn).unwrap();
+        assert_eq!(paths.get("hook_records_deadbeef_2").map(String::as_str), Some("/work/b"));
+    }
+}
@@ -0,0 +1,147 @@
+use rusqlite::{params, Connection, OptionalExtension, TransactionBehavior};
+
+/// 限流窗口长度（秒）
+const WINDOW_SECS: i64 = 60;
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct SessionRate {
+    pub window_start: i64,
+    pub sent_count: u32,
+    pub suppressed_count: u32,
+    pub last_summary_at: i64,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum RateDecision {
+    /// 正常发送；suppressed_before 为此前被合并抑制、尚未汇报的事件数
+    Send { suppressed_before: u32 },
+    /// 超出上限不发送；抑制数累计到下一个窗口的第一条通知中汇报，不额外占用发送次数
+    Suppress,
+}
+
+/// 根据会话当前状态决定是否发送，并更新状态；每个窗口最多发送 cap 条（含汇报抑制数的那一条）
+pub fn decide(state: &mut SessionRate, now: i64, cap: u32) -> RateDecision {
+    if now - state.window_start >= WINDOW_SECS {
+        state.window_start = now;
+        state.sent_count = 0;
+    }
+
+    if state.sent_count < cap {
+        state.sent_count += 1;
+        let suppressed_before = state.suppressed_count;
+        state.suppressed_count = 0;
+        return RateDecision::Send { suppressed_before };
+    }
+
+    state.suppressed_count += 1;
+    RateDecision::Suppress
+}
+
+/// 检查并记录会话的通知频率；cap 为 None 或 0 时不限流。
+/// 读取与写回在同一个 IMMEDIATE 事务中完成，并发的 hook 进程不会同时拿到同一个名额
+pub fn check(conn: &Connection, session_id: &str, cap: Option<u32>, now: i64) -> Result<RateDecision, rusqlite::Error> {
+    let cap = match cap {
+        Some(cap) if cap > 0 && !session_id.is_empty() => cap,
+        _ => return Ok(RateDecision::Send { suppressed_before: 0 }),
+    };
+
+    let tx = rusqlite::Transaction::new_unchecked(conn, TransactionBehavior::Immediate)?;
+    let mut state = tx
+        .query_row(
+            "SELECT window_start, sent_count, suppressed_count, last_summary_at
+             FROM session_notification_rate WHERE session_id = ?1",
+            params![session_id],
+            |row| {
+                Ok(SessionRate {
+                    window_start: row.get(0)?,
+                    sent_count: row.get(1)?,
+                    suppressed_count: row.get(2)?,
+                    last_summary_at: row.get(3)?,
+                })
+            },
+        )
+        .optional()?
+        .unwrap_or_default();
+
+    let decision = decide(&mut state, now, cap);
+
+    tx.execute(
+        "INSERT INTO session_notification_rate (session_id, window_start, sent_count, suppressed_count, last_summary_at)
+         VALUES (?1, ?2, ?3, ?4, ?5)
+         ON CONFLICT(session_id) DO UPDATE SET
+           window_start = excluded.window_start,
+           sent_count = excluded.sent_count,
+           suppressed_count = excluded.suppressed_count,
+           last_summary_at = excluded.last_summary_at",
+        params![session_id, state.window_start, state.sent_count, state.suppressed_count, state.last_summary_at],
+    )?;
+    // 清理一天内没有活动的会话
+    tx.execute(
+        "DELETE FROM session_notification_rate WHERE window_start < ?1",
+        params![now - 24 * 3600],
+    )?;
+    tx.commit()?;
+
+    Ok(decision)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cap_suppresses_and_reports_in_next_window() {
+        let mut state = SessionRate::default();
+        let now = 1_000;
+        assert_eq!(decide(&mut state, now, 2), RateDecision::Send { suppressed_before: 0 });
+        assert_eq!(decide(&mut state, now + 1, 2), RateDecision::Send { suppressed_before: 0 });
+        // 超出上限后不再发送任何消息（包括汇总），窗口内发送数不超过上限
+        assert_eq!(decide(&mut state, now + 2, 2), RateDecision::Suppress);
+        assert_eq!(decide(&mut state, now + 3, 2), RateDecision::Suppress);
+        assert_eq!(decide(&mut state, now + 4, 2), RateDecision::Suppress);
+        assert_eq!(state.sent_count, 2);
+        // 新窗口：恢复发送，并带上此前未汇报的抑制数
+        assert_eq!(decide(&mut state, now + 61, 2), RateDecision::Send { suppressed_before: 3 });
+        assert_eq!(decide(&mut state, now + 62, 2), RateDecision::Send { suppressed_before: 0 });
+    }
+
+    #[test]
+    fn test_check_without_cap_always_sends() {
+        let conn = Connection::open_in_memory().unwrap();
+        crate::migrations::run(&conn).unwrap();
+        let now = 100_000;
+        for _ in 0..5 {
+            assert_eq!(check(&conn, "s1", None, now).unwrap(), RateDecision::Send { suppressed_before: 0 });
+        }
+        assert_eq!(check(&conn, "s1", Some(1), now).unwrap(), RateDecision::Send { suppressed_before: 0 });
+        assert_eq!(check(&conn, "s1", Some(1), now + 1).unwrap(), RateDecision::Suppress);
+        assert_eq!(check(&conn, "s2", Some(1), now + 1).unwrap(), RateDecision::Send { suppressed_before: 0 });
+    }
+
+    #[test]
+    fn test_concurrent_checks_respect_cap() {
+        let path = std::env::temp_dir().join(format!("sparky-rate-{}.db", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        crate::migrations::run(&crate::migrations::open_connection(&path).unwrap()).unwrap();
+
+        let now = 100_000;
+        let handles: Vec<_> = (0..8)
+            .map(|_| {
+                let path = path.clone();
+                std::thread::spawn(move || {
+                    let conn = crate::migrations::open_connection(&path).unwrap();
+                    check(&conn, "s1", Some(3), now).unwrap()
+                })
+            })
+            .collect();
+        let sent = handles
+            .into_iter()
+            .map(|h| h.join().unwrap())
+            .filter(|d| matches!(d, RateDecision::Send { .. }))
+            .count();
+        for suffix in ["", "-wal", "-shm"] {
+            let _ = std::fs::remove_file(format!("{}{}", path.display(), suffix));
+        }
+        assert_eq!(sent, 3);
+    }
+}
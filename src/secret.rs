@@ -0,0 +1,234 @@
+// Encryption at rest for credentials stored in app_config_feishu / dingtalk / wework (AES-256-GCM, machine-bound key file),
+// shared by the CLI and the desktop app (src-tauri includes this file via #[path] so both sides read each other's secrets)
+
+use base64::{engine::general_purpose::STANDARD, Engine as _};
+use ring::aead::{Aad, LessSafeKey, Nonce, UnboundKey, AES_256_GCM, NONCE_LEN};
+use ring::rand::{SecureRandom, SystemRandom};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+/// 加密值的前缀；没有该前缀的值视为旧版明文
+const ENCRYPTED_PREFIX: &str = "enc:v1:";
+const KEY_LEN: usize = 32;
+
+/// 本机密钥文件路径：放在系统配置目录（如 ~/.config/sparky、~/Library/Application Support/sparky），
+/// 不与 ~/sparky/hooks.db 同目录，拷走数据库目录不会连同密钥一起带走
+fn key_path() -> Result<PathBuf, String> {
+    let config = dirs::config_dir().ok_or("Failed to get config directory")?;
+    Ok(config.join("sparky").join("secret.key"))
+}
+
+/// 旧版本放在 hooks.db 旁边的密钥文件，首次使用新路径时迁移过去
+fn legacy_key_path() -> Option<PathBuf> {
+    dirs::home_dir().map(|home| home.join("sparky").join("secret.key"))
+}
+
+/// 另一个进程刚以 create_new 创建、尚未写完密钥时，最多等待的次数与间隔
+const KEY_READ_RETRIES: usize = 20;
+const KEY_READ_RETRY_INTERVAL: std::time::Duration = std::time::Duration::from_millis(50);
+
+/// 读取密钥文件；文件不存在返回 None，长度不足（正被其他进程写入）时稍等重读
+fn read_key(path: &Path) -> Result<Option<[u8; KEY_LEN]>, String> {
+    for _ in 0..KEY_READ_RETRIES {
+        match std::fs::read(path) {
+            Ok(bytes) if bytes.len() >= KEY_LEN => return key_from_bytes(&bytes).map(Some),
+            Ok(_) => std::thread::sleep(KEY_READ_RETRY_INTERVAL),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(format!("Failed to read secret key file: {}", e)),
+        }
+    }
+    key_from_bytes(&std::fs::read(path).map_err(|e| e.to_string())?).map(Some)
+}
+
+/// 读取本机密钥，不存在时生成（或沿用旧路径下的密钥）；CLI 与桌面端并发创建时以 create_new 成功者为准，
+/// 其余进程重新读取该文件，保证只有一把密钥
+fn load_or_create_key() -> Result<[u8; KEY_LEN], String> {
+    let path = key_path()?;
+    if let Some(key) = read_key(&path)? {
+        return Ok(key);
+    }
+
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent).map_err(|e| e.to_string())?;
+    }
+    let legacy_path = legacy_key_path();
+    let key = match legacy_path.as_deref().map(read_key).transpose()?.flatten() {
+        Some(key) => key,
+        None => {
+            let mut key = [0u8; KEY_LEN];
+            SystemRandom::new()
+                .fill(&mut key)
+                .map_err(|_| "Failed to generate secret key".to_string())?;
+            key
+        }
+    };
+
+    let mut options = std::fs::OpenOptions::new();
+    options.write(true).create_new(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600);
+    }
+    match options.open(&path) {
+        Ok(mut file) => {
+            file.write_all(&key).and_then(|_| file.sync_all()).map_err(|e| e.to_string())?;
+            if let Some(legacy_path) = legacy_path {
+                let _ = std::fs::remove_file(legacy_path);
+            }
+            Ok(key)
+        }
+        Err(e) if e.kind() == std::io::ErrorKind::AlreadyExists => {
+            read_key(&path)?.ok_or_else(|| "Secret key file disappeared while reading".to_string())
+        }
+        Err(e) => Err(format!("Failed to create secret key file: {}", e)),
+    }
+}
+
+fn key_from_bytes(bytes: &[u8]) -> Result<[u8; KEY_LEN], String> {
+    bytes
+        .try_into()
+        .map_err(|_| format!("Invalid secret key file (expected {} bytes)", KEY_LEN))
+}
+
+fn aead_key(key: &[u8; KEY_LEN]) -> Result<LessSafeKey, String> {
+    UnboundKey::new(&AES_256_GCM, key)
+        .map(LessSafeKey::new)
+        .map_err(|_| "Invalid secret key".to_string())
+}
+
+pub fn is_encrypted(value: &str) -> bool {
+    value.starts_with(ENCRYPTED_PREFIX)
+}
+
+fn encrypt_with_key(key: &[u8; KEY_LEN], plain: &str) -> Result<String, String> {
+    let mut nonce_bytes = [0u8; NONCE_LEN];
+    SystemRandom::new()
+        .fill(&mut nonce_bytes)
+        .map_err(|_| "Failed to generate nonce".to_string())?;
+    let mut in_out = plain.as_bytes().to_vec();
+    aead_key(key)?
+        .seal_in_place_append_tag(Nonce::assume_unique_for_key(nonce_bytes), Aad::empty(), &mut in_out)
+        .map_err(|_| "Failed to encrypt secret".to_string())?;
+
+    let mut payload = nonce_bytes.to_vec();
+    payload.extend_from_slice(&in_out);
+    Ok(format!("{}{}", ENCRYPTED_PREFIX, STANDARD.encode(payload)))
+}
+
+fn decrypt_with_key(key: &[u8; KEY_LEN], stored: &str) -> Result<String, String> {
+    let encoded = match stored.strip_prefix(ENCRYPTED_PREFIX) {
+        Some(encoded) => encoded,
+        None => return Ok(stored.to_string()),
+    };
+    let payload = STANDARD.decode(encoded).map_err(|e| format!("Invalid encrypted secret: {}", e))?;
+    if payload.len() < NONCE_LEN {
+        return Err("Invalid encrypted secret: too short".to_string());
+    }
+    let (nonce_bytes, ciphertext) = payload.split_at(NONCE_LEN);
+    let nonce = Nonce::try_assume_unique_for_key(nonce_bytes).map_err(|_| "Invalid nonce".to_string())?;
+    let mut in_out = ciphertext.to_vec();
+    let plain = aead_key(key)?
+        .open_in_place(nonce, Aad::empty(), &mut in_out)
+        .map_err(|_| "Failed to decrypt secret (secret.key changed?), please re-enter the configuration".to_string())?;
+    String::from_utf8(plain.to_vec()).map_err(|e| e.to_string())
+}
+
+/// 加密一个配置值；空字符串保持原样
+pub fn encrypt_secret(plain: &str) -> Result<String, String> {
+    if plain.is_empty() || is_encrypted(plain) {
+        return Ok(plain.to_string());
+    }
+    encrypt_with_key(&load_or_create_key()?, plain)
+}
+
+/// 解密一个配置值；旧版明文原样返回
+pub fn decrypt_secret(stored: &str) -> Result<String, String> {
+    if !is_encrypted(stored) {
+        return Ok(stored.to_string());
+    }
+    decrypt_with_key(&load_or_create_key()?, stored)
+}
+
+pub fn encrypt_optional(plain: Option<&str>) -> Result<Option<String>, String> {
+    plain.map(encrypt_secret).transpose()
+}
+
+pub fn decrypt_optional(stored: Option<String>) -> Result<Option<String>, String> {
+    stored.map(|s| decrypt_secret(&s)).transpose()
+}
+
+/// 保存应用凭据的单行配置表；三张表的密钥列相同
+const CREDENTIAL_TABLES: [&str; 3] = ["app_config_feishu", "app_config_dingtalk", "app_config_wework"];
+
+/// 迁移：把飞书、钉钉、企业微信配置中仍为明文的 app_secret / encrypt_key / verification_token 加密
+pub fn migrate_plaintext_secrets(conn: &rusqlite::Connection) -> Result<(), String> {
+    for table in CREDENTIAL_TABLES {
+        migrate_table_secrets(conn, table)?;
+    }
+    Ok(())
+}
+
+fn migrate_table_secrets(conn: &rusqlite::Connection, table: &str) -> Result<(), String> {
+    let row: Option<(String, Option<String>, Option<String>)> = conn
+        .query_row(
+            &format!("SELECT app_secret, encrypt_key, verification_token FROM {} WHERE id = 1", table),
+            [],
+            |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)),
+        )
+        .ok();
+    let (app_secret, encrypt_key, verification_token) = match row {
+        Some(row) => row,
+        None => return Ok(()),
+    };
+
+    let needs_migration = |value: Option<&str>| value.map(|v| !v.is_empty() && !is_encrypted(v)).unwrap_or(false);
+    if !needs_migration(Some(&app_secret))
+        && !needs_migration(encrypt_key.as_deref())
+        && !needs_migration(verification_token.as_deref())
+    {
+        return Ok(());
+    }
+
+    conn.execute(
+        &format!(
+            "UPDATE {} SET app_secret = ?1, encrypt_key = ?2, verification_token = ?3 WHERE id = 1",
+            table
+        ),
+        rusqlite::params![
+            encrypt_secret(&app_secret)?,
+            encrypt_optional(encrypt_key.as_deref())?,
+            encrypt_optional(verification_token.as_deref())?
+        ],
+    )
+    .map_err(|e| e.to_string())?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_encrypt_roundtrip_and_plaintext_passthrough() {
+        let key = [7u8; KEY_LEN];
+        let encrypted = encrypt_with_key(&key, "my-app-secret").unwrap();
+        assert!(is_encrypted(&encrypted));
+        assert!(!encrypted.contains("my-app-secret"));
+        assert_eq!(decrypt_with_key(&key, &encrypted).unwrap(), "my-app-secret");
+        assert_eq!(decrypt_with_key(&key, "legacy-plain").unwrap(), "legacy-plain");
+        assert!(decrypt_with_key(&[8u8; KEY_LEN], &encrypted).is_err());
+    }
+
+    #[test]
+    fn test_read_key() {
+        let path = std::env::temp_dir().join(format!("sparky-secret-{}.key", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        assert_eq!(read_key(&path).unwrap(), None);
+        std::fs::write(&path, [3u8; KEY_LEN]).unwrap();
+        assert_eq!(read_key(&path).unwrap(), Some([3u8; KEY_LEN]));
+        std::fs::write(&path, [3u8; KEY_LEN + 1]).unwrap();
+        assert!(read_key(&path).is_err());
+        let _ = std::fs::remove_file(&path);
+    }
+}
@@ -0,0 +1,178 @@
+// Locating the confirmation prompt Claude Code is waiting on in the tail of the PTY log
+
+/// 未配置 prompt_triggers 时用于识别提问行的短语（不区分大小写）
+pub const DEFAULT_TRIGGERS: &[&str] = &[
+    "Do you want to",
+    "Would you like to",
+    "Allow this",
+    "Do you trust the files",
+    "是否继续",
+    "是否允许",
+    "要继续吗",
+];
+
+/// 提问行最多位于选项块上方几行
+const QUESTION_LOOKBACK: usize = 5;
+
+/// 从终端输出中解析出的确认提示
+#[derive(Debug, Clone, PartialEq)]
+pub struct TerminalPrompt {
+    /// 选项块上方的提问行（如 "Do you want to proceed?"）
+    pub question: String,
+    /// 去掉 ❯ 和序号后的选项文本，按出现顺序排列
+    pub options: Vec<String>,
+    /// 原始文本，用于无法结构化解析时的兜底展示
+    pub raw: String,
+}
+
+/// 匹配形如 `❯ 1. Yes` / `  2. No` 的编号选项行，返回去掉前缀后的选项文本
+fn parse_option_line(line: &str) -> Option<String> {
+    let rest = line.trim_start();
+    let rest = rest.strip_prefix('❯').unwrap_or(rest).trim_start();
+    let digits = rest.chars().take_while(|c| c.is_ascii_digit()).count();
+    if digits == 0 {
+        return None;
+    }
+    let rest = rest[digits..].strip_prefix('.')?;
+    if !rest.starts_with(char::is_whitespace) {
+        return None;
+    }
+    let text = rest.trim();
+    if text.is_empty() {
+        None
+    } else {
+        Some(text.to_string())
+    }
+}
+
+fn contains_trigger(line: &str, triggers: &[String]) -> bool {
+    let line = line.to_lowercase();
+    triggers
+        .iter()
+        .filter(|t| !t.trim().is_empty())
+        .any(|t| line.contains(&t.trim().to_lowercase()))
+}
+
+/// 在终端输出尾部查找最后一个提示：优先识别编号选项块（需带 ❯ 光标或上方有触发短语，
+/// 避免把普通的编号列表当成提示），否则退回到最后一个包含触发短语的行及其后的文本
+pub fn parse_terminal_prompt(content: &str, triggers: &[String]) -> Option<TerminalPrompt> {
+    let lines: Vec<&str> = content.lines().collect();
+
+    if let Some(last) = lines.iter().rposition(|l| parse_option_line(l).is_some()) {
+        // 向上收集连续的选项行（允许中间夹杂空行）
+        let mut first = last;
+        let mut i = last;
+        while i > 0 {
+            i -= 1;
+            if parse_option_line(lines[i]).is_some() {
+                first = i;
+            } else if !lines[i].trim().is_empty() {
+                break;
+            }
+        }
+
+        let block = &lines[first..=last];
+        let options: Vec<String> = block.iter().filter_map(|l| parse_option_line(l)).collect();
+
+        // 提问行：选项块上方最近的触发短语行，其次是带问号的行，最后取最近的非空行
+        let above = &lines[..first];
+        let nearest = |pred: &dyn Fn(&str) -> bool| {
+            above
+                .iter()
+                .rev()
+                .take(QUESTION_LOOKBACK)
+                .position(|l| pred(l))
+                .map(|offset| first - 1 - offset)
+        };
+        let trigger_idx = nearest(&|l| contains_trigger(l, triggers));
+        let has_cursor = block.iter().any(|l| l.trim_start().starts_with('❯'));
+
+        if trigger_idx.is_some() || has_cursor {
+            let question_idx = trigger_idx
+                .or_else(|| nearest(&|l| l.contains('?') || l.contains('？')))
+                .or_else(|| above.iter().rposition(|l| !l.trim().is_empty()));
+            let question = question_idx
+                .map(|idx| lines[idx].trim().to_string())
+                .unwrap_or_default();
+            let raw_start = question_idx.unwrap_or(first);
+
+            return Some(TerminalPrompt {
+                question,
+                options,
+                raw: lines[raw_start..=last].join("\n").trim().to_string(),
+            });
+        }
+    }
+
+    // 兜底：未识别到选项块时，保留最后一个触发短语所在行之后的原始文本
+    let idx = lines.iter().rposition(|l| contains_trigger(l, triggers))?;
+    Some(TerminalPrompt {
+        question: lines[idx].trim().to_string(),
+        options: Vec::new(),
+        raw: lines[idx..].join("\n").trim().to_string(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn defaults() -> Vec<String> {
+        DEFAULT_TRIGGERS.iter().map(|s| s.to_string()).collect()
+    }
+
+    #[test]
+    fn test_bash_permission_prompt() {
+        let content = "⏺ Bash(rm -rf target)\n\n Bash command\n\n   rm -rf target\n\n \
+                       Do you want to proceed?\n ❯ 1. Yes\n   2. Yes, and don't ask again for rm commands\n   \
+                       3. No, and tell Claude what to do differently (esc)\n";
+        let prompt = parse_terminal_prompt(content, &defaults()).unwrap();
+        assert_eq!(prompt.question, "Do you want to proceed?");
+        assert_eq!(prompt.options.len(), 3);
+        assert_eq!(prompt.options[0], "Yes");
+        assert!(prompt.raw.starts_with("Do you want to proceed?"));
+        assert!(prompt.raw.ends_with("(esc)"));
+    }
+
+    #[test]
+    fn test_edit_and_localized_prompts() {
+        let edit = "Do you want to make this edit to main.rs?\n❯ 1. Yes\n  2. No";
+        assert_eq!(
+            parse_terminal_prompt(edit, &defaults()).unwrap().question,
+            "Do you want to make this edit to main.rs?"
+        );
+
+        let zh = "是否继续执行该命令\n❯ 1. 是\n  2. 否";
+        let prompt = parse_terminal_prompt(zh, &defaults()).unwrap();
+        assert_eq!(prompt.question, "是否继续执行该命令");
+        assert_eq!(prompt.options, vec!["是", "否"]);
+    }
+
+    #[test]
+    fn test_ask_user_question_without_trigger() {
+        let content = "☐ Database\n\nWhich database should we use?\n\n❯ 1. Postgres\n  2. SQLite\n  3. Type something.";
+        let prompt = parse_terminal_prompt(content, &defaults()).unwrap();
+        assert_eq!(prompt.question, "Which database should we use?");
+        assert_eq!(prompt.options, vec!["Postgres", "SQLite", "Type something."]);
+    }
+
+    #[test]
+    fn test_plain_numbered_list_is_not_a_prompt() {
+        let content = "Here is the plan:\n1. Add the migration\n2. Update the CLI\n";
+        assert_eq!(parse_terminal_prompt(content, &defaults()), None);
+    }
+
+    #[test]
+    fn test_fallback_and_custom_triggers() {
+        let content = "running checks\nAllow this command to access the network? (y/n)\n";
+        let prompt = parse_terminal_prompt(content, &defaults()).unwrap();
+        assert!(prompt.options.is_empty());
+        assert_eq!(prompt.raw, "Allow this command to access the network? (y/n)");
+
+        let custom = vec!["Continue deploying".to_string()];
+        assert_eq!(parse_terminal_prompt(content, &custom), None);
+        let prompt = parse_terminal_prompt("x\ncontinue deploying to prod?\n[y/N]", &custom).unwrap();
+        assert_eq!(prompt.question, "continue deploying to prod?");
+        assert_eq!(prompt.raw, "continue deploying to prod?\n[y/N]");
+    }
+}
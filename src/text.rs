@@ -0,0 +1,123 @@
+// Text helpers shared by the CLI and the desktop app (src-tauri includes this file via #[path])
+
+/// 按字符数截取前缀，保证切在 UTF-8 字符边界上
+#[allow(dead_code)] // 由 CLI 使用
+pub fn truncate_chars(s: &str, max: usize) -> &str {
+    match s.char_indices().nth(max) {
+        Some((idx, _)) => &s[..idx],
+        None => s,
+    }
+}
+
+/// 按字符数截取后缀，保证切在 UTF-8 字符边界上
+#[allow(dead_code)] // 由 CLI 使用
+pub fn tail_chars(s: &str, max: usize) -> &str {
+    if max == 0 {
+        return "";
+    }
+    match s.char_indices().rev().nth(max - 1) {
+        Some((idx, _)) => &s[idx..],
+        None => s,
+    }
+}
+
+/// 去掉 ANSI 转义序列（CSI 颜色/光标移动、OSC 标题/超链接、DCS 等字符串序列及两字符序列）和 BEL，
+/// 用于把 PTY 原始输出转成可读文本
+pub fn strip_ansi(input: &str) -> String {
+    let mut out = String::with_capacity(input.len());
+    let mut chars = input.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '\u{1b}' => match chars.next() {
+                Some('[') => skip_csi(&mut chars),
+                // OSC / DCS / SOS / PM / APC：直到 BEL 或 ST（ESC \）
+                Some(']' | 'P' | 'X' | '^' | '_') => skip_string(&mut chars),
+                // 字符集选择等：ESC + 中间字节 + 结束字节
+                Some(' '..='/') => {
+                    while chars.next_if(|c| matches!(c, ' '..='/')).is_some() {}
+                    chars.next();
+                }
+                // 其余两字符序列（ESC 7、ESC = 等）
+                _ => {}
+            },
+            '\u{9b}' => skip_csi(&mut chars),
+            '\u{9d}' => skip_string(&mut chars),
+            '\u{7}' => {}
+            _ => out.push(c),
+        }
+    }
+    out
+}
+
+fn skip_csi(chars: &mut std::iter::Peekable<std::str::Chars>) {
+    // 参数字节与中间字节，直到 0x40-0x7E 的结束字节
+    for c in chars.by_ref() {
+        if ('\u{40}'..='\u{7e}').contains(&c) {
+            break;
+        }
+    }
+}
+
+fn skip_string(chars: &mut std::iter::Peekable<std::str::Chars>) {
+    while let Some(c) = chars.next() {
+        match c {
+            '\u{7}' | '\u{9c}' => break,
+            '\u{1b}' if chars.peek() == Some(&'\\') => {
+                chars.next();
+                break;
+            }
+            _ => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_truncate_and_tail_by_chars() {
+        assert_eq!(truncate_chars("hello", 3), "hel");
+        assert_eq!(truncate_chars("hello", 10), "hello");
+        assert_eq!(tail_chars("hello", 3), "llo");
+        assert_eq!(tail_chars("hello", 10), "hello");
+        assert_eq!(tail_chars("hello", 0), "");
+    }
+
+    #[test]
+    fn test_multibyte_boundaries_do_not_panic() {
+        // 每个汉字占 3 字节，按字节切 &s[..4] 会落在字符中间而 panic
+        let s = "权限请求：执行命令";
+        assert!(!s.is_char_boundary(4));
+        assert_eq!(truncate_chars(s, 4), "权限请求");
+        assert_eq!(tail_chars(s, 4), "执行命令");
+        let mixed = "ok✅完成";
+        assert_eq!(truncate_chars(mixed, 3), "ok✅");
+        assert_eq!(tail_chars(mixed, 3), "✅完成");
+    }
+
+    #[test]
+    fn test_strip_ansi_colored_prompt() {
+        let raw = "\u{1b}[38;5;245m╭────╮\u{1b}[39m\r\n\u{1b}[1mDo you want to proceed?\u{1b}[22m\r\n\
+                   \u{1b}[38;5;153m❯ \u{1b}[38;5;246m1.\u{1b}[39m Yes\r\n  \u{1b}[38;5;246m2.\u{1b}[39m No";
+        assert_eq!(
+            strip_ansi(raw),
+            "╭────╮\r\nDo you want to proceed?\r\n❯ 1. Yes\r\n  2. No"
+        );
+    }
+
+    #[test]
+    fn test_strip_ansi_cursor_and_osc() {
+        // 重绘时的光标上移、清行、隐藏光标
+        assert_eq!(strip_ansi("\u{1b}[2K\u{1b}[1A\u{1b}[2K\u{1b}[G\u{1b}[?25l> hi"), "> hi");
+        // 窗口标题（BEL 结尾）与超链接（ST 结尾）
+        assert_eq!(strip_ansi("\u{1b}]0;✳ Claude Code\u{7}ready"), "ready");
+        assert_eq!(
+            strip_ansi("see \u{1b}]8;;https://example.com\u{1b}\\link\u{1b}]8;;\u{1b}\\ here"),
+            "see link here"
+        );
+        // 字符集选择、两字符序列、8 位 CSI
+        assert_eq!(strip_ansi("\u{1b}(Ba\u{1b}7b\u{1b}8\u{9b}31mc"), "abc");
+        assert_eq!(strip_ansi("plain 文本"), "plain 文本");
+    }
+}
@@ -0,0 +1,158 @@
+// Access token cache for the Feishu / DingTalk / WeWork clients
+// (persisted in db_meta so each short-lived `sparky hook` process reuses the token fetched by the previous one)
+
+use rusqlite::{params, Connection, OptionalExtension};
+use serde::{Deserialize, Serialize};
+use std::future::Future;
+use std::sync::Arc;
+use tokio::sync::Mutex;
+
+/// token 剩余有效期不足该值（秒）时提前刷新
+const TOKEN_REFRESH_MARGIN_SECS: i64 = 5 * 60;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub(crate) struct CachedToken {
+    pub(crate) token: String,
+    /// 过期时间（Unix 秒）
+    pub(crate) expires_at: i64,
+}
+
+impl CachedToken {
+    pub(crate) fn is_fresh(&self, now: i64) -> bool {
+        now + TOKEN_REFRESH_MARGIN_SECS < self.expires_at
+    }
+}
+
+fn now_secs() -> i64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs() as i64
+}
+
+fn load(conn: &Connection, key: &str) -> rusqlite::Result<Option<CachedToken>> {
+    let value: Option<String> = conn
+        .query_row("SELECT value FROM db_meta WHERE key = ?1", params![key], |row| row.get(0))
+        .optional()?;
+    Ok(value.and_then(|json| serde_json::from_str(&json).ok()))
+}
+
+fn store(conn: &Connection, key: &str, cached: &CachedToken) -> rusqlite::Result<()> {
+    let json = serde_json::to_string(cached).unwrap_or_default();
+    conn.execute(
+        "INSERT INTO db_meta (key, value) VALUES (?1, ?2)
+         ON CONFLICT(key) DO UPDATE SET value = excluded.value",
+        params![key, json],
+    )?;
+    Ok(())
+}
+
+fn clear(conn: &Connection, key: &str) -> rusqlite::Result<()> {
+    conn.execute("DELETE FROM db_meta WHERE key = ?1", params![key])?;
+    Ok(())
+}
+
+fn open_db() -> Result<Connection, String> {
+    let path = crate::migrations::db_path().map_err(|e| e.to_string())?;
+    crate::migrations::open_connection(path).map_err(|e| e.to_string())
+}
+
+/// 读取持久化的 token；token 本身加密存储
+fn load_persisted(key: &str) -> Result<Option<CachedToken>, String> {
+    let cached = load(&open_db()?, key).map_err(|e| e.to_string())?;
+    cached
+        .map(|c| Ok(CachedToken { token: crate::secret::decrypt_secret(&c.token)?, ..c }))
+        .transpose()
+}
+
+fn persist(key: &str, cached: &CachedToken) -> Result<(), String> {
+    let encrypted = CachedToken { token: crate::secret::encrypt_secret(&cached.token)?, ..cached.clone() };
+    store(&open_db()?, key, &encrypted).map_err(|e| e.to_string())
+}
+
+/// 进程内缓存 + hooks.db 持久化；clone 出的实例共享同一份进程内缓存
+#[derive(Clone)]
+pub struct TokenCache {
+    key: String,
+    memory: Arc<Mutex<Option<CachedToken>>>,
+}
+
+impl TokenCache {
+    /// backend 与 app_id 区分不同应用的 token
+    pub fn new(backend: &str, app_id: &str) -> Self {
+        Self {
+            key: format!("token_cache:{}:{}", backend, app_id),
+            memory: Arc::new(Mutex::new(None)),
+        }
+    }
+
+    /// 返回未过期的 token，否则调用 fetch 获取（返回 token 与有效期秒数）并写回缓存；
+    /// 刷新期间持有锁，避免同一进程内并发请求重复刷新
+    pub async fn get_or_fetch<F, Fut>(&self, fetch: F) -> Result<String, anyhow::Error>
+    where
+        F: FnOnce() -> Fut,
+        Fut: Future<Output = Result<(String, u64), anyhow::Error>>,
+    {
+        let mut memory = self.memory.lock().await;
+        let now = now_secs();
+        if let Some(cached) = memory.as_ref().filter(|c| c.is_fresh(now)) {
+            return Ok(cached.token.clone());
+        }
+        match load_persisted(&self.key) {
+            Ok(Some(cached)) if cached.is_fresh(now) => {
+                let token = cached.token.clone();
+                *memory = Some(cached);
+                return Ok(token);
+            }
+            Ok(_) => {}
+            Err(e) => tracing::warn!("[token_cache] failed to load {}: {}", self.key, e),
+        }
+
+        let (token, ttl_secs) = fetch().await?;
+        let cached = CachedToken { token: token.clone(), expires_at: now + ttl_secs as i64 };
+        if let Err(e) = persist(&self.key, &cached) {
+            tracing::warn!("[token_cache] failed to persist {}: {}", self.key, e);
+        }
+        *memory = Some(cached);
+        Ok(token)
+    }
+
+    /// 使缓存失效（服务端返回 token 无效时调用），下次请求时重新获取
+    pub async fn invalidate(&self) {
+        *self.memory.lock().await = None;
+        if let Err(e) = open_db().and_then(|conn| clear(&conn, &self.key).map_err(|e| e.to_string())) {
+            tracing::warn!("[token_cache] failed to clear {}: {}", self.key, e);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cached_token_refreshes_near_expiry() {
+        let now = 1_700_000_000;
+        let fresh = CachedToken { token: "t".to_string(), expires_at: now + 7200 };
+        assert!(fresh.is_fresh(now));
+        let expiring = CachedToken { token: "t".to_string(), expires_at: now + 4 * 60 };
+        assert!(!expiring.is_fresh(now));
+    }
+
+    #[test]
+    fn test_store_load_and_clear() {
+        let conn = Connection::open_in_memory().unwrap();
+        crate::migrations::run(&conn).unwrap();
+        let key = "token_cache:feishu:cli_x";
+        assert_eq!(load(&conn, key).unwrap(), None);
+
+        let cached = CachedToken { token: "t-1".to_string(), expires_at: 1_700_007_200 };
+        store(&conn, key, &cached).unwrap();
+        store(&conn, key, &CachedToken { token: "t-2".to_string(), ..cached.clone() }).unwrap();
+        assert_eq!(load(&conn, key).unwrap().map(|c| c.token), Some("t-2".to_string()));
+        assert_eq!(load(&conn, "token_cache:dingtalk:cli_x").unwrap(), None);
+
+        clear(&conn, key).unwrap();
+        assert_eq!(load(&conn, key).unwrap(), None);
+    }
+}
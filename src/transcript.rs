@@ -0,0 +1,197 @@
+use serde_json::Value;
+
+use crate::text::truncate_chars;
+
+/// 最多向前扫描的 transcript 行数
+const MAX_SCAN_LINES: usize = 100;
+/// tool_use 参数展示的最大字符数
+const MAX_TOOL_INPUT_CHARS: usize = 100;
+/// thinking 摘要的最大字符数
+const MAX_THINKING_CHARS: usize = 300;
+/// 优先展示的工具参数（其余工具取第一个参数）
+const PRIMARY_INPUT_KEYS: &[&str] = &["command", "file_path", "pattern", "url", "query", "description"];
+
+/// 从 Stop 事件的 transcript（JSONL）中提取最近一轮对话的助手输出，按时间正序返回
+/// 文本块原样输出，tool_use 渲染为 "工具名(主要参数)"，tool_result 被忽略；
+/// 本轮没有任何文本时用 thinking 摘要兜底，保证卡片不为空
+pub fn extract_assistant_summary(transcript: &str) -> Vec<String> {
+    let mut elements: Vec<String> = Vec::new();
+    let mut thinking: Vec<String> = Vec::new();
+    let mut has_text = false;
+
+    // 从后向前遍历，遇到用户发出的文本消息即为本轮起点
+    for line in transcript.lines().rev().take(MAX_SCAN_LINES) {
+        let json = match serde_json::from_str::<Value>(line) {
+            Ok(json) => json,
+            Err(_) => continue,
+        };
+        let message = match json.get("message") {
+            Some(message) => message,
+            None => continue,
+        };
+        let role = message.get("role").and_then(|v| v.as_str());
+        let content = match message.get("content") {
+            Some(content) => content,
+            None => continue,
+        };
+
+        let mut turn_has_tool_result = false;
+        let mut turn_has_text = false;
+        let mut turn_elements = Vec::new();
+        let mut turn_thinking = Vec::new();
+
+        if let Some(items) = content.as_array() {
+            for item in items {
+                match item.get("type").and_then(|v| v.as_str()) {
+                    Some("text") => {
+                        if let Some(text) = item.get("text").and_then(|v| v.as_str()) {
+                            if !text.trim().is_empty() {
+                                turn_elements.push(format!("⏺ {}", text));
+                                turn_has_text = true;
+                            }
+                        }
+                    }
+                    Some("tool_use") => {
+                        let name = item.get("name").and_then(|v| v.as_str()).unwrap_or("tool");
+                        turn_elements.push(format_tool_use(name, item.get("input")));
+                    }
+                    Some("thinking") => {
+                        if let Some(text) = item.get("thinking").and_then(|v| v.as_str()) {
+                            let text = text.trim();
+                            if !text.is_empty() {
+                                turn_thinking.push(format!("💭 {}", summarize(text, MAX_THINKING_CHARS)));
+                            }
+                        }
+                    }
+                    Some("tool_result") => turn_has_tool_result = true,
+                    _ => {}
+                }
+            }
+        } else if let Some(text) = content.as_str() {
+            if !text.trim().is_empty() {
+                turn_elements.push(format!("⏺ {}", text));
+                turn_has_text = true;
+            }
+        }
+
+        // 用户发出的消息（非工具回传）是本轮起点，本身不属于助手输出
+        if role == Some("user") && !turn_has_tool_result {
+            break;
+        }
+
+        has_text |= turn_has_text;
+        // 逆序遍历，同一行内的元素也逆序存入，最后整体反转
+        elements.extend(turn_elements.into_iter().rev());
+        thinking.extend(turn_thinking.into_iter().rev());
+    }
+
+    if !has_text {
+        // 没有文本回复时，把 thinking 摘要放在工具调用之前
+        elements.extend(thinking);
+    }
+    elements.reverse();
+    elements
+}
+
+/// tool_use 摘要：工具名 + 主要参数
+fn format_tool_use(name: &str, input: Option<&Value>) -> String {
+    let object = match input.and_then(|v| v.as_object()) {
+        Some(object) if !object.is_empty() => object,
+        _ => return format!("⏺ **{}**", name),
+    };
+    let key = PRIMARY_INPUT_KEYS
+        .iter()
+        .find(|key| object.contains_key(**key))
+        .copied()
+        .or_else(|| object.keys().next().map(|k| k.as_str()));
+    match key {
+        Some(key) => {
+            let value = match &object[key] {
+                Value::String(s) => s.clone(),
+                other => other.to_string(),
+            };
+            format!("⏺ **{}**({}: {})", name, key, summarize(&value, MAX_TOOL_INPUT_CHARS))
+        }
+        None => format!("⏺ **{}**", name),
+    }
+}
+
+fn summarize(text: &str, max: usize) -> String {
+    if text.chars().count() > max {
+        format!("{}...", truncate_chars(text, max))
+    } else {
+        text.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn line(role: &str, content: Value) -> String {
+        serde_json::json!({"type": role, "message": {"role": role, "content": content}}).to_string()
+    }
+
+    #[test]
+    fn test_text_and_tool_use_in_order() {
+        let transcript = [
+            line("user", serde_json::json!("旧的问题")),
+            line("assistant", serde_json::json!([{"type": "text", "text": "旧的回答"}])),
+            line("user", serde_json::json!("列出文件")),
+            line("assistant", serde_json::json!([
+                {"type": "text", "text": "我来看看"},
+                {"type": "tool_use", "name": "Bash", "input": {"command": "ls -la", "description": "list"}}
+            ])),
+            line("user", serde_json::json!([{"type": "tool_result", "content": "a.txt"}])),
+            line("assistant", serde_json::json!([{"type": "text", "text": "只有 a.txt"}])),
+        ]
+        .join("\n");
+
+        assert_eq!(
+            extract_assistant_summary(&transcript),
+            vec![
+                "⏺ 我来看看".to_string(),
+                "⏺ **Bash**(command: ls -la)".to_string(),
+                "⏺ 只有 a.txt".to_string(),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_tool_call_only_falls_back_to_thinking() {
+        let transcript = [
+            line("user", serde_json::json!("修改配置")),
+            line("assistant", serde_json::json!([
+                {"type": "thinking", "thinking": "需要先读取配置文件"},
+                {"type": "tool_use", "name": "Read", "input": {"file_path": "/tmp/config.toml"}}
+            ])),
+        ]
+        .join("\n");
+
+        assert_eq!(
+            extract_assistant_summary(&transcript),
+            vec![
+                "💭 需要先读取配置文件".to_string(),
+                "⏺ **Read**(file_path: /tmp/config.toml)".to_string(),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_thinking_hidden_when_text_present() {
+        let transcript = [
+            line("user", serde_json::json!("你好")),
+            line("assistant", serde_json::json!([
+                {"type": "thinking", "thinking": "打个招呼"},
+                {"type": "text", "text": "你好！"}
+            ])),
+        ]
+        .join("\n");
+        assert_eq!(extract_assistant_summary(&transcript), vec!["⏺ 你好！".to_string()]);
+    }
+
+    #[test]
+    fn test_unparseable_transcript_is_empty() {
+        assert!(extract_assistant_summary("not json\n{}").is_empty());
+    }
+}
@@ -8,10 +8,48 @@ use std::sync::Arc;
 use std::time::Duration;
 use std::io::Read;
 use tokio::sync::Mutex;
-use tokio_tungstenite::{connect_async, tungstenite::protocol::Message as WsMessage};
+use tokio_tungstenite::tungstenite::protocol::Message as WsMessage;
+use tokio_tungstenite::tungstenite::protocol::{frame::coding::CloseCode, CloseFrame};
 use tokio_tungstenite::MaybeTlsStream;
 use tokio::net::TcpStream;
 use futures_util::stream::SplitSink;
+use crate::card_choice::CardChoice;
+use crate::ws_fragments::FragmentBuffer;
+
+/// 配对码回复中的选项，对应终端提示的 1/2/3
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum PairingChoice {
+    Allow,
+    AlwaysAllow,
+    Reject,
+}
+
+impl PairingChoice {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            PairingChoice::Allow => "1",
+            PairingChoice::AlwaysAllow => "2",
+            PairingChoice::Reject => "3",
+        }
+    }
+}
+
+/// 解析配对码回复 "<2 位配对码>-<1|2|3>"，允许首尾及连字符两侧的空白，兼容全角连字符
+pub fn parse_pairing_reply(text: &str) -> Option<(String, PairingChoice)> {
+    let normalized = text.trim().replace(['－', '—'], "-");
+    let (code, choice) = normalized.split_once('-')?;
+    let (code, choice) = (code.trim(), choice.trim());
+    if code.len() != 2 || !code.chars().all(|c| c.is_ascii_digit()) {
+        return None;
+    }
+    let choice = match choice {
+        "1" => PairingChoice::Allow,
+        "2" => PairingChoice::AlwaysAllow,
+        "3" => PairingChoice::Reject,
+        _ => return None,
+    };
+    Some((code.to_string(), choice))
+}
 
 // 包含由 prost 生成的 protobuf 代码
 pub mod proto {
@@ -23,6 +61,65 @@ use proto::{Frame, Header};
 const FEISHU_DOMAIN: &str = "https://open.feishu.cn";
 const GEN_ENDPOINT_URI: &str = "/callback/ws/endpoint";
 
+/// 生成附件保存文件名：以 message_id 为前缀避免冲突，并去掉原文件名中的路径分隔符
+fn attachment_file_name(message_id: &str, file_name: Option<&str>, content_type: Option<&str>) -> String {
+    let sanitize = |s: &str| -> String {
+        s.chars()
+            .map(|c| if c.is_alphanumeric() || matches!(c, '.' | '-' | '_') { c } else { '_' })
+            .collect()
+    };
+    let prefix = sanitize(message_id);
+    match file_name.map(|name| sanitize(name.trim())).filter(|name| !name.trim_matches('.').is_empty()) {
+        Some(name) => format!("{}_{}", prefix, name),
+        None => {
+            let ext = match content_type.map(|ct| ct.split(';').next().unwrap_or("").trim()) {
+                Some("image/jpeg") => "jpg",
+                Some("image/gif") => "gif",
+                Some("image/webp") => "webp",
+                Some("image/png") => "png",
+                Some(ct) if ct.starts_with("image/") => "png",
+                _ => "bin",
+            };
+            format!("{}.{}", prefix, ext)
+        }
+    }
+}
+
+/// 记录连接状态变化到 ws_connection_log（与桌面端共用 hooks.db，供 uptime 统计）
+fn record_ws_event(event: &str, reason: &str) {
+    let result = (|| -> Result<()> {
+        let conn = crate::migrations::open_connection(crate::migrations::db_path()?)?;
+        let now = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs() as i64;
+        crate::ws_connection_log::record(&conn, crate::ws_connection_log::SOURCE_CLI, event, reason, now)?;
+        Ok(())
+    })();
+    if let Err(e) = result {
+        tracing::error!("[ws] failed to record {} event: {}", event, e);
+    }
+}
+
+/// 记录最近收到帧的时间，供桌面端显示连接状态（经节流，高频事件下不会频繁写库）；
+/// 写入在阻塞线程池中执行，不阻塞 WSS 读循环
+fn record_wss_receive() {
+    let now = crate::project_tables::now_millis();
+    if !crate::wss_status::receive_due(now) {
+        return;
+    }
+    tokio::task::spawn_blocking(move || {
+        let result = (|| -> Result<()> {
+            let conn = crate::migrations::open_connection(crate::migrations::db_path()?)?;
+            crate::wss_status::set_last_receive_time(&conn, now)?;
+            Ok(())
+        })();
+        if let Err(e) = result {
+            tracing::error!("[ws] failed to record receive time: {}", e);
+        }
+    });
+}
+
 // Frame method 类型
 const FRAME_METHOD_CONTROL: i32 = 1;
 const FRAME_METHOD_DATA: i32 = 2;
@@ -87,22 +184,72 @@ pub struct EventHeader {
 pub struct FeishuWsClient {
     app_id: String,
     app_secret: String,
+    // 出站 HTTP 代理（仅用于 endpoint 请求和消息发送，WebSocket 本身直连）
+    proxy_url: Option<String>,
     connected: Arc<AtomicBool>,
     ping_interval_secs: Arc<AtomicU64>,
+    // 服务端下发的重连间隔（秒），0 表示未下发
+    reconnect_interval_secs: Arc<AtomicU64>,
+    // 复用同一个客户端，使 tenant_access_token 缓存在多次回复之间生效
+    feishu_client: crate::feishu::FeishuClient,
+    // 最近一次收到事件的时间（Unix 秒），0 表示尚未收到
+    last_event_at: Arc<AtomicU64>,
+    // 事件加密密钥与校验 token（未配置时不解密 / 不校验）
+    encrypt_key: Option<String>,
+    verification_token: Option<String>,
+    // 退出信号：置为 true 后 connect 发送 Close 帧并返回，重连循环随之结束
+    shutdown: tokio::sync::watch::Sender<bool>,
+    // 尚未收齐的分片事件
+    fragments: std::sync::Mutex<FragmentBuffer>,
 }
 
+/// 退出时等待服务端回应 Close 帧的最长时间
+const CLOSE_HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(2);
+
 impl FeishuWsClient {
-    pub fn new(app_id: String, app_secret: String) -> Self {
+    pub fn new(app_id: String, app_secret: String, proxy_url: Option<String>) -> Self {
+        let feishu_client =
+            crate::feishu::FeishuClient::new(app_id.clone(), app_secret.clone(), proxy_url.as_deref());
         FeishuWsClient {
             app_id,
             app_secret,
+            proxy_url,
+            feishu_client,
             connected: Arc::new(AtomicBool::new(false)),
             ping_interval_secs: Arc::new(AtomicU64::new(30)),
+            reconnect_interval_secs: Arc::new(AtomicU64::new(0)),
+            last_event_at: Arc::new(AtomicU64::new(0)),
+            encrypt_key: None,
+            verification_token: None,
+            shutdown: tokio::sync::watch::channel(false).0,
+            fragments: std::sync::Mutex::new(FragmentBuffer::default()),
         }
     }
 
+    /// 请求优雅退出：正在进行的连接发送 Close 帧后返回
+    pub fn shutdown(&self) {
+        self.shutdown.send_replace(true);
+    }
+
+    pub fn is_shutting_down(&self) -> bool {
+        *self.shutdown.borrow()
+    }
+
+    /// 等待退出信号
+    pub async fn wait_for_shutdown(&self) {
+        let mut rx = self.shutdown.subscribe();
+        let _ = rx.wait_for(|stopping| *stopping).await;
+    }
+
+    /// 配置事件解密密钥与 verification_token 校验
+    pub fn with_event_security(mut self, encrypt_key: Option<String>, verification_token: Option<String>) -> Self {
+        self.encrypt_key = encrypt_key.filter(|k| !k.is_empty());
+        self.verification_token = verification_token.filter(|t| !t.is_empty());
+        self
+    }
+
     async fn get_ws_url(&self) -> Result<String> {
-        let client = reqwest::Client::new();
+        let client = crate::feishu::build_http_client(self.proxy_url.as_deref())?;
         let url = format!("{}{}", FEISHU_DOMAIN, GEN_ENDPOINT_URI);
 
         let response = client
@@ -129,22 +276,37 @@ impl FeishuWsClient {
             if let Some(interval) = config.ping_interval {
                 self.ping_interval_secs.store(interval as u64, Ordering::Relaxed);
             }
+            if let Some(interval) = config.reconnect_interval.filter(|i| *i > 0) {
+                self.reconnect_interval_secs.store(interval as u64, Ordering::Relaxed);
+            }
         }
 
         Ok(data.url)
     }
 
+    /// 服务端 ClientConfig 下发的重连间隔，用作重连退避的基础值
+    pub fn reconnect_interval(&self) -> Option<Duration> {
+        match self.reconnect_interval_secs.load(Ordering::Relaxed) {
+            0 => None,
+            secs => Some(Duration::from_secs(secs)),
+        }
+    }
+
     pub async fn connect(&self) -> Result<()> {
         // 获取 WebSocket URL
         let ws_url = self.get_ws_url().await?;
         tracing::info!("Connecting to Feishu WebSocket...");
 
-        let (ws_stream, _) = connect_async(&ws_url).await?;
+        let proxy_url = crate::feishu::resolve_proxy_url(self.proxy_url.as_deref());
+        let ws_stream = crate::ws_proxy::connect_ws(&ws_url, proxy_url.as_deref())
+            .await
+            .map_err(anyhow::Error::msg)?;
         tracing::info!("WebSocket connected successfully");
 
         let (write, mut read) = ws_stream.split();
         let write = Arc::new(Mutex::new(write));
         self.connected.store(true, Ordering::SeqCst);
+        record_ws_event(crate::ws_connection_log::EVENT_CONNECTED, "");
 
         // 心跳任务
         let connected = self.connected.clone();
@@ -171,8 +333,33 @@ impl FeishuWsClient {
             }
         });
 
-        // 接收消息循环
-        while let Some(msg) = read.next().await {
+        // 接收消息循环（收到退出信号时发送 Close 帧并等待服务端回应）
+        let mut disconnect_reason = "stream ended".to_string();
+        loop {
+            let msg = tokio::select! {
+                msg = read.next() => msg,
+                _ = self.wait_for_shutdown() => {
+                    tracing::info!("Shutdown requested, closing WebSocket");
+                    disconnect_reason = "client shutdown".to_string();
+                    let close = WsMessage::Close(Some(CloseFrame {
+                        code: CloseCode::Normal,
+                        reason: "client shutdown".into(),
+                    }));
+                    if let Err(e) = write.lock().await.send(close).await {
+                        tracing::warn!("Failed to send close frame: {}", e);
+                    }
+                    let _ = tokio::time::timeout(CLOSE_HANDSHAKE_TIMEOUT, async {
+                        while let Some(Ok(msg)) = read.next().await {
+                            if matches!(msg, WsMessage::Close(_)) {
+                                break;
+                            }
+                        }
+                    })
+                    .await;
+                    break;
+                }
+            };
+            let Some(msg) = msg else { break };
             match msg {
                 Ok(WsMessage::Binary(data)) => {
                     if let Err(e) = self.handle_message(&data, &write).await {
@@ -187,11 +374,13 @@ impl FeishuWsClient {
                 }
                 Ok(WsMessage::Close(_)) => {
                     tracing::info!("WebSocket closed by server");
+                    disconnect_reason = "closed by server".to_string();
                     self.connected.store(false, Ordering::SeqCst);
                     break;
                 }
                 Err(e) => {
                     tracing::error!("WebSocket error: {}", e);
+                    disconnect_reason = format!("error: {}", e);
                     self.connected.store(false, Ordering::SeqCst);
                     break;
                 }
@@ -201,6 +390,7 @@ impl FeishuWsClient {
 
         heartbeat_handle.abort();
         self.connected.store(false, Ordering::SeqCst);
+        record_ws_event(crate::ws_connection_log::EVENT_DISCONNECTED, &disconnect_reason);
         tracing::info!("WebSocket disconnected");
 
         Ok(())
@@ -230,6 +420,7 @@ impl FeishuWsClient {
 
     async fn handle_message(&self, data: &[u8], write: &Arc<Mutex<WsWrite>>) -> Result<()> {
         let frame = Frame::decode(data)?;
+        record_wss_receive();
         let method = frame.method;
         let msg_type = Self::get_header_value(&frame, HEADER_TYPE);
         let payload_len = frame.payload.as_ref().map(|payload| payload.len()).unwrap_or(0);
@@ -307,24 +498,46 @@ impl FeishuWsClient {
 
         self.send_ack(frame, write).await?;
 
-        let payload_str = match Self::decode_payload(frame) {
+        let payload = match self.reassemble_payload(frame) {
+            Some(payload) => payload,
+            None if frame.payload.is_some() => {
+                tracing::debug!("[ws:event] buffered fragment, waiting for the rest");
+                return Ok(());
+            }
+            None => {
+                tracing::debug!("[ws:event] no payload in data frame");
+                return Ok(());
+            }
+        };
+
+        let payload_str = match Self::decode_payload(frame, &payload) {
             Ok(s) => s,
             Err(e) => {
                 tracing::warn!("[ws] failed to decode payload: {}", e);
                 return Ok(());
             }
         };
-        if let Some(payload_str) = payload_str {
-            tracing::info!("[ws:event] payload len={}, preview={}", payload_str.len(), &payload_str[..payload_str.len().min(500)]);
-            if let Ok(event) = serde_json::from_str::<EventPayload>(&payload_str) {
-                self.handle_event(&event).await?;
-            } else if let Ok(value) = serde_json::from_str::<serde_json::Value>(&payload_str) {
-                tracing::warn!("[ws:event] parsed as generic JSON but not EventPayload: {}", value);
-            } else {
-                tracing::warn!("[ws:event] payload is not valid JSON: {}", &payload_str[..payload_str.len().min(200)]);
+        let payload_str = match crate::event_crypto::open_envelope(self.encrypt_key.as_deref(), &payload_str) {
+            Ok(p) => p,
+            Err(e) => {
+                tracing::warn!("[ws:event] dropping event that failed decryption: {}", e);
+                return Ok(());
+            }
+        };
+        tracing::info!("[ws:event] payload len={}, preview={}", payload_str.len(), crate::text::truncate_chars(&payload_str, 500));
+        if let Ok(event) = serde_json::from_str::<EventPayload>(&payload_str) {
+            if !crate::event_crypto::verify_token(self.verification_token.as_deref(), &event.header.token) {
+                tracing::warn!(
+                    "[ws:event] dropping event with invalid verification token: event_id={}",
+                    event.header.event_id
+                );
+                return Ok(());
             }
+            self.handle_event(&event).await?;
+        } else if let Ok(value) = serde_json::from_str::<serde_json::Value>(&payload_str) {
+            tracing::warn!("[ws:event] parsed as generic JSON but not EventPayload: {}", value);
         } else {
-            tracing::debug!("[ws:event] no payload in data frame");
+            tracing::warn!("[ws:event] payload is not valid JSON: {}", crate::text::truncate_chars(&payload_str, 200));
         }
 
         Ok(())
@@ -333,6 +546,9 @@ impl FeishuWsClient {
     async fn handle_event(&self, event: &EventPayload) -> Result<()> {
         let event_type = &event.header.event_type;
         tracing::info!("[ws:event] type={}, event_id={}", event_type, event.header.event_id);
+        if let Ok(now) = std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH) {
+            self.last_event_at.store(now.as_secs(), Ordering::Relaxed);
+        }
 
         match event_type.as_str() {
             "card.action.trigger" => {
@@ -353,15 +569,16 @@ impl FeishuWsClient {
         tracing::info!("Card action: {}", serde_json::to_string_pretty(event_data)?);
         
         // 获取用户选择的值
-        if let Some(action) = event_data.get("action") {
-            if let Some(value) = action.get("value") {
-                if let Some(choice) = value.get("choice") {
-                    if let Some(choice_str) = choice.as_str() {
-                        tracing::info!("User choice: {}", choice_str);
-                        self.save_user_choice(choice_str).await?;
-                    }
+        let value = event_data.get("action").and_then(|action| action.get("value"));
+        match value.and_then(CardChoice::from_value) {
+            Some(choice) => match choice.user_choice() {
+                Some(answer) => {
+                    tracing::info!("User choice: {} ({:?})", answer, choice);
+                    self.save_user_choice(&answer).await?;
                 }
-            }
+                None => tracing::info!("Test card button clicked, ignoring"),
+            },
+            None => tracing::warn!("Card action without a recognised value: {:?}", value),
         }
 
         Ok(())
@@ -392,6 +609,34 @@ impl FeishuWsClient {
             if let Err(e) = crate::feishu::save_open_id_to_db(sender) {
                 tracing::error!("Failed to save open_id to DB: {}", e);
             }
+            let recorded = (|| -> Result<()> {
+                let conn = crate::migrations::open_connection(crate::migrations::db_path()?)?;
+                crate::wss_status::record_open_id(&conn, sender)?;
+                Ok(())
+            })();
+            if let Err(e) = recorded {
+                tracing::error!("Failed to record last open_id: {}", e);
+            }
+        }
+
+        if message_type == "image" || message_type == "file" {
+            let message_id = event_data
+                .get("message")
+                .and_then(|message| message.get("message_id"))
+                .and_then(|value| value.as_str())
+                .unwrap_or("");
+            let chat_id = event_data
+                .get("message")
+                .and_then(|message| message.get("chat_id"))
+                .and_then(|value| value.as_str());
+            if let Err(e) = self.handle_attachment(message_id, message_type, content, sender, chat_id).await {
+                tracing::error!("Failed to handle {} message {}: {}", message_type, message_id, e);
+                if sender != "unknown" {
+                    let msg = format!("❌ 附件保存失败: {}", e);
+                    let _ = self.feishu_client.send_message(sender, msg, None, "open_id").await;
+                }
+            }
+            return Ok(());
         }
 
         // 解析消息内容
@@ -424,24 +669,65 @@ impl FeishuWsClient {
 
         tracing::info!("Message parsed: sender={}, type={}, content={}", sender, message_type, text_content);
 
-        // 检查是否是权限确认回复（格式: XXXX-N, 例如 1234-1）
-        let trimmed = text_content.trim();
-        // 匹配 4位数字-1/2/3 格式
-        if let Some(dash_pos) = trimmed.find('-') {
-            let code_part = &trimmed[..dash_pos];
-            let choice_part = &trimmed[dash_pos+1..];
-            if code_part.len() == 2 
-                && code_part.chars().all(|c| c.is_ascii_digit())
-                && (choice_part == "1" || choice_part == "2" || choice_part == "3") 
-            {
-                tracing::info!("Received permission response: code={}, choice={}", code_part, choice_part);
-                self.send_permission_response(code_part, choice_part, sender).await?;
-            }
+        // 检查是否是权限确认回复（格式: 配对码-选项，例如 42-1）
+        if let Some((code, choice)) = parse_pairing_reply(&text_content) {
+            tracing::info!("Received permission response: code={}, choice={:?}", code, choice);
+            self.send_permission_response(&code, choice.as_str(), sender).await?;
         }
 
         Ok(())
     }
 
+    /// 下载图片/文件消息中的资源到 ~/sparky/attachments/，并记录到数据库供该会话所属项目的 relay worker 使用
+    async fn handle_attachment(
+        &self,
+        message_id: &str,
+        message_type: &str,
+        content: &str,
+        sender: &str,
+        chat_id: Option<&str>,
+    ) -> Result<()> {
+        if message_id.is_empty() {
+            anyhow::bail!("message_id missing in {} message", message_type);
+        }
+        let project_path = crate::feishu::attachment_project(chat_id)
+            .map_err(anyhow::Error::msg)?
+            .ok_or_else(|| anyhow::anyhow!("该会话未关联项目，无法确定附件要提供给哪个任务"))?;
+        let json: serde_json::Value = serde_json::from_str(content)?;
+        let key_field = if message_type == "image" { "image_key" } else { "file_key" };
+        let file_key = json
+            .get(key_field)
+            .and_then(|value| value.as_str())
+            .ok_or_else(|| anyhow::anyhow!("{} missing in message content", key_field))?;
+        let file_name = json.get("file_name").and_then(|value| value.as_str());
+
+        let (bytes, content_type) = self
+            .feishu_client
+            .download_message_resource(message_id, file_key, message_type)
+            .await?;
+
+        let dir = dirs::home_dir()
+            .ok_or_else(|| anyhow::anyhow!("Failed to get home dir"))?
+            .join("sparky")
+            .join("attachments");
+        tokio::fs::create_dir_all(&dir).await?;
+        let path = dir.join(attachment_file_name(message_id, file_name, content_type.as_deref()));
+        tokio::fs::write(&path, &bytes).await?;
+        let path_str = path.to_string_lossy().to_string();
+        tracing::info!("Attachment saved: {} ({} bytes)", path_str, bytes.len());
+
+        crate::feishu::save_attachment_to_db(message_id, message_type, &path_str, sender, &project_path)
+            .map_err(anyhow::Error::msg)?;
+
+        if sender != "unknown" {
+            let msg = format!("📎 附件已保存，将在项目 {} 的下一次任务中提供给 Claude：`{}`", project_path, path_str);
+            if let Err(e) = self.feishu_client.send_message(sender, msg, None, "open_id").await {
+                tracing::error!("Failed to send attachment confirmation to Feishu: {}", e);
+            }
+        }
+        Ok(())
+    }
+
     async fn send_permission_response(&self, code: &str, choice: &str, open_id: &str) -> Result<()> {
         // 验证是否有 pending 请求，并执行命令
         match crate::feishu::verify_and_execute_command(code, choice) {
@@ -449,9 +735,8 @@ impl FeishuWsClient {
                 tracing::info!("PTY command verified and queued for code={}, choice={}", code, choice);
                 
                 // 发送接收成功的消息到飞书，避免用户等待
-                let feishu_client = crate::feishu::FeishuClient::new(self.app_id.clone(), self.app_secret.clone());
                 let msg = format!("✅ 接收成功 (code={})，正在执行...", code);
-                if let Err(e) = feishu_client.send_message(open_id, msg, None, "open_id").await {
+                if let Err(e) = self.feishu_client.send_message(open_id, msg, None, "open_id").await {
                     tracing::error!("Failed to send confirmation message to Feishu: {}", e);
                 }
             }
@@ -462,20 +747,31 @@ impl FeishuWsClient {
                 tracing::error!("Failed to verify and execute pty command: {}", e);
                 
                 // 发送失败消息
-                let feishu_client = crate::feishu::FeishuClient::new(self.app_id.clone(), self.app_secret.clone());
                 let msg = format!("❌ 执行失败: {}", e);
-                let _ = feishu_client.send_message(open_id, msg, None, "open_id").await;
+                let _ = self.feishu_client.send_message(open_id, msg, None, "open_id").await;
             }
         }
         Ok(())
     }
 
-    fn decode_payload(frame: &Frame) -> Result<Option<String>> {
-        let payload = match &frame.payload {
-            Some(payload) => payload.as_slice(),
-            None => return Ok(None),
-        };
+    /// 取出数据帧的完整负载：单帧直接返回，分片帧缓存到收齐为止（未收齐时返回 None）
+    fn reassemble_payload(&self, frame: &Frame) -> Option<Vec<u8>> {
+        let payload = frame.payload.clone()?;
+        let sum: usize = Self::get_header_value(frame, HEADER_SUM)
+            .and_then(|v| v.parse().ok())
+            .unwrap_or(1);
+        if sum <= 1 {
+            return Some(payload);
+        }
+        let message_id = Self::get_header_value(frame, HEADER_MESSAGE_ID)?;
+        let seq: usize = Self::get_header_value(frame, HEADER_SEQ)?.parse().ok()?;
+        self.fragments
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .push(&message_id, sum, seq, payload)
+    }
 
+    fn decode_payload(frame: &Frame, payload: &[u8]) -> Result<String> {
         let payload_encoding = frame.payload_encoding.as_deref().unwrap_or("");
         let is_gzip = payload_encoding.eq_ignore_ascii_case("gzip")
             || payload.starts_with(&[0x1f, 0x8b]);
@@ -488,7 +784,7 @@ impl FeishuWsClient {
             String::from_utf8_lossy(payload).to_string()
         };
 
-        Ok(Some(decoded))
+        Ok(decoded)
     }
 
     async fn send_pong(&self, service_id: i32, write: &Arc<Mutex<WsWrite>>) -> Result<()> {
@@ -580,4 +876,61 @@ impl FeishuWsClient {
     pub fn is_connected(&self) -> bool {
         self.connected.load(Ordering::SeqCst)
     }
+
+    /// 最近一次收到事件的时间（Unix 秒）
+    pub fn last_event_at(&self) -> Option<u64> {
+        match self.last_event_at.load(Ordering::Relaxed) {
+            0 => None,
+            secs => Some(secs),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_reassemble_gzip_fragments() {
+        use flate2::{write::GzEncoder, Compression};
+        use std::io::Write;
+
+        let event = r#"{"schema":"2.0","header":{"event_id":"e1","event_type":"im.message.receive_v1","create_time":"0","token":"","app_id":"cli_a","tenant_key":"t"},"event":{}}"#;
+        let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(event.as_bytes()).unwrap();
+        let gzipped = encoder.finish().unwrap();
+        let (first, second) = gzipped.split_at(gzipped.len() / 2);
+
+        let mut buffer = FragmentBuffer::default();
+        assert_eq!(buffer.push("m1", 2, 1, second.to_vec()), None);
+        let whole = buffer.push("m1", 2, 0, first.to_vec()).unwrap();
+
+        let frame = Frame { payload_encoding: Some("gzip".to_string()), ..FeishuWsClient::create_ping_frame(1) };
+        let decoded = FeishuWsClient::decode_payload(&frame, &whole).unwrap();
+        let parsed: EventPayload = serde_json::from_str(&decoded).unwrap();
+        assert_eq!(parsed.header.event_id, "e1");
+    }
+
+    #[test]
+    fn test_parse_pairing_reply() {
+        assert_eq!(parse_pairing_reply("42-1"), Some(("42".to_string(), PairingChoice::Allow)));
+        assert_eq!(parse_pairing_reply(" 42 - 2\n"), Some(("42".to_string(), PairingChoice::AlwaysAllow)));
+        assert_eq!(parse_pairing_reply("42－3"), Some(("42".to_string(), PairingChoice::Reject)));
+    }
+
+    #[test]
+    fn test_attachment_file_name() {
+        assert_eq!(attachment_file_name("om_1", Some("error log.txt"), None), "om_1_error_log.txt");
+        assert_eq!(attachment_file_name("om_1", Some("../../etc/passwd"), None), "om_1_.._.._etc_passwd");
+        assert_eq!(attachment_file_name("om_1", None, Some("image/jpeg")), "om_1.jpg");
+        assert_eq!(attachment_file_name("om_1", Some(".."), Some("image/png; charset=binary")), "om_1.png");
+        assert_eq!(attachment_file_name("om_1", None, None), "om_1.bin");
+    }
+
+    #[test]
+    fn test_parse_pairing_reply_rejects_malformed() {
+        for input in ["", "1", "42", "42-", "-1", "4-1", "123-1", "ab-1", "42-4", "42-12", "42-1-1", "４２-1", "ok 42-1"] {
+            assert_eq!(parse_pairing_reply(input), None, "input: {:?}", input);
+        }
+    }
 }
@@ -0,0 +1,176 @@
+use reqwest::Client;
+use tracing::error;
+
+use crate::feishu::{build_http_client, CardAction};
+use crate::token_cache::TokenCache;
+
+/// 响应中缺少 expires_in 字段时使用的默认有效期（企业微信默认 2 小时）
+const DEFAULT_TOKEN_TTL_SECS: u64 = 7200;
+/// 企业微信 markdown 消息内容上限为 4096 字节
+const MAX_MARKDOWN_BYTES: usize = 4000;
+/// 企业微信自建应用客户端：向群聊（appchat）和成员发送 markdown 消息
+#[derive(Clone)]
+pub struct WeWorkClient {
+    client: Client,
+    corp_id: String,
+    corp_secret: String,
+    token_cache: TokenCache,
+}
+
+impl WeWorkClient {
+    pub fn new(corp_id: String, corp_secret: String, proxy_url: Option<&str>) -> Self {
+        let client = build_http_client(proxy_url).unwrap_or_else(|e| {
+            error!("[wework] invalid proxy config, falling back to direct connection: {}", e);
+            Client::new()
+        });
+        let token_cache = TokenCache::new("wework", &corp_id);
+        WeWorkClient {
+            client,
+            corp_id,
+            corp_secret,
+            token_cache,
+        }
+    }
+
+    /// 获取应用 access_token，优先使用缓存
+    pub async fn get_access_token(&self) -> Result<String, anyhow::Error> {
+        self.token_cache.get_or_fetch(|| self.request_access_token()).await
+    }
+
+    /// 请求新的 access token，返回 token 与有效期（秒）
+    async fn request_access_token(&self) -> Result<(String, u64), anyhow::Error> {
+        let response = self
+            .client
+            .get("https://qyapi.weixin.qq.com/cgi-bin/gettoken")
+            .query(&[("corpid", self.corp_id.as_str()), ("corpsecret", self.corp_secret.as_str())])
+            .send()
+            .await?;
+
+        let status = response.status();
+        let result: serde_json::Value = response.json().await?;
+        let errcode = result["errcode"].as_i64().unwrap_or(-1);
+        let token = match result["access_token"].as_str() {
+            Some(token) if errcode == 0 => token.to_string(),
+            _ => {
+                let msg = result["errmsg"].as_str().unwrap_or("Unknown error");
+                error!("[wework:token] FAILED: status={}, errcode={}, errmsg={}", status, errcode, msg);
+                anyhow::bail!("Failed to get WeWork access token: {}", msg);
+            }
+        };
+        let ttl_secs = result["expires_in"].as_u64().unwrap_or(DEFAULT_TOKEN_TTL_SECS);
+        tracing::info!("[wework:token] obtained token (len={}, expire={}s)", token.len(), ttl_secs);
+        Ok((token, ttl_secs))
+    }
+
+    /// 以 markdown 消息发送到群聊（appchat），返回 chatid
+    pub async fn send_message(&self, chat_id: &str, content: &str) -> Result<String, anyhow::Error> {
+        let body = serde_json::json!({
+            "chatid": chat_id,
+            "msgtype": "markdown",
+            "markdown": { "content": truncate_bytes(content, MAX_MARKDOWN_BYTES) }
+        });
+        tracing::info!("[wework:send] sending markdown: chatid={}, content_len={}", chat_id, content.len());
+        self.post("https://qyapi.weixin.qq.com/cgi-bin/appchat/send", &body).await?;
+        tracing::info!("[wework:send] message sent successfully: chatid={}", chat_id);
+        Ok(chat_id.to_string())
+    }
+
+    /// 以应用消息向成员发送 markdown（权限确认提醒），返回 msgid
+    pub async fn send_user_message(&self, agent_id: &str, to_user: &str, content: &str) -> Result<String, anyhow::Error> {
+        let agent_id: i64 = agent_id
+            .trim()
+            .parse()
+            .map_err(|_| anyhow::anyhow!("Invalid WeWork agent_id '{}'", agent_id))?;
+        let body = serde_json::json!({
+            "touser": to_user,
+            "msgtype": "markdown",
+            "agentid": agent_id,
+            "markdown": { "content": truncate_bytes(content, MAX_MARKDOWN_BYTES) }
+        });
+        tracing::info!("[wework:send] sending markdown to user: touser={}, content_len={}", to_user, content.len());
+        let result = self.post("https://qyapi.weixin.qq.com/cgi-bin/message/send", &body).await?;
+        let msg_id = result["msgid"].as_str().unwrap_or("").to_string();
+        tracing::info!("[wework:send] user message sent successfully: msgid={}", msg_id);
+        Ok(msg_id)
+    }
+
+    async fn post(&self, url: &str, body: &serde_json::Value) -> Result<serde_json::Value, anyhow::Error> {
+        let token = self.get_access_token().await?;
+        let response = self
+            .client
+            .post(url)
+            .query(&[("access_token", token.as_str())])
+            .json(body)
+            .send()
+            .await?;
+
+        let status = response.status();
+        let result: serde_json::Value = response.json().await?;
+        let errcode = result["errcode"].as_i64().unwrap_or(-1);
+        if errcode != 0 {
+            let msg = result["errmsg"].as_str().unwrap_or("Unknown error");
+            error!("[wework:send] FAILED: status={}, errcode={}, errmsg={}", status, errcode, msg);
+            anyhow::bail!("Failed to send WeWork message: {}", msg);
+        }
+        Ok(result)
+    }
+}
+
+/// 企业微信没有接收按钮回调的通道，卡片按钮改为以文本列出，由用户在终端中选择
+pub fn actions_text(content: &str, actions: &[CardAction]) -> String {
+    if actions.is_empty() {
+        return content.to_string();
+    }
+    let mut text = format!("{}\n\n**可选操作**（请在终端中选择）", content);
+    for action in actions {
+        text.push_str("\n> ");
+        text.push_str(&action.text.content);
+    }
+    text
+}
+
+/// 按字节上限截断，保证不截断在 UTF-8 字符中间
+fn truncate_bytes(s: &str, max_bytes: usize) -> &str {
+    if s.len() <= max_bytes {
+        return s;
+    }
+    let mut end = max_bytes;
+    while !s.is_char_boundary(end) {
+        end -= 1;
+    }
+    &s[..end]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::feishu::CardText;
+
+    #[test]
+    fn test_truncate_bytes_respects_char_boundary() {
+        assert_eq!(truncate_bytes("abc", 10), "abc");
+        assert_eq!(truncate_bytes("权限确认", 7), "权限");
+    }
+
+    #[test]
+    fn test_actions_text_lists_buttons() {
+        let actions = vec![
+            CardAction {
+                tag: "button".to_string(),
+                text: CardText { content: "✅ Yes (1)".to_string(), tag: "plain_text".to_string() },
+                action_type: "primary".to_string(),
+                value: serde_json::json!({"choice": "1"}),
+            },
+            CardAction {
+                tag: "button".to_string(),
+                text: CardText { content: "❌ No (2)".to_string(), tag: "plain_text".to_string() },
+                action_type: "danger".to_string(),
+                value: serde_json::json!({"choice": "2"}),
+            },
+        ];
+        let text = actions_text("🧭 **权限确认**\n\nBash: ls", &actions);
+        assert!(text.starts_with("🧭 **权限确认**\n\nBash: ls\n\n**可选操作**"));
+        assert!(text.ends_with("> ✅ Yes (1)\n> ❌ No (2)"));
+        assert_eq!(actions_text("plain", &[]), "plain");
+    }
+}
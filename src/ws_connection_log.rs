@@ -0,0 +1,105 @@
+// Feishu WSS connect/disconnect history (ws_connection_log), shared by the CLI and the desktop app
+// (src-tauri includes this file via #[path]; both processes write to the same hooks.db, tagged by source)
+
+use rusqlite::{params, Connection, OptionalExtension};
+
+pub const EVENT_CONNECTED: &str = "connected";
+pub const EVENT_DISCONNECTED: &str = "disconnected";
+
+#[allow(dead_code)] // 由 CLI 写入
+pub const SOURCE_CLI: &str = "cli";
+#[allow(dead_code)] // 由桌面应用写入
+pub const SOURCE_DESKTOP: &str = "desktop";
+
+/// 进程异常退出、未写入断开事件时补记的断开原因
+pub const REASON_STALE: &str = "stale";
+
+/// 连接日志保留时长（秒）
+const RETENTION_SECS: i64 = 30 * 24 * 3600;
+
+/// 超过该时长未收到任何帧（含心跳 pong）时，最后一条 connected 记录视为已失效
+#[allow(dead_code)] // 由桌面应用统计时使用
+pub const STALE_AFTER_SECS: i64 = 600;
+
+/// 该来源最后一条事件及其时间（秒）
+fn last_event(conn: &Connection, source: &str) -> rusqlite::Result<Option<(String, i64)>> {
+    conn.query_row(
+        "SELECT event, created_at FROM ws_connection_log WHERE source = ?1 ORDER BY id DESC LIMIT 1",
+        params![source],
+        |row| Ok((row.get(0)?, row.get(1)?)),
+    )
+    .optional()
+}
+
+/// 最近一次收到 WSS 帧的时间（秒），作为连接仍然存活的依据
+pub fn last_seen_secs(conn: &Connection) -> rusqlite::Result<Option<i64>> {
+    Ok(crate::wss_status::last_receive_time(conn)?.map(|ms| ms / 1000))
+}
+
+/// 记录一次连接状态变化。记录 connected 前若该来源仍停在 connected（上次进程崩溃），
+/// 先按最后收到帧的时间补记一条断开，避免崩溃后的离线时段被统计为在线
+pub fn record(conn: &Connection, source: &str, event: &str, reason: &str, now: i64) -> rusqlite::Result<()> {
+    if event == EVENT_CONNECTED {
+        if let Some((last, at)) = last_event(conn, source)? {
+            if last == EVENT_CONNECTED {
+                let ended_at = last_seen_secs(conn)?.unwrap_or(at).clamp(at, now);
+                conn.execute(
+                    "INSERT INTO ws_connection_log (source, event, reason, created_at) VALUES (?1, ?2, ?3, ?4)",
+                    params![source, EVENT_DISCONNECTED, REASON_STALE, ended_at],
+                )?;
+            }
+        }
+    }
+    conn.execute(
+        "INSERT INTO ws_connection_log (source, event, reason, created_at) VALUES (?1, ?2, ?3, ?4)",
+        params![source, event, reason, now],
+    )?;
+    conn.execute(
+        "DELETE FROM ws_connection_log WHERE created_at < ?1",
+        params![now - RETENTION_SECS],
+    )?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn events(conn: &Connection, source: &str) -> Vec<(String, String, i64)> {
+        let mut stmt = conn
+            .prepare("SELECT event, reason, created_at FROM ws_connection_log WHERE source = ?1 ORDER BY id")
+            .unwrap();
+        stmt.query_map(params![source], |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)))
+            .unwrap()
+            .collect::<Result<_, _>>()
+            .unwrap()
+    }
+
+    #[test]
+    fn test_stale_connected_row_is_closed_on_reconnect() {
+        let conn = Connection::open_in_memory().unwrap();
+        crate::migrations::run(&conn).unwrap();
+
+        record(&conn, SOURCE_CLI, EVENT_CONNECTED, "", 1_000).unwrap();
+        // 另一个进程的记录互不影响
+        record(&conn, SOURCE_DESKTOP, EVENT_CONNECTED, "", 1_100).unwrap();
+        crate::wss_status::set_last_receive_time(&conn, 1_500_000).unwrap();
+        // CLI 崩溃后重新连接：按最后收到帧的时间补记断开
+        record(&conn, SOURCE_CLI, EVENT_CONNECTED, "", 5_000).unwrap();
+
+        assert_eq!(
+            events(&conn, SOURCE_CLI),
+            vec![
+                (EVENT_CONNECTED.to_string(), String::new(), 1_000),
+                (EVENT_DISCONNECTED.to_string(), REASON_STALE.to_string(), 1_500),
+                (EVENT_CONNECTED.to_string(), String::new(), 5_000),
+            ]
+        );
+        assert_eq!(events(&conn, SOURCE_DESKTOP).len(), 1);
+
+        // 正常断开后再连接不补记
+        record(&conn, SOURCE_CLI, EVENT_DISCONNECTED, "closed", 6_000).unwrap();
+        record(&conn, SOURCE_CLI, EVENT_CONNECTED, "", 7_000).unwrap();
+        assert_eq!(events(&conn, SOURCE_CLI).len(), 5);
+    }
+}
@@ -0,0 +1,64 @@
+// Reassembly of Feishu WSS data frames that arrive split into sum/seq fragments, shared by the CLI
+// and the desktop app (src-tauri includes this file via #[path] so both clients decode large events)
+
+/// 分片事件在缓冲区中最长保留时间，超时未收齐的分片直接丢弃
+const FRAGMENT_TTL: std::time::Duration = std::time::Duration::from_secs(10);
+
+/// 大事件被飞书按 sum/seq 拆成多个数据帧，按 message_id 缓存分片，收齐后按 seq 顺序拼接
+#[derive(Default)]
+pub struct FragmentBuffer {
+    pending: std::collections::HashMap<String, PendingFragments>,
+}
+
+struct PendingFragments {
+    parts: Vec<Option<Vec<u8>>>,
+    first_seen: std::time::Instant,
+}
+
+impl FragmentBuffer {
+    /// 存入一个分片；全部分片到齐时返回拼接后的完整负载，否则返回 None
+    pub fn push(&mut self, message_id: &str, sum: usize, seq: usize, payload: Vec<u8>) -> Option<Vec<u8>> {
+        let now = std::time::Instant::now();
+        self.pending.retain(|_, p| now.duration_since(p.first_seen) < FRAGMENT_TTL);
+
+        if sum <= 1 {
+            return Some(payload);
+        }
+        if seq >= sum {
+            tracing::warn!("[ws] fragment seq {} out of range (sum={}) for message {}", seq, sum, message_id);
+            return None;
+        }
+        let entry = self.pending.entry(message_id.to_string()).or_insert_with(|| PendingFragments {
+            parts: vec![None; sum],
+            first_seen: now,
+        });
+        if entry.parts.len() != sum {
+            tracing::warn!("[ws] fragment sum changed for message {}, restarting reassembly", message_id);
+            *entry = PendingFragments { parts: vec![None; sum], first_seen: now };
+        }
+        entry.parts[seq] = Some(payload);
+        if entry.parts.iter().any(Option::is_none) {
+            return None;
+        }
+        let parts = self.pending.remove(message_id)?.parts;
+        Some(parts.into_iter().flatten().flatten().collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_fragments_reassemble_in_seq_order() {
+        let mut buffer = FragmentBuffer::default();
+        assert_eq!(buffer.push("m1", 3, 2, b"c".to_vec()), None);
+        assert_eq!(buffer.push("m1", 3, 0, b"a".to_vec()), None);
+        assert_eq!(buffer.push("m1", 3, 1, b"b".to_vec()), Some(b"abc".to_vec()));
+        assert!(buffer.pending.is_empty());
+
+        assert_eq!(buffer.push("m2", 1, 0, b"single".to_vec()), Some(b"single".to_vec()));
+        assert_eq!(buffer.push("m3", 2, 5, b"bad".to_vec()), None);
+        assert!(buffer.pending.is_empty());
+    }
+}
@@ -0,0 +1,103 @@
+// Last-received state of the Feishu WSS connection, shared by the CLI and the desktop app
+// (src-tauri includes this file via #[path]; values live in db_meta so either process can write them)
+
+use rusqlite::{params, Connection, OptionalExtension};
+use std::sync::atomic::{AtomicI64, Ordering};
+
+const LAST_RECEIVE_TIME_KEY: &str = "wss_last_receive_time";
+const LAST_OPEN_ID_KEY: &str = "wss_last_open_id";
+
+/// 两次写入接收时间的最小间隔（毫秒），高频事件下只落库一次，避免频繁写数据库
+pub const RECEIVE_WRITE_INTERVAL_MS: i64 = 2000;
+
+/// 写入节流：距离上次写入不足 interval 时跳过
+pub struct Debouncer {
+    last_write_ms: AtomicI64,
+    interval_ms: i64,
+}
+
+impl Debouncer {
+    pub const fn new(interval_ms: i64) -> Self {
+        Self { last_write_ms: AtomicI64::new(i64::MIN), interval_ms }
+    }
+
+    /// 返回本次是否应写入；并发调用时只有一个会成功
+    pub fn should_write(&self, now_ms: i64) -> bool {
+        let last = self.last_write_ms.load(Ordering::Relaxed);
+        if last != i64::MIN && now_ms.saturating_sub(last) < self.interval_ms {
+            return false;
+        }
+        self.last_write_ms
+            .compare_exchange(last, now_ms, Ordering::Relaxed, Ordering::Relaxed)
+            .is_ok()
+    }
+}
+
+static RECEIVE_DEBOUNCER: Debouncer = Debouncer::new(RECEIVE_WRITE_INTERVAL_MS);
+
+fn set_meta(conn: &Connection, key: &str, value: &str) -> rusqlite::Result<()> {
+    conn.execute(
+        "INSERT INTO db_meta (key, value) VALUES (?1, ?2)
+         ON CONFLICT(key) DO UPDATE SET value = excluded.value",
+        params![key, value],
+    )?;
+    Ok(())
+}
+
+fn get_meta(conn: &Connection, key: &str) -> rusqlite::Result<Option<String>> {
+    conn.query_row("SELECT value FROM db_meta WHERE key = ?1", params![key], |row| row.get(0))
+        .optional()
+}
+
+/// 本进程距离上次写入接收时间是否已超过节流间隔；在打开数据库前调用，节流期间无需任何 IO
+pub fn receive_due(now_ms: i64) -> bool {
+    RECEIVE_DEBOUNCER.should_write(now_ms)
+}
+
+/// 记录收到 WSS 帧的时间（毫秒）
+pub fn set_last_receive_time(conn: &Connection, now_ms: i64) -> rusqlite::Result<()> {
+    set_meta(conn, LAST_RECEIVE_TIME_KEY, &now_ms.to_string())
+}
+
+/// 记录最近给机器人发消息的用户 open_id（消息频率低，不节流）
+pub fn record_open_id(conn: &Connection, open_id: &str) -> rusqlite::Result<()> {
+    set_meta(conn, LAST_OPEN_ID_KEY, open_id)
+}
+
+pub fn last_receive_time(conn: &Connection) -> rusqlite::Result<Option<i64>> {
+    Ok(get_meta(conn, LAST_RECEIVE_TIME_KEY)?.and_then(|s| s.parse().ok()))
+}
+
+#[allow(dead_code)] // CLI 只写入，由桌面应用读取
+pub fn last_open_id(conn: &Connection) -> rusqlite::Result<Option<String>> {
+    get_meta(conn, LAST_OPEN_ID_KEY)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_debouncer() {
+        let debouncer = Debouncer::new(1000);
+        assert!(debouncer.should_write(10_000));
+        assert!(!debouncer.should_write(10_500));
+        assert!(!debouncer.should_write(10_999));
+        assert!(debouncer.should_write(11_000));
+        assert!(!debouncer.should_write(11_001));
+    }
+
+    #[test]
+    fn test_record_and_read() {
+        let conn = Connection::open_in_memory().unwrap();
+        crate::migrations::run(&conn).unwrap();
+        assert_eq!(last_receive_time(&conn).unwrap(), None);
+        set_last_receive_time(&conn, 1_700_000_000_000).unwrap();
+        set_last_receive_time(&conn, 1_700_000_001_000).unwrap();
+        assert_eq!(last_receive_time(&conn).unwrap(), Some(1_700_000_001_000));
+
+        record_open_id(&conn, "ou_1").unwrap();
+        record_open_id(&conn, "ou_2").unwrap();
+        assert_eq!(last_open_id(&conn).unwrap(), Some("ou_2".to_string()));
+    }
+}
@@ -1,9 +1,10 @@
 import { useState, useEffect, useRef } from 'react';
-import { Form, Input, Button, Card, Divider, Tag, Table, Empty, Modal, Space, Menu, Tabs, Checkbox, ConfigProvider, theme, Switch, App as AntApp } from 'antd';
+import { Form, Input, AutoComplete, Button, Card, Divider, Tag, Table, Empty, Modal, Space, Menu, Tabs, Checkbox, ConfigProvider, theme, Switch, App as AntApp } from 'antd';
 import { SaveOutlined, ApiOutlined, SettingOutlined, DeleteOutlined, EyeOutlined, FolderOutlined, ArrowLeftOutlined, SunOutlined, MoonOutlined, PlusOutlined, ProjectOutlined, FullscreenOutlined, FullscreenExitOutlined, RightOutlined, MessageOutlined } from '@ant-design/icons';
 import { invoke, isTauri } from '@tauri-apps/api/core';
 import { open } from '@tauri-apps/plugin-dialog';
 import { usePty } from './hooks/usePty';
+import { useFeishuEvents } from './hooks/useFeishuEvents';
 import TerminalComponent from './components/Terminal';
 import WebApp from './WebApp';
 import logo from '../../logo.png';
@@ -19,11 +20,20 @@ interface AppConfig {
   hook_events_filter?: string;
 }
 
+interface ChatInfo {
+  chat_id: string;
+  name: string;
+  chat_type: string;
+}
+
 interface Project {
   id: number;
   name: string;
   path: string;
   hooks_installed: boolean;
+  user_hooks_installed?: boolean;
+  chat_id?: string | null;
+  open_id?: string | null;
 }
 
 interface HookRecord {
@@ -35,6 +45,8 @@ interface HookRecord {
   content: string;
   result: string;
   created_at: number;
+  tool_name?: string | null;
+  permission_mode?: string | null;
 }
 
 interface HookRecordsResponse {
@@ -49,6 +61,8 @@ function AppContent({ isDarkMode, setIsDarkMode }: { isDarkMode: boolean, setIsD
   const [form] = Form.useForm();
   const [loading, setLoading] = useState(false);
   const [testingConnection, setTestingConnection] = useState(false);
+  const [feishuChats, setFeishuChats] = useState<ChatInfo[]>([]);
+  const [loadingChats, setLoadingChats] = useState(false);
   const [activeMenu, setActiveMenu] = useState<string>('project');
   const [terminalFullscreen, setTerminalFullscreen] = useState(false);
   const [projects, setProjects] = useState<Project[]>([]);
@@ -230,11 +244,40 @@ function AppContent({ isDarkMode, setIsDarkMode }: { isDarkMode: boolean, setIsD
     }
   };
 
-  const formatHookTime = (value: number) => {
-    const time = value > 1_000_000_000_000 ? value : value * 1000;
-    return new Date(time).toLocaleString();
+  // 飞书消息和卡片操作会写入 hook 记录，收到事件时刷新当前项目的记录列表
+  const { lastEvent: lastFeishuEvent } = useFeishuEvents(() => {
+    if (selectedProject) {
+      fetchHookRecords(hookRecordsPage);
+    }
+  });
+
+  const formatFeishuEventType = (eventType: string) => {
+    switch (eventType) {
+      case 'im.message.receive_v1':
+        return '收到消息';
+      case 'card.action.trigger':
+        return '卡片操作';
+      default:
+        return eventType;
+    }
+  };
+
+  // 列表不含 content，打开详情时再加载完整记录
+  const openHookDetail = async (record: HookRecord) => {
+    setHookDetailRecord(record);
+    setHookDetailOpen(true);
+    if (!tauriAvailable || !selectedProject) return;
+    try {
+      const full = await invoke<HookRecord>('get_hook_record', { projectPath: selectedProject.path, id: record.id });
+      setHookDetailRecord(full);
+    } catch (error) {
+      messageApi.error(`加载记录详情失败: ${error}`);
+    }
   };
 
+  // hook 记录的 created_at 为毫秒时间戳
+  const formatHookTime = (value: number) => new Date(value).toLocaleString();
+
   const handleDeleteHookRecord = async (id: number) => {
     if (!tauriAvailable || !selectedProject) {
       messageApi.warning('请在桌面应用中删除记录');
@@ -287,8 +330,8 @@ function AppContent({ isDarkMode, setIsDarkMode }: { isDarkMode: boolean, setIsD
     }
     setLoading(true);
     try {
-      await invoke('save_config', { config: values });
-      messageApi.success('配置保存成功');
+      const reconnected = await invoke<boolean>('save_config', { config: values });
+      messageApi.success(reconnected ? '配置保存成功，已使用新凭证重新连接' : '配置保存成功');
     } catch (error) {
       messageApi.error(`保存配置失败: ${error}`);
     } finally {
@@ -320,6 +363,31 @@ function AppContent({ isDarkMode, setIsDarkMode }: { isDarkMode: boolean, setIsD
     }
   };
 
+  const handleLoadChats = async () => {
+    if (!tauriAvailable) {
+      messageApi.warning('请在桌面应用中获取群列表');
+      return;
+    }
+    const appId = form.getFieldValue('app_id');
+    const appSecret = form.getFieldValue('app_secret');
+
+    if (!appId || !appSecret) {
+      messageApi.warning('请先填写 App ID 和 App Secret');
+      return;
+    }
+
+    setLoadingChats(true);
+    try {
+      const chats = await invoke<ChatInfo[]>('list_feishu_chats', { appId, appSecret });
+      setFeishuChats(chats);
+      messageApi.success(`已获取 ${chats.length} 个群聊`);
+    } catch (error) {
+      messageApi.error(`获取群列表失败: ${error}`);
+    } finally {
+      setLoadingChats(false);
+    }
+  };
+
   const handleAddProject = async () => {
     if (!tauriAvailable) {
       messageApi.warning('请在桌面应用中添加项目');
@@ -368,10 +436,10 @@ function AppContent({ isDarkMode, setIsDarkMode }: { isDarkMode: boolean, setIsD
       return;
     }
     try {
-      await invoke('install_hooks', { projectPath: project.path });
+      const hookCommand = await invoke<string>('install_hooks', { projectPath: project.path });
       await invoke('set_project_hooks_status', { id: project.id, hooksInstalled: true });
       setProjects(projects.map(p => p.id === project.id ? { ...p, hooks_installed: true } : p));
-      messageApi.success('Hooks 安装成功');
+      messageApi.success(`Hooks 安装成功: ${hookCommand}`);
     } catch (error) {
       messageApi.error(`安装Hooks失败: ${error}`);
     }
@@ -485,7 +553,7 @@ function AppContent({ isDarkMode, setIsDarkMode }: { isDarkMode: boolean, setIsD
                             width: 100,
                             render: (_: any, record: Project) => (
                               <Tag className={`hooks-tag ${record.hooks_installed ? 'installed' : ''}`}>
-                                {record.hooks_installed ? '已安装' : '未安装'}
+                                {record.user_hooks_installed ? '已安装（全局）' : record.hooks_installed ? '已安装' : '未安装'}
                               </Tag>
                             ),
                           },
@@ -537,6 +605,15 @@ function AppContent({ isDarkMode, setIsDarkMode }: { isDarkMode: boolean, setIsD
                         <code className="last-input-content">{lastCommand}</code>
                       </div>
                     )}
+                    {lastFeishuEvent && (
+                      <div className="last-input-bar">
+                        <span className="last-input-label">最近飞书事件</span>
+                        <code className="last-input-content">
+                          {formatFeishuEventType(lastFeishuEvent.header.event_type)}
+                          {lastFeishuEvent.header.create_time && ` · ${new Date(Number(lastFeishuEvent.header.create_time)).toLocaleString()}`}
+                        </code>
+                      </div>
+                    )}
                     <Tabs
                       defaultActiveKey="claude"
                       items={[
@@ -578,7 +655,7 @@ function AppContent({ isDarkMode, setIsDarkMode }: { isDarkMode: boolean, setIsD
                               <div className="status-row">
                                 <span className="status-label">Hooks 状态</span>
                                 <Tag color={selectedProject.hooks_installed ? 'black' : 'default'}>
-                                  {selectedProject.hooks_installed ? '已安装' : '未安装'}
+                                  {selectedProject.user_hooks_installed ? '已安装（全局）' : selectedProject.hooks_installed ? '已安装' : '未安装'}
                                 </Tag>
                               </div>
                               <Divider />
@@ -638,10 +715,7 @@ function AppContent({ isDarkMode, setIsDarkMode }: { isDarkMode: boolean, setIsD
                                         <Button
                                           size="small"
                                           className="action-btn"
-                                          onClick={() => {
-                                            setHookDetailRecord(record);
-                                            setHookDetailOpen(true);
-                                          }}
+                                          onClick={() => openHookDetail(record)}
                                         >
                                           查看详情
                                         </Button>
@@ -682,6 +756,18 @@ function AppContent({ isDarkMode, setIsDarkMode }: { isDarkMode: boolean, setIsD
                                       <span className="status-label">结果</span>
                                       <span className="status-value">{hookDetailRecord.result}</span>
                                     </div>
+                                    {hookDetailRecord.tool_name && (
+                                      <div className="status-row">
+                                        <span className="status-label">工具</span>
+                                        <span className="status-value">{hookDetailRecord.tool_name}</span>
+                                      </div>
+                                    )}
+                                    {hookDetailRecord.permission_mode && (
+                                      <div className="status-row">
+                                        <span className="status-label">权限模式</span>
+                                        <span className="status-value">{hookDetailRecord.permission_mode}</span>
+                                      </div>
+                                    )}
                                     <Divider />
                                     <div className="status-row">
                                       <span className="status-label">摘要</span>
@@ -753,8 +839,21 @@ function AppContent({ isDarkMode, setIsDarkMode }: { isDarkMode: boolean, setIsD
                                       <Form.Item label="App Secret" name="app_secret" rules={[{ required: true, message: '请输入 App Secret' }]}>
                                         <Input.Password placeholder="应用密钥" size="large" className="input-field" />
                                       </Form.Item>
-                                      <Form.Item label="默认群聊 ID" name="chat_id" extra="可选">
-                                        <Input placeholder="oc_xxxxxxxxxxxxxxxxxxxxxxxx" size="large" className="input-field" />
+                                      <Form.Item label="默认群聊 ID" extra="可选，可点击「获取群列表」从机器人所在的群中选择">
+                                        <Space.Compact style={{ width: '100%' }}>
+                                          <Form.Item name="chat_id" noStyle>
+                                            <AutoComplete
+                                              options={feishuChats.map((chat) => ({
+                                                value: chat.chat_id,
+                                                label: `${chat.name || '未命名群聊'}（${chat.chat_id}）`,
+                                              }))}
+                                              placeholder="oc_xxxxxxxxxxxxxxxxxxxxxxxx"
+                                              size="large"
+                                              className="input-field"
+                                            />
+                                          </Form.Item>
+                                          <Button size="large" onClick={handleLoadChats} loading={loadingChats}>获取群列表</Button>
+                                        </Space.Compact>
                                       </Form.Item>
                                       <Form.Item label="Encrypt Key" name="encrypt_key" extra="可选">
                                         <Input.Password placeholder="加密密钥" size="large" className="input-field" />
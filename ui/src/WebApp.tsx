@@ -56,6 +56,7 @@ function WebApp() {
               hook_type: data.hook_type,
               raw_command: data.raw_command,
               description: data.description,
+              options: data.options,
               status: 'pending',
             });
             break;
@@ -53,6 +53,13 @@ export const ActionCard: React.FC<ActionCardProps> = ({
             <pre className="block p-2 bg-gray-100 rounded text-sm overflow-x-auto">
               {request.raw_command}
             </pre>
+            {request.options && request.options.length > 0 && (
+              <ol className="mt-2 pl-5 list-decimal text-sm text-gray-600">
+                {request.options.map((option, index) => (
+                  <li key={index}>{option}</li>
+                ))}
+              </ol>
+            )}
           </div>
 
           {isPending && (
@@ -0,0 +1,32 @@
+import { useEffect, useRef, useState } from 'react';
+import { isTauri } from '@tauri-apps/api/core';
+import { listen } from '@tauri-apps/api/event';
+import { FeishuEvent } from '../types';
+
+// 订阅桌面端转发的飞书 WSS 事件（消息与卡片操作），返回最近一条事件
+export function useFeishuEvents(onEvent?: (event: FeishuEvent) => void) {
+  const [lastEvent, setLastEvent] = useState<FeishuEvent | null>(null);
+  const onEventRef = useRef(onEvent);
+  const tauriAvailable = isTauri();
+
+  useEffect(() => {
+    onEventRef.current = onEvent;
+  }, [onEvent]);
+
+  useEffect(() => {
+    if (!tauriAvailable) return;
+    let disposed = false;
+    const unlistenPromise = listen<FeishuEvent | string>('feishu-event', (e) => {
+      // 无法解析为 JSON 的事件以原始字符串转发，忽略
+      if (disposed || typeof e.payload !== 'object' || !e.payload?.header) return;
+      setLastEvent(e.payload);
+      onEventRef.current?.(e.payload);
+    });
+    return () => {
+      disposed = true;
+      unlistenPromise.then((unlisten) => unlisten());
+    };
+  }, [tauriAvailable]);
+
+  return { lastEvent };
+}
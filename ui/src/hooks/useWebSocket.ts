@@ -115,13 +115,14 @@ export function useWebSocket({ url, taskId, executionMode = 'local' }: UseWebSoc
           hook_type: data.hook_type as string,
           raw_command: data.raw_command as string,
           description: data.description as string,
+          options: data.options as string[] | undefined,
           status: 'pending',
         });
         break;
 
       case 'permission_response':
         const requestId = data.request_id as string;
-        updatePermissionRequest(requestId, data.decision === 'approve' ? 'approved' : 'rejected');
+        updatePermissionRequest(requestId, data.decision === 'reject' ? 'rejected' : 'approved');
         addMessage({
           id: `msg-${Date.now()}`,
           role: 'system',
@@ -41,6 +41,7 @@ export interface PermissionRequest {
   hook_type: string;
   raw_command: string;
   description: string;
+  options?: string[];
   status: 'pending' | 'approved' | 'rejected';
 }
 
@@ -49,3 +50,15 @@ export type ConnectionStatus = 'connecting' | 'connected' | 'disconnected' | 'er
 
 // Local worker status
 export type LocalWorkerStatus = 'online' | 'offline' | 'checking';
+
+// Feishu event forwarded by the desktop app's long connection (`feishu-event`)
+export interface FeishuEvent {
+  schema: string;
+  header: {
+    event_id: string;
+    event_type: string;
+    create_time: string;
+    app_id: string;
+  };
+  event: Record<string, unknown>;
+}